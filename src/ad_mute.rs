@@ -0,0 +1,164 @@
+//! Spots ads and jingles by their stream title so the controller can turn the
+//! radio down until the programme is back. Stations that don't label their ad
+//! breaks can still be caught by the dead air many leave before splicing one
+//! in, if the user sets a silence threshold.
+
+use crate::config::AdMuteConfig;
+use crate::mpv::AudioLevel;
+use regex::{RegexSet, RegexSetBuilder};
+use std::time::{Duration, Instant};
+
+/// How long a break found by dead air lasts at most when no new title
+/// marks its end.
+pub const SILENCE_BREAK: Duration = Duration::from_secs(4 * 60);
+
+#[derive(Debug)]
+pub struct AdRules {
+    set: RegexSet,
+    duck_percent: u8,
+    /// `(dBFS, how long)` of quiet that starts a break.
+    silence: Option<(f32, Duration)>,
+    /// Since when it has been quiet, and whether that was reported.
+    quiet_since: Option<(Instant, bool)>,
+}
+
+impl Default for AdRules {
+    fn default() -> Self {
+        Self {
+            set: RegexSet::empty(),
+            duck_percent: 0,
+            silence: None,
+            quiet_since: None,
+        }
+    }
+}
+
+impl AdRules {
+    /// Compiles the configured patterns, leaving out the ones that don't
+    /// compile; those come back as messages for the user. Matches nothing
+    /// while the feature is off.
+    pub fn new(config: &AdMuteConfig) -> (Self, Vec<String>) {
+        if !config.enabled {
+            return (Self::default(), Vec::new());
+        }
+        let mut errors = Vec::new();
+        let patterns: Vec<&str> = config
+            .patterns
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .filter(|p| match compile([*p]) {
+                Ok(_) => true,
+                Err(e) => {
+                    errors.push(format!("Ignoring ad pattern {p:?}: {e}"));
+                    false
+                }
+            })
+            .collect();
+        let set = compile(patterns).unwrap_or_else(|_| RegexSet::empty());
+        let silence = config
+            .watches_silence()
+            .then(|| (config.silence_db as f32, Duration::from_secs(u64::from(config.silence_secs))));
+        (
+            Self {
+                set,
+                duck_percent: config.duck_percent.min(100),
+                silence,
+                quiet_since: None,
+            },
+            errors,
+        )
+    }
+
+    pub fn matches(&self, title: &str) -> bool {
+        self.set.is_match(title.trim())
+    }
+
+    /// Feeds the latest audio level; true once the audio has stayed below
+    /// the silence threshold long enough. Reported once per quiet stretch.
+    pub fn silence_began(&mut self, level: Option<AudioLevel>, now: Instant) -> bool {
+        let Some((db, secs)) = self.silence else {
+            return false;
+        };
+        if !level.is_some_and(|l| l.rms_db <= db) {
+            self.quiet_since = None;
+            return false;
+        }
+        let (since, reported) = self.quiet_since.get_or_insert((now, false));
+        if *reported || now.duration_since(*since) < secs {
+            return false;
+        }
+        *reported = true;
+        true
+    }
+
+    /// Volume to play an ad at, given the user's volume.
+    pub fn ducked(&self, volume: f64) -> f64 {
+        volume * f64::from(self.duck_percent) / 100.0
+    }
+}
+
+fn compile<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<RegexSet, regex::Error> {
+    RegexSetBuilder::new(patterns).case_insensitive(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_ad_titles() {
+        let (off, _) = AdRules::new(&AdMuteConfig::default());
+        assert!(!off.matches("Advertisement"));
+
+        let config = AdMuteConfig {
+            enabled: true,
+            duck_percent: 20,
+            ..AdMuteConfig::default()
+        };
+        let (rules, errors) = AdRules::new(&config);
+        assert!(errors.is_empty());
+        assert!(rules.matches("ADVERTISEMENT"));
+        assert!(rules.matches("Ads - Your local store"));
+        assert!(rules.matches("Station Jingle"));
+        assert!(!rules.matches("Adele - Hello"));
+        assert!(!rules.matches("Massive Attack - Teardrop"));
+        assert_eq!(rules.ducked(50.0), 10.0);
+
+        let config = AdMuteConfig {
+            patterns: vec!["(unclosed".to_string(), "^promo".to_string()],
+            ..config
+        };
+        let (rules, errors) = AdRules::new(&config);
+        assert_eq!(errors.len(), 1);
+        assert!(rules.matches("Promo: weekend show"));
+    }
+
+    #[test]
+    fn reports_dead_air_once() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let level = |db: f32| Some(AudioLevel { rms_db: db, peak_db: db });
+        let (mut off, _) = AdRules::new(&AdMuteConfig {
+            enabled: true,
+            ..AdMuteConfig::default()
+        });
+        assert!(!off.silence_began(level(-90.0), at(0)));
+        assert!(!off.silence_began(level(-90.0), at(60)));
+
+        let (mut rules, _) = AdRules::new(&AdMuteConfig {
+            enabled: true,
+            silence_secs: 2,
+            silence_db: -50,
+            ..AdMuteConfig::default()
+        });
+        assert!(!rules.silence_began(level(-60.0), at(0)));
+        // A loud moment starts the count over.
+        assert!(!rules.silence_began(level(-20.0), at(1)));
+        assert!(!rules.silence_began(level(-60.0), at(2)));
+        assert!(!rules.silence_began(None, at(3)));
+        assert!(!rules.silence_began(level(-60.0), at(4)));
+        assert!(rules.silence_began(level(-55.0), at(6)));
+        assert!(!rules.silence_began(level(-60.0), at(8)));
+    }
+}
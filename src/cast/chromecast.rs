@@ -0,0 +1,541 @@
+//! Chromecast discovery and control.
+//!
+//! Devices answer an mDNS query for `_googlecast._tcp`. Control is the Cast v2
+//! protocol: length-prefixed protobuf `CastMessage`s carrying JSON, over TLS
+//! on port 8009. Google's Default Media Receiver app plays the stream URL, so
+//! nothing needs registering with Google.
+
+use anyhow::{anyhow, bail, Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+use tracing::debug;
+
+const MDNS_ADDR: &str = "224.0.0.251:5353";
+const SERVICE: &str = "_googlecast._tcp.local";
+const DEFAULT_PORT: u16 = 8009;
+/// Google's Default Media Receiver, which plays a plain media URL.
+const MEDIA_RECEIVER: &str = "CC1AD845";
+const SENDER: &str = "sender-0";
+const RECEIVER: &str = "receiver-0";
+const NS_CONNECTION: &str = "urn:x-cast:com.google.cast.tp.connection";
+const NS_HEARTBEAT: &str = "urn:x-cast:com.google.cast.tp.heartbeat";
+const NS_RECEIVER: &str = "urn:x-cast:com.google.cast.receiver";
+const NS_MEDIA: &str = "urn:x-cast:com.google.cast.media";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Launching the receiver app can take a few seconds on older devices.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+const DNS_PTR: u16 = 12;
+const DNS_TXT: u16 = 16;
+const DNS_A: u16 = 1;
+const DNS_SRV: u16 = 33;
+
+/// Asks the LAN for Chromecasts and collects the ones answering within
+/// `wait`, by name.
+pub async fn discover(wait: Duration) -> Result<Vec<(String, SocketAddr)>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.context("Bind mDNS socket")?;
+    socket
+        .send_to(&mdns_query(), MDNS_ADDR)
+        .await
+        .context("Send mDNS query")?;
+
+    let mut found: Vec<(String, SocketAddr)> = Vec::new();
+    let deadline = tokio::time::Instant::now() + wait;
+    let mut buf = [0u8; 4096];
+    while let Ok(res) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (n, from) = res.context("Receive mDNS response")?;
+        if let Some(device) = parse_mdns_response(&buf[..n], from.ip()) {
+            if !found.iter().any(|(_, addr)| *addr == device.1) {
+                found.push(device);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Starts the media receiver on the device and has it play `url`.
+pub async fn play(addr: SocketAddr, url: &str, title: &str) -> Result<()> {
+    let mut channel = Channel::open(addr).await?;
+    let status = channel
+        .request(RECEIVER, NS_RECEIVER, json!({ "type": "LAUNCH", "appId": MEDIA_RECEIVER }))
+        .await?;
+    let app = media_app(&status).ok_or_else(|| anyhow!("The Chromecast didn't start its media player"))?;
+    channel.connect(&app.transport_id).await?;
+    let content_type = if url.to_ascii_lowercase().contains(".m3u8") {
+        "application/x-mpegurl"
+    } else {
+        "audio/mpeg"
+    };
+    let reply = channel
+        .request(
+            &app.transport_id,
+            NS_MEDIA,
+            json!({
+                "type": "LOAD",
+                "autoplay": true,
+                "media": {
+                    "contentId": url,
+                    "contentType": content_type,
+                    "streamType": "LIVE",
+                    "metadata": { "metadataType": 0, "title": title },
+                },
+            }),
+        )
+        .await?;
+    match reply["type"].as_str() {
+        Some("MEDIA_STATUS") => Ok(()),
+        other => Err(anyhow!("The Chromecast couldn't play the stream ({})", other.unwrap_or("no answer"))),
+    }
+}
+
+pub async fn pause(addr: SocketAddr) -> Result<()> {
+    media_command(addr, "PAUSE").await
+}
+
+pub async fn resume(addr: SocketAddr) -> Result<()> {
+    media_command(addr, "PLAY").await
+}
+
+/// Closes the media player on the device, if it's running.
+pub async fn stop(addr: SocketAddr) -> Result<()> {
+    let mut channel = Channel::open(addr).await?;
+    let status = channel.request(RECEIVER, NS_RECEIVER, json!({ "type": "GET_STATUS" })).await?;
+    let Some(app) = media_app(&status) else {
+        return Ok(());
+    };
+    channel
+        .request(RECEIVER, NS_RECEIVER, json!({ "type": "STOP", "sessionId": app.session_id }))
+        .await?;
+    Ok(())
+}
+
+async fn media_command(addr: SocketAddr, command: &str) -> Result<()> {
+    let mut channel = Channel::open(addr).await?;
+    let status = channel.request(RECEIVER, NS_RECEIVER, json!({ "type": "GET_STATUS" })).await?;
+    let app = media_app(&status).ok_or_else(|| anyhow!("Nothing is playing on the Chromecast"))?;
+    channel.connect(&app.transport_id).await?;
+    let media = channel
+        .request(&app.transport_id, NS_MEDIA, json!({ "type": "GET_STATUS" }))
+        .await?;
+    let session = media["status"][0]["mediaSessionId"]
+        .as_u64()
+        .ok_or_else(|| anyhow!("Nothing is playing on the Chromecast"))?;
+    channel
+        .request(&app.transport_id, NS_MEDIA, json!({ "type": command, "mediaSessionId": session }))
+        .await?;
+    Ok(())
+}
+
+/// The media receiver as listed in a `RECEIVER_STATUS`.
+#[derive(Debug, PartialEq, Eq)]
+struct MediaApp {
+    transport_id: String,
+    session_id: String,
+}
+
+fn media_app(status: &Value) -> Option<MediaApp> {
+    let app = status["status"]["applications"]
+        .as_array()?
+        .iter()
+        .find(|a| a["appId"] == MEDIA_RECEIVER)?;
+    Some(MediaApp {
+        transport_id: app["transportId"].as_str()?.to_string(),
+        session_id: app["sessionId"].as_str()?.to_string(),
+    })
+}
+
+/// One TLS connection to a device, with virtual connections opened as needed.
+struct Channel {
+    stream: TlsStream<TcpStream>,
+    next_request: u64,
+}
+
+impl Channel {
+    async fn open(addr: SocketAddr) -> Result<Self> {
+        let tcp = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| anyhow!("Chromecast at {addr} didn't answer"))?
+            .with_context(|| format!("Failed to connect to the Chromecast at {addr}"))?;
+        let stream = TlsConnector::from(tls_config()?)
+            .connect(ServerName::from(addr.ip()), tcp)
+            .await
+            .context("TLS handshake with the Chromecast failed")?;
+        let mut channel = Self { stream, next_request: 1 };
+        channel.connect(RECEIVER).await?;
+        Ok(channel)
+    }
+
+    async fn connect(&mut self, destination: &str) -> Result<()> {
+        self.send(destination, NS_CONNECTION, &json!({ "type": "CONNECT" })).await
+    }
+
+    async fn send(&mut self, destination: &str, namespace: &str, payload: &Value) -> Result<()> {
+        let frame = encode_message(SENDER, destination, namespace, &payload.to_string());
+        self.stream.write_all(&frame).await.context("Chromecast connection lost")
+    }
+
+    /// Sends `payload` with a fresh request id and waits for the answer to it.
+    async fn request(&mut self, destination: &str, namespace: &str, mut payload: Value) -> Result<Value> {
+        let id = self.next_request;
+        self.next_request += 1;
+        payload["requestId"] = json!(id);
+        self.send(destination, namespace, &payload).await?;
+        tokio::time::timeout(REPLY_TIMEOUT, async {
+            loop {
+                let (namespace, reply) = self.receive().await?;
+                if reply["requestId"] == id {
+                    return Ok(reply);
+                }
+                debug!(%namespace, kind = ?reply["type"], "unrelated Chromecast message");
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("The Chromecast didn't answer"))?
+    }
+
+    /// Next message with a JSON payload; heartbeats are answered on the way.
+    async fn receive(&mut self) -> Result<(String, Value)> {
+        loop {
+            let len = self.stream.read_u32().await.context("Chromecast connection lost")? as usize;
+            if len > MAX_MESSAGE_BYTES {
+                bail!("Chromecast message too large ({len} bytes)");
+            }
+            let mut buf = vec![0u8; len];
+            self.stream.read_exact(&mut buf).await.context("Chromecast connection lost")?;
+            let message = decode_message(&buf)?;
+            let Ok(payload) = serde_json::from_str::<Value>(&message.payload) else {
+                continue;
+            };
+            if message.namespace == NS_HEARTBEAT && payload["type"] == "PING" {
+                self.send(&message.source, NS_HEARTBEAT, &json!({ "type": "PONG" })).await?;
+                continue;
+            }
+            if message.namespace == NS_CONNECTION && payload["type"] == "CLOSE" {
+                bail!("The Chromecast closed the connection");
+            }
+            return Ok((message.namespace, payload));
+        }
+    }
+}
+
+/// Chromecasts present self-signed certificates, so there's nothing to check
+/// them against; the handshake signatures are still verified.
+#[derive(Debug)]
+struct AcceptDeviceCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptDeviceCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn tls_config() -> Result<Arc<rustls::ClientConfig>> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .context("No TLS versions for the Chromecast")?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(AcceptDeviceCert(provider)))
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+/// The parts of a `CastMessage` this client reads.
+#[derive(Debug, PartialEq, Eq)]
+struct CastMessage {
+    source: String,
+    namespace: String,
+    payload: String,
+}
+
+/// A length-prefixed `CastMessage` with a string payload.
+fn encode_message(source: &str, destination: &str, namespace: &str, payload: &str) -> Vec<u8> {
+    let mut msg = Vec::new();
+    // protocol_version = CASTV2_1_0
+    msg.extend([0x08, 0x00]);
+    put_string(&mut msg, 2, source);
+    put_string(&mut msg, 3, destination);
+    put_string(&mut msg, 4, namespace);
+    // payload_type = STRING
+    msg.extend([0x28, 0x00]);
+    put_string(&mut msg, 6, payload);
+    let mut frame = (msg.len() as u32).to_be_bytes().to_vec();
+    frame.extend(msg);
+    frame
+}
+
+fn put_string(out: &mut Vec<u8>, field: u8, s: &str) {
+    out.push(field << 3 | 2);
+    put_varint(out, s.len() as u64);
+    out.extend(s.as_bytes());
+}
+
+fn put_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn decode_message(buf: &[u8]) -> Result<CastMessage> {
+    let mut fields: HashMap<u64, String> = HashMap::new();
+    let mut pos = 0;
+    while pos < buf.len() {
+        let key = read_varint(buf, &mut pos)?;
+        match key & 7 {
+            0 => {
+                read_varint(buf, &mut pos)?;
+            }
+            2 => {
+                let len = read_varint(buf, &mut pos)? as usize;
+                let bytes = buf
+                    .get(pos..pos.saturating_add(len))
+                    .ok_or_else(|| anyhow!("Truncated Chromecast message"))?;
+                fields.insert(key >> 3, String::from_utf8_lossy(bytes).into_owned());
+                pos += len;
+            }
+            wire => bail!("Unexpected protobuf wire type {wire}"),
+        }
+    }
+    let mut take = |field| fields.remove(&field).unwrap_or_default();
+    Ok(CastMessage {
+        source: take(2),
+        namespace: take(4),
+        payload: take(6),
+    })
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buf.get(*pos).ok_or_else(|| anyhow!("Truncated Chromecast message"))?;
+        *pos += 1;
+        n |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    bail!("Overlong varint in Chromecast message")
+}
+
+/// A PTR question for the Cast service, asking for unicast answers so they
+/// come back to this socket.
+fn mdns_query() -> Vec<u8> {
+    let mut packet = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in SERVICE.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend(DNS_PTR.to_be_bytes());
+    // IN class with the unicast-response bit.
+    packet.extend(0x8001u16.to_be_bytes());
+    packet
+}
+
+/// Name and control address of the Chromecast answering in `packet`, sent
+/// from `from`. `None` for anything that isn't a Cast device.
+fn parse_mdns_response(packet: &[u8], from: IpAddr) -> Option<(String, SocketAddr)> {
+    let count = |at: usize| Some(u16::from_be_bytes([*packet.get(at)?, *packet.get(at + 1)?]) as usize);
+    let questions = count(4)?;
+    let records = count(6)? + count(8)? + count(10)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_dns_name(packet, pos)?.1 + 4;
+    }
+
+    let mut is_cast = false;
+    let (mut instance, mut friendly, mut port, mut ip) = (None, None, None, None);
+    for _ in 0..records {
+        let (owner, next) = read_dns_name(packet, pos)?;
+        let kind = count(next)? as u16;
+        let len = count(next + 8)?;
+        let data_at = next + 10;
+        let data = packet.get(data_at..data_at + len)?;
+        pos = data_at + len;
+        let cast_record = owner.to_ascii_lowercase().ends_with(SERVICE);
+        match kind {
+            DNS_PTR if owner.eq_ignore_ascii_case(SERVICE) => {
+                is_cast = true;
+                instance = read_dns_name(packet, data_at).map(|(name, _)| name);
+            }
+            DNS_SRV if cast_record && len >= 6 => {
+                is_cast = true;
+                port = Some(u16::from_be_bytes([data[4], data[5]]));
+            }
+            DNS_TXT if cast_record => {
+                is_cast = true;
+                friendly = txt_value(data, "fn").or(friendly);
+            }
+            DNS_A if len == 4 => ip = Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            _ => {}
+        }
+    }
+    if !is_cast {
+        return None;
+    }
+    let name = friendly
+        .or_else(|| instance.and_then(|i| i.split('.').next().map(str::to_string)))
+        .unwrap_or_else(|| "Chromecast".to_string());
+    Some((name, SocketAddr::new(ip.unwrap_or(from), port.unwrap_or(DEFAULT_PORT))))
+}
+
+/// Reads a possibly compressed DNS name at `pos`; returns it with the offset
+/// just past it.
+fn read_dns_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Bounds pointer loops in malformed packets.
+    for _ in 0..64 {
+        let len = *packet.get(pos)? as usize;
+        match len {
+            0 => return Some((labels.join("."), end.unwrap_or(pos + 1))),
+            l if l & 0xc0 == 0xc0 => {
+                let target = (l & 0x3f) << 8 | *packet.get(pos + 1)? as usize;
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            l => {
+                labels.push(String::from_utf8_lossy(packet.get(pos + 1..pos + 1 + l)?).into_owned());
+                pos += 1 + l;
+            }
+        }
+    }
+    None
+}
+
+/// The value of `key=…` among a TXT record's strings.
+fn txt_value(data: &[u8], key: &str) -> Option<String> {
+    let mut pos = 0;
+    while let Some(&len) = data.get(pos) {
+        let entry = String::from_utf8_lossy(data.get(pos + 1..pos + 1 + len as usize)?).into_owned();
+        pos += 1 + len as usize;
+        if let Some((k, v)) = entry.split_once('=') {
+            if k.eq_ignore_ascii_case(key) && !v.trim().is_empty() {
+                return Some(v.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(out: &mut Vec<u8>, name: &str) {
+        for label in name.split('.') {
+            out.push(label.len() as u8);
+            out.extend(label.as_bytes());
+        }
+        out.push(0);
+    }
+
+    fn record(out: &mut Vec<u8>, owner: &str, kind: u16, data: &[u8]) {
+        name(out, owner);
+        out.extend(kind.to_be_bytes());
+        out.extend([0x80, 0x01, 0, 0, 0, 120]);
+        out.extend((data.len() as u16).to_be_bytes());
+        out.extend(data);
+    }
+
+    #[test]
+    fn round_trips_cast_messages() {
+        let payload = format!(r#"{{"type":"LOAD","pad":"{}"}}"#, "x".repeat(200));
+        let frame = encode_message(SENDER, RECEIVER, NS_MEDIA, &payload);
+        assert_eq!(u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize, frame.len() - 4);
+        let message = decode_message(&frame[4..]).unwrap();
+        assert_eq!(
+            message,
+            CastMessage {
+                source: SENDER.to_string(),
+                namespace: NS_MEDIA.to_string(),
+                payload,
+            }
+        );
+        assert!(decode_message(&frame[4..frame.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn finds_chromecasts_in_mdns_answers() {
+        let query = mdns_query();
+        assert_eq!(&query[12..24], b"\x0b_googlecast");
+
+        let instance = "Chromecast-abc123._googlecast._tcp.local";
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 3];
+        let mut ptr = Vec::new();
+        name(&mut ptr, instance);
+        record(&mut packet, SERVICE, DNS_PTR, &ptr);
+        let mut srv = vec![0, 0, 0, 0, 0x1f, 0x49];
+        name(&mut srv, "abc123.local");
+        record(&mut packet, instance, DNS_SRV, &srv);
+        let txt = b"\x05id=ab\x0efn=Living Room";
+        record(&mut packet, instance, DNS_TXT, txt);
+        record(&mut packet, "abc123.local", DNS_A, &[192, 168, 1, 30]);
+
+        let from = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 99));
+        let (found, addr) = parse_mdns_response(&packet, from).unwrap();
+        assert_eq!(found, "Living Room");
+        assert_eq!(addr, "192.168.1.30:8009".parse().unwrap());
+
+        let mut printer = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        record(&mut printer, "printer.local", DNS_A, &[192, 168, 1, 40]);
+        assert!(parse_mdns_response(&printer, from).is_none());
+        assert!(parse_mdns_response(&packet[..40], from).is_none());
+    }
+
+    #[test]
+    fn reads_the_media_receiver_from_status() {
+        let status = json!({ "type": "RECEIVER_STATUS", "status": { "applications": [
+            { "appId": "E8C28D3C", "transportId": "backdrop", "sessionId": "1" },
+            { "appId": MEDIA_RECEIVER, "transportId": "web-5", "sessionId": "abc" },
+        ] } });
+        assert_eq!(
+            media_app(&status),
+            Some(MediaApp { transport_id: "web-5".to_string(), session_id: "abc".to_string() })
+        );
+        assert_eq!(media_app(&json!({ "status": {} })), None);
+    }
+}
@@ -0,0 +1,356 @@
+//! Media renderer discovery and control.
+//!
+//! UPnP/DLNA renderers are found via SSDP and driven through their AVTransport
+//! service, which is enough to hand over a resolved stream URL and
+//! play/pause/stop it. Chromecasts are handled in [`chromecast`].
+
+mod chromecast;
+
+use crate::radio_browser::read_limited;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::debug;
+use url::Url;
+
+const SSDP_ADDR: &str = "239.255.255.250:1900";
+const MEDIA_RENDERER: &str = "urn:schemas-upnp-org:device:MediaRenderer:1";
+const AV_TRANSPORT: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+const MAX_DESCRIPTION_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    pub name: String,
+    pub device: Device,
+}
+
+/// How a renderer is reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Device {
+    Upnp { control_url: String },
+    Chromecast { addr: SocketAddr },
+}
+
+#[derive(Debug, Clone)]
+pub struct CastClient {
+    http: reqwest::Client,
+}
+
+impl CastClient {
+    pub fn new() -> Result<Self> {
+        let http = reqwest::ClientBuilder::new()
+            .connect_timeout(Duration::from_secs(2))
+            .timeout(Duration::from_secs(5))
+            .build()
+            .context("Failed to build cast HTTP client")?;
+        Ok(Self { http })
+    }
+
+    /// Searches for UPnP renderers and Chromecasts at the same time and
+    /// collects the ones answering within `wait`. Fails only if neither
+    /// search could be sent.
+    pub async fn discover(&self, wait: Duration) -> Result<Vec<Renderer>> {
+        let (upnp, cast) = tokio::join!(self.discover_upnp(wait), chromecast::discover(wait));
+        let (mut renderers, chromecasts) = match (upnp, cast) {
+            (Err(e), Err(cast_err)) => {
+                debug!(error = ?cast_err, "Chromecast discovery failed");
+                return Err(e);
+            }
+            (upnp, cast) => {
+                if let Err(e) = &upnp {
+                    debug!(error = ?e, "UPnP discovery failed");
+                }
+                if let Err(e) = &cast {
+                    debug!(error = ?e, "Chromecast discovery failed");
+                }
+                (upnp.unwrap_or_default(), cast.unwrap_or_default())
+            }
+        };
+        renderers.extend(chromecasts.into_iter().map(|(name, addr)| Renderer {
+            name,
+            device: Device::Chromecast { addr },
+        }));
+        renderers.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(renderers)
+    }
+
+    /// Sends an SSDP search and collects the renderers answering within `wait`.
+    async fn discover_upnp(&self, wait: Duration) -> Result<Vec<Renderer>> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .context("Bind SSDP socket")?;
+        let request = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {SSDP_ADDR}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {MEDIA_RENDERER}\r\n\r\n"
+        );
+        socket
+            .send_to(request.as_bytes(), SSDP_ADDR)
+            .await
+            .context("Send SSDP search")?;
+
+        let mut locations: Vec<String> = Vec::new();
+        let mut seen = HashSet::new();
+        let deadline = tokio::time::Instant::now() + wait;
+        let mut buf = [0u8; 2048];
+        while let Ok(res) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            let (n, _) = res.context("Receive SSDP response")?;
+            let text = String::from_utf8_lossy(&buf[..n]);
+            if let Some(loc) = parse_ssdp_location(&text) {
+                if seen.insert(loc.clone()) {
+                    locations.push(loc);
+                }
+            }
+        }
+
+        let mut renderers = Vec::new();
+        for location in locations {
+            match self.describe(&location).await {
+                Ok(r) => renderers.push(r),
+                Err(e) => debug!(%location, error = ?e, "skipping renderer"),
+            }
+        }
+        Ok(renderers)
+    }
+
+    async fn describe(&self, location: &str) -> Result<Renderer> {
+        let resp = self
+            .http
+            .get(location)
+            .send()
+            .await
+            .context("Fetch renderer description")?;
+        let bytes = read_limited(resp, MAX_DESCRIPTION_BYTES)
+            .await
+            .context("Read renderer description")?;
+        parse_description(location, &String::from_utf8_lossy(&bytes))
+    }
+
+    pub async fn play(&self, renderer: &Renderer, url: &str, title: &str) -> Result<()> {
+        if let Device::Chromecast { addr } = renderer.device {
+            return chromecast::play(addr, url, title).await;
+        }
+        let metadata = didl_metadata(url, title);
+        let args = format!(
+            "<CurrentURI>{}</CurrentURI><CurrentURIMetaData>{}</CurrentURIMetaData>",
+            xml_escape(url),
+            xml_escape(&metadata)
+        );
+        self.soap(renderer, "SetAVTransportURI", &args).await?;
+        self.soap(renderer, "Play", "<Speed>1</Speed>").await
+    }
+
+    pub async fn pause(&self, renderer: &Renderer) -> Result<()> {
+        match renderer.device {
+            Device::Chromecast { addr } => chromecast::pause(addr).await,
+            Device::Upnp { .. } => self.soap(renderer, "Pause", "").await,
+        }
+    }
+
+    pub async fn resume(&self, renderer: &Renderer) -> Result<()> {
+        match renderer.device {
+            Device::Chromecast { addr } => chromecast::resume(addr).await,
+            Device::Upnp { .. } => self.soap(renderer, "Play", "<Speed>1</Speed>").await,
+        }
+    }
+
+    pub async fn stop(&self, renderer: &Renderer) -> Result<()> {
+        match renderer.device {
+            Device::Chromecast { addr } => chromecast::stop(addr).await,
+            Device::Upnp { .. } => self.soap(renderer, "Stop", "").await,
+        }
+    }
+
+    async fn soap(&self, renderer: &Renderer, action: &str, args: &str) -> Result<()> {
+        let Device::Upnp { control_url } = &renderer.device else {
+            return Err(anyhow!("{} is not a UPnP renderer", renderer.name));
+        };
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+             s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+             <s:Body><u:{action} xmlns:u=\"{AV_TRANSPORT}\">\
+             <InstanceID>0</InstanceID>{args}</u:{action}></s:Body></s:Envelope>"
+        );
+        let resp = self
+            .http
+            .post(control_url)
+            .header("Content-Type", "text/xml; charset=\"utf-8\"")
+            .header("SOAPACTION", format!("\"{AV_TRANSPORT}#{action}\""))
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("{action} request to {} failed", renderer.name))?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "{} rejected {action}: HTTP {}",
+                renderer.name,
+                resp.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse_ssdp_location(response: &str) -> Option<String> {
+    response.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case("location")
+            .then(|| value.trim().to_string())
+            .filter(|v| !v.is_empty())
+    })
+}
+
+fn parse_description(location: &str, xml: &str) -> Result<Renderer> {
+    let name = xml_text(xml, "friendlyName")
+        .map(xml_unescape)
+        .unwrap_or_else(|| "Unnamed renderer".to_string());
+
+    let control = xml_blocks(xml, "service")
+        .into_iter()
+        .find(|svc| {
+            xml_text(svc, "serviceType").is_some_and(|t| t.contains("AVTransport"))
+        })
+        .and_then(|svc| xml_text(svc, "controlURL"))
+        .ok_or_else(|| anyhow!("{name} has no AVTransport service"))?;
+
+    let base = xml_text(xml, "URLBase").unwrap_or(location);
+    let base = Url::parse(base.trim()).context("Invalid renderer location")?;
+    let control_url = base
+        .join(control.trim())
+        .context("Invalid renderer control URL")?;
+
+    Ok(Renderer {
+        name,
+        device: Device::Upnp {
+            control_url: control_url.to_string(),
+        },
+    })
+}
+
+fn didl_metadata(url: &str, title: &str) -> String {
+    format!(
+        "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:upnp=\"urn:schemas-upnp-org:metadata-1-0/upnp/\">\
+         <item id=\"0\" parentID=\"-1\" restricted=\"1\">\
+         <dc:title>{}</dc:title>\
+         <upnp:class>object.item.audioItem.audioBroadcast</upnp:class>\
+         <res protocolInfo=\"http-get:*:*:*\">{}</res>\
+         </item></DIDL-Lite>",
+        xml_escape(title),
+        xml_escape(url)
+    )
+}
+
+/// Text content of the first `<tag>` element.
+fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    xml_blocks(xml, tag).into_iter().next()
+}
+
+/// Inner content of every `<tag>…</tag>` element, in document order.
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = find_open_tag(rest, tag) {
+        let after_open = &rest[start..];
+        let Some(gt) = after_open.find('>') else {
+            break;
+        };
+        let inner = &after_open[gt + 1..];
+        let close = format!("</{tag}>");
+        let Some(end) = inner.find(&close) else {
+            break;
+        };
+        out.push(&inner[..end]);
+        rest = &inner[end + close.len()..];
+    }
+    out
+}
+
+fn find_open_tag(xml: &str, tag: &str) -> Option<usize> {
+    let needle = format!("<{tag}");
+    let mut offset = 0;
+    while let Some(pos) = xml[offset..].find(&needle) {
+        let idx = offset + pos;
+        let next = xml[idx + needle.len()..].chars().next();
+        if matches!(next, Some('>') | Some(' ') | Some('\t') | Some('\r') | Some('\n')) {
+            return Some(idx);
+        }
+        offset = idx + needle.len();
+    }
+    None
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ssdp_location_header() {
+        let resp = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nLocation: http://192.168.1.20:49152/desc.xml\r\nST: urn:schemas-upnp-org:device:MediaRenderer:1\r\n\r\n";
+        assert_eq!(
+            parse_ssdp_location(resp).as_deref(),
+            Some("http://192.168.1.20:49152/desc.xml")
+        );
+        assert_eq!(parse_ssdp_location("HTTP/1.1 200 OK\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn parses_renderer_description() {
+        let xml = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+  <device>
+    <friendlyName>Living Room &amp; Kitchen</friendlyName>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:RenderingControl:1</serviceType>
+        <controlURL>/rc/control</controlURL>
+      </service>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:AVTransport:1</serviceType>
+        <controlURL>/avt/control</controlURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+        let r = parse_description("http://192.168.1.20:49152/desc.xml", xml).unwrap();
+        assert_eq!(r.name, "Living Room & Kitchen");
+        assert_eq!(
+            r.device,
+            Device::Upnp {
+                control_url: "http://192.168.1.20:49152/avt/control".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_renderer_without_av_transport() {
+        let xml = "<root><device><friendlyName>TV</friendlyName><serviceList><service><serviceType>urn:schemas-upnp-org:service:RenderingControl:1</serviceType><controlURL>/rc</controlURL></service></serviceList></device></root>";
+        assert!(parse_description("http://10.0.0.2/desc.xml", xml).is_err());
+    }
+
+    #[test]
+    fn escapes_metadata() {
+        let didl = didl_metadata("http://x/?a=1&b=2", "Rock <FM>");
+        assert!(didl.contains("Rock &lt;FM&gt;"));
+        assert!(didl.contains("a=1&amp;b=2"));
+    }
+}
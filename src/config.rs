@@ -1,10 +1,13 @@
-use crate::models::StationRef;
+use crate::models::{LabelColor, ResultOrder, Station, StationRef};
 use anyhow::{Context, Result};
 use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -12,8 +15,895 @@ pub struct AppConfig {
     pub last_station: Option<StationRef>,
     #[serde(default)]
     pub last_server: Option<String>,
+    /// Radio Browser mirror to always try first, e.g. `de1.api.radio-browser.info`.
+    #[serde(default)]
+    pub pinned_server: Option<String>,
     #[serde(default)]
     pub favorites: Vec<StationRef>,
+    /// Set once the user has acknowledged the first-run network summary.
+    #[serde(default)]
+    pub network_consent: bool,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    /// Color labels for favorites, keyed by station UUID.
+    #[serde(default)]
+    pub favorite_colors: BTreeMap<String, LabelColor>,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub shuffle: ShuffleConfig,
+    #[serde(default)]
+    pub resume: ResumeConfig,
+    #[serde(default)]
+    pub power: PowerConfig,
+    #[serde(default)]
+    pub quality: QualityConfig,
+    #[serde(default)]
+    pub stream_choice: StreamChoice,
+    /// Favorites that override `stream_choice`, keyed by station UUID.
+    #[serde(default)]
+    pub favorite_stream_choice: BTreeMap<String, StreamChoice>,
+    #[serde(default)]
+    pub ad_mute: AdMuteConfig,
+    #[serde(default)]
+    pub titles: TitleConfig,
+    /// What gets written to the log file under `$XDG_STATE_HOME/radiowidget/logs`.
+    #[serde(default)]
+    pub log_level: LogLevel,
+    #[serde(default)]
+    pub equalizer: EqualizerConfig,
+    #[serde(default)]
+    pub channels: ChannelConfig,
+    #[serde(default)]
+    pub alarm: AlarmConfig,
+    #[serde(default)]
+    pub voice: VoiceConfig,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub recording: RecordingConfig,
+}
+
+/// Presentation preferences, kept apart from playback and library data.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Which list the popup shows when it opens.
+    #[serde(default)]
+    pub start_view: StartView,
+    /// List that was open when the popup was last used.
+    #[serde(default)]
+    pub last_view: PopupView,
+    #[serde(default)]
+    pub label_mode: LabelMode,
+    /// Tint the panel label with the playing favorite's color.
+    #[serde(default)]
+    pub tint_panel: bool,
+    /// Show full station names and titles in the now-playing strip.
+    #[serde(default)]
+    pub wrap_titles: bool,
+    #[serde(default)]
+    pub result_order: ResultOrder,
+    /// Scroll panel labels that don't fit instead of shortening them.
+    #[serde(default)]
+    pub marquee: bool,
+    #[serde(default)]
+    pub marquee_speed: MarqueeSpeed,
+    #[serde(default)]
+    pub panel_style: PanelStyle,
+    #[serde(default)]
+    pub row_actions: RowActions,
+    /// Stations left out of search results.
+    #[serde(default)]
+    pub blocked: Vec<StationRef>,
+    /// Offer play, pause, stop and volume to the lock screen's media controls.
+    #[serde(default)]
+    pub lock_screen_controls: bool,
+    /// Show a level meter for the playing stream. Measuring costs mpv a
+    /// little CPU, so it's off by default.
+    #[serde(default)]
+    pub level_meter: bool,
+}
+
+/// What clicking, double-clicking and holding a station row do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowActions {
+    #[serde(default)]
+    pub click: RowAction,
+    /// Runs after the first click's action; a double-click is two clicks.
+    #[serde(default = "default_double_click")]
+    pub double_click: RowAction,
+    /// Press and hold, for touchscreens.
+    #[serde(default = "default_long_press")]
+    pub long_press: RowAction,
+}
+
+impl Default for RowActions {
+    fn default() -> Self {
+        Self {
+            click: RowAction::Play,
+            double_click: default_double_click(),
+            long_press: default_long_press(),
+        }
+    }
+}
+
+fn default_double_click() -> RowAction {
+    RowAction::Nothing
+}
+
+fn default_long_press() -> RowAction {
+    RowAction::Menu
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowAction {
+    #[default]
+    Play,
+    /// Add to favorites, or remove (with undo) if it already is one.
+    Favorite,
+    Details,
+    /// Play, favorite, details and hide as buttons under the row.
+    Menu,
+    Nothing,
+}
+
+/// Label button or icon-only button in a horizontal panel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelStyle {
+    /// Icon only on extra-small panels, the label otherwise.
+    #[default]
+    Auto,
+    Label,
+    Compact,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarqueeSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl MarqueeSpeed {
+    /// Milliseconds between one-character steps.
+    pub fn step_ms(self) -> u64 {
+        match self {
+            Self::Slow => 400,
+            Self::Normal => 250,
+            Self::Fast => 125,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PopupView {
+    #[default]
+    Search,
+    Favorites,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StartView {
+    #[default]
+    Search,
+    Favorites,
+    /// Reopen whatever list was shown last.
+    Last,
+}
+
+impl StartView {
+    pub fn resolve(self, last: PopupView) -> PopupView {
+        match self {
+            Self::Search => PopupView::Search,
+            Self::Favorites => PopupView::Favorites,
+            Self::Last => last,
+        }
+    }
+}
+
+/// What the horizontal panel button shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelMode {
+    #[default]
+    Station,
+    Title,
+    TitleAndStation,
+}
+
+/// Three-band equalizer, gains in dB.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct EqualizerConfig {
+    #[serde(default)]
+    pub preset: EqPreset,
+    #[serde(default)]
+    pub bass: f32,
+    #[serde(default)]
+    pub mid: f32,
+    #[serde(default)]
+    pub treble: f32,
+}
+
+impl EqualizerConfig {
+    pub const MAX_GAIN: f32 = 12.0;
+
+    pub fn from_preset(preset: EqPreset) -> Self {
+        let (bass, mid, treble) = match preset {
+            EqPreset::Flat | EqPreset::Custom => (0.0, 0.0, 0.0),
+            EqPreset::Rock => (4.0, -1.0, 3.0),
+            EqPreset::Speech => (-4.0, 3.0, 1.0),
+        };
+        Self {
+            preset,
+            bass,
+            mid,
+            treble,
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.bass == 0.0 && self.mid == 0.0 && self.treble == 0.0
+    }
+}
+
+/// Channel mixing, for listening on one earbud or with uneven hearing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelConfig {
+    /// Both channels mixed into each ear.
+    #[serde(default)]
+    pub mono: bool,
+    /// -1.0 is left only, 1.0 right only.
+    #[serde(default)]
+    pub balance: f32,
+}
+
+impl ChannelConfig {
+    pub fn is_neutral(&self) -> bool {
+        !self.mono && self.balance == 0.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EqPreset {
+    #[default]
+    Flat,
+    Rock,
+    Speech,
+    /// Gains set by hand.
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[default]
+    Off,
+    /// Errors and warnings.
+    Error,
+    Debug,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// Resolve streams through Radio Browser's click-counting endpoint.
+    #[serde(default = "default_true")]
+    pub count_clicks: bool,
+    /// Remember the last station and API mirror between sessions.
+    #[serde(default = "default_true")]
+    pub remember_history: bool,
+    /// Keep listening statistics on this machine (never sent anywhere).
+    #[serde(default)]
+    pub usage_stats: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            count_clicks: true,
+            remember_history: true,
+            usage_stats: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShuffleConfig {
+    /// Random picks skip stations played within this many hours.
+    #[serde(default = "default_avoid_repeats_hours")]
+    pub avoid_repeats_hours: u64,
+}
+
+impl Default for ShuffleConfig {
+    fn default() -> Self {
+        Self {
+            avoid_repeats_hours: default_avoid_repeats_hours(),
+        }
+    }
+}
+
+fn default_avoid_repeats_hours() -> u64 {
+    12
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerConfig {
+    /// Block idle suspend while a station plays locally.
+    #[serde(default)]
+    pub inhibit_suspend: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdMuteConfig {
+    /// Turn the radio down while the stream title matches one of `patterns`.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Regular expressions, matched case-insensitively anywhere in the title.
+    #[serde(default = "default_ad_patterns")]
+    pub patterns: Vec<String>,
+    /// Volume during ads in percent of the normal volume; 0 mutes.
+    #[serde(default)]
+    pub duck_percent: u8,
+    /// Dead air this long starts a break that lasts until the title changes,
+    /// for stations that splice ads in without labelling them; 0 is off.
+    #[serde(default)]
+    pub silence_secs: u32,
+    /// Audio quieter than this (RMS, dBFS) counts as dead air.
+    #[serde(default = "default_silence_db")]
+    pub silence_db: i32,
+}
+
+impl AdMuteConfig {
+    /// Whether audio levels are needed to spot breaks.
+    pub fn watches_silence(&self) -> bool {
+        self.enabled && self.silence_secs > 0
+    }
+}
+
+impl Default for AdMuteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            patterns: default_ad_patterns(),
+            duck_percent: 0,
+            silence_secs: 0,
+            silence_db: default_silence_db(),
+        }
+    }
+}
+
+fn default_silence_db() -> i32 {
+    -50
+}
+
+fn default_ad_patterns() -> Vec<String> {
+    [r"\badvert", r"\bcommercial break\b", r"^ads?\b", r"\bjingle\b", r"\bwerbung\b", r"\bpublicit[eé]\b"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// Cleanup applied to stream titles before they're shown.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TitleConfig {
+    /// Drop the station's own name from the start of titles.
+    #[serde(default = "default_true")]
+    pub strip_station_name: bool,
+    /// Applied in order after the station name is gone.
+    #[serde(default = "default_title_rules")]
+    pub rules: Vec<TitleRule>,
+}
+
+impl Default for TitleConfig {
+    fn default() -> Self {
+        Self {
+            strip_station_name: true,
+            rules: default_title_rules(),
+        }
+    }
+}
+
+/// Matches of `pattern` (a case-insensitive regular expression) become `replace`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TitleRule {
+    pub pattern: String,
+    /// May refer to groups as `$1`; empty deletes the match.
+    #[serde(default)]
+    pub replace: String,
+    /// Station UUID the rule is limited to; every station when unset.
+    #[serde(default)]
+    pub station: Option<String>,
+}
+
+fn default_title_rules() -> Vec<TitleRule> {
+    vec![TitleRule {
+        // Decoration around the whole title: "*** Artist - Title ***". Runs
+        // of marks, or single ones set apart by a space, so "Prelude in C#"
+        // and "*NSYNC" are left alone.
+        pattern: r"^\s*(?:[*~=#|]{2,}|[*~=#|]\s)[\s*~=#|]*|[\s*~=#|]*(?:[*~=#|]{2,}|\s[*~=#|])\s*$".to_string(),
+        replace: String::new(),
+        station: None,
+    }]
+}
+
+/// Codecs offered as switches in the settings; hand-edited entries are kept.
+pub const QUALITY_CODECS: [&str; 5] = ["MP3", "AAC", "AAC+", "OGG", "FLAC"];
+
+/// Minimum quality for search results; stations below it are hidden, not dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QualityConfig {
+    /// Lowest bitrate in kbps; 0 allows any.
+    #[serde(default)]
+    pub min_bitrate: u32,
+    /// Codecs as Radio Browser names them; empty allows any.
+    #[serde(default)]
+    pub codecs: Vec<String>,
+}
+
+impl QualityConfig {
+    /// Whether `station` meets the minimum. Stations that don't report a
+    /// bitrate or codec get the benefit of the doubt.
+    pub fn allows(&self, station: &Station) -> bool {
+        let bitrate_ok = match station.bitrate {
+            Some(kbps) if kbps > 0 => kbps >= self.min_bitrate,
+            _ => true,
+        };
+        let codec_ok = match station.codec.as_deref().map(str::trim) {
+            Some(codec) if !codec.is_empty() && !codec.eq_ignore_ascii_case("unknown") => self.allows_codec(codec),
+            _ => true,
+        };
+        bitrate_ok && codec_ok
+    }
+
+    pub fn allows_codec(&self, codec: &str) -> bool {
+        self.codecs.is_empty() || self.codecs.iter().any(|c| c.eq_ignore_ascii_case(codec))
+    }
+
+    /// Switches one codec on or off. Switching off the last allowed codec is
+    /// ignored, and allowing every codec clears the list again.
+    pub fn set_codec(&mut self, codec: &str, allowed: bool) {
+        let mut codecs = if self.codecs.is_empty() {
+            QUALITY_CODECS.map(String::from).to_vec()
+        } else {
+            self.codecs.clone()
+        };
+        codecs.retain(|c| !c.eq_ignore_ascii_case(codec));
+        if allowed {
+            codecs.push(codec.to_string());
+        }
+        if codecs.is_empty() {
+            return;
+        }
+        let everything = codecs.len() == QUALITY_CODECS.len()
+            && QUALITY_CODECS.iter().all(|q| codecs.iter().any(|c| c.eq_ignore_ascii_case(q)));
+        self.codecs = if everything { Vec::new() } else { codecs };
+    }
+}
+
+/// Which of a broadcaster's streams to play. Radio Browser often lists one
+/// station several times, once per codec or bitrate; by default the entry
+/// the user picked plays.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamChoice {
+    /// Prefer streams in this codec, e.g. `"AAC"`.
+    #[serde(default)]
+    pub codec: Option<String>,
+    /// Prefer streams with at least this many kbps; 0 takes any.
+    #[serde(default)]
+    pub min_bitrate: u32,
+    /// What to play when no stream is preferred, or none matches.
+    #[serde(default)]
+    pub otherwise: StreamFallback,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamFallback {
+    /// The entry the user picked.
+    #[default]
+    Listed,
+    /// The highest bitrate.
+    Highest,
+    /// The lowest bitrate, to save data.
+    Lowest,
+}
+
+impl StreamChoice {
+    /// Whether the listed entry could be swapped for another one at all.
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// The entry to play out of `listed` and other entries for the same
+    /// station (most voted first). Broken streams are passed over, and the
+    /// listed entry wins ties.
+    pub fn choose<'a>(&self, listed: &'a Station, others: &'a [Station]) -> &'a Station {
+        let playable: Vec<&Station> = std::iter::once(listed)
+            .chain(others.iter().filter(|s| s.stationuuid != listed.stationuuid && s.lastcheckok != Some(0)))
+            .collect();
+        if self.codec.is_some() || self.min_bitrate > 0 {
+            let codec_ok = |s: &Station| match &self.codec {
+                Some(codec) => s.codec.as_deref().is_some_and(|c| c.trim().eq_ignore_ascii_case(codec)),
+                None => true,
+            };
+            if let Some(s) = playable
+                .iter()
+                .find(|s| codec_ok(s) && s.bitrate.unwrap_or(0) >= self.min_bitrate)
+            {
+                return s;
+            }
+        }
+        let picked = match self.otherwise {
+            StreamFallback::Listed => None,
+            // Reversed so the first of equals comes out.
+            StreamFallback::Highest => playable.iter().rev().max_by_key(|s| s.bitrate.unwrap_or(0)),
+            StreamFallback::Lowest => playable
+                .iter()
+                .filter(|s| s.bitrate.is_some_and(|b| b > 0))
+                .min_by_key(|s| s.bitrate.unwrap_or(0)),
+        };
+        picked.copied().unwrap_or(listed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeConfig {
+    /// Resuming a live stream paused for longer than this reloads it at the
+    /// live edge instead of playing the stale buffer. 0 always resumes.
+    #[serde(default = "default_snap_to_live_minutes")]
+    pub snap_to_live_minutes: u32,
+}
+
+impl Default for ResumeConfig {
+    fn default() -> Self {
+        Self {
+            snap_to_live_minutes: default_snap_to_live_minutes(),
+        }
+    }
+}
+
+fn default_snap_to_live_minutes() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    /// Send audio to a Snapcast pipe source instead of the local sound card.
+    #[serde(default)]
+    pub snapcast: bool,
+    #[serde(default = "default_snapcast_fifo")]
+    pub snapcast_fifo: PathBuf,
+    /// Run mpv with small buffers and no video/ytdl handling.
+    #[serde(default)]
+    pub low_resource: bool,
+    /// mpv audio device name, e.g. `pipewire/alsa_output.usb-headset`.
+    #[serde(default)]
+    pub audio_device: Option<String>,
+    /// Keep a second mpv idle so a new station can buffer while the current
+    /// one plays. Not used with Snapcast or low-resource playback.
+    #[serde(default = "default_true")]
+    pub standby: bool,
+    /// Pause when the output device goes away (headphones unplugged,
+    /// Bluetooth dropped) instead of carrying on through the speakers.
+    #[serde(default = "default_true")]
+    pub pause_on_disconnect: bool,
+    /// Resume once the device that caused the pause is back.
+    #[serde(default)]
+    pub resume_on_reconnect: bool,
+    /// Let mpv read the user's mpv.conf, scripts and input bindings. Off by
+    /// default so desktop video settings don't affect the radio.
+    #[serde(default)]
+    pub mpv_user_config: bool,
+    /// How much mpv buffers ahead.
+    #[serde(default)]
+    pub cache_preset: CachePreset,
+    /// Seconds to buffer with the custom preset.
+    #[serde(default = "default_cache_secs")]
+    pub cache_secs: u32,
+    /// Demuxer buffer limit in MiB with the custom preset.
+    #[serde(default = "default_demuxer_max_mib")]
+    pub demuxer_max_mib: u32,
+    /// Which version of an HLS stream to play when it offers several.
+    #[serde(default)]
+    pub hls_variant: HlsVariant,
+    /// Bring back the volume, mute and device last used with the system's
+    /// default output whenever that changes (dock plugged in, headphones
+    /// made the default).
+    #[serde(default = "default_true")]
+    pub per_output: bool,
+    /// Those settings, keyed by the sound server's name for the default
+    /// output (its sink).
+    #[serde(default)]
+    pub profiles: BTreeMap<String, OutputProfile>,
+}
+
+/// Audio settings remembered for one default output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputProfile {
+    /// In percent.
+    #[serde(default = "default_profile_volume")]
+    pub volume: u8,
+    #[serde(default)]
+    pub muted: bool,
+    /// mpv audio device, as in [`OutputConfig::audio_device`].
+    #[serde(default)]
+    pub audio_device: Option<String>,
+}
+
+fn default_profile_volume() -> u8 {
+    100
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HlsVariant {
+    /// The highest bandwidth on offer.
+    #[default]
+    Quality,
+    /// The lowest, for metered connections.
+    DataSaver,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CachePreset {
+    /// mpv's own defaults.
+    #[default]
+    Default,
+    /// Stay close to the live edge, e.g. for sports commentary.
+    LowLatency,
+    /// Ride out Wi-Fi dropouts with a long buffer.
+    Robust,
+    /// `cache_secs` and `demuxer_max_mib` from the config.
+    Custom,
+}
+
+impl OutputConfig {
+    /// `(cache seconds, demuxer MiB)` to pass to mpv; `None` keeps mpv's defaults.
+    pub fn cache_limits(&self) -> Option<(u32, u32)> {
+        match self.cache_preset {
+            CachePreset::Default => None,
+            CachePreset::LowLatency => Some((2, 1)),
+            CachePreset::Robust => Some((60, 64)),
+            CachePreset::Custom => Some((self.cache_secs.max(1), self.demuxer_max_mib.max(1))),
+        }
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            snapcast: false,
+            snapcast_fifo: default_snapcast_fifo(),
+            low_resource: false,
+            audio_device: None,
+            standby: true,
+            pause_on_disconnect: true,
+            resume_on_reconnect: false,
+            mpv_user_config: false,
+            cache_preset: CachePreset::Default,
+            cache_secs: default_cache_secs(),
+            demuxer_max_mib: default_demuxer_max_mib(),
+            hls_variant: HlsVariant::Quality,
+            per_output: true,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+/// Starts a station at a set time of day, fading in from silence.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlarmConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Local time of day.
+    #[serde(default = "default_alarm_hour")]
+    pub hour: u8,
+    #[serde(default)]
+    pub minute: u8,
+    /// Without a station the alarm never goes off.
+    #[serde(default)]
+    pub station: Option<StationRef>,
+    /// Volume the fade-in ends at, in percent.
+    #[serde(default = "default_alarm_volume")]
+    pub volume: u8,
+    /// Seconds from silence to full volume, 1–60.
+    #[serde(default = "default_alarm_fade_in_secs")]
+    pub fade_in_secs: u32,
+}
+
+/// How late an alarm may still go off, e.g. when the check ran right after a resume.
+const ALARM_GRACE_MINUTES: u32 = 5;
+
+impl AlarmConfig {
+    pub const MAX_FADE_IN_SECS: u32 = 60;
+
+    /// Minutes after local midnight.
+    pub fn minute_of_day(&self) -> u32 {
+        u32::from(self.hour.min(23)) * 60 + u32::from(self.minute.min(59))
+    }
+
+    /// Whether the alarm should go off at this local time (minutes after midnight).
+    pub fn is_due(&self, now: u32) -> bool {
+        self.enabled
+            && self.station.is_some()
+            && (now + 24 * 60 - self.minute_of_day()) % (24 * 60) < ALARM_GRACE_MINUTES
+    }
+
+    pub fn fade_in(&self) -> Duration {
+        Duration::from_secs(self.fade_in_secs.clamp(1, Self::MAX_FADE_IN_SECS).into())
+    }
+}
+
+impl Default for AlarmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hour: default_alarm_hour(),
+            minute: 0,
+            station: None,
+            volume: default_alarm_volume(),
+            fade_in_secs: default_alarm_fade_in_secs(),
+        }
+    }
+}
+
+/// Where recordings go and which shows are recorded on a schedule.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingConfig {
+    /// `None` records to `~/Music/Radio recordings`.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub schedules: Vec<RecordingSchedule>,
+    /// Start a new file, tagged with artist and title, for each song.
+    #[serde(default)]
+    pub split_songs: bool,
+}
+
+impl RecordingConfig {
+    pub fn dir(&self) -> Result<PathBuf> {
+        match &self.dir {
+            Some(dir) => Ok(dir.clone()),
+            None => {
+                let home = std::env::var_os("HOME").context("Could not determine the home directory")?;
+                Ok(PathBuf::from(home).join("Music").join("Radio recordings"))
+            }
+        }
+    }
+}
+
+/// A show recorded every week, e.g. a station on Saturdays 18:00–19:00.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingSchedule {
+    pub station: StationRef,
+    /// Days it runs on, 0 for Sunday to 6 for Saturday.
+    #[serde(default)]
+    pub days: Vec<u8>,
+    /// Local start time.
+    pub hour: u8,
+    #[serde(default)]
+    pub minute: u8,
+    /// Length of the show; at most a day.
+    pub minutes: u32,
+    /// Record without playing it.
+    #[serde(default = "default_true")]
+    pub muted: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl RecordingSchedule {
+    pub const MAX_MINUTES: u32 = 24 * 60;
+
+    /// Minutes after local midnight.
+    pub fn minute_of_day(&self) -> u32 {
+        u32::from(self.hour.min(23)) * 60 + u32::from(self.minute.min(59))
+    }
+
+    /// Whether the show starts at this local time; like the alarm, a few
+    /// minutes late still counts.
+    pub fn is_due(&self, weekday: u8, now: u32) -> bool {
+        self.enabled
+            && self.days.contains(&weekday)
+            && now >= self.minute_of_day()
+            && now - self.minute_of_day() < ALARM_GRACE_MINUTES
+    }
+
+    pub fn length(&self) -> Duration {
+        Duration::from_secs(u64::from(self.minutes.clamp(1, Self::MAX_MINUTES)) * 60)
+    }
+}
+
+/// The station of the day card.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveryConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// The latest pick, kept so it stays the same all day.
+    #[serde(default)]
+    pub today: Option<DailyStation>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            today: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyStation {
+    /// Local calendar day it was picked for, as `year * 1000 + day of year`.
+    pub day: u32,
+    pub station: Station,
+    /// The user closed the card; it comes back with tomorrow's pick.
+    #[serde(default)]
+    pub dismissed: bool,
+}
+
+/// Dictated searches, in builds with the `voice-search` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoiceConfig {
+    /// Speech-to-text program and its arguments. The recorded 16 kHz mono WAV
+    /// is appended and the text read from stdout, e.g.
+    /// `["whisper-cli", "-m", "/path/to/ggml-base.en.bin", "-nt", "-np", "-f"]`.
+    /// Empty hides the microphone button.
+    #[serde(default)]
+    pub transcriber: Vec<String>,
+    /// How long to listen, in seconds.
+    #[serde(default = "default_record_secs")]
+    pub record_secs: u32,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            transcriber: Vec::new(),
+            record_secs: default_record_secs(),
+        }
+    }
+}
+
+fn default_record_secs() -> u32 {
+    4
+}
+
+fn default_alarm_hour() -> u8 {
+    7
+}
+
+fn default_alarm_volume() -> u8 {
+    60
+}
+
+fn default_alarm_fade_in_secs() -> u32 {
+    30
+}
+
+fn default_cache_secs() -> u32 {
+    10
+}
+
+fn default_demuxer_max_mib() -> u32 {
+    32
+}
+
+fn default_snapcast_fifo() -> PathBuf {
+    PathBuf::from("/tmp/snapfifo")
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl AppConfig {
@@ -28,77 +918,579 @@ impl AppConfig {
         toml::from_str(&text).with_context(|| format!("Invalid config TOML: {path:?}"))
     }
 
-    pub fn save_atomic(&self) -> Result<()> {
+    fn save_atomic(&self) -> Result<()> {
         let path = config_path()?;
-        if let Some(parent) = path.parent() {
-            ensure_private_dir(parent)?;
-        }
         let data = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        // Before the write, so the file watcher can't see it first.
+        *LAST_SAVED.lock().unwrap_or_else(|e| e.into_inner()) = Some(text_hash(data.as_bytes()));
+        write_atomic(&path, data.as_bytes())
+    }
 
-        let parent = path.parent().context("Config path has no parent")?;
-        let suffix: String = rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(10)
-            .map(char::from)
-            .collect();
-        let tmp = parent.join(format!(
-            ".{}.tmp.{suffix}",
-            path.file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("config.toml")
-        ));
+    pub fn accept_consent(&mut self, privacy: PrivacyConfig) {
+        if !privacy.remember_history {
+            self.last_station = None;
+            self.last_server = None;
+        }
+        self.network_consent = true;
+        self.privacy = privacy;
+    }
 
-        {
-            let mut file = fs::File::create(&tmp).with_context(|| format!("Create temp file: {tmp:?}"))?;
-            file.write_all(data.as_bytes())
-                .with_context(|| format!("Write temp file: {tmp:?}"))?;
-            file.sync_all()
-                .with_context(|| format!("Sync temp file: {tmp:?}"))?;
+    pub fn set_favorite_color(&mut self, stationuuid: &str, color: Option<LabelColor>) {
+        match color {
+            Some(c) if self.favorites.iter().any(|f| f.stationuuid == stationuuid) => {
+                self.favorite_colors.insert(stationuuid.to_string(), c);
+            }
+            _ => {
+                self.favorite_colors.remove(stationuuid);
+            }
         }
+    }
 
-        fs::rename(&tmp, &path).with_context(|| format!("Atomic rename to: {path:?}"))?;
+    pub fn toggle_favorite(&mut self, station: StationRef) {
+        if !self.remove_favorite(&station.stationuuid) {
+            self.favorites.push(station);
+        }
+    }
 
-        if let Some(dir) = path.parent() {
-            let dir_file = fs::File::open(dir).with_context(|| format!("Open config dir: {dir:?}"))?;
-            let _ = dir_file.sync_all();
+    /// Points the favorite `old` at a new Radio Browser entry, keeping its
+    /// place, color and stream preference, and moves the last station, the
+    /// alarm and recording schedules that play it along. Returns whether
+    /// anything changed.
+    pub fn remap_favorite(&mut self, old: &str, new: &StationRef) -> bool {
+        if self.favorites.iter().any(|s| s.stationuuid == new.stationuuid) {
+            // Already a favorite under the new id; the stale entry stays for the user to remove.
+            return false;
+        }
+        let Some(fav) = self.favorites.iter_mut().find(|s| s.stationuuid == old) else {
+            return false;
+        };
+        fav.stationuuid = new.stationuuid.clone();
+        if let Some(color) = self.favorite_colors.remove(old) {
+            self.favorite_colors.insert(new.stationuuid.clone(), color);
         }
+        if let Some(choice) = self.favorite_stream_choice.remove(old) {
+            self.favorite_stream_choice.insert(new.stationuuid.clone(), choice);
+        }
+        let schedules = self.recording.schedules.iter_mut().map(|s| &mut s.station);
+        for station in self.last_station.iter_mut().chain(self.alarm.station.as_mut()).chain(schedules) {
+            if station.stationuuid == old {
+                station.stationuuid = new.stationuuid.clone();
+            }
+        }
+        true
+    }
 
-        Ok(())
+    /// Replaces the host-name placeholder of a custom favorite with the name
+    /// its stream announces; names given any other way are kept. Returns
+    /// whether anything changed.
+    pub fn name_custom_favorite(&mut self, stationuuid: &str, announced: &str) -> bool {
+        let Some(fav) = self.favorites.iter_mut().find(|s| s.stationuuid == stationuuid) else {
+            return false;
+        };
+        let Some(url) = fav.url.as_deref() else {
+            return false;
+        };
+        let host = url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string));
+        if fav.name == announced || (fav.name != url && host.as_deref() != Some(fav.name.as_str())) {
+            return false;
+        }
+        fav.name = announced.to_string();
+        if let Some(last) = self.last_station.as_mut().filter(|s| s.stationuuid == stationuuid) {
+            last.name = announced.to_string();
+        }
+        true
     }
 
-    pub fn toggle_favorite(&mut self, station: StationRef) {
-        if let Some(idx) = self
+    /// Replaces the favorites with an edited copy of the list: renamed,
+    /// regrouped, reordered or with entries dropped, but never with new
+    /// stations. Returns whether anything changed.
+    pub fn apply_favorites_edit(&mut self, edited: Vec<StationRef>) -> bool {
+        let edited: Vec<StationRef> = edited
+            .into_iter()
+            .filter(|e| self.favorites.iter().any(|f| f.stationuuid == e.stationuuid))
+            .map(|mut e| {
+                e.group = e.group.as_deref().map(str::trim).filter(|g| !g.is_empty()).map(str::to_string);
+                e
+            })
+            .collect();
+        if edited == self.favorites {
+            return false;
+        }
+        self.favorite_colors
+            .retain(|uuid, _| edited.iter().any(|e| e.stationuuid == *uuid));
+        self.favorite_stream_choice
+            .retain(|uuid, _| edited.iter().any(|e| e.stationuuid == *uuid));
+        if let Some(last) = self.last_station.as_mut() {
+            if let Some(e) = edited.iter().find(|e| e.stationuuid == last.stationuuid) {
+                last.name = e.name.clone();
+            }
+        }
+        self.favorites = edited;
+        true
+    }
+
+    /// Removes a favorite with its color label and stream preference.
+    /// Returns whether it existed.
+    pub fn remove_favorite(&mut self, stationuuid: &str) -> bool {
+        let Some(idx) = self
             .favorites
             .iter()
-            .position(|s| s.stationuuid == station.stationuuid)
-        {
-            self.favorites.remove(idx);
-        } else {
-            self.favorites.push(station);
+            .position(|s| s.stationuuid == stationuuid)
+        else {
+            return false;
+        };
+        self.favorites.remove(idx);
+        self.favorite_colors.remove(stationuuid);
+        self.favorite_stream_choice.remove(stationuuid);
+        true
+    }
+
+    /// The stream preference for a station: its own if it's a favorite with
+    /// one, the global one otherwise.
+    pub fn stream_choice_for(&self, stationuuid: &str) -> &StreamChoice {
+        self.favorite_stream_choice.get(stationuuid).unwrap_or(&self.stream_choice)
+    }
+
+    /// Gives a favorite its own stream preference, or `None` to follow the
+    /// global one.
+    pub fn set_favorite_stream_choice(&mut self, stationuuid: &str, choice: Option<StreamChoice>) {
+        match choice {
+            Some(c) if self.favorites.iter().any(|f| f.stationuuid == stationuuid) => {
+                self.favorite_stream_choice.insert(stationuuid.to_string(), c);
+            }
+            _ => {
+                self.favorite_stream_choice.remove(stationuuid);
+            }
         }
     }
 }
 
-fn config_path() -> Result<PathBuf> {
-    let base = std::env::var_os("XDG_CONFIG_HOME")
+/// Writes the config one save at a time, newest wins: saves queued while a
+/// write runs collapse into one of the latest config, so an older copy can
+/// never land after a newer one.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSaver {
+    latest: Arc<Mutex<Option<AppConfig>>>,
+    writing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl ConfigSaver {
+    /// Saves `config` in the background.
+    pub fn save(&self, config: &AppConfig) {
+        *self.latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+        let saver = self.clone();
+        tokio::spawn(async move { saver.write_latest().await });
+    }
+
+    /// Saves `config` and waits until it's on disk, e.g. before quitting.
+    pub async fn save_now(&self, config: &AppConfig) {
+        *self.latest.lock().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+        self.flush().await;
+    }
+
+    /// Waits for queued saves to reach the disk.
+    pub async fn flush(&self) {
+        self.write_latest().await;
+    }
+
+    async fn write_latest(&self) {
+        // Fair lock: whoever holds it next sees the newest config.
+        let _writing = self.writing.lock().await;
+        let Some(config) = self.latest.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            // A save queued earlier already wrote it.
+            return;
+        };
+        match tokio::task::spawn_blocking(move || config.save_atomic()).await {
+            Ok(Err(e)) => tracing::warn!(error = ?e, "config not saved"),
+            Err(e) => tracing::warn!(error = ?e, "config save task failed"),
+            Ok(Ok(())) => {}
+        }
+    }
+}
+
+/// Hash of the config text this process wrote last, so the file watcher can
+/// tell its own saves from edits made elsewhere.
+static LAST_SAVED: Mutex<Option<u64>> = Mutex::new(None);
+
+fn text_hash(data: &[u8]) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether `data` is exactly what this process last saved.
+pub fn saved_by_us(data: &[u8]) -> bool {
+    *LAST_SAVED.lock().unwrap_or_else(|e| e.into_inner()) == Some(text_hash(data))
+}
+
+/// Profile whose config and state are used, from `--profile` or `RADIOWIDGET_PROFILE`.
+static PROFILE: OnceLock<Option<String>> = OnceLock::new();
+
+/// Picks the profile for this process, `cli` taking precedence over
+/// `RADIOWIDGET_PROFILE`. Must run before any path below is used.
+pub fn select_profile(cli: Option<String>) -> Result<()> {
+    let name = cli
+        .or_else(|| std::env::var("RADIOWIDGET_PROFILE").ok())
+        .filter(|n| !n.is_empty());
+    if let Some(n) = &name {
+        anyhow::ensure!(
+            valid_profile_name(n),
+            "Invalid profile name {n:?}: use letters, digits, '-' and '_'"
+        );
+    }
+    PROFILE
+        .set(name)
+        .map_err(|_| anyhow::anyhow!("Profile selected twice"))
+}
+
+/// The selected profile; `None` for the default setup.
+pub fn profile() -> Option<&'static str> {
+    PROFILE
+        .get_or_init(|| {
+            std::env::var("RADIOWIDGET_PROFILE")
+                .ok()
+                .filter(|n| valid_profile_name(n))
+        })
+        .as_deref()
+}
+
+fn valid_profile_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `base/radiowidget`, or `base/radiowidget/profiles/<name>` for a profile.
+pub fn app_dir(base: &Path) -> PathBuf {
+    profile_dir(base, profile())
+}
+
+fn profile_dir(base: &Path, profile: Option<&str>) -> PathBuf {
+    let dir = base.join("radiowidget");
+    match profile {
+        Some(name) => dir.join("profiles").join(name),
+        None => dir,
+    }
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    Ok(xdg_dir("XDG_CONFIG_HOME", ".config")?.join("config.toml"))
+}
+
+/// `$XDG_CACHE_HOME/radiowidget`: data that can be refetched at any time.
+pub fn cache_dir() -> Result<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_STATE_HOME/radiowidget`: runtime state worth keeping across restarts.
+pub fn state_dir() -> Result<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+fn xdg_dir(var: &str, home_fallback: &str) -> Result<PathBuf> {
+    let base = std::env::var_os(var)
         .map(PathBuf::from)
         .or_else(|| {
-            std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config"))
+            std::env::var_os("HOME").map(|h| PathBuf::from(h).join(home_fallback))
         })
-        .context("Could not determine XDG config directory")?;
-    Ok(base.join("radiowidget").join("config.toml"))
+        .with_context(|| format!("Could not determine {var} directory"))?;
+    Ok(app_dir(&base))
+}
+
+/// Writes `data` to a temp file next to `path` and renames it into place, so
+/// readers never see a half-written file.
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let parent = path.parent().context("Path has no parent")?;
+    ensure_private_dir(parent)?;
+
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(10)
+        .map(char::from)
+        .collect();
+    let tmp = parent.join(format!(
+        ".{}.tmp.{suffix}",
+        path.file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("data")
+    ));
+
+    {
+        let mut file = fs::File::create(&tmp).with_context(|| format!("Create temp file: {tmp:?}"))?;
+        file.write_all(data)
+            .with_context(|| format!("Write temp file: {tmp:?}"))?;
+        file.sync_all()
+            .with_context(|| format!("Sync temp file: {tmp:?}"))?;
+    }
+
+    fs::rename(&tmp, path).with_context(|| format!("Atomic rename to: {path:?}"))?;
+
+    let dir_file = fs::File::open(parent).with_context(|| format!("Open dir: {parent:?}"))?;
+    let _ = dir_file.sync_all();
+
+    Ok(())
 }
 
-fn ensure_private_dir(path: &Path) -> Result<()> {
+pub fn ensure_private_dir(path: &Path) -> Result<()> {
     if path.exists() {
         return Ok(());
     }
-    fs::create_dir_all(path).with_context(|| format!("Create config dir: {path:?}"))?;
+    fs::create_dir_all(path).with_context(|| format!("Create dir: {path:?}"))?;
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
         fs::set_permissions(path, fs::Permissions::from_mode(0o700))
-            .with_context(|| format!("Set permissions on config dir: {path:?}"))?;
+            .with_context(|| format!("Set permissions on dir: {path:?}"))?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_get_their_own_directories() {
+        let base = Path::new("/home/me/.config");
+        assert_eq!(profile_dir(base, None), Path::new("/home/me/.config/radiowidget"));
+        assert_eq!(
+            profile_dir(base, Some("work")),
+            Path::new("/home/me/.config/radiowidget/profiles/work")
+        );
+        assert!(valid_profile_name("work_2-laptop"));
+        assert!(!valid_profile_name("../personal"));
+        assert!(!valid_profile_name(""));
+    }
+
+    #[test]
+    fn alarm_goes_off_within_a_few_minutes() {
+        let mut alarm = AlarmConfig {
+            enabled: true,
+            hour: 23,
+            minute: 58,
+            station: Some(StationRef {
+                stationuuid: "abc".to_string(),
+                name: "Morning FM".to_string(),
+                url: None,
+                group: None,
+            }),
+            ..AlarmConfig::default()
+        };
+        assert!(!alarm.is_due(23 * 60 + 57));
+        assert!(alarm.is_due(23 * 60 + 58));
+        // Across midnight.
+        assert!(alarm.is_due(2));
+        assert!(!alarm.is_due(3));
+        alarm.station = None;
+        assert!(!alarm.is_due(23 * 60 + 58));
+
+        alarm.fade_in_secs = 0;
+        assert_eq!(alarm.fade_in(), Duration::from_secs(1));
+        alarm.fade_in_secs = 600;
+        assert_eq!(alarm.fade_in(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn recordings_start_on_their_days() {
+        let mut show = RecordingSchedule {
+            station: StationRef {
+                stationuuid: "abc".to_string(),
+                name: "Late Show".to_string(),
+                url: None,
+                group: None,
+            },
+            days: vec![6],
+            hour: 18,
+            minute: 0,
+            minutes: 60,
+            muted: true,
+            enabled: true,
+        };
+        assert!(show.is_due(6, 18 * 60 + 2));
+        assert!(!show.is_due(5, 18 * 60 + 2));
+        assert!(!show.is_due(6, 17 * 60 + 59));
+        assert!(!show.is_due(6, 18 * 60 + 5));
+        show.enabled = false;
+        assert!(!show.is_due(6, 18 * 60));
+        show.minutes = 0;
+        assert_eq!(show.length(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn applies_batch_edits_to_favorites() {
+        let station = |uuid: &str, name: &str| StationRef {
+            stationuuid: uuid.to_string(),
+            name: name.to_string(),
+            url: None,
+            group: None,
+        };
+        let mut config = AppConfig {
+            favorites: vec![station("a", "Radio Jazz"), station("b", "Radio Rock"), station("c", "News")],
+            last_station: Some(station("a", "Radio Jazz")),
+            ..AppConfig::default()
+        };
+        config.favorite_colors.insert("b".to_string(), LabelColor::Red);
+        let aac = StreamChoice {
+            codec: Some("AAC".to_string()),
+            ..StreamChoice::default()
+        };
+        config.set_favorite_stream_choice("a", Some(aac.clone()));
+        config.set_favorite_stream_choice("b", Some(aac.clone()));
+
+        let edited = vec![station("a", "Jazz"), station("c", "News"), station("zzz", "Not a favorite")];
+        assert!(config.apply_favorites_edit(edited.clone()));
+        assert_eq!(config.favorites, edited[..2]);
+        assert!(config.favorite_colors.is_empty());
+        assert_eq!(config.stream_choice_for("a"), &aac);
+        assert!(!config.favorite_stream_choice.contains_key("b"));
+        assert_eq!(config.last_station.as_ref().unwrap().name, "Jazz");
+        assert!(!config.apply_favorites_edit(edited));
+
+        let mut grouped = config.favorites.clone();
+        grouped[0].group = Some(" Jazz ".to_string());
+        grouped[1].group = Some(String::new());
+        assert!(config.apply_favorites_edit(grouped));
+        assert_eq!(config.favorites[0].group.as_deref(), Some("Jazz"));
+        assert_eq!(config.favorites[1].group, None);
+    }
+
+    #[test]
+    fn remapping_a_favorite_keeps_its_settings() {
+        let station = |uuid: &str| StationRef {
+            stationuuid: uuid.to_string(),
+            name: "Jazz FM".to_string(),
+            url: None,
+            group: None,
+        };
+        let mut config = AppConfig {
+            favorites: vec![station("old")],
+            ..AppConfig::default()
+        };
+        config.favorite_colors.insert("old".to_string(), LabelColor::Red);
+        let aac = StreamChoice {
+            codec: Some("AAC".to_string()),
+            ..StreamChoice::default()
+        };
+        config.set_favorite_stream_choice("old", Some(aac.clone()));
+        config.alarm.station = Some(station("old"));
+
+        assert!(config.remap_favorite("old", &station("new")));
+        assert_eq!(config.favorites, [station("new")]);
+        assert_eq!(config.alarm.station, Some(station("new")));
+        assert_eq!(config.favorite_colors.get("new"), Some(&LabelColor::Red));
+        assert_eq!(config.stream_choice_for("new"), &aac);
+        assert!(!config.favorite_stream_choice.contains_key("old"));
+    }
+
+    #[test]
+    fn quality_filter_hides_weak_streams() {
+        let station = |codec: Option<&str>, bitrate: Option<u32>| Station {
+            stationuuid: "a".to_string(),
+            name: "Jazz FM".to_string(),
+            codec: codec.map(str::to_string),
+            bitrate,
+            ..Default::default()
+        };
+        let mut quality = QualityConfig {
+            min_bitrate: 128,
+            ..QualityConfig::default()
+        };
+        assert!(quality.allows(&station(Some("MP3"), Some(128))));
+        assert!(!quality.allows(&station(Some("MP3"), Some(64))));
+        // Unknown bitrate or codec isn't held against a station.
+        assert!(quality.allows(&station(Some("UNKNOWN"), Some(0))));
+
+        quality.set_codec("MP3", false);
+        assert!(!quality.allows(&station(Some("mp3"), Some(320))));
+        assert!(quality.allows(&station(Some("AAC"), Some(128))));
+        for codec in QUALITY_CODECS {
+            quality.set_codec(codec, false);
+        }
+        assert_eq!(quality.codecs, ["FLAC"]);
+        quality.set_codec("mp3", true);
+        for codec in ["AAC", "AAC+", "OGG"] {
+            quality.set_codec(codec, true);
+        }
+        assert!(quality.codecs.is_empty());
+    }
+
+    #[test]
+    fn chooses_the_preferred_stream() {
+        let station = |uuid: &str, codec: &str, bitrate: u32| Station {
+            stationuuid: uuid.to_string(),
+            name: "Jazz FM".to_string(),
+            codec: Some(codec.to_string()),
+            bitrate: Some(bitrate),
+            lastcheckok: Some(1),
+            ..Default::default()
+        };
+        let listed = station("listed", "MP3", 128);
+        let mut broken = station("broken", "AAC", 192);
+        broken.lastcheckok = Some(0);
+        let others = vec![
+            broken,
+            station("aac-64", "AAC", 64),
+            station("aac-128", "aac", 128),
+            station("mp3-320", "MP3", 320),
+            listed.clone(),
+        ];
+        let chosen = |choice: StreamChoice| choice.choose(&listed, &others).stationuuid.clone();
+
+        assert!(!StreamChoice::default().is_active());
+        assert_eq!(chosen(StreamChoice::default()), "listed");
+        let aac = StreamChoice {
+            codec: Some("AAC".to_string()),
+            min_bitrate: 128,
+            otherwise: StreamFallback::Lowest,
+        };
+        assert_eq!(chosen(aac.clone()), "aac-128");
+        let flac = StreamChoice {
+            codec: Some("FLAC".to_string()),
+            ..aac.clone()
+        };
+        assert_eq!(chosen(flac.clone()), "aac-64");
+        assert_eq!(
+            chosen(StreamChoice {
+                otherwise: StreamFallback::Listed,
+                ..flac
+            }),
+            "listed"
+        );
+        assert_eq!(
+            chosen(StreamChoice {
+                otherwise: StreamFallback::Highest,
+                ..StreamChoice::default()
+            }),
+            "mp3-320"
+        );
+    }
+
+    #[test]
+    fn names_custom_favorites_from_the_stream() {
+        let url = "http://stream.example.org:8000/live";
+        let mut config = AppConfig::default();
+        config.favorites.push(StationRef {
+            stationuuid: format!("custom:{url}"),
+            name: "stream.example.org".to_string(),
+            url: Some(url.to_string()),
+            group: None,
+        });
+        let uuid = format!("custom:{url}");
+        assert!(config.name_custom_favorite(&uuid, "Jazz FM"));
+        assert_eq!(config.favorites[0].name, "Jazz FM");
+        // A real name is never overwritten.
+        assert!(!config.name_custom_favorite(&uuid, "Something else"));
+    }
+
+    #[test]
+    fn cache_presets() {
+        let mut output = OutputConfig::default();
+        assert_eq!(output.cache_limits(), None);
+        output.cache_preset = CachePreset::LowLatency;
+        assert_eq!(output.cache_limits(), Some((2, 1)));
+        output.cache_preset = CachePreset::Custom;
+        output.cache_secs = 0;
+        assert_eq!(output.cache_limits(), Some((1, 32)));
+    }
+}
@@ -0,0 +1,127 @@
+//! Reloads the config when something else changes it: a second instance, a
+//! sync tool or a text editor. Watches the directory rather than the file,
+//! since atomic saves replace the file with a new one.
+
+use crate::controller::UiCommand;
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Editors and sync tools often write in several steps; wait for them to finish.
+const SETTLE: Duration = Duration::from_millis(300);
+/// Size of `struct inotify_event` before the name.
+const EVENT_HEADER: usize = 16;
+
+/// Sends [`UiCommand::ReloadConfig`] whenever `path` changes on disk, except
+/// for this process's own saves. Returns when the controller goes away.
+pub async fn watch(path: PathBuf, tx: mpsc::UnboundedSender<UiCommand>) -> Result<()> {
+    let dir = path.parent().context("Config path has no parent")?;
+    let name = path.file_name().context("Config path has no file name")?.as_bytes().to_vec();
+    crate::config::ensure_private_dir(dir)?;
+
+    // SAFETY: plain syscalls; the returned descriptor is owned from here on.
+    let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error()).context("inotify_init1");
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let cdir = CString::new(dir.as_os_str().as_bytes()).context("Config dir contains NUL")?;
+    let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), cdir.as_ptr(), libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO) };
+    if wd < 0 {
+        return Err(io::Error::last_os_error()).with_context(|| format!("Watch {dir:?}"));
+    }
+    let fd = AsyncFd::new(fd).context("Register inotify descriptor")?;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = read_events(&fd, &mut buf).await?;
+        if !event_names(&buf[..n]).any(|n| n == name.as_slice()) {
+            continue;
+        }
+        tokio::time::sleep(SETTLE).await;
+        // Whatever arrived meanwhile is covered by the reload below.
+        while let Ok(Ok(_)) = tokio::time::timeout(Duration::ZERO, read_events(&fd, &mut buf)).await {}
+        let data = match tokio::fs::read(&path).await {
+            Ok(d) => d,
+            Err(e) => {
+                debug!(error = %e, "config changed but can't be read");
+                continue;
+            }
+        };
+        if crate::config::saved_by_us(&data) {
+            continue;
+        }
+        debug!("config changed on disk");
+        if tx.send(UiCommand::ReloadConfig).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn read_events(fd: &AsyncFd<OwnedFd>, buf: &mut [u8]) -> Result<usize> {
+    loop {
+        let mut guard = fd.readable().await.context("Wait for inotify")?;
+        let res = guard.try_io(|inner| {
+            // SAFETY: `buf` is valid for `buf.len()` bytes.
+            let n = unsafe { libc::read(inner.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if n < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(n as usize)
+            }
+        });
+        match res {
+            Ok(n) => return n.context("Read inotify events"),
+            Err(_would_block) => continue,
+        }
+    }
+}
+
+/// File names in a buffer of `inotify_event` records, without their NUL padding.
+fn event_names(buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = buf;
+    std::iter::from_fn(move || {
+        if rest.len() < EVENT_HEADER {
+            return None;
+        }
+        let len = u32::from_ne_bytes(rest[12..16].try_into().ok()?) as usize;
+        let name = rest.get(EVENT_HEADER..EVENT_HEADER + len)?;
+        rest = &rest[EVENT_HEADER + len..];
+        let end = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        Some(&name[..end])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str) -> Vec<u8> {
+        let padded = (name.len() + 1).next_multiple_of(EVENT_HEADER);
+        let mut e = Vec::new();
+        e.extend_from_slice(&1i32.to_ne_bytes());
+        e.extend_from_slice(&(libc::IN_MOVED_TO).to_ne_bytes());
+        e.extend_from_slice(&0u32.to_ne_bytes());
+        e.extend_from_slice(&(padded as u32).to_ne_bytes());
+        e.extend_from_slice(name.as_bytes());
+        e.resize(EVENT_HEADER + padded, 0);
+        e
+    }
+
+    #[test]
+    fn reads_inotify_event_names() {
+        let mut buf = event(".config.toml.tmp.abc123");
+        buf.extend(event("config.toml"));
+        let names: Vec<&[u8]> = event_names(&buf).collect();
+        assert_eq!(names, [b".config.toml.tmp.abc123".as_slice(), b"config.toml".as_slice()]);
+        // A truncated record ends the list.
+        assert_eq!(event_names(&buf[..20]).count(), 0);
+    }
+}
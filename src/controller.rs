@@ -1,49 +1,356 @@
-use crate::config::AppConfig;
-use crate::models::{Station, StationRef};
-use crate::mpv::{MpvCommand, MpvEvent, MpvProcess};
-use crate::radio_browser::RadioBrowserClient;
+use crate::ad_mute::{AdRules, SILENCE_BREAK};
+use crate::cast::{CastClient, Renderer};
+use crate::config::{
+    AdMuteConfig, AlarmConfig, AppConfig, CachePreset, ChannelConfig, ConfigSaver, DailyStation, EqualizerConfig, HlsVariant, LogLevel, OutputConfig, OutputProfile, PowerConfig, PrivacyConfig, QualityConfig, StreamChoice,
+    RecordingConfig, RecordingSchedule, ResumeConfig, UiConfig, VoiceConfig,
+};
+use crate::error_log::ErrorLog;
+use crate::fallback::FallbackPlayer;
+use crate::models::{find_successor, LabelColor, Station, StationRef};
+use crate::mpv::{
+    channel_filter, equalizer_filter, AudioDevice, AudioLevel, MpvCommand, MpvEvent, MpvOptions, MpvProcess, ResourceUsage,
+    StreamTags, StreamTech, LEVEL_FILTER,
+};
+use crate::positions::PlaybackPositions;
+use crate::radio_browser::{MirrorHealth, RadioBrowserClient};
+use crate::recording::{Recorder, RecorderEvent, RecordingFile};
+use crate::shuffle::RecentPlays;
+use crate::station_cache::StationCache;
+use crate::stream_probe::{ProbeClient, StreamInfo, StreamKind, StreamOrigin};
+use crate::suspend::SuspendEvent;
+use crate::title_rules::TitleRules;
+use crate::usage_stats::{is_new_search, Feature, UsageEvent, UsageStats, UsageSummary};
 use anyhow::{Context, Result};
-use std::path::PathBuf;
-use std::time::Duration;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 use std::sync::Arc;
 use tokio::sync::{mpsc, watch, Mutex};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum PlaybackPhase {
+    #[default]
     NotConfigured,
     Idle,
+    /// A stream is loaded but no audio is coming out yet (connecting,
+    /// buffering or stalled).
+    Buffering,
+    /// A live stream dropped; waiting to load it again.
+    Reconnecting,
     Playing,
     Paused,
     Error,
+    /// mpv isn't installed; nothing can play until it is.
+    MissingBackend,
 }
 
-#[derive(Debug, Clone)]
+/// Why a playing stream is being loaded again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// mpv crashed and was restarted.
+    Crashed,
+    /// A live stream ended as if it were a file.
+    Ended,
+    /// No audio arrived for a while.
+    Stalled,
+    /// The connection broke while audio was flowing.
+    ConnectionLost,
+    /// A reconnect attempt couldn't reach the station.
+    Unreachable,
+}
+
+impl DropReason {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Crashed => "the player crashed",
+            Self::Ended => "the stream ended",
+            Self::Stalled => "the stream stopped sending audio",
+            Self::ConnectionLost => "the connection dropped",
+            Self::Unreachable => "the station can't be reached",
+        }
+    }
+}
+
+/// The recording in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingStatus {
+    pub station: String,
+    /// The file being written, once the stream has started.
+    pub file: Option<PathBuf>,
+    /// When a scheduled recording stops, in Unix seconds.
+    pub until: Option<u64>,
+}
+
+/// The reconnect in progress, shown while the phase is
+/// [`PlaybackPhase::Reconnecting`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reconnect {
+    /// 1-based, up to [`MAX_RECONNECTS`].
+    pub attempt: u32,
+    pub reason: DropReason,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct ControllerState {
     pub phase: PlaybackPhase,
     pub station: Option<StationRef>,
     pub media_title: Option<String>,
-    pub error: Option<String>,
+    pub errors: ErrorLog,
     pub search_query: String,
     pub search_loading: bool,
+    /// Listening for, or transcribing, a spoken search.
+    pub dictating: bool,
+    pub voice: VoiceConfig,
     pub search_results: Vec<Station>,
     pub favorites: Vec<StationRef>,
+    pub consent_pending: bool,
+    pub privacy: PrivacyConfig,
+    pub renderers: Vec<Renderer>,
+    pub renderers_loading: bool,
+    pub cast_target: Option<Renderer>,
+    pub output: OutputConfig,
+    pub volume: f64,
+    pub muted: bool,
+    pub favorite_colors: BTreeMap<String, LabelColor>,
+    pub stream_choice: StreamChoice,
+    pub favorite_stream_choice: BTreeMap<String, StreamChoice>,
+    pub ui: UiConfig,
+    /// Set once the config has been loaded and applied.
+    pub ready: bool,
+    /// A pasted stream waiting for the user to play or save it.
+    pub stream_offer: Option<StreamInfo>,
+    pub stream_probe_loading: bool,
+    /// Where the current stream is served from, once checked.
+    pub stream_origin: Option<StreamOrigin>,
+    /// What the stream says about itself (icy name, genre, homepage).
+    pub stream_tags: Option<StreamTags>,
+    /// What the stream actually delivers, once mpv has decoded some of it.
+    pub stream_tech: Option<StreamTech>,
+    /// Seconds of the stream before the playing position that are still
+    /// cached, so the seek buttons can go back that far.
+    pub rewindable: u32,
+    /// When the sleep timer stops playback, in Unix seconds.
+    pub sleep_until: Option<u64>,
+    pub alarm: AlarmConfig,
+    pub recording: RecordingConfig,
+    /// Set from a record request until the recording ends.
+    pub recorder: Option<RecordingStatus>,
+    /// Files in the recordings folder, newest first.
+    pub recordings: Vec<RecordingFile>,
+    pub resume: ResumeConfig,
+    pub power: PowerConfig,
+    /// Minimum quality for search results; the UI hides what falls short.
+    pub quality: QualityConfig,
+    pub ad_mute: AdMuteConfig,
+    /// The volume is down because the title looks like an ad.
+    pub ad_ducked: bool,
+    /// Bumped whenever a Play for the station already playing was ignored.
+    pub already_playing: u64,
+    /// Click-to-audio times of recent station starts.
+    pub start_latency: StartLatency,
+    /// Stream data downloaded since listening last started from a stop.
+    pub session_bytes: u64,
+    /// How full mpv's cache is while buffering, in percent.
+    pub buffer_percent: Option<u8>,
+    /// Cached Radio Browser records for favorites, keyed by station UUID.
+    pub station_details: BTreeMap<String, Station>,
+    /// Today's suggestion, unless the user closed it.
+    pub station_of_day: Option<Station>,
+    pub discovery_enabled: bool,
+    pub favorites_refreshing: bool,
+    /// Radio Browser mirrors used this session.
+    pub mirrors: Vec<MirrorHealth>,
+    pub pinned_server: Option<String>,
+    /// Favorites removed from the list that can still be restored, oldest first.
+    pub pending_removals: Vec<StationRef>,
+    /// Something the controller did on its own that the user should know about.
+    pub notice: Option<String>,
+    /// Other entries for the station that just failed to play.
+    pub alternates: Vec<Station>,
+    /// Set while mpv uses far more CPU, memory or bandwidth than audio needs.
+    pub resource_warning: Option<String>,
+    /// Set while a dropped stream is being brought back.
+    pub reconnect: Option<Reconnect>,
+    /// Why the controller quit, e.g. an unreadable config. Nothing works
+    /// until the UI restarts it.
+    pub stopped: Option<String>,
+    /// Stations seen sending video this session.
+    pub video_stations: BTreeSet<String>,
+    /// Output devices as reported by mpv.
+    pub audio_devices: Vec<AudioDevice>,
+    pub log_level: LogLevel,
+    pub equalizer: EqualizerConfig,
+    pub channels: ChannelConfig,
+    /// Local listening statistics, if any were recorded.
+    pub usage: UsageSummary,
 }
 
+/// How long a removed favorite can be restored before the removal is saved.
+pub const UNDO_WINDOW: Duration = Duration::from_secs(5);
+
+const MAX_ALTERNATES: usize = 4;
+
+// Audio streams stay far below these; crossing them usually means video.
+const CPU_WARN_PERCENT: f64 = 50.0;
+const RSS_WARN_BYTES: u64 = 512 * 1024 * 1024;
+const READ_WARN_BYTES_PER_SEC: u64 = 1024 * 1024;
+/// Consecutive samples over a threshold before warning.
+const USAGE_STRIKES: u32 = 2;
+
+/// How long the old station takes to fade out when switching stations.
+const FADE_OUT: Duration = Duration::from_millis(800);
+const FADE_STEPS: u32 = 8;
+/// Longest the old station keeps playing while the new one buffers.
+const PRELOAD_TIMEOUT: Duration = Duration::from_secs(8);
+/// Click-to-audio time we aim for; slower starts are logged.
+const START_BUDGET: Duration = Duration::from_millis(1500);
+/// Start times kept for the median shown in diagnostics.
+const START_SAMPLES: usize = 20;
+/// Attempts to bring a dropped live stream back before giving up.
+pub const MAX_RECONNECTS: u32 = 5;
+/// The sleep timer lowers the volume over this last stretch.
+const SLEEP_FADE: Duration = Duration::from_secs(60);
+const SLEEP_FADE_STEPS: u32 = 30;
+/// How often the alarm time is checked against the clock.
+const ALARM_CHECK: Duration = Duration::from_secs(15);
+const ALARM_RAMP_STEPS: u32 = 20;
+/// Give up on the fade-in if the alarm station hasn't started by then.
+const ALARM_START_TIMEOUT: Duration = Duration::from_secs(60);
+/// Listening time is written out at most this often.
+const USAGE_SAVE: Duration = Duration::from_secs(60);
+/// Wait this long before looking for a station of the day again after a
+/// failed lookup.
+const DISCOVERY_RETRY: Duration = Duration::from_secs(30 * 60);
+/// Candidates fetched for the station of the day.
+const DISCOVERY_CANDIDATES: u32 = 50;
+/// Entries of one station weighed against its stream choice.
+const STREAM_CHOICES: u32 = 10;
 
 #[derive(Debug, Clone)]
 pub enum UiCommand {
     Search(String),
+    /// Record a spoken query and search for it.
+    Dictate,
+    /// Ignored for the station that is already playing; see [`UiCommand::Reload`].
     Play(StationRef),
+    /// [`UiCommand::Play`], reloading even a station that is already playing.
+    Replay(StationRef),
+    /// Load the current station again, even while it plays.
+    Reload,
+    /// Play a random favorite that hasn't played recently.
+    PlayRandomFavorite,
+    /// Play the next favorite of this group's shuffled round.
+    ShuffleGroup(String),
     TogglePause,
+    /// Jump back (negative) or forward within the buffered stream.
+    SeekRelative(f64),
+    SetVolume(f64),
+    AdjustVolume(f64),
+    SetMute(bool),
     Stop,
     ToggleFavorite(StationRef),
+    /// Remove after [`UNDO_WINDOW`] unless undone in the meantime.
+    RemoveFavorite(StationRef),
+    UndoRemoveFavorite(String),
+    /// The favorites list after a batch edit (renames, deletions), saved at once.
+    EditFavorites(Vec<StationRef>),
+    /// Show an error the UI ran into, in the same list as the controller's.
+    ReportError(String),
+    DismissError(u64),
+    DismissNotice,
+    /// Hide the station of the day until tomorrow's.
+    DismissStationOfDay,
+    SetDiscovery(bool),
+    /// Stop playback after this long, fading out over the last minute;
+    /// `None` cancels the timer.
+    SetSleepTimer(Option<Duration>),
+    SetAlarm(AlarmConfig),
+    /// Record the current station until stopped.
+    StartRecording,
+    StopRecording,
+    SetRecording(RecordingConfig),
+    DeleteRecording(PathBuf),
+    OpenRecordingsFolder,
+    SetQuality(QualityConfig),
+    SetAdMute(AdMuteConfig),
+    /// Refetch favorite details, ignoring the cache age.
+    RefreshFavorites,
+    SetFavoriteColor(String, Option<LabelColor>),
+    SetStreamChoice(StreamChoice),
+    /// `None` makes the favorite follow the global stream choice again.
+    SetFavoriteStreamChoice(String, Option<StreamChoice>),
+    UpdateUi(UiConfig),
+    ProbeStream(String),
+    DismissStreamOffer,
+    ReloadConfig,
+    /// Look for mpv again after it was found missing.
+    RetryBackend,
+    /// Kill and start mpv again, then reload the current station.
+    RestartBackend,
+    /// Give up on bringing a dropped stream back.
+    StopReconnecting,
+    OpenConfigFolder,
+    AcceptConsent(PrivacyConfig),
+    DiscoverRenderers,
+    SetCastTarget(Option<Renderer>),
+    SetSnapcast(bool),
+    SetLowResource(bool),
+    /// `None` returns to mpv's automatic device choice.
+    SetAudioDevice(Option<String>),
+    /// Turn the idle second mpv used for gapless switching on or off.
+    SetStandby(bool),
+    /// What to do when the output device disappears and comes back.
+    SetDisconnectPolicy { pause: bool, resume: bool },
+    /// Remember volume, mute and device for each default output.
+    SetPerOutput(bool),
+    /// Keep local listening statistics from now on, or stop.
+    SetUsageStats(bool),
+    /// Forget the listening statistics recorded so far.
+    ClearUsageStats,
+    /// Cache preset plus the seconds and MiB used by [`CachePreset::Custom`].
+    SetCache { preset: CachePreset, secs: u32, max_mib: u32 },
+    /// Which HLS variant to play; restarts mpv with it.
+    SetHlsVariant(HlsVariant),
+    SetLogLevel(LogLevel),
+    SetResume(ResumeConfig),
+    /// Block idle suspend while playing.
+    SetInhibitSuspend(bool),
+    SetEqualizer(EqualizerConfig),
+    /// Mono downmix and left/right balance.
+    SetChannels(ChannelConfig),
+    PinMirror(Option<String>),
     Shutdown,
 }
 
+impl UiCommand {
+    /// Whether this reaches Radio Browser or a stream host, which waits for
+    /// the first-run network consent.
+    fn reaches_network(&self) -> bool {
+        matches!(
+            self,
+            UiCommand::Search(_)
+                | UiCommand::Dictate
+                | UiCommand::Play(_)
+                | UiCommand::Replay(_)
+                | UiCommand::Reload
+                | UiCommand::PlayRandomFavorite
+                | UiCommand::ShuffleGroup(_)
+                | UiCommand::TogglePause
+                | UiCommand::StartRecording
+                | UiCommand::RefreshFavorites
+                | UiCommand::ProbeStream(_)
+        )
+    }
+}
+
 pub struct ControllerHandle {
     pub cmd_tx: mpsc::UnboundedSender<UiCommand>,
     pub state_rx: watch::Receiver<ControllerState>,
+    /// Latest reading for the level meter, while `ui.level_meter` is on. It
+    /// changes several times a second, so it has a channel of its own.
+    pub level_rx: watch::Receiver<Option<AudioLevel>>,
     _thread: Option<std::thread::JoinHandle<()>>,
 }
 
@@ -58,16 +365,9 @@ impl Drop for ControllerHandle {
 
 pub fn start_controller() -> ControllerHandle {
     let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
-    let (state_tx, state_rx) = watch::channel(ControllerState {
-        phase: PlaybackPhase::NotConfigured,
-        station: None,
-        media_title: None,
-        error: None,
-        search_query: String::new(),
-        search_loading: false,
-        search_results: vec![],
-        favorites: vec![],
-    });
+    let (state_tx, state_rx) = watch::channel(ControllerState::default());
+    let (level_tx, level_rx) = watch::channel(None);
+    let cmd_tx_loop = cmd_tx.clone();
 
     let thread = std::thread::spawn(move || {
         let rt = tokio::runtime::Builder::new_multi_thread()
@@ -75,8 +375,9 @@ pub fn start_controller() -> ControllerHandle {
             .build()
             .expect("tokio runtime");
         rt.block_on(async move {
-            if let Err(e) = controller_main(cmd_rx, state_tx).await {
+            if let Err(e) = controller_main(cmd_tx_loop, cmd_rx, &state_tx, &level_tx).await {
                 warn!(error = ?e, "controller exited with error");
+                state_tx.send_modify(|s| s.stopped = Some(format!("{e:#}")));
             }
         });
     });
@@ -84,82 +385,570 @@ pub fn start_controller() -> ControllerHandle {
     ControllerHandle {
         cmd_tx,
         state_rx,
+        level_rx,
         _thread: Some(thread),
     }
 }
 
+/// Turns a state or level channel into a stream that only yields the newest
+/// snapshot. Values published while the consumer is busy are coalesced instead
+/// of queued, so a slow UI never replays stale intermediate states.
+pub fn latest_states<T: Clone + Send + Sync>(rx: watch::Receiver<T>) -> impl futures_util::Stream<Item = T> {
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.changed().await.ok()?;
+        let snapshot = rx.borrow_and_update().clone();
+        Some((snapshot, rx))
+    })
+}
+
 async fn controller_main(
+    self_tx: mpsc::UnboundedSender<UiCommand>,
     mut cmd_rx: mpsc::UnboundedReceiver<UiCommand>,
-    state_tx: watch::Sender<ControllerState>,
+    state_tx: &watch::Sender<ControllerState>,
+    level_tx: &watch::Sender<Option<AudioLevel>>,
 ) -> Result<()> {
     let mut config = tokio::task::spawn_blocking(AppConfig::load)
         .await
         .context("Join config load task")?
         .context("Failed to load config")?;
+    let config_saver = ConfigSaver::default();
+    let save_config = |config: &AppConfig| config_saver.save(config);
     let mut state = state_tx.borrow().clone();
-    state.favorites = config.favorites.clone();
+    sync_config_state(&mut state, &config);
     state.station = config.last_station.clone();
     state.phase = if state.station.is_some() {
         PlaybackPhase::Idle
     } else {
         PlaybackPhase::NotConfigured
     };
+    let mut cache = tokio::task::spawn_blocking(StationCache::load)
+        .await
+        .context("Join station cache load task")?;
+    state.station_details = station_details(&config, state.station.as_ref(), &cache);
     let _ = state_tx.send(state.clone());
+    let mut positions = tokio::task::spawn_blocking(PlaybackPositions::load)
+        .await
+        .context("Join resume positions load task")?;
+    let usage_enabled = config.privacy.usage_stats;
+    let mut usage = tokio::task::spawn_blocking(move || UsageStats::load(usage_enabled))
+        .await
+        .context("Join usage statistics load task")?;
+    state.usage = usage.summary();
+    let mut usage_dirty = false;
+    let mut usage_saved = Instant::now();
+    // The last write of the statistics file; the next one waits for it, so a
+    // save can't land after the file was cleared.
+    let mut usage_write: Option<tokio::task::JoinHandle<()>> = None;
+    // The station being listened to and since when its time was last counted.
+    let mut listening: Option<(String, Instant)> = None;
 
-    let socket_path = mpv_socket_path()?;
-    let (mpv, mut mpv_events) = MpvProcess::spawn(socket_path).await?;
+    let socket_path = mpv_socket_path("mpv.sock")?;
+    let (mut mpv, mut mpv_events) = MpvProcess::spawn(socket_path, mpv_options(&config)).await?;
+    let mut standby = spawn_standby(&config).await;
 
-    let rb = Arc::new(Mutex::new(RadioBrowserClient::new(config.last_server.clone())?));
+    let mut client = RadioBrowserClient::new(config.last_server.clone())?;
+    client.set_pinned_server(config.pinned_server.clone());
+    let rb = Arc::new(Mutex::new(client));
+    let cast = CastClient::new()?;
+    let probe = ProbeClient::new()?;
     let (internal_tx, mut internal_rx) = mpsc::unbounded_channel::<InternalMsg>();
     let mut current_url: Option<String> = None;
     let mut want_paused = false;
+    // mpv's core-idle: nothing is being decoded right now.
+    let mut core_idle = true;
+    // Length of the loaded file; only set for seekable, non-live streams.
+    let mut duration: Option<f64> = None;
+    // Stream URL and duration a position query was sent for.
+    let mut pending_position: Option<(String, f64)> = None;
+    // Set when the supervisor stopped restarting a crash-looping mpv.
+    let mut mpv_gave_up = false;
+    // Set once mpv has reported the volume the user listens at; a respawned
+    // mpv gets it back rather than starting at its own default.
+    let mut levels_known = false;
+    // Volume and mute given to a respawned mpv; until it reports them, its
+    // events are its own defaults and aren't recorded.
+    let mut awaiting_levels: Option<f64> = None;
+    let mut awaiting_mute = false;
+    // GStreamer playing the stream while mpv is parked after a crash loop.
+    let mut fallback: Option<FallbackPlayer> = None;
+    // Favorites waiting out the undo window, by UUID, with the removal request
+    // they belong to so a stale timer can't commit a newer request.
+    let mut pending_removals: BTreeMap<String, u64> = BTreeMap::new();
+    let mut removal_seq = 0u64;
+    let mut recent_plays = RecentPlays::default();
+    let mut usage_strikes = 0u32;
+    let mut fade: Option<Fade> = None;
+    let mut fade_seq = 0u64;
+    // Whether the station being switched away from was still audible.
+    let mut old_audible = false;
+    let mut plays = PlayGeneration::default();
+    let mut start_timer: Option<StartTimer> = None;
+    // Played again once a restarted mpv is up, when it has no stream to reload.
+    let mut play_when_ready: Option<StationRef> = None;
+    let mut sleep_seq = 0u64;
+    // Volume from before the sleep fade started.
+    let mut sleep_restore: Option<f64> = None;
+    // The current play uses a URL remembered from an earlier lookup.
+    let mut known_url = false;
+    let mut alarm_tick = tokio::time::interval(ALARM_CHECK);
+    alarm_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The `local_day` the alarm last went off, so it goes off once per day
+    // however long the machine was suspended.
+    let mut alarm_fired: Option<u32> = None;
+    let mut alarm_ramp: Option<AlarmRamp> = None;
+    let mut alarm_seq = 0u64;
+    // The next Play is the alarm's own and keeps its ramp.
+    let mut alarm_play = false;
+    let mut recorder: Option<Recorder> = None;
+    // Bumped per record request, so a stopped one can't start late.
+    let mut recording_seq = 0u64;
+    // Local day each schedule (by index) last started on.
+    let mut schedules_started: BTreeMap<usize, u32> = BTreeMap::new();
+    list_recordings(&config, &internal_tx, None);
+    let (suspend_tx, mut suspend_rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        if let Err(e) = crate::suspend::watch(suspend_tx).await {
+            debug!(error = %e, "not following suspend and resume");
+        }
+    });
+    // When the local player was last paused, by the wall clock so a suspend
+    // in between counts too.
+    let mut paused_at: Option<SystemTime> = None;
+    tokio::spawn(crate::inhibit::follow(state_tx.subscribe()));
+    tokio::spawn(crate::mpris::serve(state_tx.subscribe(), self_tx.clone()));
+    if let Ok(path) = crate::config::config_path() {
+        let tx = self_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::config_watch::watch(path, tx).await {
+                warn!(error = ?e, "not watching the config file");
+            }
+        });
+    }
+    // Output device whose disappearance paused playback.
+    let mut disconnect_paused: Option<String> = None;
+    // The system's default output; volume, mute and device are remembered for it.
+    let mut sink: Option<String> = None;
+    // Remembered volumes not yet written to the config.
+    let mut profiles_dirty = false;
+    // Station cache changes not written yet.
+    let mut cache_dirty = false;
+    // Last lookup of a station of the day, successful or not.
+    let mut discovery_tried: Option<Instant> = None;
+    let mut ad_rules = AdRules::default();
+    // User volume to go back to once an ad is over.
+    let mut ad_duck: Option<f64> = None;
+    // When dead air started the current break; the next title ends it.
+    let mut ad_silence_break: Option<Instant> = None;
+    reload_ad_rules(&config.ad_mute, &mut ad_rules, &mut ad_duck, &mpv, &mut state);
+    let mut title_rules = load_title_rules(&config, &mut state);
+    // Paused for a suspend; reload once the system is back.
+    let mut wake_reload = false;
+    let mut preload_seq = 0u64;
+
+    if config.network_consent {
+        let stale = cache.needs_refresh(detail_uuids(&config, state.station.as_ref()));
+        fetch_stations(&rb, &internal_tx, stale, false);
+    }
 
     loop {
+        // At the top, so handlers that `continue` are covered too.
+        let playing = state
+            .station
+            .as_ref()
+            .filter(|_| state.phase == PlaybackPhase::Playing)
+            .map(|s| s.stationuuid.as_str());
+        if listening.as_ref().map(|(uuid, _)| uuid.as_str()) != playing {
+            if let Some((uuid, since)) = listening.take() {
+                let secs = since.elapsed().as_secs();
+                usage_dirty |= usage.record(UsageEvent::Listened { stationuuid: &uuid, secs });
+            }
+            listening = playing.map(|uuid| (uuid.to_string(), Instant::now()));
+        }
         tokio::select! {
             Some(cmd) = cmd_rx.recv() => {
+                if !config.network_consent && cmd.reaches_network() {
+                    // Alarms, schedules and media keys get here before the prompt does.
+                    debug!(?cmd, "ignored until network access is accepted");
+                    continue;
+                }
+                let replay = matches!(cmd, UiCommand::Replay(_));
                 match cmd {
                     UiCommand::Search(q) => {
+                        if is_new_search(&state.search_query, &q) {
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::Search));
+                        }
                         state.search_query = q;
                         state.search_loading = true;
-                        state.error = None;
+                        state.errors.clear();
                         let _ = state_tx.send(state.clone());
 
                         let q = state.search_query.clone();
                         let rb = rb.clone();
                         let tx = internal_tx.clone();
                         tokio::spawn(async move {
-                            let res = {
+                            let (res, health) = {
                                 let mut client = rb.lock().await;
-                                client.search(&q, 25).await
+                                (client.search(&q, 25).await, client.mirror_health())
                             };
+                            let _ = tx.send(InternalMsg::MirrorHealth(health));
                             let _ = tx.send(InternalMsg::SearchDone { query: q, res });
                         });
                     }
-                    UiCommand::Play(station) => {
-                        state.error = None;
+                    UiCommand::Dictate => {
+                        if state.dictating {
+                            continue;
+                        }
+                        #[cfg(feature = "voice-search")]
+                        {
+                            state.dictating = true;
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::VoiceSearch));
+                            let voice = config.voice.clone();
+                            let tx = internal_tx.clone();
+                            tokio::spawn(async move {
+                                let _ = tx.send(InternalMsg::Dictated(crate::voice::dictate(&voice).await));
+                            });
+                        }
+                        #[cfg(not(feature = "voice-search"))]
+                        state.errors.push("Voice search isn't part of this build".to_string());
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::SetSleepTimer(after) => {
+                        sleep_seq += 1;
+                        if let Some(v) = sleep_restore.take() {
+                            // Cancelled mid-fade.
+                            let _ = mpv.command(MpvCommand::SetVolume(v));
+                        }
+                        state.sleep_until = after.map(|d| now_secs() + d.as_secs());
+                        if let Some(after) = after {
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::SleepTimer));
+                            spawn_sleep_timer(&internal_tx, sleep_seq, after);
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::SetAlarm(mut alarm) => {
+                        alarm.fade_in_secs = alarm.fade_in_secs.clamp(1, AlarmConfig::MAX_FADE_IN_SECS);
+                        alarm.volume = alarm.volume.min(100);
+                        if config.alarm == alarm {
+                            continue;
+                        }
+                        if alarm.minute_of_day() != config.alarm.minute_of_day() {
+                            alarm_fired = None;
+                        }
+                        config.alarm = alarm;
+                        state.alarm = config.alarm.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::StartRecording => {
+                        let Some(station) = state.station.clone() else {
+                            continue;
+                        };
+                        if state.recorder.is_some() {
+                            continue;
+                        }
+                        recording_seq += 1;
+                        state.recorder = Some(RecordingStatus { station: station.name.clone(), file: None, until: None });
+                        let _ = state_tx.send(state.clone());
+                        match current_url.clone().filter(|_| is_listening(state.phase)) {
+                            Some(url) => {
+                                let _ = internal_tx.send(InternalMsg::RecordUrl { seq: recording_seq, station, res: Ok(url) });
+                            }
+                            None => record_station(&rb, &internal_tx, recording_seq, station),
+                        }
+                    }
+                    UiCommand::StopRecording => {
+                        recording_seq += 1;
+                        finish_recording(&mut recorder, &mut state, &config, &internal_tx).await;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::SetRecording(mut recording) => {
+                        for schedule in &mut recording.schedules {
+                            schedule.minutes = schedule.minutes.clamp(1, RecordingSchedule::MAX_MINUTES);
+                            schedule.days.retain(|d| *d < 7);
+                            schedule.days.sort_unstable();
+                            schedule.days.dedup();
+                        }
+                        if config.recording == recording {
+                            continue;
+                        }
+                        if recording.schedules != config.recording.schedules {
+                            schedules_started.clear();
+                        }
+                        let moved = recording.dir != config.recording.dir;
+                        config.recording = recording;
+                        state.recording = config.recording.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                        if moved {
+                            list_recordings(&config, &internal_tx, None);
+                        }
+                    }
+                    UiCommand::DeleteRecording(path) => {
+                        if state.recorder.as_ref().and_then(|r| r.file.as_ref()) == Some(&path) {
+                            continue;
+                        }
+                        list_recordings(&config, &internal_tx, Some(path));
+                    }
+                    UiCommand::OpenRecordingsFolder => {
+                        let opened = config.recording.dir().and_then(|dir| {
+                            std::fs::create_dir_all(&dir).with_context(|| format!("Create {}", dir.display()))?;
+                            open_folder(&dir)
+                        });
+                        if let Err(e) = opened {
+                            state.errors.push(format!("{e:#}"));
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    UiCommand::Reload => {
+                        if let Some(station) = state.station.clone() {
+                            let _ = self_tx.send(UiCommand::Replay(station));
+                        }
+                    }
+                    UiCommand::Play(station) | UiCommand::Replay(station) => {
+                        play_when_ready = None;
+                        if !std::mem::take(&mut alarm_play) {
+                            cancel_alarm_ramp(&mpv, &mut alarm_ramp);
+                        }
+                        end_ad_duck(&mpv, &mut ad_duck, &mut state);
+                        let same = state.station.as_ref().is_some_and(|s| s.stationuuid == station.stationuuid);
+                        if !replay
+                            && same
+                            && matches!(state.phase, PlaybackPhase::Playing | PlaybackPhase::Buffering)
+                        {
+                            // Reloading would only cause a gap.
+                            state.already_playing += 1;
+                            let _ = state_tx.send(state.clone());
+                            continue;
+                        }
+                        if let Some(p) = query_resume_position(&mpv, &state, current_url.as_deref(), duration) {
+                            pending_position = Some(p);
+                        }
+                        if let Some(sb) = standby.as_mut() {
+                            if sb.preload.take().is_some() {
+                                let _ = sb.mpv.command(MpvCommand::Stop);
+                            }
+                        }
+                        if let Some(f) = fade.take_if(|f| f.crossfade) {
+                            // Cut the running crossfade short; the new player becomes the old one.
+                            let _ = mpv.command(MpvCommand::SetVolume(f.restore));
+                            if let Some(sb) = &standby {
+                                let _ = sb.mpv.command(MpvCommand::Stop);
+                            }
+                        }
+                        old_audible = state.phase == PlaybackPhase::Playing
+                            && state.cast_target.is_none()
+                            && !mpv_gave_up
+                            && state.volume > 0.0;
+                        if let Some(f) = fade.as_mut() {
+                            // Already fading: keep going, but forget the stream it was meant for.
+                            f.load = None;
+                        } else if old_audible && !standby.as_ref().is_some_and(|sb| sb.ready) {
+                            fade_seq += 1;
+                            fade = Some(Fade::out(fade_seq, state.volume));
+                            spawn_fade(&internal_tx, fade_seq);
+                        }
+                        duration = None;
+                        state.reconnect = None;
+                        state.alternates.clear();
+                        state.resource_warning = None;
+                        usage_strikes = 0;
+                        if std::mem::take(&mut mpv_gave_up) {
+                            fallback = None;
+                            let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        }
+                        state.errors.clear();
                         state.media_title = None;
+                        state.stream_origin = None;
+                        state.buffer_percent = None;
+                        state.stream_tech = None;
+                        state.rewindable = 0;
+                        state.stream_tags = None;
+                        let _ = level_tx.send(None);
+                        if !is_listening(state.phase) {
+                            // Switching stations continues the session; starting over begins a new one.
+                            state.session_bytes = 0;
+                        }
                         state.station = Some(station.clone());
                         state.phase = PlaybackPhase::Idle;
                         want_paused = false;
+                        if station.url.is_none() && config.network_consent {
+                            // For the details panel, and the history once it's the last station.
+                            let stale = cache.needs_refresh([station.stationuuid.as_str()]);
+                            fetch_stations(&rb, &internal_tx, stale, false);
+                        }
                         let _ = state_tx.send(state.clone());
                         let _ = mpv.command(MpvCommand::SetTitle(station.name.clone()));
-                        let rb = rb.clone();
-                        let tx = internal_tx.clone();
-                        tokio::spawn(async move {
-                            let res = {
-                                let mut client = rb.lock().await;
-                                client.resolve_station_url(&station.stationuuid).await
-                            };
-                            let _ = tx.send(InternalMsg::ResolveDone { station, res: res.map(|u| u.to_string()) });
-                        });
+                        let generation = plays.start();
+                        start_timer = Some(StartTimer { clicked: Instant::now(), resolved: None });
+                        known_url = false;
+                        if !(replay && same) {
+                            usage_dirty |= usage.record(UsageEvent::Played(&station));
+                        }
+                        if let Some(url) = station.url.clone() {
+                            // Custom stations carry their stream URL; nothing to resolve.
+                            check_stream(&probe, &internal_tx, station, generation, url);
+                            continue;
+                        }
+                        if config.stream_choice_for(&station.stationuuid).is_active() {
+                            // Other entries for the station have to be looked up first.
+                            choose_stream(&rb, &probe, &internal_tx, station, generation, &config);
+                            continue;
+                        }
+                        if let Some(url) = known_stream_url(&state, &cache, &station.stationuuid) {
+                            // Search results and cached favorites already carry the
+                            // resolved address: start right away and count the click
+                            // in the background.
+                            known_url = true;
+                            if config.privacy.count_clicks {
+                                count_click(&rb, &internal_tx, &station.stationuuid);
+                            }
+                            check_stream(&probe, &internal_tx, station, generation, url);
+                            continue;
+                        }
+                        resolve_station(
+                            &rb,
+                            &probe,
+                            &internal_tx,
+                            station,
+                            generation,
+                            config.privacy.count_clicks,
+                        );
+                    }
+                    UiCommand::PlayRandomFavorite => {
+                        let pool: Vec<StationRef> = config
+                            .favorites
+                            .iter()
+                            .filter(|f| !pending_removals.contains_key(&f.stationuuid))
+                            .cloned()
+                            .collect();
+                        let avoid = Duration::from_secs(config.shuffle.avoid_repeats_hours * 3600);
+                        let pick = recent_plays.pick(&pool, avoid, now_secs(), &mut rand::thread_rng());
+                        if let Some(station) = pick {
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::RandomFavorite));
+                            let _ = self_tx.send(UiCommand::Play(station.clone()));
+                        }
+                    }
+                    UiCommand::ShuffleGroup(group) => {
+                        let members: Vec<StationRef> = config
+                            .favorites
+                            .iter()
+                            .filter(|f| f.group.as_ref() == Some(&group) && !pending_removals.contains_key(&f.stationuuid))
+                            .cloned()
+                            .collect();
+                        if let Some(station) = recent_plays.next_in_group(&group, &members, &mut rand::thread_rng()) {
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::RandomFavorite));
+                            let _ = self_tx.send(UiCommand::Play(station.clone()));
+                        }
                     }
                     UiCommand::TogglePause => {
-                        state.error = None;
-                        let _ = mpv.command(MpvCommand::TogglePause);
+                        state.errors.clear();
+                        disconnect_paused = None;
+                        if let Some(target) = state.cast_target.clone() {
+                            // Renderers don't report pause state back; track it locally.
+                            let cast = cast.clone();
+                            if state.phase == PlaybackPhase::Paused {
+                                state.phase = PlaybackPhase::Playing;
+                                spawn_cast(&internal_tx, async move { cast.resume(&target).await });
+                            } else if state.phase == PlaybackPhase::Playing {
+                                state.phase = PlaybackPhase::Paused;
+                                spawn_cast(&internal_tx, async move { cast.pause(&target).await });
+                            }
+                            let _ = state_tx.send(state.clone());
+                        } else if state.phase == PlaybackPhase::Paused
+                            && duration.is_none()
+                            && paused_at.is_some_and(|t| snap_to_live(t.elapsed().unwrap_or_default(), &config.resume))
+                        {
+                            // Minutes-old audio from the buffer isn't what anyone wants from a live station.
+                            info!("long pause; reloading at the live edge");
+                            let _ = self_tx.send(UiCommand::Reload);
+                        } else if fallback.is_some() {
+                            // GStreamer can't pause; silence is the next best thing.
+                            let _ = self_tx.send(UiCommand::Stop);
+                        } else {
+                            let _ = mpv.command(MpvCommand::TogglePause);
+                        }
+                    }
+                    UiCommand::SeekRelative(secs) => {
+                        if state.cast_target.is_none()
+                            && matches!(state.phase, PlaybackPhase::Playing | PlaybackPhase::Paused)
+                        {
+                            if -secs > f64::from(state.rewindable) {
+                                state.notice = Some(format!(
+                                    "Only {} seconds of this stream are buffered.",
+                                    state.rewindable
+                                ));
+                                let _ = state_tx.send(state.clone());
+                            } else {
+                                usage_dirty |= usage.record(UsageEvent::Used(Feature::Seek));
+                                let _ = mpv.command(MpvCommand::SeekRelative(secs));
+                            }
+                        }
+                    }
+                    UiCommand::SetVolume(volume) => {
+                        alarm_ramp = None;
+                        // The user took over; don't bring the old volume back later.
+                        ad_duck = None;
+                        state.ad_ducked = false;
+                        let volume = volume.clamp(0.0, 100.0);
+                        if let Some(f) = fade.as_mut() {
+                            // Applied once the next station starts.
+                            f.restore = volume;
+                            state.volume = volume;
+                            let _ = state_tx.send(state.clone());
+                        } else {
+                            if let Some(target) = awaiting_levels.as_mut() {
+                                *target = volume;
+                            }
+                            let _ = mpv.command(MpvCommand::SetVolume(volume));
+                        }
+                    }
+                    UiCommand::SetMute(mute) => {
+                        for player in std::iter::once(&mpv).chain(standby.as_ref().map(|sb| &sb.mpv)) {
+                            let _ = player.command(MpvCommand::SetMute(mute));
+                        }
+                        if awaiting_mute {
+                            // mpv's answer is compared against this.
+                            state.muted = mute;
+                            profiles_dirty |= remember_output(&mut config, sink.as_deref(), &state);
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    UiCommand::AdjustVolume(delta) => {
+                        cancel_alarm_ramp(&mpv, &mut alarm_ramp);
+                        if let Some(volume) = ad_duck.take() {
+                            state.ad_ducked = false;
+                            let _ = mpv.command(MpvCommand::SetVolume((volume + delta).clamp(0.0, 100.0)));
+                        } else if let Some(f) = fade.as_mut() {
+                            f.restore = (f.restore + delta).clamp(0.0, 100.0);
+                            state.volume = f.restore;
+                            let _ = state_tx.send(state.clone());
+                        } else {
+                            let _ = mpv.command(MpvCommand::AdjustVolume(delta));
+                        }
                     }
                     UiCommand::Stop => {
-                        state.error = None;
+                        cancel_alarm_ramp(&mpv, &mut alarm_ramp);
+                        end_ad_duck(&mpv, &mut ad_duck, &mut state);
+                        plays.cancel();
+                        start_timer = None;
+                        state.errors.clear();
+                        state.reconnect = None;
+                        if let Some(p) = query_resume_position(&mpv, &state, current_url.as_deref(), duration) {
+                            pending_position = Some(p);
+                        }
                         let _ = mpv.command(MpvCommand::Stop);
+                        fallback = None;
+                        if let Some(f) = fade.take() {
+                            let _ = mpv.command(MpvCommand::SetVolume(f.restore));
+                        }
+                        if let Some(sb) = standby.as_mut() {
+                            sb.preload = None;
+                            let _ = sb.mpv.command(MpvCommand::Stop);
+                        }
+                        if let Some(target) = state.cast_target.clone() {
+                            let cast = cast.clone();
+                            spawn_cast(&internal_tx, async move { cast.stop(&target).await });
+                        }
                         let _ = mpv.command(MpvCommand::SetTitle(String::new()));
 
                         current_url = None;
@@ -174,134 +963,3094 @@ async fn controller_main(
 
                         // Clear persisted last station too
                         config.last_station = None;
-                        let cfg = config.clone();
-                        tokio::spawn(async move {
-                            let _ = tokio::task::spawn_blocking(move || cfg.save_atomic()).await;
-                        });
+                        save_config(&config);
                     }
 
                     UiCommand::ToggleFavorite(station) => {
+                        // Starring a favorite that is about to go away just keeps it.
+                        if pending_removals.remove(&station.stationuuid).is_some() {
+                            state.pending_removals.retain(|s| s.stationuuid != station.stationuuid);
+                            let _ = state_tx.send(state.clone());
+                            continue;
+                        }
                         config.toggle_favorite(station);
                         state.favorites = config.favorites.clone();
+                        state.favorite_colors = config.favorite_colors.clone();
+                        state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::RemoveFavorite(station) => {
+                        removal_seq += 1;
+                        pending_removals.insert(station.stationuuid.clone(), removal_seq);
+                        state.pending_removals.retain(|s| s.stationuuid != station.stationuuid);
+                        let stationuuid = station.stationuuid.clone();
+                        state.pending_removals.push(station);
                         let _ = state_tx.send(state.clone());
-                        let cfg = config.clone();
+                        let tx = internal_tx.clone();
+                        let seq = removal_seq;
                         tokio::spawn(async move {
-                            let _ = tokio::task::spawn_blocking(move || cfg.save_atomic()).await;
+                            tokio::time::sleep(UNDO_WINDOW).await;
+                            let _ = tx.send(InternalMsg::CommitRemoval { stationuuid, seq });
                         });
                     }
-                    UiCommand::Shutdown => {
-                        let _ = mpv.command(MpvCommand::Shutdown);
-                        return Ok(());
+                    UiCommand::EditFavorites(edited) => {
+                        if !config.apply_favorites_edit(edited) {
+                            continue;
+                        }
+                        if let Some(station) = state.station.as_mut() {
+                            if let Some(fav) = config.favorites.iter().find(|f| f.stationuuid == station.stationuuid) {
+                                station.name = fav.name.clone();
+                            }
+                        }
+                        state.favorites = config.favorites.clone();
+                        state.favorite_colors = config.favorite_colors.clone();
+                        state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
                     }
-                }
-            }
-            ev = mpv_events.recv() => {
-                let Some(ev) = ev else {
-                    state.phase = PlaybackPhase::Error;
-                    state.error = Some("mpv controller stopped".to_string());
-                    let _ = state_tx.send(state.clone());
-                    return Ok(());
-                };
-                match ev {
-                    MpvEvent::Ready => {
-                        if let Some(url) = current_url.clone() {
-                            let _ = mpv.command(MpvCommand::LoadUrl { url });
-                            let _ = mpv.command(MpvCommand::SetPause(want_paused));
-                            state.phase = if want_paused { PlaybackPhase::Paused } else { PlaybackPhase::Playing };
-                            state.error = None;
+                    UiCommand::UndoRemoveFavorite(stationuuid) => {
+                        if pending_removals.remove(&stationuuid).is_some() {
+                            state.pending_removals.retain(|s| s.stationuuid != stationuuid);
                             let _ = state_tx.send(state.clone());
                         }
                     }
-                    MpvEvent::MediaTitle(t) => {
-                        state.media_title = t;
+                    UiCommand::RefreshFavorites => {
+                        let uuids: Vec<String> = favorite_uuids(&config).map(str::to_string).collect();
+                        if uuids.is_empty() || state.favorites_refreshing {
+                            continue;
+                        }
+                        cache.invalidate(uuids.iter().map(String::as_str));
+                        state.favorites_refreshing = true;
+                        state.errors.clear();
                         let _ = state_tx.send(state.clone());
+                        fetch_stations(&rb, &internal_tx, uuids, true);
                     }
-                    MpvEvent::Pause(p) => {
-                        want_paused = p;
-                        state.phase = if p { PlaybackPhase::Paused } else { PlaybackPhase::Playing };
+                    UiCommand::SetFavoriteColor(stationuuid, color) => {
+                        config.set_favorite_color(&stationuuid, color);
+                        state.favorite_colors = config.favorite_colors.clone();
                         let _ = state_tx.send(state.clone());
+                        save_config(&config);
                     }
-                    MpvEvent::Crashed(e) => {
-                        warn!(error = %e, "mpv crashed/restarting");
-                        state.phase = PlaybackPhase::Error;
-                        state.error = Some(format!("mpv error: {e}"));
+                    UiCommand::SetStreamChoice(choice) => {
+                        if config.stream_choice == choice {
+                            continue;
+                        }
+                        config.stream_choice = choice;
+                        state.stream_choice = config.stream_choice.clone();
                         let _ = state_tx.send(state.clone());
-                        tokio::time::sleep(Duration::from_millis(250)).await;
+                        save_config(&config);
                     }
-                }
-            }
-            Some(msg) = internal_rx.recv() => {
-                match msg {
-                    InternalMsg::SearchDone { query, res } => {
-                        if query != state.search_query {
+                    UiCommand::SetFavoriteStreamChoice(stationuuid, choice) => {
+                        config.set_favorite_stream_choice(&stationuuid, choice);
+                        state.favorite_stream_choice = config.favorite_stream_choice.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::UpdateUi(ui) => {
+                        if config.ui == ui {
                             continue;
                         }
-                        match res {
-                            Ok(results) => {
-                                state.search_results = results;
-                                state.search_loading = false;
-                                state.error = None;
-                            }
+                        let metering_changed = config.ui.level_meter != ui.level_meter;
+                        config.ui = ui;
+                        state.ui = config.ui.clone();
+                        if metering_changed {
+                            apply_audio_filters(&mpv, standby.as_ref(), &config);
+                            let _ = level_tx.send(None);
+                        }
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::ProbeStream(url) => {
+                        let url = match url::Url::parse(&url) {
+                            Ok(u) => u,
                             Err(e) => {
-                                state.search_loading = false;
-                                state.error = Some(e.to_string());
+                                state.errors.push(format!("Invalid stream URL: {e}"));
+                                let _ = state_tx.send(state.clone());
+                                continue;
                             }
+                        };
+                        state.stream_offer = None;
+                        state.stream_probe_loading = true;
+                        state.errors.clear();
+                        let _ = state_tx.send(state.clone());
+                        let probe = probe.clone();
+                        let tx = internal_tx.clone();
+                        tokio::spawn(async move {
+                            let res = probe.probe(url).await;
+                            let _ = tx.send(InternalMsg::ProbeDone(res));
+                        });
+                    }
+                    UiCommand::DismissStreamOffer => {
+                        state.stream_offer = None;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::RetryBackend => {
+                        if state.phase == PlaybackPhase::MissingBackend && std::mem::take(&mut mpv_gave_up) {
+                            let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        }
+                    }
+                    UiCommand::RestartBackend => {
+                        if state.phase == PlaybackPhase::MissingBackend {
+                            let _ = self_tx.send(UiCommand::RetryBackend);
+                            continue;
+                        }
+                        info!("restarting mpv on request");
+                        mpv_gave_up = false;
+                        fallback = None;
+                        end_ad_duck(&mpv, &mut ad_duck, &mut state);
+                        state.errors.clear();
+                        state.resource_warning = None;
+                        usage_strikes = 0;
+                        // Its steps would land on the new players; Ready sets the volume
+                        // and loads the stream a fade was holding back.
+                        fade = None;
+                        if state.phase == PlaybackPhase::Error && current_url.is_none() {
+                            // Nothing left to reload; play the station again once mpv is back.
+                            play_when_ready = state.station.clone();
                         }
                         let _ = state_tx.send(state.clone());
+                        // Ready reloads the current stream.
+                        let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        sync_standby(&mut standby, &config).await;
                     }
-                    InternalMsg::ResolveDone { station, res } => {
-                        if state.station.as_ref().map(|s| &s.stationuuid) != Some(&station.stationuuid) {
+                    UiCommand::StopReconnecting => {
+                        if state.phase != PlaybackPhase::Reconnecting {
                             continue;
                         }
-                        match res {
-                            Ok(url) => {
-                                info!(stationuuid = %station.stationuuid, "starting playback");
-                                current_url = Some(url.clone());
-                                let _ = mpv.command(MpvCommand::LoadUrl { url });
-                                state.phase = PlaybackPhase::Playing;
-                                state.error = None;
+                        // The station stays selected, so Play tries it again.
+                        info!("stopped reconnecting");
+                        let _ = mpv.command(MpvCommand::Stop);
+                        current_url = None;
+                        core_idle = true;
+                        state.reconnect = None;
+                        state.phase = PlaybackPhase::Idle;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::ReloadConfig => {
+                        let tx = internal_tx.clone();
+                        tokio::spawn(async move {
+                            let res = match tokio::task::spawn_blocking(AppConfig::load).await {
+                                Ok(res) => res,
+                                Err(e) => Err(e).context("Join config load task"),
+                            };
+                            let _ = tx.send(InternalMsg::ConfigLoaded(res.map(Box::new)));
+                        });
+                    }
+                    UiCommand::OpenConfigFolder => {
+                        let dir = crate::config::config_path()
+                            .ok()
+                            .and_then(|p| p.parent().map(|d| d.to_path_buf()));
+                        if let Some(dir) = dir {
+                            if let Err(e) = open_folder(&dir) {
+                                state.errors.push(format!("{e:#}"));
                                 let _ = state_tx.send(state.clone());
-
-                                config.last_station = Some(station);
-                                if let Some(s) = rb.lock().await.last_server().map(|s| s.to_string()) {
-                                    config.last_server = Some(s);
+                            }
+                        }
+                    }
+                    UiCommand::AcceptConsent(privacy) => {
+                        config.accept_consent(privacy);
+                        usage.set_enabled(config.privacy.usage_stats);
+                        state.consent_pending = false;
+                        state.privacy = config.privacy.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                        let stale = cache.needs_refresh(detail_uuids(&config, state.station.as_ref()));
+                        fetch_stations(&rb, &internal_tx, stale, false);
+                    }
+                    UiCommand::DiscoverRenderers => {
+                        state.renderers_loading = true;
+                        let _ = state_tx.send(state.clone());
+                        let cast = cast.clone();
+                        let tx = internal_tx.clone();
+                        tokio::spawn(async move {
+                            let res = cast.discover(Duration::from_secs(3)).await;
+                            let _ = tx.send(InternalMsg::RenderersFound(res));
+                        });
+                    }
+                    UiCommand::SetCastTarget(target) => {
+                        if target == state.cast_target {
+                            continue;
+                        }
+                        if target.is_some() {
+                            usage_dirty |= usage.record(UsageEvent::Used(Feature::Cast));
+                        }
+                        let active = matches!(
+                            state.phase,
+                            PlaybackPhase::Buffering
+                                | PlaybackPhase::Reconnecting
+                                | PlaybackPhase::Playing
+                                | PlaybackPhase::Paused
+                        );
+                        if let (true, Some(url)) = (active, current_url.clone()) {
+                            match state.cast_target.clone() {
+                                Some(old) => {
+                                    let cast = cast.clone();
+                                    spawn_cast(&internal_tx, async move { cast.stop(&old).await });
+                                }
+                                None => {
+                                    let _ = mpv.command(MpvCommand::Stop);
+                                    if let Some(sb) = standby.as_mut().filter(|sb| sb.preload.is_some()) {
+                                        sb.preload = None;
+                                        let _ = sb.mpv.command(MpvCommand::Stop);
+                                    }
                                 }
-                                let cfg = config.clone();
-                                tokio::spawn(async move {
-                                    let _ = tokio::task::spawn_blocking(move || cfg.save_atomic()).await;
-                                });
                             }
-                            Err(e) => {
-                                state.phase = PlaybackPhase::Error;
-                                state.error = Some(e.to_string());
-                                let _ = state_tx.send(state.clone());
+                            match target.clone() {
+                                Some(new) => {
+                                    let cast = cast.clone();
+                                    let title = state.station.as_ref().map(|s| s.name.clone()).unwrap_or_default();
+                                    spawn_cast(&internal_tx, async move { cast.play(&new, &url, &title).await });
+                                }
+                                None => {
+                                    want_paused = false;
+                                    core_idle = true;
+                                    let _ = mpv.command(MpvCommand::LoadUrl { url, start: None });
+                                    let _ = mpv.command(MpvCommand::SetPause(false));
+                                }
                             }
+                            state.phase = if target.is_some() {
+                                PlaybackPhase::Playing
+                            } else {
+                                PlaybackPhase::Buffering
+                            };
                         }
+                        state.cast_target = target;
+                        let _ = state_tx.send(state.clone());
                     }
-                }
-            }
-        }
-    }
-}
-
-#[derive(Debug)]
-enum InternalMsg {
-    SearchDone { query: String, res: Result<Vec<Station>> },
-    ResolveDone { station: StationRef, res: Result<String> },
-}
-
-fn mpv_socket_path() -> Result<PathBuf> {
-    let runtime = std::env::var_os("XDG_RUNTIME_DIR")
-        .map(PathBuf::from)
-        .context("XDG_RUNTIME_DIR not set")?;
-
-    let dir = runtime.join("radiowidget");
-    std::fs::create_dir_all(&dir).with_context(|| format!("Create runtime dir: {dir:?}"))?;
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
+                    UiCommand::DismissNotice => {
+                        state.notice = None;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::DismissStationOfDay => {
+                        let Some(today) = config.discovery.today.as_mut() else {
+                            continue;
+                        };
+                        today.dismissed = true;
+                        state.station_of_day = None;
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetDiscovery(enabled) => {
+                        if config.discovery.enabled == enabled {
+                            continue;
+                        }
+                        config.discovery.enabled = enabled;
+                        // Look right away on the next clock check.
+                        discovery_tried = None;
+                        sync_config_state(&mut state, &config);
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::ReportError(e) => {
+                        state.errors.push(e);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::DismissError(id) => {
+                        state.errors.dismiss(id);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    UiCommand::SetSnapcast(enabled) => {
+                        if config.output.snapcast == enabled {
+                            continue;
+                        }
+                        config.output.snapcast = enabled;
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        // Output changes need a fresh mpv; Ready reloads the current stream.
+                        let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        sync_standby(&mut standby, &config).await;
+                        save_config(&config);
+                    }
+                    UiCommand::PinMirror(server) => {
+                        if config.pinned_server == server {
+                            continue;
+                        }
+                        config.pinned_server = server;
+                        state.pinned_server = config.pinned_server.clone();
+                        let _ = state_tx.send(state.clone());
+                        apply_pinned_server(&rb, &config);
+                        save_config(&config);
+                    }
+                    UiCommand::SetLowResource(enabled) => {
+                        if config.output.low_resource == enabled {
+                            continue;
+                        }
+                        config.output.low_resource = enabled;
+                        state.output = config.output.clone();
+                        state.resource_warning = None;
+                        usage_strikes = 0;
+                        let _ = state_tx.send(state.clone());
+                        let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        sync_standby(&mut standby, &config).await;
+                        save_config(&config);
+                    }
+                    UiCommand::SetAudioDevice(device) => {
+                        if config.output.audio_device == device {
+                            continue;
+                        }
+                        set_audio_device(&mpv, standby.as_ref(), &mut config, device);
+                        remember_output(&mut config, sink.as_deref(), &state);
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetEqualizer(eq) => {
+                        if config.equalizer == eq {
+                            continue;
+                        }
+                        config.equalizer = eq;
+                        state.equalizer = eq;
+                        let _ = state_tx.send(state.clone());
+                        apply_audio_filters(&mpv, standby.as_ref(), &config);
+                        save_config(&config);
+                    }
+                    UiCommand::SetChannels(mut channels) => {
+                        // Whole percents, so the slider's center is exactly centered.
+                        channels.balance = (channels.balance.clamp(-1.0, 1.0) * 100.0).round() / 100.0;
+                        if config.channels == channels {
+                            continue;
+                        }
+                        config.channels = channels;
+                        state.channels = channels;
+                        let _ = state_tx.send(state.clone());
+                        apply_audio_filters(&mpv, standby.as_ref(), &config);
+                        save_config(&config);
+                    }
+                    UiCommand::SetStandby(enabled) => {
+                        if config.output.standby == enabled {
+                            continue;
+                        }
+                        config.output.standby = enabled;
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        if enabled != standby.is_some() {
+                            sync_standby(&mut standby, &config).await;
+                        }
+                        save_config(&config);
+                    }
+                    UiCommand::SetDisconnectPolicy { pause, resume } => {
+                        if config.output.pause_on_disconnect == pause && config.output.resume_on_reconnect == resume {
+                            continue;
+                        }
+                        config.output.pause_on_disconnect = pause;
+                        config.output.resume_on_reconnect = resume;
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetPerOutput(enabled) => {
+                        if config.output.per_output == enabled {
+                            continue;
+                        }
+                        config.output.per_output = enabled;
+                        if enabled {
+                            // Start from what's set now for the current output.
+                            remember_output(&mut config, sink.as_deref(), &state);
+                        }
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetUsageStats(enabled) => {
+                        if config.privacy.usage_stats == enabled {
+                            continue;
+                        }
+                        config.privacy.usage_stats = enabled;
+                        usage.set_enabled(enabled);
+                        state.privacy = config.privacy.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::ClearUsageStats => {
+                        usage.clear();
+                        usage_dirty = false;
+                        if let Some((_, since)) = listening.as_mut() {
+                            *since = Instant::now();
+                        }
+                        state.usage = usage.summary();
+                        let _ = state_tx.send(state.clone());
+                        write_usage(&mut usage_write, UsageStats::remove_file);
+                    }
+                    UiCommand::SetCache { preset, secs, max_mib } => {
+                        let output = &mut config.output;
+                        if (output.cache_preset, output.cache_secs, output.demuxer_max_mib) == (preset, secs, max_mib) {
+                            continue;
+                        }
+                        output.cache_preset = preset;
+                        output.cache_secs = secs;
+                        output.demuxer_max_mib = max_mib;
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        // Cache limits are launch options; Ready reloads the current stream.
+                        let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        sync_standby(&mut standby, &config).await;
+                        save_config(&config);
+                    }
+                    UiCommand::SetHlsVariant(hls) => {
+                        if config.output.hls_variant == hls {
+                            continue;
+                        }
+                        config.output.hls_variant = hls;
+                        state.output = config.output.clone();
+                        let _ = state_tx.send(state.clone());
+                        // mpv picks the variant from a launch option; Ready
+                        // reloads the current stream.
+                        let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                        sync_standby(&mut standby, &config).await;
+                        save_config(&config);
+                    }
+                    UiCommand::SetResume(resume) => {
+                        if config.resume == resume {
+                            continue;
+                        }
+                        config.resume = resume;
+                        state.resume = config.resume.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetQuality(quality) => {
+                        if config.quality == quality {
+                            continue;
+                        }
+                        config.quality = quality;
+                        state.quality = config.quality.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetAdMute(ad_mute) => {
+                        if config.ad_mute == ad_mute {
+                            continue;
+                        }
+                        let metering = config.ad_mute.watches_silence() != ad_mute.watches_silence();
+                        config.ad_mute = ad_mute;
+                        state.ad_mute = config.ad_mute.clone();
+                        reload_ad_rules(&config.ad_mute, &mut ad_rules, &mut ad_duck, &mpv, &mut state);
+                        if metering {
+                            apply_audio_filters(&mpv, standby.as_ref(), &config);
+                        }
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetInhibitSuspend(enabled) => {
+                        if config.power.inhibit_suspend == enabled {
+                            continue;
+                        }
+                        config.power.inhibit_suspend = enabled;
+                        state.power = config.power.clone();
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::SetLogLevel(level) => {
+                        if config.log_level == level {
+                            continue;
+                        }
+                        config.log_level = level;
+                        crate::logging::set_level(level);
+                        state.log_level = level;
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    UiCommand::Shutdown => {
+                        if cache_dirty {
+                            save_cache(cache.clone()).await;
+                        }
+                        if !pending_removals.is_empty() {
+                            // Closing inside the undo window still honours the removal.
+                            for stationuuid in pending_removals.keys() {
+                                config.remove_favorite(stationuuid);
+                            }
+                            config_saver.save_now(&config).await;
+                        } else {
+                            config_saver.flush().await;
+                        }
+                        if let Some((url, length)) = query_resume_position(&mpv, &state, current_url.as_deref(), duration) {
+                            let answer = tokio::time::timeout(Duration::from_millis(500), async {
+                                loop {
+                                    match mpv_events.recv().await {
+                                        Some(MpvEvent::Position(p)) => return p,
+                                        Some(_) => {}
+                                        None => return None,
+                                    }
+                                }
+                            });
+                            if let Ok(Some(pos)) = answer.await {
+                                if positions.record(&url, pos, length, now_secs()) {
+                                    let _ = tokio::task::spawn_blocking(move || positions.save()).await;
+                                }
+                            }
+                        }
+                        if let Some(recorder) = recorder.take() {
+                            recorder.finish().await;
+                        }
+                        let _ = mpv.command(MpvCommand::Shutdown);
+                        return Ok(());
+                    }
+                }
+            }
+            ev = mpv_events.recv() => {
+                let Some(ev) = ev else {
+                    // The task supervising mpv is gone, so nothing can play until a restart.
+                    warn!("mpv supervisor ended, controller stopping");
+                    state.phase = PlaybackPhase::Error;
+                    state.errors.push("mpv controller stopped".to_string());
+                    state.stopped = Some("The task looking after mpv ended, so its events stopped coming.".to_string());
+                    let _ = state_tx.send(state.clone());
+                    return Ok(());
+                };
+                match ev {
+                    MpvEvent::Ready => {
+                        if state.phase == PlaybackPhase::MissingBackend {
+                            // Installed in the meantime.
+                            state.phase = if state.station.is_some() {
+                                PlaybackPhase::Idle
+                            } else {
+                                PlaybackPhase::NotConfigured
+                            };
+                            let _ = state_tx.send(state.clone());
+                        }
+                        // Filters live in the process; a fresh mpv needs them again.
+                        let filters = audio_filters(&config, true);
+                        if !filters.is_empty() {
+                            let _ = mpv.command(MpvCommand::SetAudioFilter(filters));
+                        }
+                        if levels_known {
+                            // A running fade sets its own steps and ends on state.volume.
+                            let volume = match ad_duck {
+                                Some(_) => ad_rules.ducked(state.volume),
+                                None => state.volume,
+                            };
+                            awaiting_levels = Some(volume);
+                            awaiting_mute = true;
+                            let _ = mpv.command(MpvCommand::SetVolume(volume));
+                            let _ = mpv.command(MpvCommand::SetMute(state.muted));
+                        }
+                        if let Some(station) = play_when_ready.take() {
+                            let _ = self_tx.send(UiCommand::Replay(station));
+                            continue;
+                        }
+                        if state.cast_target.is_some() {
+                            continue;
+                        }
+                        if let Some(url) = current_url.clone() {
+                            let _ = mpv.command(MpvCommand::LoadUrl { url, start: None });
+                            let _ = mpv.command(MpvCommand::SetPause(want_paused));
+                            core_idle = true;
+                            // After a crash this reload is the reconnect attempt.
+                            if state.phase != PlaybackPhase::Reconnecting {
+                                state.phase = active_phase(want_paused, core_idle);
+                            }
+                            state.errors.clear();
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    MpvEvent::MediaTitle(t) => {
+                        ad_silence_break = None;
+                        let ad = state.cast_target.is_none() && t.as_deref().is_some_and(|t| ad_rules.matches(t));
+                        // A crossfade heads for the ducked volume by itself.
+                        if ad && ad_duck.is_none() && fade.as_ref().is_none_or(|f| f.crossfade) {
+                            debug!(title = ?t, "turning an ad down");
+                            ad_duck = Some(state.volume);
+                            state.ad_ducked = true;
+                            let _ = mpv.command(MpvCommand::SetVolume(ad_rules.ducked(state.volume)));
+                        } else if !ad {
+                            end_ad_duck(&mpv, &mut ad_duck, &mut state);
+                        }
+                        state.media_title = t.and_then(|t| title_rules.clean(state.station.as_ref(), &t));
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Pause(p) => {
+                        if state.cast_target.is_some() {
+                            continue;
+                        }
+                        paused_at = p.then(|| paused_at.unwrap_or_else(SystemTime::now));
+                        want_paused = p;
+                        state.phase = active_phase(p, core_idle);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Volume(v) => {
+                        if let Some(target) = awaiting_levels {
+                            // The user's volume also counts: an ad break or fade
+                            // may have ended before the respawned mpv answered.
+                            if (v - target).abs() < 0.5 || (v - state.volume).abs() < 0.5 {
+                                awaiting_levels = None;
+                            }
+                            continue;
+                        }
+                        levels_known = true;
+                        if fade.is_some() || ad_duck.is_some() || alarm_ramp.is_some() {
+                            // The ramp or ad volume isn't the user's volume.
+                            continue;
+                        }
+                        state.volume = v;
+                        profiles_dirty |= remember_output(&mut config, sink.as_deref(), &state);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Mute(muted) => {
+                        if awaiting_mute {
+                            awaiting_mute = muted != state.muted;
+                            continue;
+                        }
+                        state.muted = muted;
+                        profiles_dirty |= remember_output(&mut config, sink.as_deref(), &state);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::AudioDevices(devices) => {
+                        let chosen = config.output.audio_device.as_deref();
+                        let local = state.cast_target.is_none() && !config.output.snapcast;
+                        if let Some(lost) = lost_output(&state.audio_devices, &devices, chosen, sink.as_deref())
+                            .filter(|_| local && config.output.pause_on_disconnect)
+                            .filter(|_| {
+                                matches!(
+                                    state.phase,
+                                    PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing
+                                )
+                            })
+                        {
+                            info!(device = %lost.name, "output disconnected; pausing");
+                            let _ = mpv.command(MpvCommand::SetPause(true));
+                            state.notice = Some(format!("Paused: {} disconnected.", device_label(&lost)));
+                            disconnect_paused = Some(lost.name);
+                        } else if let Some(name) = disconnect_paused.take_if(|n| devices.iter().any(|d| d.name == *n)) {
+                            if config.output.resume_on_reconnect && state.phase == PlaybackPhase::Paused {
+                                info!(device = %name, "output reconnected; resuming");
+                                state.notice = None;
+                                let _ = self_tx.send(UiCommand::TogglePause);
+                            }
+                        }
+                        state.audio_devices = devices;
+                        let _ = state_tx.send(state.clone());
+                        if config.output.per_output || config.output.pause_on_disconnect {
+                            // A dock or headset coming or going usually moves the default.
+                            find_default_sink(&internal_tx);
+                        }
+                    }
+                    MpvEvent::HasVideo(has_video) => {
+                        let Some(station) = state.station.as_ref().filter(|_| has_video) else {
+                            continue;
+                        };
+                        // --vid=no keeps the picture off, but the container still
+                        // downloads every video packet; refuse the stream instead.
+                        info!(stationuuid = %station.stationuuid, "station sends video, stopping it");
+                        state.video_stations.insert(station.stationuuid.clone());
+                        let _ = mpv.command(MpvCommand::Stop);
+                        current_url = None;
+                        core_idle = true;
+                        state.reconnect = None;
+                        state.phase = PlaybackPhase::Error;
+                        state.errors.push("This station streams video. Only audio streams play here, to save data.");
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Usage(usage) => {
+                        let Some(problem) = usage_problem(&usage) else {
+                            usage_strikes = 0;
+                            continue;
+                        };
+                        usage_strikes += 1;
+                        if usage_strikes == USAGE_STRIKES && !config.output.low_resource {
+                            warn!(?usage, "mpv resource usage is high");
+                            state.resource_warning = Some(problem);
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    MpvEvent::Duration(d) => {
+                        duration = d;
+                    }
+                    MpvEvent::Position(pos) => {
+                        let (Some((url, length)), Some(pos)) = (pending_position.take(), pos) else {
+                            continue;
+                        };
+                        if positions.record(&url, pos, length, now_secs()) {
+                            let positions = positions.clone();
+                            tokio::spawn(async move {
+                                if let Ok(Err(e)) = tokio::task::spawn_blocking(move || positions.save()).await {
+                                    warn!(error = ?e, "failed to save resume positions");
+                                }
+                            });
+                        }
+                    }
+                    MpvEvent::Crashed(e) => {
+                        warn!(error = %e, "mpv crashed/restarting");
+                        // The restarted mpv loads the stream again once it's ready.
+                        let live = state.cast_target.is_none()
+                            && current_url.is_some()
+                            && matches!(
+                                state.phase,
+                                PlaybackPhase::Buffering | PlaybackPhase::Playing | PlaybackPhase::Reconnecting
+                            );
+                        if !live || next_reconnect(&mut state, DropReason::Crashed).is_none() {
+                            state.phase = PlaybackPhase::Error;
+                            state.errors.push(format!("mpv error: {e}"));
+                        }
+                        let _ = state_tx.send(state.clone());
+                        tokio::time::sleep(Duration::from_millis(250)).await;
+                    }
+                    MpvEvent::PlaybackStarted => {
+                        if let Some(timer) = start_timer.take() {
+                            timer.finish(&mut state.start_latency);
+                            let _ = state_tx.send(state.clone());
+                        }
+                        if let Some(ramp) = alarm_ramp.as_mut().filter(|r| !r.started) {
+                            ramp.started = true;
+                            spawn_alarm_ramp(&internal_tx, ramp.seq, config.alarm.fade_in());
+                        }
+                    }
+                    MpvEvent::CoreIdle(idle) => {
+                        core_idle = idle;
+                        if state.cast_target.is_some()
+                            || !matches!(
+                                state.phase,
+                                PlaybackPhase::Buffering | PlaybackPhase::Playing | PlaybackPhase::Reconnecting
+                            )
+                            || (idle && state.phase == PlaybackPhase::Reconnecting)
+                        {
+                            continue;
+                        }
+                        let phase = active_phase(want_paused, idle);
+                        if phase == PlaybackPhase::Playing {
+                            state.reconnect = None;
+                        }
+                        if phase != state.phase {
+                            state.phase = phase;
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    MpvEvent::StreamEnded | MpvEvent::EofReached(true) => {
+                        if state.cast_target.is_some()
+                            || current_url.is_none()
+                            || !matches!(
+                                state.phase,
+                                PlaybackPhase::Buffering | PlaybackPhase::Playing | PlaybackPhase::Reconnecting
+                            )
+                        {
+                            continue;
+                        }
+                        if duration.is_some() {
+                            // A podcast or archive played to its end.
+                            current_url = None;
+                            core_idle = true;
+                            state.phase = PlaybackPhase::Idle;
+                        } else if let Some(attempt) = next_reconnect(&mut state, DropReason::Ended) {
+                            schedule_reconnect(&internal_tx, plays.current, attempt);
+                        } else {
+                            current_url = None;
+                            state.phase = PlaybackPhase::Error;
+                            state.errors.push("The stream ended and could not be reconnected.".to_string());
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Stalled => {
+                        // Files just wait for data; a frozen live stream rarely
+                        // recovers without a fresh connection.
+                        if state.cast_target.is_some()
+                            || duration.is_some()
+                            || current_url.is_none()
+                            || !matches!(
+                                state.phase,
+                                PlaybackPhase::Buffering | PlaybackPhase::Playing | PlaybackPhase::Reconnecting
+                            )
+                        {
+                            continue;
+                        }
+                        if let Some(attempt) = next_reconnect(&mut state, DropReason::Stalled) {
+                            schedule_reconnect(&internal_tx, plays.current, attempt);
+                        } else {
+                            let _ = mpv.command(MpvCommand::Stop);
+                            current_url = None;
+                            state.phase = PlaybackPhase::Error;
+                            state.errors.push("The stream stopped sending audio.");
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::IdleActive(true) => {
+                        // Only once audio has flowed: before that, idle is just
+                        // the player coming up or the load failing (reported
+                        // separately).
+                        if state.cast_target.is_some() || state.phase != PlaybackPhase::Playing {
+                            continue;
+                        }
+                        current_url = None;
+                        core_idle = true;
+                        state.phase = PlaybackPhase::Idle;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::IdleActive(false) | MpvEvent::EofReached(false) => {}
+                    MpvEvent::PausedForCache(waiting) => {
+                        if state.cast_target.is_some()
+                            || !matches!(state.phase, PlaybackPhase::Buffering | PlaybackPhase::Playing)
+                        {
+                            continue;
+                        }
+                        let phase = if waiting {
+                            PlaybackPhase::Buffering
+                        } else {
+                            active_phase(want_paused, core_idle)
+                        };
+                        if phase != state.phase {
+                            state.phase = phase;
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    MpvEvent::StreamTech(tech) => {
+                        state.stream_tech = Some(tech);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::DataUsed(bytes) => {
+                        state.session_bytes += bytes;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Rewindable(secs) => {
+                        state.rewindable = secs;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::Level(level) => {
+                        let now = Instant::now();
+                        if ad_rules.silence_began(level, now)
+                            && state.phase == PlaybackPhase::Playing
+                            && state.cast_target.is_none()
+                            && ad_duck.is_none()
+                            && fade.is_none()
+                        {
+                            debug!("dead air; turning the break down");
+                            ad_duck = Some(state.volume);
+                            ad_silence_break = Some(now);
+                            state.ad_ducked = true;
+                            let _ = mpv.command(MpvCommand::SetVolume(ad_rules.ducked(state.volume)));
+                            let _ = state_tx.send(state.clone());
+                        } else if ad_silence_break.is_some_and(|t| now.duration_since(t) > SILENCE_BREAK) {
+                            // No title came to end it; don't stay down for good.
+                            ad_silence_break = None;
+                            end_ad_duck(&mpv, &mut ad_duck, &mut state);
+                            let _ = state_tx.send(state.clone());
+                        }
+                        if config.ui.level_meter && *level_tx.borrow() != level {
+                            let _ = level_tx.send(level);
+                        }
+                    }
+                    MpvEvent::StreamTags(tags) => {
+                        if apply_stream_tags(&mut config, &mut state, tags) {
+                            save_config(&config);
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::CacheBuffering(percent) => {
+                        if percent == state.buffer_percent {
+                            continue;
+                        }
+                        state.buffer_percent = percent;
+                        // Only shown while waiting; no need to redraw during playback.
+                        if matches!(state.phase, PlaybackPhase::Buffering | PlaybackPhase::Reconnecting) {
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    MpvEvent::CommandError { command, error } if command == "seek" => {
+                        debug!(%error, "seek failed");
+                        state.notice = Some("This stream can't be rewound.".to_string());
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::CommandError { command, error } if command != "loadfile" => {
+                        warn!(%command, %error, "mpv command failed");
+                    }
+                    MpvEvent::PlaybackFailed(e) | MpvEvent::CommandError { error: e, .. } => {
+                        if state.cast_target.is_some() {
+                            continue;
+                        }
+                        if state.phase == PlaybackPhase::Playing && duration.is_none() {
+                            // Audio was flowing, so the address is right: the
+                            // connection dropped (network loss, server restart).
+                            known_url = false;
+                            if let Some(attempt) = next_reconnect(&mut state, DropReason::ConnectionLost) {
+                                schedule_reconnect(&internal_tx, plays.current, attempt);
+                                let _ = state_tx.send(state.clone());
+                                continue;
+                            }
+                        }
+                        if std::mem::take(&mut known_url) {
+                            if let Some(station) = state.station.clone() {
+                                // The remembered address may be outdated; ask Radio Browser.
+                                info!(error = %e, "remembered stream URL failed, resolving again");
+                                resolve_station(
+                                    &rb,
+                                    &probe,
+                                    &internal_tx,
+                                    station,
+                                    plays.current,
+                                    false,
+                                );
+                                continue;
+                            }
+                        }
+                        if state.phase == PlaybackPhase::Reconnecting {
+                            // Still down; try again a bit later.
+                            debug!(error = %e, "reconnect failed");
+                            if let Some(attempt) = next_reconnect(&mut state, DropReason::Unreachable) {
+                                schedule_reconnect(&internal_tx, plays.current, attempt);
+                                let _ = state_tx.send(state.clone());
+                                continue;
+                            }
+                        }
+                        warn!(error = %e, "stream failed to play");
+                        current_url = None;
+                        state.phase = PlaybackPhase::Error;
+                        state.errors.push(format!("Playback failed: {e}"));
+                        let _ = state_tx.send(state.clone());
+                        if let Some(station) = &state.station {
+                            find_alternates(&rb, &internal_tx, station);
+                        }
+                    }
+                    MpvEvent::Missing => {
+                        warn!("mpv is not installed");
+                        // Like a crash loop: playing again looks for it again.
+                        mpv_gave_up = true;
+                        state.phase = PlaybackPhase::MissingBackend;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::GaveUp(e) => {
+                        warn!(error = %e, "mpv crash loop, not restarting");
+                        mpv_gave_up = true;
+                        let volume = if state.muted { 0.0 } else { state.volume };
+                        let started = current_url
+                            .as_deref()
+                            .filter(|_| state.cast_target.is_none() && FallbackPlayer::available())
+                            .map(|url| FallbackPlayer::start(url, volume));
+                        match started {
+                            Some(Ok(player)) => {
+                                info!("playing through GStreamer instead");
+                                fallback = Some(player);
+                                state.phase = PlaybackPhase::Playing;
+                                state.notice = Some(format!(
+                                    "{e}, so GStreamer is playing instead. Volume and pause come back with mpv: press Restart under Output."
+                                ));
+                            }
+                            started => {
+                                if let Some(Err(err)) = started {
+                                    warn!(error = ?err, "GStreamer fallback failed");
+                                }
+                                current_url = None;
+                                state.phase = PlaybackPhase::Error;
+                                state.errors.push(format!("{e}. Playback stopped; press play to try again."));
+                            }
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                }
+            }
+            status = fallback_exited(&mut fallback) => {
+                fallback = None;
+                warn!(?status, "GStreamer fallback stopped");
+                current_url = None;
+                state.phase = PlaybackPhase::Error;
+                state.notice = None;
+                state.errors.push("GStreamer stopped playing too. Press play to try mpv again.".to_string());
+                let _ = state_tx.send(state.clone());
+            }
+            ev = next_recorder_event(&mut recorder) => {
+                match ev {
+                    RecorderEvent::Started(path) => {
+                        if let Some(status) = state.recorder.as_mut() {
+                            status.file = Some(path);
+                        }
+                        list_recordings(&config, &internal_tx, None);
+                    }
+                    RecorderEvent::Failed(e) => {
+                        let station = state.recorder.as_ref().map(|r| r.station.clone()).unwrap_or_default();
+                        warn!(error = %e, %station, "recording failed");
+                        finish_recording(&mut recorder, &mut state, &config, &internal_tx).await;
+                        state.notice = None;
+                        state.errors.push(format!("Recording {station} stopped: {e}"));
+                    }
+                }
+                let _ = state_tx.send(state.clone());
+            }
+            ev = next_standby_event(&mut standby) => {
+                let Some(ev) = ev else {
+                    warn!("standby mpv stopped");
+                    standby = None;
+                    continue;
+                };
+                let Some(sb) = standby.as_mut() else {
+                    continue;
+                };
+                match ev {
+                    MpvEvent::Ready => {
+                        sb.ready = true;
+                        let filters = audio_filters(&config, false);
+                        if !filters.is_empty() {
+                            let _ = sb.mpv.command(MpvCommand::SetAudioFilter(filters));
+                        }
+                    }
+                    MpvEvent::Crashed(_) | MpvEvent::GaveUp(_) | MpvEvent::Missing => {
+                        sb.ready = false;
+                        if let Some(p) = sb.preload.take() {
+                            // Play it the old way, with a hard cut.
+                            let _ = mpv.command(MpvCommand::LoadUrl { url: p.url, start: p.start });
+                        }
+                    }
+                    MpvEvent::MediaTitle(t) => {
+                        if let Some(p) = sb.preload.as_mut() {
+                            p.ad = t.as_deref().is_some_and(|t| ad_rules.matches(t));
+                            p.media_title = t.and_then(|t| title_rules.clean(state.station.as_ref(), &t));
+                        }
+                    }
+                    MpvEvent::Duration(d) => {
+                        if let Some(p) = sb.preload.as_mut() {
+                            p.duration = d;
+                        }
+                    }
+                    MpvEvent::HasVideo(true) => {
+                        if let Some(p) = sb.preload.take() {
+                            // Hand it to the active player, which refuses it.
+                            let _ = sb.mpv.command(MpvCommand::Stop);
+                            let _ = mpv.command(MpvCommand::LoadUrl { url: p.url, start: p.start });
+                        }
+                    }
+                    MpvEvent::StreamTags(tags) => {
+                        if let Some(p) = sb.preload.as_mut() {
+                            p.stream_tags = tags;
+                        }
+                    }
+                    MpvEvent::PlaybackStarted => {
+                        let Some(p) = sb.preload.take() else {
+                            continue;
+                        };
+                        if p.ad && state.cast_target.is_none() {
+                            // The crossfade below comes up to the ducked volume.
+                            debug!(title = ?p.media_title, "preloaded station is playing an ad");
+                            ad_duck = Some(state.volume);
+                            state.ad_ducked = true;
+                        }
+                        ad_silence_break = None;
+                        if take_over(&mut mpv, &mut mpv_events, sb, p, &mut config, &mut state, &mut duration) {
+                            save_config(&config);
+                        }
+                        if let Some(timer) = start_timer.take() {
+                            timer.finish(&mut state.start_latency);
+                        }
+                        core_idle = false;
+                        state.phase = PlaybackPhase::Playing;
+                        fade_seq += 1;
+                        fade = Some(Fade::cross(fade_seq, state.volume));
+                        spawn_fade(&internal_tx, fade_seq);
+                        let _ = state_tx.send(state.clone());
+                    }
+                    MpvEvent::DataUsed(bytes) => {
+                        // Preloading downloads too.
+                        state.session_bytes += bytes;
+                    }
+                    MpvEvent::CommandError { command, error } if command != "loadfile" => {
+                        warn!(%command, %error, "standby mpv command failed");
+                    }
+                    MpvEvent::PlaybackFailed(e) | MpvEvent::CommandError { error: e, .. } => {
+                        if sb.preload.take().is_none() {
+                            continue;
+                        }
+                        warn!(error = %e, "stream failed to play");
+                        // Don't leave the previous station playing under an error.
+                        let _ = mpv.command(MpvCommand::Stop);
+                        current_url = None;
+                        state.phase = PlaybackPhase::Error;
+                        state.errors.push(format!("Playback failed: {e}"));
+                        let _ = state_tx.send(state.clone());
+                        if let Some(station) = &state.station {
+                            find_alternates(&rb, &internal_tx, station);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(ev) = suspend_rx.recv() => {
+                match ev {
+                    SuspendEvent::Suspending(done) => {
+                        // The connection won't survive the sleep; pausing first keeps
+                        // the applet from claiming to play a dead stream afterwards.
+                        wake_reload = state.cast_target.is_none()
+                            && matches!(
+                                state.phase,
+                                PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing
+                            );
+                        if wake_reload {
+                            info!("pausing for suspend");
+                            let _ = mpv.command(MpvCommand::SetPause(true));
+                        }
+                        let _ = done.send(());
+                    }
+                    SuspendEvent::Resumed => {
+                        if std::mem::take(&mut wake_reload) && state.phase == PlaybackPhase::Paused {
+                            info!("reloading the stream after resume");
+                            let _ = self_tx.send(UiCommand::Reload);
+                        }
+                    }
+                }
+            }
+            _ = alarm_tick.tick() => {
+                // Volume changes come in bursts while the slider moves; save
+                // them here rather than on each step.
+                if std::mem::take(&mut profiles_dirty) {
+                    save_config(&config);
+                }
+                // Search results arrive per keystroke; write the cache at most
+                // once a tick.
+                if std::mem::take(&mut cache_dirty) {
+                    tokio::spawn(save_cache(cache.clone()));
+                }
+                if let Some((uuid, since)) = listening.as_mut() {
+                    // Counted as it goes, so a long session shows up before it ends.
+                    let secs = since.elapsed().as_secs();
+                    usage_dirty |= usage.record(UsageEvent::Listened { stationuuid: uuid, secs });
+                    *since += Duration::from_secs(secs);
+                }
+                if usage_dirty && usage_saved.elapsed() >= USAGE_SAVE {
+                    usage_dirty = false;
+                    usage_saved = Instant::now();
+                    state.usage = usage.summary();
+                    let _ = state_tx.send(state.clone());
+                    let stats = usage.clone();
+                    write_usage(&mut usage_write, move || stats.save());
+                }
+                let today = local_day();
+                if let Some(path) = recorder.as_ref().and_then(|r| r.missing_file()) {
+                    let e = format!("Recording stopped: could not create {}", path.display());
+                    finish_recording(&mut recorder, &mut state, &config, &internal_tx).await;
+                    state.notice = None;
+                    state.errors.push(e);
+                    let _ = state_tx.send(state.clone());
+                }
+                if state.recorder.as_ref().and_then(|r| r.until).is_some_and(|until| now_secs() >= until) {
+                    recording_seq += 1;
+                    finish_recording(&mut recorder, &mut state, &config, &internal_tx).await;
+                    let _ = state_tx.send(state.clone());
+                }
+                let (weekday, minute) = (local_weekday(), local_minute_of_day());
+                for (i, schedule) in config.recording.schedules.iter().enumerate() {
+                    if !config.network_consent
+                        || !schedule.is_due(weekday, minute)
+                        || schedules_started.get(&i) == Some(&today)
+                    {
+                        continue;
+                    }
+                    schedules_started.insert(i, today);
+                    if let Some(busy) = &state.recorder {
+                        state.errors.push(format!(
+                            "Not recording {}: still recording {}",
+                            schedule.station.name, busy.station
+                        ));
+                        let _ = state_tx.send(state.clone());
+                        continue;
+                    }
+                    info!(station = %schedule.station.name, "scheduled recording starting");
+                    recording_seq += 1;
+                    state.recorder = Some(RecordingStatus {
+                        station: schedule.station.name.clone(),
+                        file: None,
+                        until: Some(now_secs() + schedule.length().as_secs()),
+                    });
+                    let _ = state_tx.send(state.clone());
+                    record_station(&rb, &internal_tx, recording_seq, schedule.station.clone());
+                    if !schedule.muted && !is_listening(state.phase) {
+                        let _ = self_tx.send(UiCommand::Play(schedule.station.clone()));
+                    }
+                }
+                if config.discovery.enabled
+                    && config.network_consent
+                    && config.discovery.today.as_ref().is_none_or(|t| t.day != today)
+                    && discovery_tried.is_none_or(|t| t.elapsed() > DISCOVERY_RETRY)
+                {
+                    discovery_tried = Some(Instant::now());
+                    find_station_of_day(&rb, &internal_tx, &config, &state, &usage, today);
+                }
+                if alarm_ramp.as_ref().is_some_and(|r| {
+                    !r.started && (state.phase == PlaybackPhase::Error || r.fired.elapsed() > ALARM_START_TIMEOUT)
+                }) {
+                    // The station never started; don't leave the player silent.
+                    cancel_alarm_ramp(&mpv, &mut alarm_ramp);
+                }
+                if !config.alarm.is_due(local_minute_of_day())
+                    || alarm_fired == Some(today)
+                {
+                    continue;
+                }
+                let Some(station) = config.alarm.station.clone() else {
+                    continue;
+                };
+                alarm_fired = Some(today);
+                if is_listening(state.phase) {
+                    info!("alarm time reached while already listening");
+                    continue;
+                }
+                info!(station = %station.name, "alarm going off");
+                usage_dirty |= usage.record(UsageEvent::Used(Feature::Alarm));
+                if state.cast_target.is_none() {
+                    alarm_seq += 1;
+                    let _ = mpv.command(MpvCommand::SetVolume(0.0));
+                    alarm_ramp = Some(AlarmRamp {
+                        seq: alarm_seq,
+                        target: f64::from(config.alarm.volume),
+                        fired: Instant::now(),
+                        started: false,
+                    });
+                }
+                alarm_play = true;
+                let _ = self_tx.send(UiCommand::Play(station));
+            }
+            Some(msg) = internal_rx.recv() => {
+                match msg {
+                    InternalMsg::SearchDone { query, res } => {
+                        if query != state.search_query {
+                            continue;
+                        }
+                        match res {
+                            Ok(results) => {
+                                cache.insert(results.iter().cloned());
+                                cache_dirty = true;
+                                state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                                state.search_results = results;
+                                state.search_loading = false;
+                                state.errors.clear();
+                            }
+                            Err(e) => {
+                                state.search_loading = false;
+                                state.errors.push(e.to_string());
+                            }
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::StationsFetched { requested, explicit, res } => {
+                        if explicit {
+                            state.favorites_refreshing = false;
+                        }
+                        match res {
+                            Ok(stations) => {
+                                // Favorites Radio Browser no longer knows were probably
+                                // merged into another entry; look for it by name.
+                                let gone: Vec<(StationRef, Option<String>)> = config
+                                    .favorites
+                                    .iter()
+                                    .filter(|f| f.url.is_none() && requested.contains(&f.stationuuid))
+                                    .filter(|f| !stations.iter().any(|s| s.stationuuid == f.stationuuid))
+                                    .map(|f| (f.clone(), cache.get(&f.stationuuid).and_then(|s| s.url.clone())))
+                                    .collect();
+                                find_successors(&rb, &internal_tx, gone);
+                                cache.insert(stations);
+                                cache_dirty = true;
+                                state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                            }
+                            // Background refreshes fail quietly; cached details stay usable.
+                            Err(e) if explicit => state.errors.push(format!("Refresh failed: {e}")),
+                            Err(e) => warn!(error = ?e, "station refresh failed"),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::SuccessorsFound(found) => {
+                        let mut renamed = Vec::new();
+                        for (old, station) in found {
+                            let new = StationRef {
+                                stationuuid: station.stationuuid.clone(),
+                                name: station.name.clone(),
+                                url: None,
+                                group: None,
+                            };
+                            if remap_station(&old, new, &mut config, &mut usage, &mut state, &mut listening) {
+                                info!(%old, new = %station.stationuuid, "favorite moved to merged station");
+                                usage_dirty = true;
+                                renamed.push(station.name.clone());
+                                cache.insert([station]);
+                            }
+                        }
+                        if renamed.is_empty() {
+                            continue;
+                        }
+                        cache_dirty = true;
+                        // Favorites, the alarm and recording schedules may all have moved.
+                        sync_config_state(&mut state, &config);
+                        state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                        state.notice = Some(format!(
+                            "Radio Browser merged {}; your favorites now point to the current entry.",
+                            renamed.join(", ")
+                        ));
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    InternalMsg::FadeStep { seq, step } => {
+                        let Some(f) = fade.as_mut().filter(|f| f.seq == seq) else {
+                            continue;
+                        };
+                        if f.crossfade {
+                            let volume = f.restore - fade_volume(f.restore, step);
+                            let volume = if ad_duck.is_some() { ad_rules.ducked(volume) } else { volume };
+                            let _ = mpv.command(MpvCommand::SetVolume(volume));
+                            if let Some(sb) = &standby {
+                                let _ = sb.mpv.command(MpvCommand::SetVolume(fade_volume(f.restore, step)));
+                                if step == FADE_STEPS {
+                                    let _ = sb.mpv.command(MpvCommand::Stop);
+                                }
+                            }
+                            if step == FADE_STEPS {
+                                fade = None;
+                            }
+                            continue;
+                        }
+                        let _ = mpv.command(MpvCommand::SetVolume(fade_volume(f.restore, step)));
+                        if step < FADE_STEPS {
+                            continue;
+                        }
+                        f.finished = true;
+                        if let Some((url, start)) = f.load.take() {
+                            let _ = mpv.command(MpvCommand::LoadUrl { url, start });
+                            let _ = mpv.command(MpvCommand::SetVolume(f.restore));
+                            fade = None;
+                        }
+                    }
+                    InternalMsg::SleepStep { seq, step } => {
+                        if seq != sleep_seq || state.sleep_until.is_none() {
+                            continue;
+                        }
+                        let local = state.cast_target.is_none() && is_listening(state.phase);
+                        if step < SLEEP_FADE_STEPS {
+                            if local {
+                                let from = *sleep_restore.get_or_insert(state.volume);
+                                let left = f64::from(SLEEP_FADE_STEPS - step) / f64::from(SLEEP_FADE_STEPS);
+                                let _ = mpv.command(MpvCommand::SetVolume(from * left));
+                            }
+                            continue;
+                        }
+                        info!("sleep timer stopping playback");
+                        state.sleep_until = None;
+                        if is_listening(state.phase) {
+                            // Unlike Stop, keep the station so it's one click away tomorrow.
+                            plays.cancel();
+                            start_timer = None;
+                            if let Some(p) = query_resume_position(&mpv, &state, current_url.as_deref(), duration) {
+                                pending_position = Some(p);
+                            }
+                            let _ = mpv.command(MpvCommand::Stop);
+                            if let Some(sb) = standby.as_mut() {
+                                sb.preload = None;
+                                let _ = sb.mpv.command(MpvCommand::Stop);
+                            }
+                            if let Some(target) = state.cast_target.clone() {
+                                let cast = cast.clone();
+                                spawn_cast(&internal_tx, async move { cast.stop(&target).await });
+                            }
+                            current_url = None;
+                            want_paused = false;
+                            core_idle = true;
+                            state.phase = PlaybackPhase::Idle;
+                        }
+                        if let Some(v) = sleep_restore.take() {
+                            let _ = mpv.command(MpvCommand::SetVolume(v));
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    #[cfg(feature = "voice-search")]
+                    InternalMsg::Dictated(res) => {
+                        state.dictating = false;
+                        match res {
+                            Ok(query) => {
+                                info!(%query, "dictated search");
+                                let _ = self_tx.send(UiCommand::Search(query));
+                            }
+                            Err(e) => state.errors.push(format!("Voice search: {e:#}")),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::AlarmStep { seq, step } => {
+                        let Some(ramp) = alarm_ramp.as_ref().filter(|r| r.seq == seq) else {
+                            continue;
+                        };
+                        let volume = ramp.target * f64::from(step) / f64::from(ALARM_RAMP_STEPS);
+                        let _ = mpv.command(MpvCommand::SetVolume(volume));
+                        if step == ALARM_RAMP_STEPS {
+                            // The alarm's volume is the user's volume from now on.
+                            alarm_ramp = None;
+                            state.volume = volume;
+                            profiles_dirty |= remember_output(&mut config, sink.as_deref(), &state);
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    InternalMsg::Reconnect { generation } => {
+                        if !plays.is_current(generation)
+                            || state.phase != PlaybackPhase::Reconnecting
+                            || state.cast_target.is_some()
+                        {
+                            continue;
+                        }
+                        let Some(url) = current_url.clone() else {
+                            continue;
+                        };
+                        core_idle = true;
+                        let _ = mpv.command(MpvCommand::LoadUrl { url, start: None });
+                    }
+                    InternalMsg::PreloadTimeout { seq } => {
+                        // Still buffering: switch anyway rather than keep the old station going.
+                        let Some(sb) = standby.as_mut() else {
+                            continue;
+                        };
+                        let Some(p) = sb.preload.take_if(|p| p.seq == seq) else {
+                            continue;
+                        };
+                        if take_over(&mut mpv, &mut mpv_events, sb, p, &mut config, &mut state, &mut duration) {
+                            save_config(&config);
+                        }
+                        let _ = sb.mpv.command(MpvCommand::Stop);
+                        let _ = mpv.command(MpvCommand::SetVolume(state.volume));
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::CommitRemoval { stationuuid, seq } => {
+                        if pending_removals.get(&stationuuid) != Some(&seq) {
+                            continue;
+                        }
+                        pending_removals.remove(&stationuuid);
+                        state.pending_removals.retain(|s| s.stationuuid != stationuuid);
+                        config.remove_favorite(&stationuuid);
+                        state.favorites = config.favorites.clone();
+                        state.favorite_colors = config.favorite_colors.clone();
+                        state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                        let _ = state_tx.send(state.clone());
+                        save_config(&config);
+                    }
+                    InternalMsg::AlternatesFound { stationuuid, res } => {
+                        let current = state.station.as_ref().map(|s| s.stationuuid.as_str());
+                        if current != Some(stationuuid.as_str()) || state.phase != PlaybackPhase::Error {
+                            continue;
+                        }
+                        match res {
+                            Ok(stations) => {
+                                state.alternates = stations
+                                    .into_iter()
+                                    .filter(|s| s.stationuuid != stationuuid)
+                                    .take(MAX_ALTERNATES)
+                                    .collect();
+                            }
+                            Err(e) => warn!(error = ?e, "alternate lookup failed"),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::MirrorHealth(health) => {
+                        state.mirrors = health;
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::RenderersFound(res) => {
+                        state.renderers_loading = false;
+                        match res {
+                            Ok(renderers) => state.renderers = renderers,
+                            Err(e) => state.errors.push(format!("Speaker discovery failed: {e}")),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::StationOfDay { day, res } => match res {
+                        Ok(Some(station)) => {
+                            info!(stationuuid = %station.stationuuid, "picked the station of the day");
+                            config.discovery.today = Some(DailyStation {
+                                day,
+                                station,
+                                dismissed: false,
+                            });
+                            sync_config_state(&mut state, &config);
+                            let _ = state_tx.send(state.clone());
+                            save_config(&config);
+                        }
+                        Ok(None) => debug!("no station of the day to suggest"),
+                        Err(e) => debug!(error = ?e, "station of the day lookup failed"),
+                    },
+                    InternalMsg::RecordUrl { seq, station, res } => {
+                        if seq != recording_seq || recorder.is_some() {
+                            continue;
+                        }
+                        let started = match res {
+                            Ok(url) => {
+                                let stem = format!("{} {}", station.name, local_timestamp());
+                                match (config.recording.dir(), mpv_socket_path("mpv-record.sock")) {
+                                    (Ok(dir), Ok(socket)) => {
+                                        Recorder::start(socket, url, dir, &stem, config.recording.split_songs).await
+                                    }
+                                    (Err(e), _) | (_, Err(e)) => Err(e),
+                                }
+                            }
+                            Err(e) => Err(e),
+                        };
+                        match started {
+                            Ok(started) => {
+                                info!(station = %station.name, "recording");
+                                recorder = Some(started);
+                            }
+                            Err(e) => {
+                                state.recorder = None;
+                                state.errors.push(format!("Could not record {}: {e:#}", station.name));
+                                let _ = state_tx.send(state.clone());
+                            }
+                        }
+                    }
+                    InternalMsg::RecordingsListed(res) => {
+                        match res {
+                            Ok(files) => state.recordings = files,
+                            Err(e) => state.errors.push(format!("{e:#}")),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::DefaultSink(found) => {
+                        let Some(found) = found.filter(|f| sink.as_ref() != Some(f)) else {
+                            continue;
+                        };
+                        let first = sink.is_none();
+                        sink = Some(found.clone());
+                        if !config.output.per_output {
+                            continue;
+                        }
+                        let Some(profile) = config.output.profiles.get(&found).cloned() else {
+                            // New output: keep what's set now for it.
+                            profiles_dirty |= remember_output(&mut config, sink.as_deref(), &state);
+                            continue;
+                        };
+                        info!(sink = %found, "default output changed; applying its settings");
+                        let mut changed = false;
+                        if profile.audio_device != config.output.audio_device {
+                            // Not through SetAudioDevice: that would record the
+                            // old output's volume for this one.
+                            set_audio_device(&mpv, standby.as_ref(), &mut config, profile.audio_device.clone());
+                            state.output = config.output.clone();
+                            save_config(&config);
+                            changed = true;
+                        }
+                        if f64::from(profile.volume) != state.volume.round() {
+                            let _ = self_tx.send(UiCommand::SetVolume(f64::from(profile.volume)));
+                            changed = true;
+                        }
+                        if profile.muted != state.muted {
+                            let _ = self_tx.send(UiCommand::SetMute(profile.muted));
+                            changed = true;
+                        }
+                        if changed && !first {
+                            let name = crate::output_profile::sink_device(&state.audio_devices, &found)
+                                .map_or(found.as_str(), device_label);
+                            state.notice = Some(format!("Switched to {name}: using the volume you set for it."));
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    InternalMsg::ConfigLoaded(res) => {
+                        match res {
+                            Ok(mut loaded) => {
+                                // Runtime-owned fields aren't meant to be hand edited.
+                                loaded.last_station = config.last_station.clone();
+                                loaded.last_server = config.last_server.clone();
+                                loaded.output.profiles = config.output.profiles.clone();
+                                loaded.discovery.today = config.discovery.today.clone();
+                                let output_changed = loaded.output != config.output;
+                                let pin_changed = loaded.pinned_server != config.pinned_server;
+                                let filters_changed = audio_filters(&loaded, true) != audio_filters(&config, true);
+                                config = *loaded;
+                                if pin_changed {
+                                    apply_pinned_server(&rb, &config);
+                                }
+                                if filters_changed {
+                                    apply_audio_filters(&mpv, standby.as_ref(), &config);
+                                }
+                                sync_config_state(&mut state, &config);
+                                usage.set_enabled(config.privacy.usage_stats);
+                                state.station_details = station_details(&config, state.station.as_ref(), &cache);
+                                state.errors.clear();
+                                reload_ad_rules(&config.ad_mute, &mut ad_rules, &mut ad_duck, &mpv, &mut state);
+                                title_rules = load_title_rules(&config, &mut state);
+                                if output_changed {
+                                    let _ = mpv.command(MpvCommand::Respawn(mpv_options(&config)));
+                                    sync_standby(&mut standby, &config).await;
+                                }
+                                info!("config reloaded from disk");
+                            }
+                            Err(e) => {
+                                state.errors.push(format!("{e:#}"));
+                            }
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::CodecDetected { stationuuid, codec } => {
+                        let set = |s: &mut Station| s.codec = Some(codec.to_string());
+                        state
+                            .search_results
+                            .iter_mut()
+                            .chain(state.alternates.iter_mut())
+                            .filter(|s| s.stationuuid == stationuuid)
+                            .for_each(set);
+                        if let Some(s) = state.station_details.get_mut(&stationuuid) {
+                            set(s);
+                        }
+                        if cache.update(&stationuuid, set) {
+                            cache_dirty = true;
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::OriginFound { generation, origin } => {
+                        if plays.is_current(generation) {
+                            state.stream_origin = Some(origin);
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    InternalMsg::ProbeDone(res) => {
+                        state.stream_probe_loading = false;
+                        match res {
+                            Ok(info) => state.stream_offer = Some(info),
+                            Err(e) => state.errors.push(format!("{e:#}")),
+                        }
+                        let _ = state_tx.send(state.clone());
+                    }
+                    InternalMsg::CastDone(res) => {
+                        if let Err(e) = res {
+                            warn!(error = ?e, "cast command failed");
+                            state.errors.push(e.to_string());
+                            let _ = state_tx.send(state.clone());
+                        }
+                    }
+                    InternalMsg::ResolveDone { station, generation, res } => {
+                        // Superseded by a later Play, or cancelled by Stop: the
+                        // result, URL or error, mustn't touch playback at all.
+                        if !plays.is_current(generation) {
+                            continue;
+                        }
+                        if state.cast_target.is_some() {
+                            // No local audio to time.
+                            start_timer = None;
+                        } else if let Some(timer) = start_timer.as_mut() {
+                            timer.resolved.get_or_insert_with(Instant::now);
+                        }
+                        match res {
+                            Ok(url) => {
+                                info!(stationuuid = %station.stationuuid, "starting playback");
+                                recent_plays.record(&station.stationuuid, now_secs());
+                                let sniff = station.url.is_none() && missing_codec(&state, &station.stationuuid);
+                                // The check before loading usually found the origin already.
+                                if sniff || state.stream_origin.is_none() {
+                                    inspect_stream(&probe, &internal_tx, generation, &station.stationuuid, &url, sniff);
+                                }
+                                current_url = Some(url.clone());
+                                if let Some(target) = state.cast_target.clone() {
+                                    let _ = mpv.command(MpvCommand::Stop);
+                                    let cast = cast.clone();
+                                    let title = station.name.clone();
+                                    spawn_cast(&internal_tx, async move { cast.play(&target, &url, &title).await });
+                                } else {
+                                    // Only custom stations can be podcasts or archives.
+                                    let start = station.url.as_ref().and_then(|_| positions.get(&url));
+                                    let preload = std::mem::take(&mut old_audible) && fade.is_none();
+                                    match (fade.as_mut(), standby.as_mut().filter(|sb| sb.ready)) {
+                                        (Some(f), _) if !f.finished => f.load = Some((url, start)),
+                                        (None, Some(sb)) if preload => {
+                                            // Buffer silently on the standby player; it takes over
+                                            // once audio starts.
+                                            preload_seq += 1;
+                                            let _ = sb.mpv.command(MpvCommand::SetVolume(0.0));
+                                            let _ = sb.mpv.command(MpvCommand::SetPause(false));
+                                            let _ = sb.mpv.command(MpvCommand::LoadUrl { url: url.clone(), start });
+                                            sb.preload = Some(Preload::new(preload_seq, url, start));
+                                            let tx = internal_tx.clone();
+                                            let seq = preload_seq;
+                                            tokio::spawn(async move {
+                                                tokio::time::sleep(PRELOAD_TIMEOUT).await;
+                                                let _ = tx.send(InternalMsg::PreloadTimeout { seq });
+                                            });
+                                        }
+                                        _ => {
+                                            let _ = mpv.command(MpvCommand::LoadUrl { url, start });
+                                            // mpv keeps `pause` across loads, e.g. when reloading a paused stream.
+                                            let _ = mpv.command(MpvCommand::SetPause(false));
+                                            if let Some(f) = fade.take() {
+                                                let _ = mpv.command(MpvCommand::SetVolume(f.restore));
+                                            }
+                                        }
+                                    }
+                                    // Playing once mpv reports audio flowing.
+                                    core_idle = true;
+                                }
+                                state.phase = if state.cast_target.is_some() {
+                                    PlaybackPhase::Playing
+                                } else {
+                                    PlaybackPhase::Buffering
+                                };
+                                state.errors.clear();
+                                let _ = state_tx.send(state.clone());
+
+                                if config.privacy.remember_history {
+                                    config.last_station = Some(station);
+                                    if let Some(s) = rb.lock().await.last_server().map(|s| s.to_string()) {
+                                        config.last_server = Some(s);
+                                    }
+                                }
+                                save_config(&config);
+                            }
+                            Err(e) => {
+                                if std::mem::take(&mut known_url) {
+                                    // The remembered address may be outdated; ask Radio Browser.
+                                    info!(error = %e, "remembered stream URL is dead, resolving again");
+                                    resolve_station(
+                                        &rb,
+                                        &probe,
+                                        &internal_tx,
+                                        station,
+                                        generation,
+                                        false,
+                                    );
+                                    continue;
+                                }
+                                if let Some(f) = fade.take() {
+                                    let _ = mpv.command(MpvCommand::SetVolume(f.restore));
+                                }
+                                state.phase = PlaybackPhase::Error;
+                                state.errors.push(e.to_string());
+                                let _ = state_tx.send(state.clone());
+                                find_alternates(&rb, &internal_tx, &station);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum InternalMsg {
+    SearchDone { query: String, res: Result<Vec<Station>> },
+    /// `explicit` for the user's Refresh; background refreshes fail quietly.
+    StationsFetched { requested: Vec<String>, explicit: bool, res: Result<Vec<Station>> },
+    /// Favorite UUIDs Radio Browser dropped, with the entries that replaced them.
+    SuccessorsFound(Vec<(String, Station)>),
+    MirrorHealth(Vec<MirrorHealth>),
+    CommitRemoval { stationuuid: String, seq: u64 },
+    FadeStep { seq: u64, step: u32 },
+    PreloadTimeout { seq: u64 },
+    AlternatesFound { stationuuid: String, res: Result<Vec<Station>> },
+    ResolveDone { station: StationRef, generation: u64, res: Result<String> },
+    RenderersFound(Result<Vec<Renderer>>),
+    CastDone(Result<()>),
+    ProbeDone(Result<StreamInfo>),
+    CodecDetected { stationuuid: String, codec: &'static str },
+    OriginFound { generation: u64, origin: StreamOrigin },
+    /// Time to load a dropped live stream again.
+    Reconnect { generation: u64 },
+    /// One step of the sleep timer's fade; the last one stops playback.
+    SleepStep { seq: u64, step: u32 },
+    #[cfg(feature = "voice-search")]
+    Dictated(Result<String>),
+    /// One step of an alarm's fade-in.
+    AlarmStep { seq: u64, step: u32 },
+    ConfigLoaded(Result<Box<AppConfig>>),
+    /// The system's default output, when it can be found out.
+    DefaultSink(Option<String>),
+    StationOfDay { day: u32, res: Result<Option<Station>> },
+    /// The stream to record for record request `seq`.
+    RecordUrl { seq: u64, station: StationRef, res: Result<String> },
+    RecordingsListed(Result<Vec<RecordingFile>>),
+}
+
+/// How long recent stations took from click to first audio.
+#[derive(Debug, Clone, Default)]
+pub struct StartLatency {
+    /// Milliseconds, oldest first.
+    samples: VecDeque<u32>,
+    /// Resolve share of the most recent start, in milliseconds.
+    pub last_resolve_ms: Option<u32>,
+}
+
+impl StartLatency {
+    fn record(&mut self, total: Duration, resolve: Option<Duration>) {
+        if self.samples.len() >= START_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(millis(total));
+        self.last_resolve_ms = resolve.map(millis);
+    }
+
+    pub fn last_ms(&self) -> Option<u32> {
+        self.samples.back().copied()
+    }
+
+    pub fn median_ms(&self) -> Option<u32> {
+        let mut sorted: Vec<u32> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted.get(sorted.len().checked_sub(1)? / 2).copied()
+    }
+
+    pub fn budget_ms(&self) -> u32 {
+        millis(START_BUDGET)
+    }
+}
+
+fn millis(d: Duration) -> u32 {
+    d.as_millis().min(u32::MAX as u128) as u32
+}
+
+/// Timing of the Play in progress, finished when audio starts.
+#[derive(Debug)]
+struct StartTimer {
+    clicked: Instant,
+    resolved: Option<Instant>,
+}
+
+impl StartTimer {
+    fn finish(self, latency: &mut StartLatency) {
+        let total = self.clicked.elapsed();
+        let resolve = self.resolved.map(|r| r - self.clicked);
+        let resolve_ms = resolve.map(millis);
+        if total > START_BUDGET {
+            warn!(total_ms = millis(total), ?resolve_ms, "slow station start");
+        } else {
+            info!(total_ms = millis(total), ?resolve_ms, "station started");
+        }
+        latency.record(total, resolve);
+    }
+}
+
+/// Numbers each Play so results that arrive after the user moved on (another
+/// Play, or Stop) can be recognised and dropped.
+#[derive(Debug, Default)]
+struct PlayGeneration {
+    current: u64,
+    cancelled: bool,
+}
+
+impl PlayGeneration {
+    fn start(&mut self) -> u64 {
+        self.current += 1;
+        self.cancelled = false;
+        self.current
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        !self.cancelled && generation == self.current
+    }
+}
+
+/// A volume ramp during a station switch. Without a standby player the old
+/// stream fades out, the new one is held back until it has, and the volume is
+/// then put back. A crossfade ramps the standby (old) player down while the
+/// new one comes up.
+struct Fade {
+    seq: u64,
+    restore: f64,
+    crossfade: bool,
+    finished: bool,
+    load: Option<(String, Option<f64>)>,
+}
+
+impl Fade {
+    fn out(seq: u64, restore: f64) -> Self {
+        Self { seq, restore, crossfade: false, finished: false, load: None }
+    }
+
+    fn cross(seq: u64, restore: f64) -> Self {
+        Self { crossfade: true, ..Self::out(seq, restore) }
+    }
+}
+
+fn spawn_fade(tx: &mpsc::UnboundedSender<InternalMsg>, seq: u64) {
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        for step in 1..=FADE_STEPS {
+            tokio::time::sleep(FADE_OUT / FADE_STEPS).await;
+            if tx.send(InternalMsg::FadeStep { seq, step }).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Sends the sleep timer's fade steps, the last one when `after` has passed.
+fn spawn_sleep_timer(tx: &mpsc::UnboundedSender<InternalMsg>, seq: u64, after: Duration) {
+    let tx = tx.clone();
+    let fade = after.min(SLEEP_FADE);
+    tokio::spawn(async move {
+        tokio::time::sleep(after - fade).await;
+        for step in 1..=SLEEP_FADE_STEPS {
+            tokio::time::sleep(fade / SLEEP_FADE_STEPS).await;
+            if tx.send(InternalMsg::SleepStep { seq, step }).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Fade-in for an alarm that just went off. The player is muted until the
+/// station starts, then ramps up to `target`.
+struct AlarmRamp {
+    seq: u64,
+    target: f64,
+    fired: Instant,
+    /// Audio is flowing and the ramp steps are on their way.
+    started: bool,
+}
+
+/// Ends an alarm fade-in early, at the volume it was heading for.
+fn cancel_alarm_ramp(mpv: &MpvProcess, ramp: &mut Option<AlarmRamp>) {
+    if let Some(r) = ramp.take() {
+        let _ = mpv.command(MpvCommand::SetVolume(r.target));
+    }
+}
+
+/// Sends the alarm fade-in steps, spread over `fade`.
+fn spawn_alarm_ramp(tx: &mpsc::UnboundedSender<InternalMsg>, seq: u64, fade: Duration) {
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        for step in 1..=ALARM_RAMP_STEPS {
+            tokio::time::sleep(fade / ALARM_RAMP_STEPS).await;
+            if tx.send(InternalMsg::AlarmStep { seq, step }).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Second mpv kept idle so the next station can buffer while the current one
+/// keeps playing.
+struct Standby {
+    mpv: MpvProcess,
+    events: mpsc::UnboundedReceiver<MpvEvent>,
+    ready: bool,
+    preload: Option<Preload>,
+}
+
+/// A station loading on the standby player, and what it reported meanwhile.
+struct Preload {
+    seq: u64,
+    url: String,
+    start: Option<f64>,
+    media_title: Option<String>,
+    /// The raw title matched an ad rule.
+    ad: bool,
+    duration: Option<f64>,
+    stream_tags: StreamTags,
+}
+
+impl Preload {
+    fn new(seq: u64, url: String, start: Option<f64>) -> Self {
+        Self {
+            seq,
+            url,
+            start,
+            media_title: None,
+            ad: false,
+            duration: None,
+            stream_tags: StreamTags::default(),
+        }
+    }
+}
+
+fn standby_allowed(config: &AppConfig) -> bool {
+    let output = &config.output;
+    // Two players can't share one Snapcast pipe.
+    output.standby && !output.low_resource && !output.snapcast
+}
+
+async fn spawn_standby(config: &AppConfig) -> Option<Standby> {
+    if !standby_allowed(config) {
+        return None;
+    }
+    let spawned = match mpv_socket_path("mpv-standby.sock") {
+        Ok(path) => MpvProcess::spawn(path, mpv_options(config)).await,
+        Err(e) => Err(e),
+    };
+    match spawned {
+        Ok((mpv, events)) => Some(Standby { mpv, events, ready: false, preload: None }),
+        Err(e) => {
+            warn!(error = ?e, "no standby mpv; station switches will have a gap");
+            None
+        }
+    }
+}
+
+/// Starts, restarts or drops the standby player after output settings changed.
+async fn sync_standby(standby: &mut Option<Standby>, config: &AppConfig) {
+    match standby {
+        Some(_) if !standby_allowed(config) => *standby = None,
+        Some(sb) => {
+            sb.ready = false;
+            sb.preload = None;
+            let _ = sb.mpv.command(MpvCommand::Respawn(mpv_options(config)));
+        }
+        None => *standby = spawn_standby(config).await,
+    }
+}
+
+async fn next_standby_event(standby: &mut Option<Standby>) -> Option<MpvEvent> {
+    match standby {
+        Some(sb) => sb.events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn fallback_exited(fallback: &mut Option<FallbackPlayer>) -> std::io::Result<std::process::ExitStatus> {
+    match fallback {
+        Some(f) => f.exited().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Moves everything kept for the favorite `old` over to its successor `new`:
+/// the config's references, listening statistics and this session's state.
+/// Resume positions are kept by stream URL and only for custom stations,
+/// which are never remapped. Returns whether the favorite moved.
+fn remap_station(
+    old: &str,
+    new: StationRef,
+    config: &mut AppConfig,
+    usage: &mut UsageStats,
+    state: &mut ControllerState,
+    listening: &mut Option<(String, Instant)>,
+) -> bool {
+    if !config.remap_favorite(old, &new) {
+        return false;
+    }
+    usage.remap(old, &new.stationuuid);
+    if let Some((uuid, _)) = listening.as_mut().filter(|(uuid, _)| uuid == old) {
+        *uuid = new.stationuuid.clone();
+    }
+    if state.video_stations.remove(old) {
+        state.video_stations.insert(new.stationuuid.clone());
+    }
+    if state.station.as_ref().is_some_and(|s| s.stationuuid == old) {
+        state.station = Some(new);
+    }
+    true
+}
+
+/// Makes the standby player the active one, carrying over what it reported
+/// while preloading. The previous player becomes the standby. Returns
+/// whether the config changed.
+fn take_over(
+    mpv: &mut MpvProcess,
+    events: &mut mpsc::UnboundedReceiver<MpvEvent>,
+    sb: &mut Standby,
+    preload: Preload,
+    config: &mut AppConfig,
+    state: &mut ControllerState,
+    duration: &mut Option<f64>,
+) -> bool {
+    std::mem::swap(mpv, &mut sb.mpv);
+    std::mem::swap(events, &mut sb.events);
+    if metered(config) {
+        // Only the active player is measured.
+        apply_audio_filters(mpv, Some(sb), config);
+    }
+    state.media_title = preload.media_title;
+    *duration = preload.duration;
+    apply_stream_tags(config, state, preload.stream_tags)
+}
+
+/// Shows what the stream announces about itself, and uses its name for a
+/// custom favorite that only had its host name so far. Returns whether the
+/// config changed.
+fn apply_stream_tags(config: &mut AppConfig, state: &mut ControllerState, tags: StreamTags) -> bool {
+    let announced = tags.name.clone();
+    state.stream_tags = (!tags.is_empty()).then_some(tags);
+    let (Some(station), Some(name)) = (state.station.as_mut(), announced) else {
+        return false;
+    };
+    if !config.name_custom_favorite(&station.stationuuid, &name) {
+        return false;
+    }
+    info!(%name, "named custom station from its stream");
+    station.name = name;
+    state.favorites = config.favorites.clone();
+    true
+}
+
+/// The output playback depends on, if it's in `before` but not in `after`.
+/// With no device chosen, that's the default output (`default_sink`, as
+/// last known): the sound server would otherwise move the stream to whatever
+/// is left, usually speakers. Other outputs coming and going don't matter.
+fn lost_output(
+    before: &[AudioDevice],
+    after: &[AudioDevice],
+    chosen: Option<&str>,
+    default_sink: Option<&str>,
+) -> Option<AudioDevice> {
+    let gone = |d: &&AudioDevice| d.name != "auto" && !after.iter().any(|a| a.name == d.name);
+    match chosen.filter(|c| *c != "auto") {
+        Some(chosen) => before.iter().filter(gone).find(|d| d.name == chosen).cloned(),
+        None => default_sink
+            .and_then(|sink| crate::output_profile::sink_device(before, sink))
+            .filter(gone)
+            .cloned(),
+    }
+}
+
+/// Records the current volume, mute and device for the default output;
+/// true if that changed anything.
+fn remember_output(config: &mut AppConfig, sink: Option<&str>, state: &ControllerState) -> bool {
+    let Some(sink) = sink.filter(|_| config.output.per_output) else {
+        return false;
+    };
+    let profile = OutputProfile {
+        volume: state.volume.round().clamp(0.0, 100.0) as u8,
+        muted: state.muted,
+        audio_device: config.output.audio_device.clone(),
+    };
+    if config.output.profiles.get(sink) == Some(&profile) {
+        return false;
+    }
+    config.output.profiles.insert(sink.to_string(), profile);
+    true
+}
+
+/// Looks for the station of the day off the select loop: a popular station
+/// in one of the favorites' genres, or just a popular one when that finds
+/// nothing new.
+fn find_station_of_day(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    config: &AppConfig,
+    state: &ControllerState,
+    usage: &UsageStats,
+    day: u32,
+) {
+    let tag = crate::discovery::tag_for_day(state.station_details.values(), day);
+    let known: HashSet<String> = config
+        .favorites
+        .iter()
+        .chain(&config.ui.blocked)
+        .map(|s| s.stationuuid.clone())
+        // Played ones are known too, favorite or not.
+        .chain(usage.played().cloned())
+        // Not the same station two days running.
+        .chain(config.discovery.today.as_ref().map(|t| t.station.stationuuid.clone()))
+        .collect();
+    let quality = config.quality.clone();
+    let rb = rb.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let (res, health) = {
+            let mut client = rb.lock().await;
+            let pick = |res: Result<Vec<Station>>| {
+                res.map(|c| {
+                    let good = c.into_iter().filter(|s| quality.allows(s)).collect();
+                    crate::discovery::pick(good, &known, day)
+                })
+            };
+            let mut res = pick(client.top_stations(tag.as_deref(), DISCOVERY_CANDIDATES).await);
+            if matches!(res, Ok(None)) && tag.is_some() {
+                res = pick(client.top_stations(None, DISCOVERY_CANDIDATES).await);
+            }
+            (res, client.mirror_health())
+        };
+        let _ = tx.send(InternalMsg::MirrorHealth(health));
+        let _ = tx.send(InternalMsg::StationOfDay { day, res });
+    });
+}
+
+/// Asks the sound server for its default output off the select loop.
+fn find_default_sink(tx: &mpsc::UnboundedSender<InternalMsg>) {
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let sink = match crate::output_profile::default_sink().await {
+            Ok(sink) => Some(sink),
+            Err(e) => {
+                debug!(error = %e, "default output unknown");
+                None
+            }
+        };
+        let _ = tx.send(InternalMsg::DefaultSink(sink));
+    });
+}
+
+fn device_label(device: &AudioDevice) -> &str {
+    if device.description.is_empty() {
+        &device.name
+    } else {
+        &device.description
+    }
+}
+
+/// Whether a station is loaded, playing or not.
+fn is_listening(phase: PlaybackPhase) -> bool {
+    matches!(
+        phase,
+        PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing | PlaybackPhase::Paused
+    )
+}
+
+/// Whether a live stream paused this long should be reloaded rather than resumed.
+fn snap_to_live(paused_for: Duration, resume: &ResumeConfig) -> bool {
+    resume.snap_to_live_minutes > 0 && paused_for >= Duration::from_secs(u64::from(resume.snap_to_live_minutes) * 60)
+}
+
+/// Phase of a loaded local stream, from mpv's pause and core-idle properties.
+fn active_phase(paused: bool, core_idle: bool) -> PlaybackPhase {
+    if paused {
+        PlaybackPhase::Paused
+    } else if core_idle {
+        PlaybackPhase::Buffering
+    } else {
+        PlaybackPhase::Playing
+    }
+}
+
+/// Counts another attempt at bringing the stream back after `reason` and
+/// switches to [`PlaybackPhase::Reconnecting`]. Every kind of drop shares the
+/// one count, which only resets once audio flows again; `None` when
+/// [`MAX_RECONNECTS`] are used up, for the caller to give up.
+fn next_reconnect(state: &mut ControllerState, reason: DropReason) -> Option<u32> {
+    let attempt = state.reconnect.map_or(0, |r| r.attempt) + 1;
+    if attempt > MAX_RECONNECTS {
+        warn!(?reason, "stream keeps dropping, giving up");
+        state.reconnect = None;
+        return None;
+    }
+    info!(?reason, attempt, "reconnecting");
+    state.phase = PlaybackPhase::Reconnecting;
+    state.reconnect = Some(Reconnect { attempt, reason });
+    Some(attempt)
+}
+
+/// Wait before reconnect `attempt` (1-based): 1 s, 2 s, 4 s, … up to 16 s.
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.clamp(1, 5).saturating_sub(1))
+}
+
+fn schedule_reconnect(tx: &mpsc::UnboundedSender<InternalMsg>, generation: u64, attempt: u32) {
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(reconnect_delay(attempt)).await;
+        let _ = tx.send(InternalMsg::Reconnect { generation });
+    });
+}
+
+/// Volume after `step` of [`FADE_STEPS`], fading linearly from `from` to silence.
+fn fade_volume(from: f64, step: u32) -> f64 {
+    from * f64::from(FADE_STEPS.saturating_sub(step)) / f64::from(FADE_STEPS)
+}
+
+/// Copies the user-facing settings from `config` into the published state.
+fn sync_config_state(state: &mut ControllerState, config: &AppConfig) {
+    state.favorites = config.favorites.clone();
+    state.consent_pending = !config.network_consent;
+    state.privacy = config.privacy.clone();
+    state.output = config.output.clone();
+    state.favorite_colors = config.favorite_colors.clone();
+    state.stream_choice = config.stream_choice.clone();
+    state.favorite_stream_choice = config.favorite_stream_choice.clone();
+    state.ui = config.ui.clone();
+    state.pinned_server = config.pinned_server.clone();
+    state.log_level = config.log_level;
+    state.equalizer = config.equalizer;
+    state.channels = config.channels;
+    state.alarm = config.alarm.clone();
+    state.recording = config.recording.clone();
+    state.voice = config.voice.clone();
+    state.resume = config.resume.clone();
+    state.power = config.power.clone();
+    state.quality = config.quality.clone();
+    state.ad_mute = config.ad_mute.clone();
+    state.discovery_enabled = config.discovery.enabled;
+    state.station_of_day = config
+        .discovery
+        .today
+        .as_ref()
+        .filter(|t| config.discovery.enabled && !t.dismissed)
+        .map(|t| t.station.clone());
+    crate::logging::set_level(config.log_level);
+    state.ready = true;
+}
+
+/// Recompiles the ad rules after a config change, reporting bad patterns, and
+/// ends a duck the new rules no longer call for.
+fn reload_ad_rules(
+    config: &AdMuteConfig,
+    rules: &mut AdRules,
+    duck: &mut Option<f64>,
+    mpv: &MpvProcess,
+    state: &mut ControllerState,
+) {
+    let (compiled, errors) = AdRules::new(config);
+    *rules = compiled;
+    for e in errors {
+        state.errors.push(e);
+    }
+    if !state.media_title.as_deref().is_some_and(|t| rules.matches(t)) {
+        end_ad_duck(mpv, duck, state);
+    }
+}
+
+/// Compiles the title cleanup rules, reporting bad patterns.
+fn load_title_rules(config: &AppConfig, state: &mut ControllerState) -> TitleRules {
+    let (rules, errors) = TitleRules::new(&config.titles);
+    for e in errors {
+        state.errors.push(e);
+    }
+    rules
+}
+
+/// Puts the user's volume back after an ad.
+fn end_ad_duck(mpv: &MpvProcess, duck: &mut Option<f64>, state: &mut ControllerState) {
+    if let Some(volume) = duck.take() {
+        let _ = mpv.command(MpvCommand::SetVolume(volume));
+    }
+    state.ad_ducked = false;
+}
+
+/// Asks mpv where a non-live custom stream currently is, so the answer can be
+/// stored as its resume position. Returns what the answer should be filed under.
+fn query_resume_position(
+    mpv: &MpvProcess,
+    state: &ControllerState,
+    current_url: Option<&str>,
+    duration: Option<f64>,
+) -> Option<(String, f64)> {
+    let custom = state.station.as_ref().is_some_and(|s| s.url.is_some());
+    let active = matches!(state.phase, PlaybackPhase::Buffering | PlaybackPhase::Playing | PlaybackPhase::Paused);
+    if !custom || !active || state.cast_target.is_some() {
+        return None;
+    }
+    let (url, length) = (current_url?, duration?);
+    mpv.command(MpvCommand::QueryPosition).ok()?;
+    Some((url.to_string(), length))
+}
+
+/// Radio Browser favorites; custom stations have no record to fetch.
+fn favorite_uuids(config: &AppConfig) -> impl Iterator<Item = &str> {
+    config
+        .favorites
+        .iter()
+        .filter(|f| f.url.is_none())
+        .map(|f| f.stationuuid.as_str())
+}
+
+/// Radio Browser stations whose details are kept at hand: the favorites,
+/// the last played station and the current one.
+fn detail_uuids<'a>(config: &'a AppConfig, current: Option<&'a StationRef>) -> impl Iterator<Item = &'a str> {
+    let mut seen = HashSet::new();
+    favorite_uuids(config)
+        .chain(
+            config
+                .last_station
+                .iter()
+                .chain(current)
+                .filter(|s| s.url.is_none())
+                .map(|s| s.stationuuid.as_str()),
+        )
+        .filter(move |uuid| seen.insert(*uuid))
+}
+
+fn station_details(config: &AppConfig, current: Option<&StationRef>, cache: &StationCache) -> BTreeMap<String, Station> {
+    detail_uuids(config, current)
+        .filter_map(|uuid| cache.get(uuid).map(|s| (uuid.to_string(), s.clone())))
+        .collect()
+}
+
+fn fetch_stations(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    uuids: Vec<String>,
+    explicit: bool,
+) {
+    if uuids.is_empty() {
+        return;
+    }
+    let rb = rb.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let (res, health) = {
+            let mut client = rb.lock().await;
+            (client.stations_by_uuid(&uuids).await, client.mirror_health())
+        };
+        let _ = tx.send(InternalMsg::MirrorHealth(health));
+        let _ = tx.send(InternalMsg::StationsFetched { requested: uuids, explicit, res });
+    });
+}
+
+/// Resolves a Radio Browser station's stream URL off the select loop, then
+/// checks that it answers.
+fn resolve_station(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    station: StationRef,
+    generation: u64,
+    count_click: bool,
+) {
+    let rb = rb.clone();
+    let probe = probe.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let res = resolved_url(&rb, &probe, &tx, &station.stationuuid, generation, count_click).await;
+        let _ = tx.send(InternalMsg::ResolveDone { station, generation, res });
+    });
+}
+
+/// Like [`resolve_station`], but first looks for the station's other entries
+/// on Radio Browser and plays the one its [`StreamChoice`] prefers.
+fn choose_stream(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    station: StationRef,
+    generation: u64,
+    config: &AppConfig,
+) {
+    let rb = rb.clone();
+    let probe = probe.clone();
+    let tx = tx.clone();
+    let choice = config.stream_choice_for(&station.stationuuid).clone();
+    let count_click = config.privacy.count_clicks;
+    tokio::spawn(async move {
+        // Fresh records: the codec and bitrate may have changed since the
+        // station was favorited.
+        let chosen = match same_broadcaster(&rb, &tx, &station.stationuuid, STREAM_CHOICES).await {
+            Ok((listed, others)) => choice.choose(&listed, &others).stationuuid.clone(),
+            Err(e) => {
+                debug!(error = ?e, "station lookup for stream choice failed");
+                station.stationuuid.clone()
+            }
+        };
+        if chosen != station.stationuuid {
+            info!(listed = %station.stationuuid, %chosen, "playing the preferred stream");
+        }
+        let res = resolved_url(&rb, &probe, &tx, &chosen, generation, count_click).await;
+        let _ = tx.send(InternalMsg::ResolveDone { station, generation, res });
+    });
+}
+
+/// A station's current record and the other entries Radio Browser lists for
+/// the same broadcaster, most voted first. The client is locked per request
+/// so playback and searches aren't held up behind the lookups.
+async fn same_broadcaster(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    stationuuid: &str,
+    limit: u32,
+) -> Result<(Station, Vec<Station>)> {
+    let listed = rb.lock().await.stations_by_uuid(&[stationuuid.to_string()]).await;
+    let listed = listed?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Station not found"))?;
+    let (named, health) = {
+        let mut client = rb.lock().await;
+        let named = client.stations_named(&listed.name, limit).await;
+        (named, client.mirror_health())
+    };
+    let _ = tx.send(InternalMsg::MirrorHealth(health));
+    let others = named?
+        .into_iter()
+        .filter(|s| s.stationuuid != listed.stationuuid && listed.same_broadcaster(s))
+        .collect();
+    Ok((listed, others))
+}
+
+/// The stream URL Radio Browser gives for a station, checked.
+async fn resolved_url(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    stationuuid: &str,
+    generation: u64,
+    count_click: bool,
+) -> Result<String> {
+    let (res, health) = {
+        let mut client = rb.lock().await;
+        let res = client.resolve_station_url(stationuuid, count_click).await;
+        (res, client.mirror_health())
+    };
+    let _ = tx.send(InternalMsg::MirrorHealth(health));
+    checked_url(probe, tx, generation, res?.to_string()).await
+}
+
+/// Checks an already known stream URL off the select loop before it's played.
+fn check_stream(
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    station: StationRef,
+    generation: u64,
+    url: String,
+) {
+    let probe = probe.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let res = checked_url(&probe, &tx, generation, url).await;
+        let _ = tx.send(InternalMsg::ResolveDone { station, generation, res });
+    });
+}
+
+/// `url` unless the stream clearly won't play; reports where it's served from.
+/// Playlist files are opened here and their best entry returned, since mpv
+/// doesn't cope well with every `.m3u`/`.pls` stations serve.
+async fn checked_url(
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    generation: u64,
+    url: String,
+) -> Result<String> {
+    let Ok(parsed) = url::Url::parse(&url) else {
+        // mpv gets to say what's wrong with it.
+        return Ok(url);
+    };
+    let Some(check) = probe.check(parsed.clone()).await? else {
+        return Ok(url);
+    };
+    debug!(content_type = ?check.content_type, "stream answered");
+    let (url, check) = match check.kind {
+        // `.m3u8` is usually HLS, but not always.
+        StreamKind::Playlist | StreamKind::Hls => match probe.playlist_entry(parsed).await? {
+            Some((entry, entry_check)) => {
+                debug!(%entry, "playing entry from playlist");
+                (entry.to_string(), entry_check)
+            }
+            None => (url, Some(check)),
+        },
+        StreamKind::Direct => (url, Some(check)),
+    };
+    if let Some(check) = check {
+        let _ = tx.send(InternalMsg::OriginFound { generation, origin: check.origin });
+    }
+    Ok(url)
+}
+
+/// Registers a play with Radio Browser when the URL was already known.
+fn count_click(rb: &Arc<Mutex<RadioBrowserClient>>, tx: &mpsc::UnboundedSender<InternalMsg>, stationuuid: &str) {
+    let rb = rb.clone();
+    let tx = tx.clone();
+    let stationuuid = stationuuid.to_string();
+    tokio::spawn(async move {
+        let health = {
+            let mut client = rb.lock().await;
+            if let Err(e) = client.resolve_station_url(&stationuuid, true).await {
+                debug!(error = ?e, "click count failed");
+            }
+            client.mirror_health()
+        };
+        let _ = tx.send(InternalMsg::MirrorHealth(health));
+    });
+}
+
+/// A playable URL from a station record fetched earlier (search results,
+/// alternates, cached favorites), saving a resolve round trip.
+fn known_stream_url(state: &ControllerState, cache: &StationCache, stationuuid: &str) -> Option<String> {
+    state
+        .search_results
+        .iter()
+        .chain(state.alternates.iter())
+        .find(|s| s.stationuuid == stationuuid)
+        .or_else(|| cache.get(stationuuid))
+        .and_then(Station::stream_url)
+        .map(str::to_string)
+}
+
+/// Looks up same-named entries for favorites whose UUID disappeared, with
+/// their last known stream URL to tell mirrors apart.
+fn find_successors(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    gone: Vec<(StationRef, Option<String>)>,
+) {
+    if gone.is_empty() {
+        return;
+    }
+    let rb = rb.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let mut found = Vec::new();
+        for (fav, old_url) in gone {
+            let res = rb.lock().await.stations_named(&fav.name, 10).await;
+            match res {
+                Ok(candidates) => {
+                    if let Some(s) = find_successor(&fav, old_url.as_deref(), &candidates) {
+                        found.push((fav.stationuuid, s.clone()));
+                    }
+                }
+                Err(e) => debug!(error = ?e, name = %fav.name, "successor lookup failed"),
+            }
+        }
+        if !found.is_empty() {
+            let _ = tx.send(InternalMsg::SuccessorsFound(found));
+        }
+    });
+}
+
+/// The client may be busy with a request, so update it off the select loop.
+fn apply_pinned_server(rb: &Arc<Mutex<RadioBrowserClient>>, config: &AppConfig) {
+    let rb = rb.clone();
+    let pinned = config.pinned_server.clone();
+    tokio::spawn(async move {
+        rb.lock().await.set_pinned_server(pinned);
+    });
+}
+
+/// Looks for other Radio Browser entries of the same broadcaster. Custom
+/// stations have nothing to look up.
+fn find_alternates(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    station: &StationRef,
+) {
+    if station.url.is_some() {
+        return;
+    }
+    let rb = rb.clone();
+    let tx = tx.clone();
+    let stationuuid = station.stationuuid.clone();
+    tokio::spawn(async move {
+        // One extra, since the failed station itself is usually in the results.
+        let limit = MAX_ALTERNATES as u32 + 1;
+        let res = same_broadcaster(&rb, &tx, &stationuuid, limit).await.map(|(_, others)| others);
+        let _ = tx.send(InternalMsg::AlternatesFound { stationuuid, res });
+    });
+}
+
+/// The local time now, unless the C library can't tell.
+fn local_time() -> Option<libc::tm> {
+    let now = now_secs() as libc::time_t;
+    // SAFETY: all-zero is a valid `tm`, and both pointers outlive the call.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    (!unsafe { libc::localtime_r(&now, &mut tm) }.is_null()).then_some(tm)
+}
+
+/// Minutes after local midnight.
+fn local_minute_of_day() -> u32 {
+    match local_time() {
+        Some(tm) => (tm.tm_hour * 60 + tm.tm_min) as u32,
+        None => (now_secs() / 60 % (24 * 60)) as u32,
+    }
+}
+
+/// Local calendar day as `year * 1000 + day of year`.
+fn local_day() -> u32 {
+    match local_time() {
+        Some(tm) => (tm.tm_year + 1900) as u32 * 1000 + tm.tm_yday as u32,
+        None => (now_secs() / (24 * 60 * 60)) as u32,
+    }
+}
+
+/// 0 for Sunday to 6 for Saturday.
+fn local_weekday() -> u8 {
+    match local_time() {
+        Some(tm) => tm.tm_wday as u8,
+        // 1970-01-01 was a Thursday.
+        None => ((now_secs() / (24 * 60 * 60) + 4) % 7) as u8,
+    }
+}
+
+/// "2026-10-17 1800", for file names.
+fn local_timestamp() -> String {
+    match local_time() {
+        Some(tm) => format!(
+            "{}-{:02}-{:02} {:02}{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min
+        ),
+        None => now_secs().to_string(),
+    }
+}
+
+/// Looks up the stream of `station` for record request `seq`.
+fn record_station(
+    rb: &Arc<Mutex<RadioBrowserClient>>,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    seq: u64,
+    station: StationRef,
+) {
+    let rb = rb.clone();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let res = match &station.url {
+            Some(url) => Ok(url.clone()),
+            None => {
+                let (res, health) = {
+                    let mut client = rb.lock().await;
+                    let res = client.resolve_station_url(&station.stationuuid, false).await;
+                    (res, client.mirror_health())
+                };
+                let _ = tx.send(InternalMsg::MirrorHealth(health));
+                res.map(|url| url.to_string())
+            }
+        };
+        let _ = tx.send(InternalMsg::RecordUrl { seq, station, res });
+    });
+}
+
+async fn next_recorder_event(recorder: &mut Option<Recorder>) -> RecorderEvent {
+    match recorder {
+        Some(r) => r.next_event().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Closes the current recording, if any, and says where it went.
+async fn finish_recording(
+    recorder: &mut Option<Recorder>,
+    state: &mut ControllerState,
+    config: &AppConfig,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+) {
+    let status = state.recorder.take();
+    let Some(recorder) = recorder.take() else {
+        return;
+    };
+    if let Some(path) = recorder.finish().await {
+        let station = status.map(|s| s.station).unwrap_or_default();
+        state.notice = Some(format!("Recorded {station} to {}", path.display()));
+    }
+    list_recordings(config, tx, None);
+}
+
+/// Rescans the recordings folder, deleting `delete` from it first.
+fn list_recordings(config: &AppConfig, tx: &mpsc::UnboundedSender<InternalMsg>, delete: Option<PathBuf>) {
+    let dir = config.recording.dir();
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let res = tokio::task::spawn_blocking(move || {
+            let dir = dir?;
+            if let Some(path) = delete {
+                crate::recording::delete(&dir, &path)?;
+            }
+            crate::recording::list(&dir)
+        })
+        .await
+        .context("Join recordings scan")
+        .and_then(|res| res);
+        let _ = tx.send(InternalMsg::RecordingsListed(res));
+    });
+}
+
+fn open_folder(dir: &Path) -> Result<()> {
+    let mut child = tokio::process::Command::new("xdg-open")
+        .arg(dir)
+        .spawn()
+        .with_context(|| format!("Could not open {}", dir.display()))?;
+    tokio::spawn(async move {
+        let _ = child.wait().await;
+    });
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+async fn save_cache(cache: StationCache) {
+    if let Ok(Err(e)) = tokio::task::spawn_blocking(move || cache.save()).await {
+        warn!(error = ?e, "failed to save station cache");
+    }
+}
+
+/// Whether what we know about a station lacks a usable codec.
+fn missing_codec(state: &ControllerState, stationuuid: &str) -> bool {
+    let known = state
+        .search_results
+        .iter()
+        .chain(state.station_details.values())
+        .chain(&state.alternates)
+        .find(|s| s.stationuuid == stationuuid);
+    known.is_some_and(|s| {
+        s.codec
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|c| c.is_empty() || c.eq_ignore_ascii_case("unknown"))
+    })
+}
+
+/// Sniffs the codec from the start of the stream in the background.
+fn inspect_stream(
+    probe: &ProbeClient,
+    tx: &mpsc::UnboundedSender<InternalMsg>,
+    generation: u64,
+    stationuuid: &str,
+    url: &str,
+    sniff: bool,
+) {
+    let Ok(url) = url::Url::parse(url) else {
+        return;
+    };
+    let probe = probe.clone();
+    let tx = tx.clone();
+    let stationuuid = stationuuid.to_string();
+    tokio::spawn(async move {
+        match probe.inspect(url, sniff).await {
+            Ok((origin, codec)) => {
+                let _ = tx.send(InternalMsg::OriginFound { generation, origin });
+                if let Some(codec) = codec {
+                    let _ = tx.send(InternalMsg::CodecDetected { stationuuid, codec });
+                }
+            }
+            Err(e) => debug!(error = ?e, "stream inspection failed"),
+        }
+    });
+}
+
+fn spawn_cast<F>(tx: &mpsc::UnboundedSender<InternalMsg>, fut: F)
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let _ = tx.send(InternalMsg::CastDone(fut.await));
+    });
+}
+
+fn mpv_options(config: &AppConfig) -> MpvOptions {
+    MpvOptions {
+        pcm_fifo: config
+            .output
+            .snapcast
+            .then(|| config.output.snapcast_fifo.clone()),
+        low_resource: config.output.low_resource,
+        audio_device: config.output.audio_device.clone(),
+        user_config: config.output.mpv_user_config,
+        cache: config.output.cache_limits(),
+        restart_forever: false,
+        silent: false,
+        hls_lowest: config.output.hls_variant == HlsVariant::DataSaver,
+    }
+}
+
+/// Whether the active player measures its level, for the meter or dead air.
+fn metered(config: &AppConfig) -> bool {
+    config.ui.level_meter || config.ad_mute.watches_silence()
+}
+
+/// The `af` chain for the equalizer, channel settings and, on the active
+/// player, the level meter; empty when none of them is in use.
+fn audio_filters(config: &AppConfig, active: bool) -> String {
+    let eq = &config.equalizer;
+    let channels = &config.channels;
+    [
+        equalizer_filter(eq.bass, eq.mid, eq.treble),
+        channel_filter(channels.mono, channels.balance),
+        if active && metered(config) {
+            LEVEL_FILTER.to_string()
+        } else {
+            String::new()
+        },
+    ]
+    .into_iter()
+    .filter(|f| !f.is_empty())
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Moves both players to `device`, or mpv's automatic choice for `None`.
+fn set_audio_device(mpv: &MpvProcess, standby: Option<&Standby>, config: &mut AppConfig, device: Option<String>) {
+    let name = device.clone().unwrap_or_else(|| "auto".to_string());
+    if let Some(sb) = standby {
+        let _ = sb.mpv.command(MpvCommand::SetAudioDevice(name.clone()));
+    }
+    let _ = mpv.command(MpvCommand::SetAudioDevice(name));
+    config.output.audio_device = device;
+}
+
+fn apply_audio_filters(mpv: &MpvProcess, standby: Option<&Standby>, config: &AppConfig) {
+    if let Some(sb) = standby {
+        let _ = sb.mpv.command(MpvCommand::SetAudioFilter(audio_filters(config, false)));
+    }
+    let _ = mpv.command(MpvCommand::SetAudioFilter(audio_filters(config, true)));
+}
+
+/// Saves or deletes the usage statistics in the background, after the write
+/// before it has finished.
+fn write_usage(last: &mut Option<tokio::task::JoinHandle<()>>, write: impl FnOnce() -> Result<()> + Send + 'static) {
+    let previous = last.take();
+    *last = Some(tokio::spawn(async move {
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(write).await {
+            warn!(error = ?e, "failed to write usage statistics");
+        }
+    }));
+}
+
+/// Describes the first threshold `usage` crosses, if any.
+fn usage_problem(usage: &ResourceUsage) -> Option<String> {
+    const MIB: u64 = 1024 * 1024;
+    if usage.cpu_percent > CPU_WARN_PERCENT {
+        Some(format!("mpv is using {:.0}% CPU", usage.cpu_percent))
+    } else if usage.rss_bytes > RSS_WARN_BYTES {
+        Some(format!("mpv is using {} MiB of memory", usage.rss_bytes / MIB))
+    } else if usage.read_bytes_per_sec > READ_WARN_BYTES_PER_SEC {
+        Some(format!(
+            "The stream is downloading {:.1} MiB/s, more than audio needs",
+            usage.read_bytes_per_sec as f64 / MIB as f64
+        ))
+    } else {
+        None
+    }
+}
+
+fn mpv_socket_path(name: &str) -> Result<PathBuf> {
+    let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .context("XDG_RUNTIME_DIR not set")?;
+
+    // Per profile, so a work and a personal instance don't share sockets.
+    let dir = crate::config::app_dir(&runtime);
+    std::fs::create_dir_all(&dir).with_context(|| format!("Create runtime dir: {dir:?}"))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))
             .with_context(|| format!("chmod 700 runtime dir: {dir:?}"))?;
     }
-    Ok(dir.join("mpv.sock"))
+    Ok(dir.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    fn state_with_query(q: &str) -> ControllerState {
+        ControllerState {
+            search_query: q.to_string(),
+            ..ControllerState::default()
+        }
+    }
+
+    #[test]
+    fn notices_the_output_going_away() {
+        let device = |name: &str| AudioDevice {
+            name: name.to_string(),
+            description: String::new(),
+        };
+        let before = [device("auto"), device("pipewire/speakers"), device("pipewire/bluez_output.headset")];
+        let after = [device("auto"), device("pipewire/speakers")];
+        let lost = lost_output(&before, &after, None, Some("bluez_output.headset")).map(|d| d.name);
+        assert_eq!(lost.as_deref(), Some("pipewire/bluez_output.headset"));
+        // Automatic output: another device going away doesn't matter.
+        assert!(lost_output(&before, &after, None, Some("speakers")).is_none());
+        assert!(lost_output(&before, &after, None, None).is_none());
+        assert!(lost_output(&before, &after, Some("pipewire/speakers"), None).is_none());
+        assert!(lost_output(&before, &after, Some("pipewire/bluez_output.headset"), None).is_some());
+        assert!(lost_output(&after, &before, None, Some("speakers")).is_none());
+    }
+
+    #[test]
+    fn long_pauses_snap_to_live() {
+        let resume = ResumeConfig { snap_to_live_minutes: 10 };
+        assert!(!snap_to_live(Duration::from_secs(9 * 60), &resume));
+        assert!(snap_to_live(Duration::from_secs(10 * 60), &resume));
+        let off = ResumeConfig { snap_to_live_minutes: 0 };
+        assert!(!snap_to_live(Duration::from_secs(24 * 3600), &off));
+    }
+
+    #[test]
+    fn stop_cancels_pending_play() {
+        let mut plays = PlayGeneration::default();
+        let first = plays.start();
+        assert!(plays.is_current(first));
+        plays.cancel();
+        assert!(!plays.is_current(first));
+    }
+
+    #[test]
+    fn replay_of_same_station_ignores_older_result() {
+        let mut plays = PlayGeneration::default();
+        let first = plays.start();
+        plays.cancel();
+        let second = plays.start();
+        // The first resolve finishing late must not start playback twice.
+        assert!(!plays.is_current(first));
+        assert!(plays.is_current(second));
+        let third = plays.start();
+        assert!(!plays.is_current(second));
+        assert!(plays.is_current(third));
+    }
+
+    #[test]
+    fn late_resolves_leave_playback_alone() {
+        // Play, then Stop before the address came back.
+        let mut plays = PlayGeneration::default();
+        let stopped = plays.start();
+        plays.cancel();
+        assert!(!plays.is_current(stopped));
+
+        // Play A, then Play B; A answering last must not switch back to it.
+        let a = plays.start();
+        let b = plays.start();
+        assert!(!plays.is_current(a));
+        assert!(plays.is_current(b));
+    }
+
+    #[test]
+    fn fade_reaches_silence_on_the_last_step() {
+        assert_eq!(fade_volume(80.0, 0), 80.0);
+        assert_eq!(fade_volume(80.0, FADE_STEPS / 2), 40.0);
+        assert_eq!(fade_volume(80.0, FADE_STEPS), 0.0);
+    }
+
+    #[test]
+    fn start_latency_median() {
+        let mut latency = StartLatency::default();
+        assert_eq!(latency.median_ms(), None);
+        for ms in [900, 3000, 1200, 700] {
+            latency.record(Duration::from_millis(ms), None);
+        }
+        assert_eq!(latency.median_ms(), Some(900));
+        assert_eq!(latency.last_ms(), Some(700));
+        for _ in 0..START_SAMPLES {
+            latency.record(Duration::from_millis(500), Some(Duration::from_millis(80)));
+        }
+        assert_eq!(latency.median_ms(), Some(500));
+        assert_eq!(latency.last_resolve_ms, Some(80));
+    }
+
+    #[test]
+    fn reconnects_back_off() {
+        let delays: Vec<u64> = (1..=MAX_RECONNECTS + 1).map(|a| reconnect_delay(a).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 16]);
+    }
+
+    #[test]
+    fn every_kind_of_drop_shares_the_reconnect_count() {
+        let mut state = ControllerState::default();
+        assert_eq!(next_reconnect(&mut state, DropReason::Crashed), Some(1));
+        assert_eq!(next_reconnect(&mut state, DropReason::Unreachable), Some(2));
+        assert_eq!(
+            state.reconnect,
+            Some(Reconnect { attempt: 2, reason: DropReason::Unreachable })
+        );
+        assert_eq!(state.phase, PlaybackPhase::Reconnecting);
+        for attempt in 3..=MAX_RECONNECTS {
+            assert_eq!(next_reconnect(&mut state, DropReason::Stalled), Some(attempt));
+        }
+        assert_eq!(next_reconnect(&mut state, DropReason::Ended), None);
+        assert_eq!(state.reconnect, None);
+        // Once audio flowed again (which clears it), the count starts over.
+        assert_eq!(next_reconnect(&mut state, DropReason::ConnectionLost), Some(1));
+    }
+
+    #[tokio::test]
+    async fn latest_states_skips_intermediate_snapshots() {
+        let (tx, rx) = watch::channel(ControllerState::default());
+        let stream = latest_states(rx);
+        futures_util::pin_mut!(stream);
+
+        for i in 0..100 {
+            tx.send(state_with_query(&i.to_string())).unwrap();
+        }
+
+        let first = stream.next().await.unwrap();
+        assert_eq!(first.search_query, "99");
+    }
+
+    #[tokio::test]
+    async fn latest_states_converges_to_newest_state() {
+        let (tx, rx) = watch::channel(ControllerState::default());
+        let stream = latest_states(rx);
+        futures_util::pin_mut!(stream);
+
+        tx.send(state_with_query("a")).unwrap();
+        assert_eq!(stream.next().await.unwrap().search_query, "a");
+
+        tx.send(state_with_query("b")).unwrap();
+        tx.send(state_with_query("c")).unwrap();
+        assert_eq!(stream.next().await.unwrap().search_query, "c");
+
+        drop(tx);
+        assert!(stream.next().await.is_none());
+    }
 }
@@ -0,0 +1,187 @@
+//! Station of the day: once a day, a well-liked station the user doesn't
+//! know yet, from the genres their favorites lean towards. The same tags
+//! suggest groups for a long list of favorites.
+
+use crate::models::{Station, StationRef};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// How many of the favorites' top tags take turns, one per day.
+const TASTE_TAGS: usize = 3;
+/// The pick comes from this many of the most voted candidates.
+const SHORTLIST: usize = 10;
+/// Groups are suggested once there are this many favorites.
+pub const SUGGEST_GROUPS_FROM: usize = 8;
+/// Fewest favorites sharing a tag for it to be worth a group.
+const MIN_GROUP: usize = 3;
+const MAX_SUGGESTIONS: usize = 2;
+
+/// The tag to look in on `day`: the favorites' most common tags take turns,
+/// so it isn't jazz every single day. `None` without favorites with tags.
+pub fn tag_for_day<'a>(favorites: impl IntoIterator<Item = &'a Station>, day: u32) -> Option<String> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for station in favorites {
+        for tag in station_tags(station) {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    let mut tags: Vec<(String, u32)> = counts.into_iter().collect();
+    // Alphabetical among equals, so the order doesn't change between runs.
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tags.truncate(TASTE_TAGS);
+    let count = tags.len();
+    (count > 0).then(|| tags.swap_remove(day as usize % count).0)
+}
+
+/// A group the favorites' tags call for, e.g. "Jazz" for eight jazz stations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSuggestion {
+    pub name: String,
+    /// The tag as Radio Browser lists it, e.g. "jazz".
+    pub tag: String,
+    /// Station UUIDs of the ungrouped favorites with the tag.
+    pub members: Vec<String>,
+}
+
+/// Groups for favorites not in one yet, by their most shared tags; none
+/// until there are [`SUGGEST_GROUPS_FROM`] favorites. Each favorite is
+/// suggested for one group at most, and names already in use are skipped.
+pub fn group_suggestions(favorites: &[StationRef], details: &BTreeMap<String, Station>) -> Vec<GroupSuggestion> {
+    if favorites.len() < SUGGEST_GROUPS_FROM {
+        return Vec::new();
+    }
+    let taken: HashSet<String> = favorites.iter().filter_map(|f| f.group.as_deref()).map(str::to_lowercase).collect();
+    let mut by_tag: HashMap<String, Vec<&str>> = HashMap::new();
+    for fav in favorites.iter().filter(|f| f.group.is_none()) {
+        for tag in details.get(&fav.stationuuid).map(station_tags).unwrap_or_default() {
+            by_tag.entry(tag).or_default().push(&fav.stationuuid);
+        }
+    }
+    let mut tags: Vec<(String, Vec<&str>)> = by_tag.into_iter().filter(|(tag, _)| !taken.contains(tag)).collect();
+    tags.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+    let mut suggested: HashSet<&str> = HashSet::new();
+    let mut suggestions = Vec::new();
+    for (tag, members) in tags {
+        let members: Vec<&str> = members.into_iter().filter(|m| !suggested.contains(m)).collect();
+        if members.len() < MIN_GROUP {
+            continue;
+        }
+        suggested.extend(&members);
+        suggestions.push(GroupSuggestion {
+            name: title_case(&tag),
+            tag,
+            members: members.into_iter().map(str::to_string).collect(),
+        });
+        if suggestions.len() == MAX_SUGGESTIONS {
+            break;
+        }
+    }
+    suggestions
+}
+
+/// A station's tags, lowercased and without duplicates.
+fn station_tags(station: &Station) -> HashSet<String> {
+    station
+        .tags
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// "smooth jazz" as "Smooth Jazz".
+fn title_case(tag: &str) -> String {
+    tag.split(' ')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Today's station among `candidates` (most voted first), skipping the ones
+/// in `known`. The same candidates give the same pick all day.
+pub fn pick(candidates: Vec<Station>, known: &HashSet<String>, day: u32) -> Option<Station> {
+    let mut shortlist: Vec<Station> = candidates
+        .into_iter()
+        .filter(|s| s.lastcheckok != Some(0) && !known.contains(&s.stationuuid))
+        .take(SHORTLIST)
+        .collect();
+    let count = shortlist.len();
+    (count > 0).then(|| shortlist.swap_remove(day as usize % count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(uuid: &str, tags: &str) -> Station {
+        Station {
+            stationuuid: uuid.to_string(),
+            name: uuid.to_string(),
+            tags: Some(tags.to_string()),
+            lastcheckok: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rotates_through_the_favorites_top_tags() {
+        let favorites = [
+            station("a", "Jazz, smooth jazz"),
+            station("b", "jazz,soul"),
+            station("c", "soul,funk,jazz"),
+            station("d", "news"),
+        ];
+        let tags: Vec<String> = (0..4).filter_map(|day| tag_for_day(&favorites, day)).collect();
+        assert_eq!(tags, ["jazz", "soul", "funk", "jazz"]);
+        assert_eq!(tag_for_day(&[station("e", "")], 0), None);
+    }
+
+    #[test]
+    fn picks_an_unknown_working_station() {
+        let mut broken = station("broken", "jazz");
+        broken.lastcheckok = Some(0);
+        let candidates = || vec![station("fav", "jazz"), broken.clone(), station("x", "jazz"), station("y", "jazz")];
+        let known = HashSet::from(["fav".to_string()]);
+        assert_eq!(pick(candidates(), &known, 0).unwrap().stationuuid, "x");
+        assert_eq!(pick(candidates(), &known, 1).unwrap().stationuuid, "y");
+        assert_eq!(pick(candidates(), &known, 2).unwrap().stationuuid, "x");
+        assert!(pick(vec![station("fav", "")], &known, 0).is_none());
+    }
+
+    #[test]
+    fn suggests_groups_for_shared_tags() {
+        let tagged = [
+            ("a", "jazz,smooth jazz"),
+            ("b", "jazz"),
+            ("c", "Jazz, soul"),
+            ("d", "smooth jazz"),
+            ("e", "smooth jazz"),
+            ("f", "news"),
+            ("g", "news"),
+            ("h", "rock"),
+        ];
+        let details: BTreeMap<String, Station> =
+            tagged.iter().map(|(uuid, tags)| (uuid.to_string(), station(uuid, tags))).collect();
+        let mut favorites: Vec<StationRef> = tagged
+            .iter()
+            .map(|(uuid, _)| StationRef { stationuuid: uuid.to_string(), name: uuid.to_string(), url: None, group: None })
+            .collect();
+
+        let suggestions = group_suggestions(&favorites, &details);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].name, "Jazz");
+        assert_eq!(suggestions[0].members, ["a", "b", "c"]);
+        // "smooth jazz" has 3 too, but "a" is already in the jazz suggestion.
+
+        // With "a" in a group of that name, neither tag has enough left.
+        favorites[0].group = Some("jazz".to_string());
+        assert!(group_suggestions(&favorites, &details).is_empty());
+
+        assert!(group_suggestions(&favorites[..7], &details).is_empty());
+    }
+}
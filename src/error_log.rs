@@ -0,0 +1,92 @@
+//! Errors shown to the user: dismissible banners plus a short history that
+//! stays visible in the settings after the banners are gone.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Banners shown at once; older ones make room for new ones.
+const MAX_ACTIVE: usize = 3;
+/// Errors kept for the "Recent errors" list.
+const MAX_RECENT: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportedError {
+    pub id: u64,
+    pub message: String,
+    /// Unix seconds.
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ErrorLog {
+    next_id: u64,
+    /// Undismissed errors, oldest first.
+    active: Vec<ReportedError>,
+    /// Everything reported lately, oldest first, dismissed or not.
+    recent: VecDeque<ReportedError>,
+}
+
+impl ErrorLog {
+    pub fn push(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        // The same failure again (e.g. a retried search) just moves to the end.
+        self.active.retain(|e| e.message != message);
+        if self.active.len() >= MAX_ACTIVE {
+            self.active.remove(0);
+        }
+        self.next_id += 1;
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let error = ReportedError { id: self.next_id, message, at };
+        if self.recent.len() >= MAX_RECENT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(error.clone());
+        self.active.push(error);
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.active.retain(|e| e.id != id);
+    }
+
+    /// Dismisses every banner; the history is kept.
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn active(&self) -> &[ReportedError] {
+        &self.active
+    }
+
+    pub fn recent(&self) -> impl DoubleEndedIterator<Item = &ReportedError> {
+        self.recent.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banners_are_bounded_and_dismissible() {
+        let mut log = ErrorLog::default();
+        for i in 0..5 {
+            log.push(format!("error {i}"));
+        }
+        let shown: Vec<&str> = log.active().iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(shown, ["error 2", "error 3", "error 4"]);
+
+        log.push("error 3");
+        assert_eq!(log.active().last().unwrap().message, "error 3");
+        assert_eq!(log.active().len(), 3);
+
+        let id = log.active()[0].id;
+        log.dismiss(id);
+        assert_eq!(log.active().len(), 2);
+        log.clear();
+        assert!(log.active().is_empty());
+        assert_eq!(log.recent().count(), 6);
+    }
+}
@@ -0,0 +1,65 @@
+//! Second backend for when mpv keeps crashing: `gst-play-1.0` from
+//! GStreamer's base tools plays the stream on its own. It only plays and
+//! stops; volume, pause and stream details need mpv back.
+
+use crate::mpv::find_on_path;
+use anyhow::{Context, Result};
+use std::process::{ExitStatus, Stdio};
+use tokio::process::{Child, Command};
+
+const PROGRAM: &str = "gst-play-1.0";
+
+/// A stream playing through GStreamer. Dropping it stops playback.
+#[derive(Debug)]
+pub struct FallbackPlayer {
+    child: Child,
+}
+
+impl FallbackPlayer {
+    /// Whether GStreamer's player is installed.
+    pub fn available() -> bool {
+        find_on_path(PROGRAM, &std::env::var_os("PATH").unwrap_or_default()).is_some()
+    }
+
+    /// Starts playing `url` at `volume` percent.
+    pub fn start(url: &str, volume: f64) -> Result<Self> {
+        let child = Command::new(PROGRAM)
+            .args(args(url, volume))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {PROGRAM}"))?;
+        Ok(Self { child })
+    }
+
+    /// Resolves when the player exits, e.g. because the stream broke.
+    pub async fn exited(&mut self) -> std::io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+}
+
+fn args(url: &str, volume: f64) -> Vec<String> {
+    vec![
+        "--no-interactive".to_string(),
+        "--quiet".to_string(),
+        // gst-play takes a linear factor where mpv takes percent.
+        format!("--volume={:.2}", (volume / 100.0).clamp(0.0, 1.0)),
+        url.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_volume_as_a_factor() {
+        assert_eq!(
+            args("http://example.org/live", 40.0),
+            ["--no-interactive", "--quiet", "--volume=0.40", "http://example.org/live"]
+        );
+        assert_eq!(args("x", 250.0)[2], "--volume=1.00");
+    }
+}
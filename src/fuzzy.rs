@@ -0,0 +1,155 @@
+//! Small fuzzy matcher for resolving typed names like "bbc4" to favorites.
+//!
+//! Abbreviations are handled as ordered subsequences with bonuses for hitting
+//! word starts; queries that aren't subsequences get a second chance with a
+//! per-word edit distance, which covers the usual typos.
+
+/// Scores `candidate` against `query`; higher is better, `None` means no match.
+pub fn score(query: &str, candidate: &str) -> Option<u32> {
+    let q: Vec<char> = normalize(query).collect();
+    if q.is_empty() {
+        return Some(0);
+    }
+    subsequence_score(&q, candidate).or_else(|| typo_score(query, candidate))
+}
+
+/// Items matching `query`, best first. Ties keep their original order.
+pub fn rank<'a, T>(query: &str, items: &'a [T], name: impl Fn(&T) -> &str) -> Vec<&'a T> {
+    let mut scored: Vec<(u32, &T)> = items
+        .iter()
+        .filter_map(|item| score(query, name(item)).map(|s| (s, item)))
+        .collect();
+    scored.sort_by_key(|(s, _)| std::cmp::Reverse(*s));
+    scored.into_iter().map(|(_, item)| item).collect()
+}
+
+fn normalize(s: &str) -> impl Iterator<Item = char> + '_ {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+}
+
+fn words(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Greedy left-to-right subsequence match over the candidate's words.
+fn subsequence_score(q: &[char], candidate: &str) -> Option<u32> {
+    // (char, starts a word)
+    let chars: Vec<(char, bool)> = words(candidate)
+        .iter()
+        .flat_map(|w| w.chars().enumerate().map(|(i, c)| (c, i == 0)))
+        .collect();
+
+    let mut score = 100u32;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for &qc in q {
+        let found = pos + chars[pos..].iter().position(|&(c, _)| c == qc)?;
+        if chars[found].1 {
+            score += 10;
+        }
+        match prev {
+            Some(p) if p + 1 == found => score += 5,
+            Some(p) => score = score.saturating_sub((found - p - 1).min(5) as u32),
+            None => score = score.saturating_sub(found.min(10) as u32),
+        }
+        prev = Some(found);
+        pos = found + 1;
+    }
+    // Prefer names that don't carry much beyond what was typed.
+    Some(score.saturating_sub((chars.len() - q.len()).min(20) as u32 / 2))
+}
+
+/// Every query word must be within a small edit distance of some candidate
+/// word. Scores below typical subsequence matches.
+fn typo_score(query: &str, candidate: &str) -> Option<u32> {
+    let cand = words(candidate);
+    let mut penalty = 0;
+    for qw in words(query) {
+        let allowed = if qw.chars().count() <= 4 { 1 } else { 2 };
+        let best = cand
+            .iter()
+            .map(|cw| edit_distance(&qw, cw))
+            .min()?;
+        if best > allowed {
+            return None;
+        }
+        penalty += best as u32;
+    }
+    Some(50u32.saturating_sub(penalty * 10))
+}
+
+/// Optimal string alignment distance: Levenshtein plus adjacent transpositions.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&str; 6] = [
+        "BBC Radio 1",
+        "BBC Radio 4",
+        "Radio Bob 4",
+        "Jazz Radio",
+        "Radio Paradise",
+        "FIP",
+    ];
+
+    fn best(query: &str) -> Option<&'static str> {
+        rank(query, &NAMES, |n| n).first().map(|n| **n)
+    }
+
+    #[test]
+    fn resolves_abbreviations() {
+        assert_eq!(best("bbc4"), Some("BBC Radio 4"));
+        assert_eq!(best("bbc r1"), Some("BBC Radio 1"));
+        assert_eq!(best("rp"), Some("Radio Paradise"));
+        assert_eq!(best("fip"), Some("FIP"));
+    }
+
+    #[test]
+    fn tolerates_typos() {
+        assert_eq!(best("jaz radio"), Some("Jazz Radio"));
+        assert_eq!(best("raido paradise"), Some("Radio Paradise"));
+        assert_eq!(best("paradsie"), Some("Radio Paradise"));
+    }
+
+    #[test]
+    fn rejects_unrelated_queries() {
+        assert_eq!(best("classic fm"), None);
+        assert!(score("bbc4", "Radio Bob 4").is_none());
+    }
+
+    #[test]
+    fn prefers_word_starts_and_tight_matches() {
+        assert!(score("radio", "Radio Paradise") > score("radio", "BBC Radio 4"));
+        assert!(score("bbc", "BBC Radio 1") > score("bbc", "Bob's Big Classics"));
+        assert!(score("bbc4", "BBC Radio 4") > score("raido paradise", "Radio Paradise"));
+    }
+}
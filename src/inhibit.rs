@@ -0,0 +1,120 @@
+//! Keeps the machine from suspending on idle while the radio plays, through
+//! the desktop portal's Inhibit interface. The screen can still blank, and a
+//! manual suspend still goes ahead (see `suspend.rs` for that side).
+
+use crate::controller::{ControllerState, PlaybackPhase};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use tokio::sync::watch;
+use tracing::{debug, info};
+
+/// Portal flag for "suspend the session"; logout, user switch and idle are separate bits.
+const INHIBIT_SUSPEND: u32 = 4;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Inhibit",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait Inhibit {
+    fn inhibit(
+        &self,
+        window: &str,
+        flags: u32,
+        options: HashMap<&str, zbus::zvariant::Value<'_>>,
+    ) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+}
+
+#[zbus::proxy(interface = "org.freedesktop.portal.Request", default_service = "org.freedesktop.portal.Desktop")]
+trait Request {
+    fn close(&self) -> zbus::Result<()>;
+}
+
+/// Whether this state should keep the machine awake.
+pub fn keeps_awake(state: &ControllerState) -> bool {
+    state.power.inhibit_suspend
+        && state.cast_target.is_none()
+        && matches!(
+            state.phase,
+            PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing
+        )
+}
+
+/// Takes and releases the inhibitor as the controller state changes, until
+/// the controller goes away.
+pub async fn follow(mut states: watch::Receiver<ControllerState>) {
+    let mut conn: Option<zbus::Connection> = None;
+    let mut lock: Option<zbus::zvariant::OwnedObjectPath> = None;
+    // Don't ask again for every state update once the portal said no.
+    let mut refused = false;
+    loop {
+        let want = keeps_awake(&states.borrow_and_update());
+        if want && lock.is_none() && !refused {
+            match inhibit(&mut conn).await {
+                Ok(path) => {
+                    info!("inhibiting idle suspend while playing");
+                    lock = Some(path);
+                }
+                Err(e) => {
+                    debug!(error = ?e, "can't inhibit suspend");
+                    refused = true;
+                }
+            }
+        } else if !want {
+            refused = false;
+            if let (Some(path), Some(conn)) = (lock.take(), conn.as_ref()) {
+                if let Err(e) = release(conn, path).await {
+                    debug!(error = ?e, "failed to release suspend inhibitor");
+                }
+            }
+        }
+        if states.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn inhibit(conn: &mut Option<zbus::Connection>) -> Result<zbus::zvariant::OwnedObjectPath> {
+    let conn = match conn {
+        Some(c) => c,
+        None => conn.insert(zbus::Connection::session().await.context("Connect to the session bus")?),
+    };
+    let portal = InhibitProxy::new(conn).await.context("Reach the desktop portal")?;
+    let options = HashMap::from([("reason", zbus::zvariant::Value::from("Playing the radio"))]);
+    portal
+        .inhibit("", INHIBIT_SUSPEND, options)
+        .await
+        .context("Inhibit request")
+}
+
+async fn release(conn: &zbus::Connection, path: zbus::zvariant::OwnedObjectPath) -> Result<()> {
+    RequestProxy::builder(conn).path(path)?.build().await?.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cast::{Device, Renderer};
+
+    #[test]
+    fn keeps_awake_only_while_playing_locally() {
+        let mut state = ControllerState {
+            phase: PlaybackPhase::Playing,
+            ..ControllerState::default()
+        };
+        assert!(!keeps_awake(&state));
+        state.power.inhibit_suspend = true;
+        assert!(keeps_awake(&state));
+        state.phase = PlaybackPhase::Paused;
+        assert!(!keeps_awake(&state));
+        state.phase = PlaybackPhase::Playing;
+        state.cast_target = Some(Renderer {
+            name: "Living room".to_string(),
+            device: Device::Upnp {
+                control_url: "http://192.168.1.20/control".to_string(),
+            },
+        });
+        assert!(!keeps_awake(&state));
+    }
+}
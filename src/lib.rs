@@ -0,0 +1,100 @@
+//! The radio applet for the COSMIC panel. The binary just calls [`run`];
+//! the station widgets in [`ui::widgets`] are public so other COSMIC apps
+//! can build the same rows and now-playing heading with their own messages.
+
+/// The libcosmic the widgets are built against.
+pub use cosmic;
+
+mod ad_mute;
+mod cast;
+mod config;
+mod config_watch;
+mod controller;
+mod discovery;
+mod error_log;
+mod fallback;
+mod fuzzy;
+mod inhibit;
+mod logging;
+mod models;
+mod mpris;
+mod mpv;
+mod output_profile;
+mod positions;
+mod radio_browser;
+mod ranked_search;
+mod recording;
+mod shuffle;
+mod smoke;
+mod soak;
+mod station_cache;
+mod stream_probe;
+mod suspend;
+mod title_rules;
+mod usage_stats;
+pub mod ui;
+#[cfg(feature = "voice-search")]
+mod voice;
+
+/// Parses the command line and runs the applet, or a developer mode
+/// (`--soak`, `--smoke`) instead.
+pub fn run() -> cosmic::iced::Result {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut profile = None;
+    let mut soak = None;
+    let mut smoke = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--soak" => {
+                let secs = args.next_if(|a| !a.starts_with("--")).and_then(|s| s.parse().ok());
+                soak = Some(secs.unwrap_or(3600));
+            }
+            "--smoke" => smoke = true,
+            "--profile" => profile = args.next_if(|a| !a.starts_with("--")),
+            _ => match arg.strip_prefix("--profile=") {
+                Some(name) => profile = Some(name.to_string()),
+                None => eprintln!("ignoring unknown argument {arg:?}"),
+            },
+        }
+    }
+    let smoke_dir = match smoke.then(smoke::isolate).transpose() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{e:#}");
+            std::process::exit(2);
+        }
+    };
+    // Paths depend on the profile, so this comes before logging starts.
+    if let Err(e) = config::select_profile(profile) {
+        eprintln!("{e:#}");
+        std::process::exit(2);
+    }
+    logging::init();
+    if let Some(profile) = config::profile() {
+        tracing::info!(%profile, "using profile");
+    }
+
+    if let Some(secs) = soak {
+        run_dev("soak", soak::run(std::time::Duration::from_secs(secs)));
+    }
+    if let Some(dir) = smoke_dir {
+        run_dev("smoke test", smoke::run(dir));
+    }
+
+    cosmic::applet::run::<ui::RadioWidget>(())
+}
+
+/// Developer modes (soak, smoke test) run instead of the applet.
+fn run_dev(name: &str, task: impl std::future::Future<Output = anyhow::Result<()>>) -> ! {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    match rt.block_on(task) {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{name} failed: {e:#}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Tracing setup: stderr for developers (`RUST_LOG`) plus an optional
+//! rotating log file whose level is chosen in the settings.
+
+use crate::config::{state_dir, LogLevel};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Size at which the current log is rotated out.
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept next to the current one.
+const KEEP_LOGS: usize = 3;
+const LOG_NAME: &str = "radiowidget.log";
+
+static FILE_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Off as u8);
+
+pub fn init() {
+    let stderr = fmt::layer()
+        .with_target(false)
+        .with_filter(EnvFilter::from_default_env());
+    let registry = tracing_subscriber::registry().with(stderr);
+
+    match log_dir() {
+        Ok(dir) => {
+            let file = fmt::layer()
+                .with_ansi(false)
+                .with_writer(Arc::new(RotatingLog::new(dir)))
+                .with_filter(filter_fn(|meta| file_enabled(meta.level())));
+            registry.with(file).init();
+        }
+        Err(_) => registry.init(),
+    }
+}
+
+/// Changes what reaches the log file; takes effect immediately.
+pub fn set_level(level: LogLevel) {
+    FILE_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn log_dir() -> anyhow::Result<PathBuf> {
+    Ok(state_dir()?.join("logs"))
+}
+
+fn file_enabled(level: &Level) -> bool {
+    let current = FILE_LEVEL.load(Ordering::Relaxed);
+    if current == LogLevel::Debug as u8 {
+        *level <= Level::DEBUG
+    } else if current == LogLevel::Error as u8 {
+        *level <= Level::WARN
+    } else {
+        false
+    }
+}
+
+/// Size-capped log file. Nothing touches the disk until the first write, so
+/// with logging off no directory or file is created.
+struct RotatingLog {
+    dir: PathBuf,
+    file: Mutex<Option<(File, u64)>>,
+}
+
+impl RotatingLog {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn path(&self, generation: usize) -> PathBuf {
+        match generation {
+            0 => self.dir.join(LOG_NAME),
+            n => self.dir.join(format!("{LOG_NAME}.{n}")),
+        }
+    }
+
+    fn open(&self) -> io::Result<(File, u64)> {
+        fs::create_dir_all(&self.dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))?;
+        let len = file.metadata()?.len();
+        Ok((file, len))
+    }
+
+    /// Shifts `radiowidget.log` → `.1` → `.2` …, dropping the oldest.
+    fn rotate(&self) -> io::Result<()> {
+        for n in (1..KEEP_LOGS).rev() {
+            let from = self.path(n);
+            if from.exists() {
+                fs::rename(&from, self.path(n + 1))?;
+            }
+        }
+        fs::rename(self.path(0), self.path(1))
+    }
+}
+
+impl Write for &RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((_, len)) = guard.as_ref() {
+            if *len > 0 && len + buf.len() as u64 > MAX_LOG_BYTES {
+                *guard = None;
+                self.rotate()?;
+            }
+        }
+        if guard.is_none() {
+            *guard = Some(self.open()?);
+        }
+        let (file, len) = guard.as_mut().expect("log file opened above");
+        let n = file.write(buf)?;
+        *len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut guard = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        match guard.as_mut() {
+            Some((file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_keeps_a_bounded_number_of_files() {
+        let dir = std::env::temp_dir().join(format!("radiowidget-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let log = RotatingLog::new(dir.clone());
+        assert!(!dir.exists());
+
+        let line = vec![b'x'; (MAX_LOG_BYTES / 2) as usize];
+        for _ in 0..10 {
+            (&log).write_all(&line).unwrap();
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            ["radiowidget.log", "radiowidget.log.1", "radiowidget.log.2", "radiowidget.log.3"]
+        );
+        for name in &names {
+            assert!(fs::metadata(dir.join(name)).unwrap().len() <= MAX_LOG_BYTES);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
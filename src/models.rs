@@ -1,23 +1,197 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StationRef {
     pub stationuuid: String,
     pub name: String,
+    /// Direct stream URL for custom stations that are not in Radio Browser.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Favorites group, e.g. "Jazz"; favorites without one are listed first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Station {
     pub stationuuid: String,
     pub name: String,
     #[serde(default)]
     pub country: Option<String>,
+    /// ISO 3166-1 alpha-2, e.g. "GB".
+    #[serde(default)]
+    pub countrycode: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// Comma-separated, like the tags ("english,german").
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Comma-separated, as Radio Browser lists them ("jazz,smooth jazz").
+    #[serde(default)]
+    pub tags: Option<String>,
     #[serde(default)]
     pub codec: Option<String>,
     #[serde(default)]
     pub bitrate: Option<u32>,
     #[serde(default)]
     pub votes: Option<u32>,
+    /// Whether Radio Browser's last check of the stream succeeded (0 or 1).
+    #[serde(default)]
+    pub lastcheckok: Option<u8>,
+    /// Stream URL as listed (before playlist resolution).
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Playable URL after Radio Browser resolved playlists; empty when it couldn't.
+    #[serde(default)]
+    pub url_resolved: Option<String>,
+}
+
+impl Station {
+    /// The address to hand to the player, if the record carries one.
+    pub fn stream_url(&self) -> Option<&str> {
+        [self.url_resolved.as_deref(), self.url.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .find(|u| u.starts_with("http://") || u.starts_with("https://"))
+    }
+
+    /// Passing the last check first, then community votes.
+    pub fn reliability(&self) -> (bool, u32) {
+        (self.lastcheckok != Some(0), self.votes.unwrap_or(0))
+    }
+
+    /// Whether `other` looks like another entry for the same broadcaster.
+    /// Names alone are too common ("Radio 1"), so the country or homepage
+    /// has to match too, or failing those a language and a tag.
+    pub fn same_broadcaster(&self, other: &Station) -> bool {
+        if !self.name.trim().eq_ignore_ascii_case(other.name.trim()) {
+            return false;
+        }
+        let same = |a: Option<String>, b: Option<String>| a.is_some() && a == b;
+        if same(known(&self.countrycode), known(&other.countrycode)) {
+            return true;
+        }
+        if same(homepage_site(&self.homepage), homepage_site(&other.homepage)) {
+            return true;
+        }
+        let shares = |a: &Option<String>, b: &Option<String>| {
+            let theirs = list(b);
+            list(a).iter().any(|x| theirs.contains(x))
+        };
+        shares(&self.language, &other.language) && shares(&self.tags, &other.tags)
+    }
+}
+
+/// A trimmed, lowercased field; `None` when blank.
+fn known(field: &Option<String>) -> Option<String> {
+    field.as_deref().map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty())
+}
+
+/// The entries of a comma-separated field, trimmed and lowercased.
+fn list(field: &Option<String>) -> Vec<String> {
+    field
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// A homepage's host without `www.`, so http/https and paths don't matter.
+fn homepage_site(homepage: &Option<String>) -> Option<String> {
+    let url = known(homepage)?;
+    let rest = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next()?;
+    Some(host.trim_start_matches("www.").to_string()).filter(|h| !h.is_empty())
+}
+
+/// Client-side order of search results.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultOrder {
+    /// As ranked by the search (see `ranked_search.rs`).
+    #[default]
+    Relevance,
+    Name,
+    Country,
+    Bitrate,
+    Reliability,
+}
+
+/// Re-sorts items referring to already fetched stations (the stations
+/// themselves or indices into a list). Ties keep their current (server)
+/// order; stations missing the sort field go last.
+pub fn sort_stations<'a, T>(
+    items: &mut [T],
+    order: ResultOrder,
+    station: impl Fn(&T) -> &'a Station,
+) {
+    let missing = |v: Option<&str>| v.map(str::trim).is_none_or(str::is_empty);
+    match order {
+        ResultOrder::Relevance => {}
+        ResultOrder::Name => items.sort_by_cached_key(|i| station(i).name.trim().to_lowercase()),
+        ResultOrder::Country => items.sort_by_cached_key(|i| {
+            let country = station(i).country.as_deref();
+            (missing(country), country.unwrap_or("").trim().to_lowercase())
+        }),
+        ResultOrder::Bitrate => items.sort_by_key(|i| Reverse(station(i).bitrate.unwrap_or(0))),
+        ResultOrder::Reliability => items.sort_by_key(|i| Reverse(station(i).reliability())),
+    }
+}
+
+/// The entry that replaced `gone` after Radio Browser merged or re-created
+/// it: same name and, when the old stream URL is known, the same URL.
+/// Without a URL only an unambiguous name match counts.
+pub fn find_successor<'a>(
+    gone: &StationRef,
+    old_url: Option<&str>,
+    candidates: &'a [Station],
+) -> Option<&'a Station> {
+    let norm = |u: &str| u.trim().trim_end_matches('/').to_ascii_lowercase();
+    let mut named = candidates.iter().filter(|s| {
+        s.stationuuid != gone.stationuuid && s.name.trim().eq_ignore_ascii_case(gone.name.trim())
+    });
+    match old_url {
+        Some(old) => named.find(|s| s.url.as_deref().is_some_and(|u| norm(u) == norm(old))),
+        None => {
+            let first = named.next();
+            if named.next().is_some() {
+                None
+            } else {
+                first
+            }
+        }
+    }
+}
+
+/// Color label a user can attach to a favorite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelColor {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl LabelColor {
+    /// Cycles through the palette, ending with no label.
+    pub fn cycle(current: Option<Self>) -> Option<Self> {
+        match current {
+            None => Some(Self::Red),
+            Some(Self::Red) => Some(Self::Orange),
+            Some(Self::Orange) => Some(Self::Yellow),
+            Some(Self::Yellow) => Some(Self::Green),
+            Some(Self::Green) => Some(Self::Blue),
+            Some(Self::Blue) => Some(Self::Purple),
+            Some(Self::Purple) => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -25,3 +199,91 @@ pub struct RadioBrowserServer {
     pub name: String,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(name: &str, country: Option<&str>, bitrate: u32, votes: u32, ok: u8) -> Station {
+        Station {
+            stationuuid: name.to_lowercase(),
+            name: name.to_string(),
+            country: country.map(str::to_string),
+            bitrate: Some(bitrate),
+            votes: Some(votes),
+            lastcheckok: Some(ok),
+            ..Default::default()
+        }
+    }
+
+    fn sorted(all: &[Station], order: ResultOrder) -> Vec<&str> {
+        let mut idx: Vec<usize> = (0..all.len()).collect();
+        sort_stations(&mut idx, order, |&i| &all[i]);
+        idx.into_iter().map(|i| all[i].name.as_str()).collect()
+    }
+
+    #[test]
+    fn sorts_fetched_results() {
+        let all = [
+            station("jazz", Some("France"), 128, 10, 1),
+            station("Blues", None, 320, 50, 0),
+            station("ambient", Some("Austria"), 64, 10, 1),
+        ];
+        assert_eq!(sorted(&all, ResultOrder::Relevance), ["jazz", "Blues", "ambient"]);
+        assert_eq!(sorted(&all, ResultOrder::Name), ["ambient", "Blues", "jazz"]);
+        assert_eq!(sorted(&all, ResultOrder::Country), ["ambient", "jazz", "Blues"]);
+        assert_eq!(sorted(&all, ResultOrder::Bitrate), ["Blues", "jazz", "ambient"]);
+        // A failed last check outweighs votes; equal votes keep server order.
+        assert_eq!(sorted(&all, ResultOrder::Reliability), ["jazz", "ambient", "Blues"]);
+    }
+
+    #[test]
+    fn finds_merged_station_by_name_and_url() {
+        let gone = StationRef { stationuuid: "old".into(), name: "Jazz".into(), url: None, group: None };
+        let mut a = station("Jazz", None, 128, 1, 1);
+        a.stationuuid = "a".into();
+        a.url = Some("http://jazz.example/live".into());
+        let mut b = station("jazz ", None, 128, 1, 1);
+        b.stationuuid = "b".into();
+        b.url = Some("http://mirror.example/jazz".into());
+        let candidates = [a, b];
+
+        let found = find_successor(&gone, Some("http://jazz.example/live/"), &candidates);
+        assert_eq!(found.map(|s| s.stationuuid.as_str()), Some("a"));
+        assert!(find_successor(&gone, Some("http://other.example/"), &candidates).is_none());
+        // Two same-named entries and no URL to tell them apart.
+        assert!(find_successor(&gone, None, &candidates).is_none());
+        assert_eq!(
+            find_successor(&gone, None, &candidates[1..]).map(|s| s.stationuuid.as_str()),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn same_broadcaster_needs_more_than_the_name() {
+        let mut bbc = station("Radio 1", None, 128, 1, 1);
+        bbc.countrycode = Some("GB".into());
+        bbc.homepage = Some("https://www.bbc.co.uk/radio1".into());
+        bbc.language = Some("english".into());
+        bbc.tags = Some("pop,chart".into());
+
+        let mut mirror = station("radio 1 ", None, 48, 1, 1);
+        mirror.countrycode = Some("gb".into());
+        assert!(bbc.same_broadcaster(&mirror));
+
+        let mut elsewhere = station("Radio 1", None, 128, 1, 1);
+        elsewhere.countrycode = Some("NO".into());
+        assert!(!bbc.same_broadcaster(&elsewhere));
+        elsewhere.homepage = Some("http://bbc.co.uk/".into());
+        assert!(bbc.same_broadcaster(&elsewhere));
+
+        let mut unlabelled = station("Radio 1", None, 128, 1, 1);
+        assert!(!bbc.same_broadcaster(&unlabelled));
+        unlabelled.language = Some("English,Welsh".into());
+        unlabelled.tags = Some("talk".into());
+        assert!(!bbc.same_broadcaster(&unlabelled));
+        unlabelled.tags = Some("Pop".into());
+        assert!(bbc.same_broadcaster(&unlabelled));
+
+        assert!(!bbc.same_broadcaster(&station("Radio 2", None, 128, 1, 1)));
+    }
+}
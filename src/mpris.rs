@@ -0,0 +1,398 @@
+//! A pared-down MPRIS player so the media controls on the lock screen can
+//! drive the radio. Anyone at the locked machine can press them, so they get
+//! play, pause, stop and volume and nothing else: no station browsing, no
+//! opening URLs, no raising the popup. Off unless `ui.lock_screen_controls`
+//! is set.
+
+use crate::controller::{ControllerState, PlaybackPhase, UiCommand};
+use crate::models::StationRef;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+use tracing::{debug, info};
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::Connection;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+/// MPRIS' placeholder for "no track list".
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+/// First wait before asking the bus again after it said no; doubled after
+/// every further refusal up to [`RETRY_MAX`].
+const RETRY_FIRST: Duration = Duration::from_secs(5);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+
+/// Everything the lock screen can ask for. Kept apart from [`UiCommand`] so
+/// nothing beyond these can come in over D-Bus.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LockControl {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    /// 0.0 to 1.0, as MPRIS has it.
+    SetVolume(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Playing => "Playing",
+            Status::Paused => "Paused",
+            Status::Stopped => "Stopped",
+        }
+    }
+}
+
+/// The part of the controller state the lock screen sees.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Snapshot {
+    phase: PlaybackPhase,
+    station: Option<StationRef>,
+    title: Option<String>,
+    /// Percent.
+    volume: f64,
+}
+
+impl Snapshot {
+    fn of(state: &ControllerState) -> Self {
+        Self {
+            phase: state.phase,
+            station: state.station.clone(),
+            title: state.media_title.clone(),
+            volume: state.volume,
+        }
+    }
+
+    fn status(&self) -> Status {
+        match self.phase {
+            PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing => Status::Playing,
+            PlaybackPhase::Paused => Status::Paused,
+            _ => Status::Stopped,
+        }
+    }
+
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut map = HashMap::new();
+        let mut put = |key: &str, value: Value<'_>| {
+            if let Ok(value) = OwnedValue::try_from(value) {
+                map.insert(key.to_string(), value);
+            }
+        };
+        put("mpris:trackid", Value::from(ObjectPath::from_static_str_unchecked(NO_TRACK)));
+        if let Some(station) = &self.station {
+            let name = station.name.trim().to_string();
+            match &self.title {
+                Some(title) => {
+                    put("xesam:title", Value::from(title.clone()));
+                    put("xesam:artist", Value::from(vec![name]));
+                }
+                None => put("xesam:title", Value::from(name)),
+            }
+        }
+        map
+    }
+}
+
+impl LockControl {
+    /// The command `self` stands for in `snapshot`; `None` if it does nothing there.
+    fn command(self, snapshot: &Snapshot) -> Option<UiCommand> {
+        match (self, snapshot.status()) {
+            (LockControl::SetVolume(v), _) if v.is_finite() => {
+                Some(UiCommand::SetVolume((v * 100.0).clamp(0.0, 100.0)))
+            }
+            (LockControl::Stop, Status::Playing | Status::Paused) => Some(UiCommand::Stop),
+            (LockControl::Play | LockControl::PlayPause, Status::Paused)
+            | (LockControl::Pause | LockControl::PlayPause, Status::Playing) => Some(UiCommand::TogglePause),
+            (LockControl::Play | LockControl::PlayPause, Status::Stopped) => {
+                snapshot.station.clone().map(UiCommand::Play)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Root;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "Radio".to_string()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Player {
+    snapshot: Snapshot,
+    commands: mpsc::UnboundedSender<UiCommand>,
+}
+
+impl Player {
+    fn control(&self, control: LockControl) {
+        if let Some(command) = control.command(&self.snapshot) {
+            debug!(?control, "lock screen control");
+            let _ = self.commands.send(command);
+        }
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        self.control(LockControl::Play);
+    }
+
+    fn pause(&self) {
+        self.control(LockControl::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.control(LockControl::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.control(LockControl::Stop);
+    }
+
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    /// Deliberately ignored: the lock screen doesn't get to pick what plays.
+    fn open_uri(&self, _uri: String) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        self.snapshot.status().as_str().to_string()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        self.snapshot.metadata()
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.snapshot.volume / 100.0
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.control(LockControl::SetVolume(volume));
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.snapshot.station.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.snapshot.status() == Status::Playing
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Offers the player on the session bus while the option is on and keeps it
+/// in step with the controller, until the controller goes away.
+pub async fn serve(mut states: watch::Receiver<ControllerState>, commands: mpsc::UnboundedSender<UiCommand>) {
+    let mut conn: Option<Connection> = None;
+    // When to ask the bus again after it said no, and how long that wait was.
+    // Not on every state update: those come several times a second.
+    let mut retry: Option<(Instant, Duration)> = None;
+    loop {
+        let (enabled, snapshot) = {
+            let state = states.borrow_and_update();
+            (state.ui.lock_screen_controls, Snapshot::of(&state))
+        };
+        if !enabled {
+            if conn.take().is_some() {
+                info!("lock screen controls off");
+            }
+            retry = None;
+        } else if let Some(c) = &conn {
+            if let Err(e) = update(c, snapshot).await {
+                debug!(error = ?e, "lock screen controls not updated");
+            }
+        } else if retry.is_none_or(|(at, _)| at <= Instant::now()) {
+            match connect(snapshot, commands.clone()).await {
+                Ok(c) => {
+                    info!("offering lock screen controls");
+                    conn = Some(c);
+                    retry = None;
+                }
+                Err(e) => {
+                    let wait = retry.map_or(RETRY_FIRST, |(_, wait)| (wait * 2).min(RETRY_MAX));
+                    debug!(error = ?e, ?wait, "can't offer lock screen controls yet");
+                    retry = Some((Instant::now() + wait, wait));
+                }
+            }
+        }
+        let retry_at = retry.filter(|_| enabled && conn.is_none()).map(|(at, _)| at);
+        tokio::select! {
+            changed = states.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            _ = async {
+                match retry_at {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            } => {}
+        }
+    }
+}
+
+async fn connect(snapshot: Snapshot, commands: mpsc::UnboundedSender<UiCommand>) -> zbus::Result<Connection> {
+    // One name per process, so a second instance doesn't take over the first's.
+    let name = format!("org.mpris.MediaPlayer2.radiowidget.instance{}", std::process::id());
+    zbus::connection::Builder::session()?
+        .name(name)?
+        .serve_at(PATH, Root)?
+        .serve_at(PATH, Player { snapshot, commands })?
+        .build()
+        .await
+}
+
+/// Swaps in `snapshot` and announces the properties that changed.
+async fn update(conn: &Connection, snapshot: Snapshot) -> zbus::Result<()> {
+    let iface = conn.object_server().interface::<_, Player>(PATH).await?;
+    let mut player = iface.get_mut().await;
+    if player.snapshot == snapshot {
+        return Ok(());
+    }
+    let old = std::mem::replace(&mut player.snapshot, snapshot);
+    let emitter = iface.signal_emitter();
+    if old.status() != player.snapshot.status() {
+        player.playback_status_changed(emitter).await?;
+        player.can_pause_changed(emitter).await?;
+    }
+    if old.station != player.snapshot.station || old.title != player.snapshot.title {
+        player.metadata_changed(emitter).await?;
+        player.can_play_changed(emitter).await?;
+    }
+    if old.volume != player.snapshot.volume {
+        player.volume_changed(emitter).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(phase: PlaybackPhase) -> Snapshot {
+        Snapshot {
+            phase,
+            station: Some(StationRef {
+                stationuuid: "jazz".to_string(),
+                name: "Jazz FM".to_string(),
+                url: None,
+                group: None,
+            }),
+            title: None,
+            volume: 40.0,
+        }
+    }
+
+    #[test]
+    fn maps_lock_screen_controls_to_commands() {
+        let playing = snapshot(PlaybackPhase::Buffering);
+        assert!(matches!(LockControl::PlayPause.command(&playing), Some(UiCommand::TogglePause)));
+        assert!(LockControl::Play.command(&playing).is_none());
+        assert!(matches!(LockControl::Stop.command(&playing), Some(UiCommand::Stop)));
+
+        let paused = snapshot(PlaybackPhase::Paused);
+        assert!(matches!(LockControl::Play.command(&paused), Some(UiCommand::TogglePause)));
+        assert!(LockControl::Pause.command(&paused).is_none());
+
+        let idle = snapshot(PlaybackPhase::Idle);
+        assert!(matches!(LockControl::PlayPause.command(&idle), Some(UiCommand::Play(s)) if s.stationuuid == "jazz"));
+        assert!(LockControl::Stop.command(&idle).is_none());
+        assert!(LockControl::Play.command(&Snapshot::default()).is_none());
+
+        assert!(matches!(LockControl::SetVolume(1.5).command(&idle), Some(UiCommand::SetVolume(v)) if v == 100.0));
+        assert!(LockControl::SetVolume(f64::NAN).command(&idle).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+//! mpv's JSON IPC protocol: numbering requests, matching replies to them and
+//! writing lines to the socket. Knows nothing about radio playback.
+
+use super::MpvEvent;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot};
+
+/// Ids below this are reserved for the mpv task's own polls, which are
+/// answered as events rather than through [`Requests`].
+pub const FIRST_REQUEST_ID: u64 = 100;
+
+/// Where the value of a `get_property` goes; `None` when mpv has nothing to
+/// report (no file loaded, no duration on a live stream).
+pub type PropertyReply = oneshot::Sender<Result<Option<Value>>>;
+
+/// What a numbered request is waiting for.
+#[derive(Debug)]
+pub enum Pending {
+    /// Fire and forget; only a failure is reported, under this name.
+    Command(String),
+    /// A property read someone is awaiting.
+    Property { name: String, reply: PropertyReply },
+}
+
+/// Numbers outgoing IPC requests so mpv's replies can be matched to them:
+/// failed commands are reported instead of dropped, and property values go
+/// back to whoever asked.
+#[derive(Debug)]
+pub struct Requests {
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+}
+
+impl Default for Requests {
+    fn default() -> Self {
+        Self {
+            next_id: FIRST_REQUEST_ID,
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl Requests {
+    fn next_id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn tag(&mut self, command: Vec<Value>) -> Value {
+        let id = self.next_id();
+        let mut name = command.first().and_then(|c| c.as_str()).unwrap_or("?").to_string();
+        if name.ends_with("_property") || name == "add" || name == "cycle" {
+            // "set_property af" says more than "set_property".
+            if let Some(property) = command.get(1).and_then(|p| p.as_str()) {
+                name = format!("{name} {property}");
+            }
+        }
+        self.pending.insert(id, Pending::Command(name));
+        serde_json::json!({ "command": command, "request_id": id })
+    }
+
+    /// A `get_property` request whose answer is sent to `reply`.
+    pub fn get_property(&mut self, name: &str, reply: PropertyReply) -> Value {
+        let id = self.next_id();
+        self.pending.insert(
+            id,
+            Pending::Property {
+                name: name.to_string(),
+                reply,
+            },
+        );
+        serde_json::json!({ "command": ["get_property", name], "request_id": id })
+    }
+
+    /// Consumes `line` if it answers a numbered request.
+    pub fn complete(&mut self, line: &str, evt_tx: &mpsc::UnboundedSender<MpvEvent>) -> bool {
+        #[derive(Deserialize)]
+        struct Reply {
+            request_id: Option<u64>,
+            error: Option<String>,
+            #[serde(default)]
+            data: Option<Value>,
+        }
+        let Ok(Reply { request_id: Some(id), error, data }) = serde_json::from_str::<Reply>(line) else {
+            return false;
+        };
+        if id < FIRST_REQUEST_ID {
+            return false;
+        }
+        match self.pending.remove(&id) {
+            Some(Pending::Command(command)) => {
+                if let Some(error) = error.filter(|e| e != "success") {
+                    let _ = evt_tx.send(MpvEvent::CommandError { command, error });
+                }
+            }
+            Some(Pending::Property { name, reply }) => {
+                let _ = reply.send(property_value(&name, error.as_deref(), data));
+            }
+            None => {}
+        }
+        true
+    }
+}
+
+/// mpv answers "property unavailable" when there's nothing to report, which
+/// isn't a failure.
+fn property_value(name: &str, error: Option<&str>, data: Option<Value>) -> Result<Option<Value>> {
+    match error {
+        None | Some("success") => Ok(data.filter(|v| !v.is_null())),
+        Some("property unavailable") => Ok(None),
+        Some(e) => Err(anyhow!("get_property {name}: {e}")),
+    }
+}
+
+/// Writes one request line.
+pub async fn send_json(writer: &mut (impl AsyncWrite + Unpin), v: Value) -> Result<()> {
+    let mut buf = serde_json::to_vec(&v).context("Serialize mpv IPC request")?;
+    buf.push(b'\n');
+    writer.write_all(&buf).await.context("Write mpv IPC request")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(req: &Value, error: &str, data: Option<Value>) -> String {
+        let mut reply = serde_json::json!({ "request_id": req["request_id"], "error": error });
+        if let Some(data) = data {
+            reply["data"] = data;
+        }
+        reply.to_string()
+    }
+
+    #[test]
+    fn answers_property_reads() {
+        let (tx, mut events) = mpsc::unbounded_channel();
+        let mut requests = Requests::default();
+
+        let (volume_tx, mut volume_rx) = oneshot::channel();
+        let volume = requests.get_property("volume", volume_tx);
+        assert_eq!(volume["command"], serde_json::json!(["get_property", "volume"]));
+        let (duration_tx, mut duration_rx) = oneshot::channel();
+        let duration = requests.get_property("duration", duration_tx);
+        let (path_tx, mut path_rx) = oneshot::channel();
+        let path = requests.get_property("path", path_tx);
+        assert_ne!(volume["request_id"], duration["request_id"]);
+
+        // Replies may come back in any order.
+        assert!(requests.complete(&reply(&duration, "property unavailable", None), &tx));
+        assert!(requests.complete(&reply(&path, "error running command", None), &tx));
+        assert!(requests.complete(&reply(&volume, "success", Some(serde_json::json!(65.0))), &tx));
+
+        assert_eq!(volume_rx.try_recv().unwrap().unwrap(), Some(serde_json::json!(65.0)));
+        assert_eq!(duration_rx.try_recv().unwrap().unwrap(), None);
+        assert!(path_rx.try_recv().unwrap().is_err());
+        // Failed reads go to the caller, not the event stream.
+        assert!(events.try_recv().is_err());
+        assert!(requests.pending.is_empty());
+    }
+
+    #[test]
+    fn reports_failed_commands() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut requests = Requests::default();
+        let load = requests.tag(vec![serde_json::json!("loadfile"), serde_json::json!("http://x")]);
+        let volume = requests.tag(vec![
+            serde_json::json!("set_property"),
+            serde_json::json!("volume"),
+            serde_json::json!(50),
+        ]);
+
+        assert!(requests.complete(&reply(&volume, "success", None), &tx));
+        assert!(rx.try_recv().is_err());
+        assert!(requests.complete(&reply(&load, "invalid parameter", None), &tx));
+        match rx.try_recv().unwrap() {
+            MpvEvent::CommandError { command, error } => {
+                assert_eq!(command, "loadfile");
+                assert_eq!(error, "invalid parameter");
+            }
+            _ => panic!("unexpected event"),
+        }
+        assert!(requests.pending.is_empty());
+
+        // Events and position answers are left for parse_event.
+        assert!(!requests.complete(r#"{"event":"playback-restart"}"#, &tx));
+        assert!(!requests.complete(r#"{"data":1.0,"request_id":1,"error":"success"}"#, &tx));
+    }
+
+    #[test]
+    fn leaves_events_and_unknown_replies() {
+        let (tx, _events) = mpsc::unbounded_channel();
+        let mut requests = Requests::default();
+        assert!(!requests.complete(r#"{"event":"property-change","name":"pause","data":true}"#, &tx));
+        assert!(!requests.complete("not json", &tx));
+        // Already answered (or from before a restart): swallowed quietly.
+        assert!(requests.complete(r#"{"request_id":4242,"error":"success"}"#, &tx));
+    }
+}
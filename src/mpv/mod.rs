@@ -0,0 +1,1655 @@
+mod ipc;
+
+use anyhow::{anyhow, Context, Result};
+use ipc::{send_json, PropertyReply, Requests};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// This many crashes within [`CRASH_WINDOW`] stop the restart loop.
+const MAX_CRASHES: usize = 5;
+const CRASH_WINDOW: Duration = Duration::from_secs(60);
+/// How often the mpv child's resource usage is sampled.
+const USAGE_INTERVAL: Duration = Duration::from_secs(10);
+/// How often playback is polled (progress, stream details) while something
+/// should be playing.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the level meter is read while it's in the filter chain.
+const LEVEL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long [`MpvProcess::get_property`] waits for a hung mpv.
+const PROPERTY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Playback position frozen this long, unpaused, counts as a stalled stream.
+const STALL_AFTER: Duration = Duration::from_secs(20);
+/// How often mpv is pinged to check it still answers.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(10);
+/// An unanswered ping this old means mpv hung; it's killed and restarted.
+/// Generous, since opening a slow stream can hold mpv up for a while.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+/// How the stream is listed in the desktop's sound settings instead of "mpv".
+const CLIENT_NAME: &str = "Radio";
+const CLIENT_ICON: &str = "io.github.xinia.RadioWidget-symbolic";
+
+#[derive(Debug)]
+pub enum MpvCommand {
+    /// Load `url`, starting at `start` seconds when given.
+    LoadUrl { url: String, start: Option<f64> },
+    SetTitle(String),
+    TogglePause,
+    SetPause(bool),
+    /// Absolute volume in percent.
+    SetVolume(f64),
+    SetMute(bool),
+    /// Replace mpv's audio filter chain; an empty string clears it.
+    SetAudioFilter(String),
+    /// Route output to a device from [`MpvEvent::AudioDevices`].
+    SetAudioDevice(String),
+    AdjustVolume(f64),
+    /// Jump by this many seconds; on live streams only within what's cached.
+    SeekRelative(f64),
+    /// Ask for the playback position; answered with [`MpvEvent::Position`].
+    QueryPosition,
+    /// Copy the undecoded stream into this file (mpv's `stream-record`);
+    /// `None` closes the file.
+    SetStreamRecord(Option<PathBuf>),
+    /// Read a property once; see [`MpvProcess::get_property`].
+    GetProperty { name: String, reply: PropertyReply },
+    Stop,
+    /// Restart the mpv process with new launch options.
+    Respawn(MpvOptions),
+    Shutdown,
+}
+
+/// Launch options that can only be applied by (re)starting mpv.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MpvOptions {
+    /// Write raw PCM into this FIFO (e.g. a Snapcast pipe source) instead of
+    /// playing through the default audio output.
+    pub pcm_fifo: Option<PathBuf>,
+    /// Keep demuxer buffers small and skip anything that isn't plain audio.
+    pub low_resource: bool,
+    /// mpv `audio-device` name; `None` leaves it on `auto`.
+    pub audio_device: Option<String>,
+    /// Load the user's own mpv.conf, scripts and bindings.
+    pub user_config: bool,
+    /// `(seconds, MiB)` of cache; `None` keeps mpv's defaults (or the
+    /// low-resource limits).
+    pub cache: Option<(u32, u32)>,
+    /// Keep restarting mpv however often it crashes, for the soak test,
+    /// which crashes it on purpose.
+    pub restart_forever: bool,
+    /// Decode without any audio output, e.g. for a recording nobody listens to.
+    pub silent: bool,
+    /// Play the lowest bitrate an HLS master playlist offers instead of the
+    /// highest.
+    pub hls_lowest: bool,
+}
+
+impl MpvOptions {
+    fn args(&self) -> Vec<String> {
+        let mut args = vec![format!("--audio-client-name={CLIENT_NAME}")];
+        if !self.user_config {
+            // Desktop mpv settings (profiles, scripts like mpv-mpris, key
+            // bindings) are meant for video playback, not a background radio.
+            args.push("--no-config".to_string());
+        }
+        if let Some(fifo) = &self.pcm_fifo {
+            // Matches Snapcast's default pipe sample format (48000:16:2).
+            args.push("--ao=pcm".to_string());
+            args.push(format!("--ao-pcm-file={}", fifo.display()));
+            args.push("--ao-pcm-waveheader=no".to_string());
+            args.push("--audio-format=s16".to_string());
+            args.push("--audio-samplerate=48000".to_string());
+            args.push("--audio-channels=stereo".to_string());
+        }
+        if let Some(device) = &self.audio_device {
+            args.push(format!("--audio-device={device}"));
+        }
+        if self.silent {
+            args.push("--ao=null".to_string());
+        } else {
+            // Live streams count as unseekable, even within what mpv has
+            // cached; this lets the seek buttons replay from the cache.
+            args.push("--force-seekable=yes".to_string());
+        }
+        if self.low_resource {
+            for arg in [
+                "--audio-display=no",
+                "--cache-secs=5",
+                "--demuxer-max-bytes=2MiB",
+                "--demuxer-readahead-secs=5",
+            ] {
+                args.push(arg.to_string());
+            }
+        }
+        if let Some((secs, mib)) = self.cache {
+            // After the low-resource limits: an explicit choice wins.
+            args.push(format!("--cache-secs={secs}"));
+            args.push(format!("--demuxer-readahead-secs={secs}"));
+            args.push(format!("--demuxer-max-bytes={mib}MiB"));
+        }
+        if self.hls_lowest {
+            // mpv picks the variant itself so it can pair it with the
+            // playlist's separate audio renditions.
+            args.push("--hls-bitrate=min".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum MpvEvent {
+    Ready,
+    MediaTitle(Option<String>),
+    Pause(bool),
+    Volume(f64),
+    Mute(bool),
+    /// Length of the current file; `None` for live streams.
+    Duration(Option<f64>),
+    Position(Option<f64>),
+    AudioDevices(Vec<AudioDevice>),
+    /// Whether the current file carries a real video track (cover art aside).
+    HasVideo(bool),
+    /// Periodic resource sample of the mpv process.
+    Usage(ResourceUsage),
+    /// Audio from the loaded file has started (also sent after seeks).
+    PlaybackStarted,
+    /// The loaded stream could not be played.
+    PlaybackFailed(String),
+    /// The stream ended on its own (`end-file` with reason `eof`); for live
+    /// radio that means the connection dropped.
+    StreamEnded,
+    /// `core-idle`: true while nothing is being decoded (loading, buffering,
+    /// paused or stopped).
+    CoreIdle(bool),
+    /// `idle-active`: true when no file is loaded at all.
+    IdleActive(bool),
+    /// `eof-reached`: the current file played to its end.
+    EofReached(bool),
+    /// `paused-for-cache`: playback is held until the cache refills.
+    PausedForCache(bool),
+    /// `cache-buffering-state`: percent of the cache filled before playback
+    /// (re)starts; `None` when there is no cache.
+    CacheBuffering(Option<u8>),
+    /// Decoded stream details changed (polled every [`POLL_INTERVAL`]).
+    StreamTech(StreamTech),
+    /// Station details announced by the stream itself (`icy-*` tags).
+    StreamTags(StreamTags),
+    /// Bytes downloaded since the previous report, estimated from the
+    /// demuxer's input rate.
+    DataUsed(u64),
+    /// Whole seconds before the playing position that are still cached and
+    /// can be sought back to; sent when it changes.
+    Rewindable(u32),
+    /// Loudness of the audio just decoded, polled every [`LEVEL_INTERVAL`]
+    /// while [`LEVEL_FILTER`] is in the chain; `None` when mpv had no reading.
+    Level(Option<AudioLevel>),
+    /// The position hasn't moved for [`STALL_AFTER`] although nothing is
+    /// paused; sent once per stall.
+    Stalled,
+    /// mpv rejected an IPC command, e.g. `loadfile` with an unusable URL.
+    CommandError { command: String, error: String },
+    Crashed(String),
+    /// mpv kept crashing and is no longer restarted automatically; a
+    /// [`MpvCommand::Respawn`] tries again.
+    GaveUp(String),
+    /// There's no `mpv` on `PATH`. Nothing is started until a
+    /// [`MpvCommand::Respawn`] finds one.
+    Missing,
+}
+
+const POSITION_REQUEST_ID: u64 = 1;
+/// `time-pos` polls for the stall watchdog; answered inside the mpv task.
+const PROGRESS_REQUEST_ID: u64 = 2;
+/// `demuxer-cache-state` polls for the data usage counter.
+const DATA_REQUEST_ID: u64 = 7;
+/// `get_version` pings for the health check.
+const HEALTH_REQUEST_ID: u64 = 8;
+/// `af-metadata/level` polls for the level meter.
+const LEVEL_REQUEST_ID: u64 = 9;
+/// Polled stream details, in request order; the last one completes a round.
+const TECH_PROPERTIES: [(u64, &str); 4] = [
+    (3, "audio-codec-name"),
+    (4, "audio-bitrate"),
+    (5, "audio-params/samplerate"),
+    (6, "audio-params/channels"),
+];
+
+/// What the stream actually delivers, as decoded by mpv.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamTech {
+    pub codec: Option<String>,
+    pub bitrate_kbps: Option<u32>,
+    pub samplerate: Option<u32>,
+    /// Channel layout, e.g. "stereo" or "5.1".
+    pub channels: Option<String>,
+}
+
+impl StreamTech {
+    /// "mp3 · 128 kbps · 44.1 kHz · stereo", skipping unknown parts.
+    pub fn summary(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        parts.extend(self.codec.clone());
+        parts.extend(self.bitrate_kbps.map(|b| format!("{b} kbps")));
+        parts.extend(self.samplerate.map(|hz| match hz % 1000 {
+            0 => format!("{} kHz", hz / 1000),
+            _ => format!("{:.1} kHz", f64::from(hz) / 1000.0),
+        }));
+        parts.extend(self.channels.clone());
+        parts.join(" · ")
+    }
+}
+
+/// What an Icecast/Shoutcast stream says about itself, from mpv's `metadata`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamTags {
+    pub name: Option<String>,
+    pub genre: Option<String>,
+    pub description: Option<String>,
+    /// Station homepage.
+    pub url: Option<String>,
+}
+
+impl StreamTags {
+    fn from_metadata(data: Option<serde_json::Value>) -> Self {
+        let Some(serde_json::Value::Object(map)) = data else {
+            return Self::default();
+        };
+        // Key case depends on the server and the demuxer.
+        let tag = |key: &str| {
+            map.iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .and_then(|(_, v)| v.as_str())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            name: tag("icy-name"),
+            genre: tag("icy-genre"),
+            description: tag("icy-description"),
+            url: tag("icy-url"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Collects one round of [`TECH_PROPERTIES`] replies.
+#[derive(Debug, Default)]
+struct TechPoll {
+    current: StreamTech,
+    reported: Option<StreamTech>,
+}
+
+impl TechPoll {
+    /// Records a reply; returns the details when a round ends with a change.
+    fn update(&mut self, id: u64, data: Option<serde_json::Value>) -> Option<StreamTech> {
+        let text = data.as_ref().and_then(|v| v.as_str()).map(str::to_string);
+        let number = data.as_ref().and_then(|v| v.as_f64());
+        match id {
+            3 => self.current.codec = text,
+            // bits per second
+            4 => self.current.bitrate_kbps = number.map(|b| (b / 1000.0).round() as u32),
+            5 => self.current.samplerate = number.map(|hz| hz as u32),
+            6 => self.current.channels = text,
+            _ => return None,
+        }
+        let last = TECH_PROPERTIES[TECH_PROPERTIES.len() - 1].0;
+        if id != last || self.reported.as_ref() == Some(&self.current) {
+            return None;
+        }
+        self.reported = Some(self.current.clone());
+        self.reported.clone()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Turns polled `demuxer-cache-state` input rates into downloaded bytes.
+#[derive(Debug, Default)]
+struct DataMeter {
+    /// Last reply (or load) while a stream is loaded.
+    since: Option<Instant>,
+}
+
+impl DataMeter {
+    fn start(&mut self, now: Instant) {
+        self.since = Some(now);
+    }
+
+    fn stop(&mut self) {
+        self.since = None;
+    }
+
+    /// Bytes received since the previous reply, at the reported rate.
+    fn update(&mut self, data: Option<&serde_json::Value>, now: Instant) -> u64 {
+        let Some(since) = self.since else {
+            return 0;
+        };
+        self.since = Some(now);
+        let rate = data
+            .and_then(|v| v.get("raw-input-rate"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0)
+            .max(0.0);
+        // Longer gaps (suspend, a stuck socket) would only inflate the estimate.
+        let secs = now.duration_since(since).min(2 * POLL_INTERVAL).as_secs_f64();
+        (rate * secs) as u64
+    }
+}
+
+/// Seconds of `demuxer-cache-state` behind the read position, within the
+/// seekable range that contains it.
+fn rewindable_secs(data: Option<&serde_json::Value>) -> u32 {
+    let Some(data) = data else {
+        return 0;
+    };
+    let Some(pos) = data.get("reader-pts").and_then(|v| v.as_f64()) else {
+        return 0;
+    };
+    data.get("seekable-ranges")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|range| {
+            let start = range.get("start")?.as_f64()?;
+            let end = range.get("end")?.as_f64()?;
+            (start <= pos && pos <= end).then_some(pos - start)
+        })
+        .fold(0.0, f64::max) as u32
+}
+
+/// `af` value for a bass/mid/treble equalizer (gains in dB), built on
+/// ffmpeg's `firequalizer`. Flat settings give an empty chain.
+pub fn equalizer_filter(bass: f32, mid: f32, treble: f32) -> String {
+    if bass == 0.0 && mid == 0.0 && treble == 0.0 {
+        return String::new();
+    }
+    format!(
+        "@eq:lavfi=[firequalizer=gain_entry='entry(0,{bass:.1});entry(100,{bass:.1});entry(1000,{mid:.1});entry(10000,{treble:.1});entry(20000,{treble:.1})']"
+    )
+}
+
+/// `af` value for a mono downmix and left/right `balance` (-1.0 to 1.0),
+/// built on ffmpeg's `pan`. Mono sources are made stereo first. Stereo with
+/// the balance centered gives an empty chain.
+pub fn channel_filter(mono: bool, balance: f32) -> String {
+    let balance = balance.clamp(-1.0, 1.0);
+    if !mono && balance == 0.0 {
+        return String::new();
+    }
+    // Balance only ever turns one side down.
+    let (left, right) = ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0));
+    let (l, r) = if mono {
+        let (l, r) = (left / 2.0, right / 2.0);
+        (format!("{l:.3}*c0+{l:.3}*c1"), format!("{r:.3}*c0+{r:.3}*c1"))
+    } else {
+        (format!("{left:.3}*c0"), format!("{right:.3}*c1"))
+    };
+    format!("@channels:lavfi=[aformat=channel_layouts=stereo,pan=stereo|c0={l}|c1={r}]")
+}
+
+/// `af` entry measuring the audio for the level meter, read back through
+/// `af-metadata/level`. Goes last so it measures what the other filters make.
+pub const LEVEL_FILTER: &str = "@level:lavfi=[astats=metadata=1:reset=1]";
+
+/// Quietest level the meter shows; anything below reads as silence.
+const LEVEL_FLOOR_DB: f32 = -60.0;
+
+/// Loudness of the latest audio frame, in whole dBFS from
+/// [`LEVEL_FLOOR_DB`] up to 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioLevel {
+    pub rms_db: f32,
+    pub peak_db: f32,
+}
+
+impl AudioLevel {
+    /// Reads `astats` values from an `af-metadata` reply.
+    fn from_metadata(data: Option<serde_json::Value>) -> Option<Self> {
+        let map = data?;
+        // Values are strings, "-inf" for digital silence.
+        let db = |key: &str| -> Option<f32> {
+            let value: f32 = map.get(key)?.as_str()?.trim().parse().ok()?;
+            Some(if value.is_nan() { LEVEL_FLOOR_DB } else { value.clamp(LEVEL_FLOOR_DB, 0.0).round() })
+        };
+        Some(Self {
+            rms_db: db("lavfi.astats.Overall.RMS_level")?,
+            peak_db: db("lavfi.astats.Overall.Peak_level")?,
+        })
+    }
+
+    /// `db` as a share of the meter's range, 0.0 (silence) to 1.0 (full scale).
+    pub fn fraction(db: f32) -> f32 {
+        (1.0 - db / LEVEL_FLOOR_DB).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AudioDevice {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResourceUsage {
+    /// Share of one CPU core since the previous sample.
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    /// Bytes read per second (network and files) since the previous sample.
+    pub read_bytes_per_sec: u64,
+}
+
+#[derive(Debug)]
+pub struct MpvProcess {
+    cmd_tx: mpsc::UnboundedSender<MpvCommand>,
+}
+
+impl MpvProcess {
+    pub async fn spawn(
+        socket_path: PathBuf,
+        options: MpvOptions,
+    ) -> Result<(Self, mpsc::UnboundedReceiver<MpvEvent>)> {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let (evt_tx, evt_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(run_mpv(socket_path.clone(), options, cmd_rx, evt_tx));
+
+        Ok((Self { cmd_tx }, evt_rx))
+    }
+
+    pub fn command(&self, cmd: MpvCommand) -> Result<()> {
+        self.cmd_tx.send(cmd).map_err(|_| anyhow!("mpv task is not running"))
+    }
+
+    /// Reads a property once. `None` when mpv has nothing to report, e.g. the
+    /// duration of a live stream or the path with nothing loaded.
+    pub async fn get_property<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        let (reply, answer) = tokio::sync::oneshot::channel();
+        self.command(MpvCommand::GetProperty {
+            name: name.to_string(),
+            reply,
+        })?;
+        let value = tokio::time::timeout(PROPERTY_TIMEOUT, answer)
+            .await
+            .map_err(|_| anyhow!("mpv did not answer for {name}"))?
+            .map_err(|_| anyhow!("mpv restarted before answering"))??;
+        value
+            .map(|v| serde_json::from_value(v).with_context(|| format!("Unexpected {name} value")))
+            .transpose()
+    }
+
+    /// Volume in percent.
+    pub async fn volume(&self) -> Result<Option<f64>> {
+        self.get_property("volume").await
+    }
+
+    /// Length in seconds; `None` for a live stream.
+    pub async fn duration(&self) -> Result<Option<f64>> {
+        self.get_property("duration").await
+    }
+
+    /// The URL or file currently loaded.
+    pub async fn path(&self) -> Result<Option<String>> {
+        self.get_property("path").await
+    }
+
+    /// Format of the decoded audio, once decoding has started.
+    pub async fn audio_params(&self) -> Result<Option<AudioParams>> {
+        self.get_property("audio-params").await
+    }
+}
+
+/// mpv's `audio-params`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AudioParams {
+    /// Sample format, e.g. "floatp" or "s16".
+    pub format: String,
+    pub samplerate: u32,
+    /// Channel layout, e.g. "stereo".
+    pub channels: String,
+    pub channel_count: u32,
+}
+
+impl Drop for MpvProcess {
+    fn drop(&mut self) {
+        // Best effort. If the task is already gone, ignore.
+        let _ = self.cmd_tx.send(MpvCommand::Shutdown);
+    }
+}
+
+// mpv.rs
+async fn run_mpv(
+    socket_path: PathBuf,
+    mut options: MpvOptions,
+    mut cmd_rx: mpsc::UnboundedReceiver<MpvCommand>,
+    evt_tx: mpsc::UnboundedSender<MpvEvent>,
+) {
+    let mut backoff = Duration::from_millis(200);
+    let mut crashes = CrashWindow::default();
+
+    loop {
+        if cmd_rx.is_closed() {
+            return;
+        }
+        if !options.restart_forever && crashes.exceeded(Instant::now()) {
+            let _ = evt_tx.send(MpvEvent::GaveUp(format!(
+                "mpv crashed {MAX_CRASHES} times within {}s",
+                CRASH_WINDOW.as_secs()
+            )));
+            match wait_for_respawn(&mut cmd_rx).await {
+                Some(new_options) => {
+                    options = new_options;
+                    crashes = CrashWindow::default();
+                    backoff = Duration::from_millis(200);
+                }
+                None => return,
+            }
+        }
+
+        if find_on_path("mpv", &std::env::var_os("PATH").unwrap_or_default()).is_none() {
+            // Retrying can't help until the user installs it.
+            let _ = evt_tx.send(MpvEvent::Missing);
+            match wait_for_respawn(&mut cmd_rx).await {
+                Some(new_options) => {
+                    options = new_options;
+                    continue;
+                }
+                None => return,
+            }
+        }
+
+        match spawn_and_connect(&socket_path, &options).await {
+            Ok((mut child, mut stream)) => {
+                backoff = Duration::from_millis(200);
+                let mut requests = Requests::default();
+                let _ = send_observers(&mut stream, &mut requests).await;
+                let _ = evt_tx.send(MpvEvent::Ready);
+
+                match io_loop(&mut child, stream, &mut requests, &mut cmd_rx, &evt_tx).await {
+                    Ok(LoopExit::Shutdown) => {
+                        let _ = child.kill().await;
+                        let _ = child.wait().await;
+                        return;
+                    }
+                    Ok(LoopExit::Respawn(new_options)) => {
+                        let _ = child.kill().await;
+                        let _ = child.wait().await;
+                        options = new_options;
+                    }
+                    Err(e) => {
+                        // IMPORTANT: don’t leak an mpv process
+                        let _ = child.kill().await;
+                        let _ = child.wait().await;
+
+                        let _ = evt_tx.send(MpvEvent::Crashed(e.to_string()));
+                        crashes.record(Instant::now());
+                        tokio::time::sleep(backoff).await;
+                        backoff = std::cmp::min(backoff * 2, Duration::from_secs(5));
+                    }
+                }
+            }
+            Err(e) => {
+                let _ = evt_tx.send(MpvEvent::Crashed(e.to_string()));
+                crashes.record(Instant::now());
+                tokio::time::sleep(backoff).await;
+                backoff = std::cmp::min(backoff * 2, Duration::from_secs(5));
+            }
+        }
+    }
+}
+
+/// The executable `name` in one of the directories of `path` (as in `$PATH`).
+pub fn find_on_path(name: &str, path: &std::ffi::OsStr) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    std::env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|file| std::fs::metadata(file).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0))
+}
+
+/// Parks the supervisor until the controller asks for a restart. Other
+/// commands can't be served without a process and are dropped.
+async fn wait_for_respawn(cmd_rx: &mut mpsc::UnboundedReceiver<MpvCommand>) -> Option<MpvOptions> {
+    loop {
+        match cmd_rx.recv().await? {
+            MpvCommand::Respawn(options) => return Some(options),
+            MpvCommand::Shutdown => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Recent crash times, used to tell a crash loop from an occasional failure.
+#[derive(Debug, Default)]
+struct CrashWindow {
+    crashes: VecDeque<Instant>,
+}
+
+impl CrashWindow {
+    fn record(&mut self, at: Instant) {
+        self.crashes.push_back(at);
+    }
+
+    fn exceeded(&mut self, now: Instant) -> bool {
+        while self
+            .crashes
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > CRASH_WINDOW)
+        {
+            self.crashes.pop_front();
+        }
+        self.crashes.len() >= MAX_CRASHES
+    }
+}
+
+/// Notices playback that should be moving but isn't: flaky hosts often keep
+/// the connection open while sending nothing.
+#[derive(Debug)]
+struct StallWatch {
+    loaded: bool,
+    paused: bool,
+    last_pos: Option<f64>,
+    /// When the position last moved (or watching restarted).
+    since: Instant,
+    reported: bool,
+}
+
+impl StallWatch {
+    fn new(now: Instant) -> Self {
+        Self { loaded: false, paused: false, last_pos: None, since: now, reported: false }
+    }
+
+    fn watching(&self) -> bool {
+        self.loaded && !self.paused
+    }
+
+    fn restart(&mut self, now: Instant) {
+        self.last_pos = None;
+        self.since = now;
+        self.reported = false;
+    }
+
+    /// Tracks the properties that decide whether progress is expected.
+    fn observe(&mut self, ev: &MpvEvent, now: Instant) {
+        let was_watching = self.watching();
+        match ev {
+            MpvEvent::Pause(p) => self.paused = *p,
+            MpvEvent::IdleActive(idle) => self.loaded = !idle,
+            _ => return,
+        }
+        if self.watching() && !was_watching {
+            self.restart(now);
+        }
+    }
+
+    /// Feeds a polled position; true when playback has just been found stalled.
+    fn progress(&mut self, pos: Option<f64>, now: Instant) -> bool {
+        if !self.watching() {
+            return false;
+        }
+        if pos.is_some() && pos != self.last_pos {
+            self.last_pos = pos;
+            self.since = now;
+            self.reported = false;
+            return false;
+        }
+        if self.reported || now.duration_since(self.since) < STALL_AFTER {
+            return false;
+        }
+        self.reported = true;
+        true
+    }
+}
+
+/// Notices an mpv that still runs but no longer answers, e.g. stuck in a
+/// driver or a network call; only exits were noticed before.
+#[derive(Debug, Default)]
+struct Heartbeat {
+    /// When the ping still waiting for an answer went out.
+    waiting_since: Option<Instant>,
+}
+
+impl Heartbeat {
+    /// Whether to send a ping now; one at a time.
+    fn ping(&mut self, now: Instant) -> bool {
+        if self.waiting_since.is_some() {
+            return false;
+        }
+        self.waiting_since = Some(now);
+        true
+    }
+
+    fn answered(&mut self) {
+        self.waiting_since = None;
+    }
+
+    fn hung(&self, now: Instant) -> bool {
+        self.waiting_since.is_some_and(|at| now.duration_since(at) >= HEALTH_TIMEOUT)
+    }
+}
+
+/// A reply to one of the mpv task's own polls, if `line` is one.
+fn poll_reply(line: &str) -> Option<(u64, Option<serde_json::Value>)> {
+    let incoming: MpvIncoming = serde_json::from_str(line).ok()?;
+    let id = incoming
+        .request_id
+        .filter(|id| {
+            [PROGRESS_REQUEST_ID, DATA_REQUEST_ID, HEALTH_REQUEST_ID, LEVEL_REQUEST_ID].contains(id)
+                || TECH_PROPERTIES.iter().any(|(t, _)| t == id)
+        })?;
+    Some((id, incoming.data))
+}
+
+async fn spawn_and_connect(
+    socket_path: &Path,
+    options: &MpvOptions,
+) -> Result<(Child, UnixStream)> {
+    let _ = tokio::fs::remove_file(socket_path).await;
+
+    let mut cmd = Command::new("mpv");
+    cmd.kill_on_drop(true)
+        .arg("--idle=yes")
+        .arg("--no-terminal")
+        // Audio only: no video track is selected, and youtube-dl, which
+        // would happily pull in video pages, is never asked.
+        .arg("--vid=no")
+        .arg("--ytdl=no")
+        .arg("--force-window=no")
+        .arg("--keep-open=yes")
+        .arg(format!(
+            "--input-ipc-server={}",
+            socket_path
+                .to_str()
+                .ok_or_else(|| anyhow!("Invalid socket path"))?
+        ))
+        .args(options.args())
+        .envs(client_props());
+    unsafe {
+        cmd.pre_exec(|| {
+            // kill mpv when the parent (applet) dies
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().context("Failed to spawn mpv")?;
+
+    let start = tokio::time::Instant::now();
+    let stream = loop {
+        match UnixStream::connect(socket_path).await {
+            Ok(s) => break s,
+            Err(e) => {
+                if start.elapsed() > Duration::from_secs(3) {
+                    let _ = child.kill().await;
+                    return Err(e).context("Timed out connecting to mpv IPC socket");
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+    };
+
+    Ok((child, stream))
+}
+
+/// Stream properties mpv has no options for: the role lets the sound server
+/// treat the radio as music (ducking, per-role volumes), the icon shows up
+/// next to the stream. Read by PipeWire streams and the PulseAudio output.
+fn client_props() -> [(&'static str, String); 2] {
+    [
+        (
+            "PIPEWIRE_PROPS",
+            format!(r#"{{ "media.role": "Music", "application.icon-name": "{CLIENT_ICON}" }}"#),
+        ),
+        ("PULSE_PROP", format!("media.role=music application.icon_name={CLIENT_ICON}")),
+    ]
+}
+
+async fn send_observers(stream: &mut UnixStream, requests: &mut Requests) -> Result<()> {
+    // media-title
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(1),
+            serde_json::json!("media-title"),
+        ]),
+    )
+    .await?;
+
+    // pause
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(2),
+            serde_json::json!("pause"),
+        ]),
+    )
+    .await?;
+
+    // volume
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(3),
+            serde_json::json!("volume"),
+        ]),
+    )
+    .await?;
+
+    // track-list, to spot video streams
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(5),
+            serde_json::json!("track-list"),
+        ]),
+    )
+    .await?;
+
+    // audio-device-list
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(6),
+            serde_json::json!("audio-device-list"),
+        ]),
+    )
+    .await?;
+
+    // duration
+    send_json(
+        stream,
+        requests.tag(vec![
+            serde_json::json!("observe_property"),
+            serde_json::json!(4),
+            serde_json::json!("duration"),
+        ]),
+    )
+    .await?;
+
+    // whether audio is actually flowing, and how far the cache has filled
+    for (id, name) in [
+        (7, "core-idle"),
+        (8, "idle-active"),
+        (9, "eof-reached"),
+        (10, "paused-for-cache"),
+        (11, "cache-buffering-state"),
+        // icy-* headers and other container tags
+        (12, "metadata"),
+        (13, "mute"),
+    ] {
+        send_json(
+            stream,
+            requests.tag(vec![
+                serde_json::json!("observe_property"),
+                serde_json::json!(id),
+                serde_json::json!(name),
+            ]),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+enum LoopExit {
+    Shutdown,
+    Respawn(MpvOptions),
+}
+
+async fn io_loop(
+    child: &mut Child,
+    stream: UnixStream,
+    requests: &mut Requests,
+    cmd_rx: &mut mpsc::UnboundedReceiver<MpvCommand>,
+    evt_tx: &mpsc::UnboundedSender<MpvEvent>,
+) -> Result<LoopExit> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half).lines();
+    let mut usage_tick = tokio::time::interval(USAGE_INTERVAL);
+    let mut last_sample: Option<(Instant, ProcSample)> = None;
+    let mut poll_tick = tokio::time::interval(POLL_INTERVAL);
+    let mut stall = StallWatch::new(Instant::now());
+    let mut tech = TechPoll::default();
+    let mut data = DataMeter::default();
+    let mut rewindable = 0;
+    let mut health_tick = tokio::time::interval(HEALTH_INTERVAL);
+    let mut heartbeat = Heartbeat::default();
+    let mut level_tick = tokio::time::interval(LEVEL_INTERVAL);
+    level_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    // Whether the last `af` set includes LEVEL_FILTER.
+    let mut metering = false;
+
+    loop {
+        tokio::select! {
+            _ = level_tick.tick() => {
+                if metering && stall.watching() {
+                    send_json(&mut write_half, serde_json::json!({
+                        "command": ["get_property", "af-metadata/level"],
+                        "request_id": LEVEL_REQUEST_ID,
+                    })).await?;
+                }
+            }
+            _ = health_tick.tick() => {
+                let now = Instant::now();
+                if heartbeat.hung(now) {
+                    return Err(anyhow!("mpv stopped responding for {}s", HEALTH_TIMEOUT.as_secs()));
+                }
+                if heartbeat.ping(now) {
+                    send_json(&mut write_half, serde_json::json!({
+                        "command": ["get_version"],
+                        "request_id": HEALTH_REQUEST_ID,
+                    })).await?;
+                }
+            }
+            _ = usage_tick.tick() => {
+                let Some(sample) = child.id().and_then(ProcSample::read) else {
+                    continue;
+                };
+                let now = Instant::now();
+                if let Some((at, prev)) = last_sample {
+                    let _ = evt_tx.send(MpvEvent::Usage(sample.usage_since(&prev, now - at)));
+                }
+                last_sample = Some((now, sample));
+            }
+            _ = poll_tick.tick() => {
+                if stall.watching() {
+                    send_json(&mut write_half, serde_json::json!({
+                        "command": ["get_property", "time-pos"],
+                        "request_id": PROGRESS_REQUEST_ID,
+                    })).await?;
+                    for (id, property) in TECH_PROPERTIES {
+                        send_json(&mut write_half, serde_json::json!({
+                            "command": ["get_property", property],
+                            "request_id": id,
+                        })).await?;
+                    }
+                }
+                if stall.loaded {
+                    // Also while paused: mpv keeps filling its cache.
+                    send_json(&mut write_half, serde_json::json!({
+                        "command": ["get_property", "demuxer-cache-state"],
+                        "request_id": DATA_REQUEST_ID,
+                    })).await?;
+                } else {
+                    data.stop();
+                }
+            }
+            status = child.wait() => {
+                let status = status.context("mpv wait failed")?;
+                return Err(anyhow!("mpv exited: {status}"));
+            }
+            maybe_line = reader.next_line() => {
+                let line = maybe_line.context("mpv IPC read error")?;
+                let Some(line) = line else {
+                    return Err(anyhow!("mpv IPC closed"));
+                };
+                if requests.complete(&line, evt_tx) {
+                    continue;
+                }
+                if let Some((id, reply)) = poll_reply(&line) {
+                    if id == HEALTH_REQUEST_ID {
+                        heartbeat.answered();
+                    } else if id == LEVEL_REQUEST_ID {
+                        let _ = evt_tx.send(MpvEvent::Level(AudioLevel::from_metadata(reply)));
+                    } else if id == PROGRESS_REQUEST_ID {
+                        if stall.progress(reply.and_then(|v| v.as_f64()), Instant::now()) {
+                            let _ = evt_tx.send(MpvEvent::Stalled);
+                        }
+                    } else if id == DATA_REQUEST_ID {
+                        let bytes = data.update(reply.as_ref(), Instant::now());
+                        if bytes > 0 {
+                            let _ = evt_tx.send(MpvEvent::DataUsed(bytes));
+                        }
+                        let secs = rewindable_secs(reply.as_ref());
+                        if secs != rewindable {
+                            rewindable = secs;
+                            let _ = evt_tx.send(MpvEvent::Rewindable(secs));
+                        }
+                    } else if let Some(details) = tech.update(id, reply) {
+                        let _ = evt_tx.send(MpvEvent::StreamTech(details));
+                    }
+                    continue;
+                }
+                if let Ok(ev) = parse_event(&line) {
+                    stall.observe(&ev, Instant::now());
+                    let _ = evt_tx.send(ev);
+                }
+            }
+            cmd = cmd_rx.recv() => {
+                let Some(cmd) = cmd else {
+                    // App/controller dropped -> ensure mpv dies.
+                    let _ = child.kill().await;
+                    let _ = child.wait().await;
+                    return Ok(LoopExit::Shutdown);
+                };
+                match cmd {
+                    MpvCommand::LoadUrl { url, start } => {
+                        stall.restart(Instant::now());
+                        tech.reset();
+                        data.start(Instant::now());
+                        rewindable = 0;
+                        // `start` is sticky in mpv, so reset it for every load.
+                        let start = start.map_or_else(|| "none".to_string(), |s| format!("{s:.1}"));
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("start"),
+                            serde_json::json!(start),
+                        ])).await?;
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("loadfile"),
+                            serde_json::json!(url),
+                            serde_json::json!("replace"),
+                        ])).await?;
+                    }
+                    MpvCommand::SetTitle(title) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("force-media-title"),
+                            serde_json::json!(title),
+                        ])).await?;
+                    }
+                    MpvCommand::TogglePause => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("cycle"),
+                            serde_json::json!("pause"),
+                        ])).await?;
+                    }
+                    MpvCommand::SeekRelative(secs) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("seek"),
+                            serde_json::json!(secs),
+                            serde_json::json!("relative"),
+                        ])).await?;
+                    }
+                    MpvCommand::SetPause(p) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("pause"),
+                            serde_json::json!(p),
+                        ])).await?;
+                    }
+                    MpvCommand::SetVolume(volume) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("volume"),
+                            serde_json::json!(volume),
+                        ])).await?;
+                    }
+                    MpvCommand::SetMute(mute) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("mute"),
+                            serde_json::json!(mute),
+                        ])).await?;
+                    }
+                    MpvCommand::SetAudioFilter(filter) => {
+                        metering = filter.split(',').any(|f| f == LEVEL_FILTER);
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("af"),
+                            serde_json::json!(filter),
+                        ])).await?;
+                    }
+                    MpvCommand::SetAudioDevice(device) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("audio-device"),
+                            serde_json::json!(device),
+                        ])).await?;
+                    }
+                    MpvCommand::AdjustVolume(delta) => {
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("add"),
+                            serde_json::json!("volume"),
+                            serde_json::json!(delta),
+                        ])).await?;
+                    }
+                    MpvCommand::QueryPosition => {
+                        send_json(&mut write_half, serde_json::json!({
+                            "command": ["get_property", "time-pos"],
+                            "request_id": POSITION_REQUEST_ID,
+                        })).await?;
+                    }
+                    MpvCommand::SetStreamRecord(path) => {
+                        let path = path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                        send_json(&mut write_half, requests.tag(vec![
+                            serde_json::json!("set_property"),
+                            serde_json::json!("stream-record"),
+                            serde_json::json!(path),
+                        ])).await?;
+                    }
+                    MpvCommand::GetProperty { name, reply } => {
+                        send_json(&mut write_half, requests.get_property(&name, reply)).await?;
+                    }
+                    MpvCommand::Stop => {
+                        send_json(&mut write_half, requests.tag(vec![serde_json::json!("stop")])).await?;
+                    }
+                    MpvCommand::Respawn(options) => {
+                        return Ok(LoopExit::Respawn(options));
+                    }
+                    MpvCommand::Shutdown => {
+                        let _ = child.kill().await;
+                        return Ok(LoopExit::Shutdown);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Raw counters from procfs for one process.
+#[derive(Debug, Clone, Copy)]
+struct ProcSample {
+    cpu_ticks: u64,
+    rss_bytes: u64,
+    read_bytes: u64,
+}
+
+impl ProcSample {
+    fn read(pid: u32) -> Option<Self> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let statm = std::fs::read_to_string(format!("/proc/{pid}/statm")).ok()?;
+        let io = std::fs::read_to_string(format!("/proc/{pid}/io")).unwrap_or_default();
+        // SAFETY: sysconf has no preconditions.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+        let rss_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+        Some(Self {
+            cpu_ticks: parse_cpu_ticks(&stat)?,
+            rss_bytes: rss_pages * page_size,
+            read_bytes: parse_rchar(&io).unwrap_or(0),
+        })
+    }
+
+    fn usage_since(&self, prev: &Self, elapsed: Duration) -> ResourceUsage {
+        // SAFETY: sysconf has no preconditions.
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+        let secs = elapsed.as_secs_f64().max(0.001);
+        let cpu_secs = self.cpu_ticks.saturating_sub(prev.cpu_ticks) as f64 / ticks_per_sec;
+        ResourceUsage {
+            cpu_percent: cpu_secs / secs * 100.0,
+            rss_bytes: self.rss_bytes,
+            read_bytes_per_sec: (self.read_bytes.saturating_sub(prev.read_bytes) as f64 / secs)
+                as u64,
+        }
+    }
+}
+
+/// User plus system time from `/proc/<pid>/stat`, in clock ticks.
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // Fields after the `(comm)` start at field 3 (state); utime and stime are 14 and 15.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let mut fields = rest.split_whitespace().skip(11);
+    let utime: u64 = fields.next()?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+fn parse_rchar(io: &str) -> Option<u64> {
+    io.lines()
+        .find_map(|l| l.strip_prefix("rchar:"))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[derive(Debug, Deserialize)]
+struct MpvIncoming {
+    #[serde(default)]
+    event: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    request_id: Option<u64>,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    file_error: Option<String>,
+}
+
+fn parse_event(line: &str) -> Result<MpvEvent> {
+    let incoming: MpvIncoming = serde_json::from_str(line).context("Invalid mpv IPC JSON")?;
+    if incoming.request_id == Some(POSITION_REQUEST_ID) {
+        // Errors (nothing playing) come back without data.
+        return Ok(MpvEvent::Position(incoming.data.and_then(|v| v.as_f64())));
+    }
+    if incoming.event.as_deref() == Some("end-file") && incoming.reason.as_deref() == Some("error") {
+        let err = incoming.file_error.unwrap_or_else(|| "unknown error".to_string());
+        return Ok(MpvEvent::PlaybackFailed(err));
+    }
+    if incoming.event.as_deref() == Some("end-file") && incoming.reason.as_deref() == Some("eof") {
+        return Ok(MpvEvent::StreamEnded);
+    }
+    if incoming.event.as_deref() == Some("playback-restart") {
+        return Ok(MpvEvent::PlaybackStarted);
+    }
+    if incoming.event.as_deref() != Some("property-change") {
+        return Err(anyhow!("Not a property-change event"));
+    }
+    match incoming.name.as_deref() {
+        Some("media-title") => {
+            let title = incoming
+                .data
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            Ok(MpvEvent::MediaTitle(title))
+        }
+        Some("pause") => {
+            let paused = incoming
+                .data
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            Ok(MpvEvent::Pause(paused))
+        }
+        Some("volume") => {
+            let volume = incoming
+                .data
+                .and_then(|v| v.as_f64())
+                .ok_or_else(|| anyhow!("Missing volume value"))?;
+            Ok(MpvEvent::Volume(volume))
+        }
+        Some("mute") => {
+            let muted = incoming
+                .data
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            Ok(MpvEvent::Mute(muted))
+        }
+        Some("audio-device-list") => {
+            let devices = incoming
+                .data
+                .and_then(|v| serde_json::from_value(v).ok())
+                .unwrap_or_default();
+            Ok(MpvEvent::AudioDevices(devices))
+        }
+        Some("track-list") => {
+            let tracks = incoming.data.unwrap_or_default();
+            let has_video = tracks.as_array().is_some_and(|tracks| {
+                tracks.iter().any(|t| {
+                    t.get("type").and_then(|v| v.as_str()) == Some("video")
+                        && !t.get("albumart").and_then(|v| v.as_bool()).unwrap_or(false)
+                })
+            });
+            Ok(MpvEvent::HasVideo(has_video))
+        }
+        Some("duration") => Ok(MpvEvent::Duration(
+            incoming.data.and_then(|v| v.as_f64()),
+        )),
+        Some(name @ ("core-idle" | "idle-active" | "eof-reached" | "paused-for-cache")) => {
+            // Unavailable (e.g. eof-reached with nothing loaded) arrives as null.
+            let flag = incoming.data.and_then(|v| v.as_bool()).unwrap_or(false);
+            Ok(match name {
+                "core-idle" => MpvEvent::CoreIdle(flag),
+                "idle-active" => MpvEvent::IdleActive(flag),
+                "paused-for-cache" => MpvEvent::PausedForCache(flag),
+                _ => MpvEvent::EofReached(flag),
+            })
+        }
+        Some("metadata") => Ok(MpvEvent::StreamTags(StreamTags::from_metadata(incoming.data))),
+        Some("cache-buffering-state") => Ok(MpvEvent::CacheBuffering(
+            incoming.data.and_then(|v| v.as_u64()).map(|p| p.min(100) as u8),
+        )),
+        _ => Err(anyhow!("Unrecognized property-change")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_media_title() {
+        let line = r#"{"event":"property-change","name":"media-title","data":"Song Title"}"#;
+        let ev = parse_event(line).unwrap();
+        match ev {
+            MpvEvent::MediaTitle(Some(t)) => assert_eq!(t, "Song Title"),
+            _ => panic!("unexpected event"),
+        }
+    }
+
+    #[test]
+    fn parses_pause() {
+        let line = r#"{"event":"property-change","name":"pause","data":true}"#;
+        let ev = parse_event(line).unwrap();
+        match ev {
+            MpvEvent::Pause(true) => {}
+            _ => panic!("unexpected event"),
+        }
+    }
+
+    #[test]
+    fn parses_volume() {
+        let line = r#"{"event":"property-change","name":"volume","data":42.5}"#;
+        match parse_event(line).unwrap() {
+            MpvEvent::Volume(v) => assert_eq!(v, 42.5),
+            _ => panic!("unexpected event"),
+        }
+        let line = r#"{"event":"property-change","name":"mute","data":true}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::Mute(true)));
+    }
+
+    #[test]
+    fn parses_icy_metadata() {
+        let line = r#"{"event":"property-change","name":"metadata","data":{"icy-name":"Jazz FM ","ICY-GENRE":"Jazz","icy-url":"https://jazz.example","icy-br":"128","icy-description":""}}"#;
+        match parse_event(line).unwrap() {
+            MpvEvent::StreamTags(tags) => {
+                assert_eq!(tags.name.as_deref(), Some("Jazz FM"));
+                assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+                assert_eq!(tags.url.as_deref(), Some("https://jazz.example"));
+                assert_eq!(tags.description, None);
+            }
+            _ => panic!("unexpected event"),
+        }
+        let line = r#"{"event":"property-change","name":"metadata"}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::StreamTags(t) if t.is_empty()));
+    }
+
+    #[test]
+    fn parses_activity_properties() {
+        let line = r#"{"event":"property-change","id":7,"name":"core-idle","data":false}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::CoreIdle(false)));
+        let line = r#"{"event":"property-change","id":8,"name":"idle-active","data":true}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::IdleActive(true)));
+        let line = r#"{"event":"property-change","id":9,"name":"eof-reached"}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::EofReached(false)));
+        let line = r#"{"event":"property-change","id":10,"name":"paused-for-cache","data":true}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::PausedForCache(true)));
+        let line = r#"{"event":"property-change","id":11,"name":"cache-buffering-state","data":42}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::CacheBuffering(Some(42))));
+    }
+
+    #[test]
+    fn tech_poll_reports_changed_rounds() {
+        let mut poll = TechPoll::default();
+        let round = |poll: &mut TechPoll, bitrate: f64| {
+            assert!(poll.update(3, Some(serde_json::json!("mp3"))).is_none());
+            assert!(poll.update(4, Some(serde_json::json!(bitrate))).is_none());
+            assert!(poll.update(5, Some(serde_json::json!(44100))).is_none());
+            poll.update(6, Some(serde_json::json!("stereo")))
+        };
+        let tech = round(&mut poll, 127_600.0).unwrap();
+        assert_eq!(tech.summary(), "mp3 · 128 kbps · 44.1 kHz · stereo");
+        assert!(round(&mut poll, 128_100.0).is_none());
+        assert_eq!(round(&mut poll, 96_000.0).unwrap().bitrate_kbps, Some(96));
+
+        let tech = StreamTech { samplerate: Some(48_000), ..Default::default() };
+        assert_eq!(tech.summary(), "48 kHz");
+    }
+
+    #[test]
+    fn data_meter_integrates_input_rate() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let state = |rate: u64| serde_json::json!({ "raw-input-rate": rate, "fw-bytes": 1 });
+        let mut meter = DataMeter::default();
+        // Nothing loaded yet.
+        assert_eq!(meter.update(Some(&state(16_000)), at(0)), 0);
+
+        meter.start(at(0));
+        assert_eq!(meter.update(Some(&state(16_000)), at(5)), 80_000);
+        assert_eq!(meter.update(None, at(10)), 0);
+        // A long gap counts as two polls at most.
+        assert_eq!(meter.update(Some(&state(1_000)), at(100)), 10_000);
+        meter.stop();
+        assert_eq!(meter.update(Some(&state(16_000)), at(105)), 0);
+    }
+
+    #[test]
+    fn measures_cache_behind_the_read_position() {
+        let state = serde_json::json!({
+            "reader-pts": 95.5,
+            "seekable-ranges": [{ "start": 0.0, "end": 20.0 }, { "start": 50.0, "end": 110.0 }],
+        });
+        assert_eq!(rewindable_secs(Some(&state)), 45);
+        // Not seekable, or nothing read yet.
+        assert_eq!(rewindable_secs(Some(&serde_json::json!({ "reader-pts": 5.0, "seekable-ranges": [] }))), 0);
+        assert_eq!(rewindable_secs(Some(&serde_json::json!({ "seekable-ranges": [{ "start": 0.0, "end": 9.0 }] }))), 0);
+        assert_eq!(rewindable_secs(None), 0);
+    }
+
+    #[test]
+    fn stall_watch_reports_frozen_playback_once() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut watch = StallWatch::new(start);
+        // Nothing loaded: nothing to watch.
+        assert!(!watch.progress(None, at(60)));
+
+        watch.observe(&MpvEvent::IdleActive(false), at(100));
+        assert!(!watch.progress(Some(1.0), at(105)));
+        assert!(!watch.progress(Some(6.0), at(110)));
+        assert!(!watch.progress(Some(6.0), at(125)));
+        assert!(watch.progress(Some(6.0), at(130)));
+        assert!(!watch.progress(Some(6.0), at(135)));
+
+        // Moving again re-arms it; pausing stops it.
+        assert!(!watch.progress(Some(7.0), at(140)));
+        watch.observe(&MpvEvent::Pause(true), at(141));
+        assert!(!watch.progress(Some(7.0), at(200)));
+        watch.observe(&MpvEvent::Pause(false), at(300));
+        assert!(!watch.progress(Some(7.0), at(315)));
+        assert!(!watch.progress(Some(7.0), at(330)));
+        assert!(watch.progress(Some(7.0), at(335)));
+    }
+
+    #[test]
+    fn heartbeat_notices_a_hung_mpv() {
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+        let mut heartbeat = Heartbeat::default();
+        assert!(heartbeat.ping(at(0)));
+        assert!(!heartbeat.hung(at(10)));
+        heartbeat.answered();
+
+        assert!(heartbeat.ping(at(10)));
+        // Still waiting: no second ping, and the timeout runs from the first.
+        assert!(!heartbeat.ping(at(20)));
+        assert!(!heartbeat.hung(at(30)));
+        assert!(heartbeat.hung(at(40)));
+    }
+
+    #[test]
+    fn crash_window_only_counts_recent_crashes() {
+        let start = Instant::now();
+        let mut window = CrashWindow::default();
+        for i in 0..MAX_CRASHES - 1 {
+            window.record(start + Duration::from_secs(i as u64));
+        }
+        assert!(!window.exceeded(start + Duration::from_secs(10)));
+
+        window.record(start + Duration::from_secs(10));
+        assert!(window.exceeded(start + Duration::from_secs(10)));
+        assert!(!window.exceeded(start + CRASH_WINDOW + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn finds_executables_on_path() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("radiowidget-path-{}", std::process::id()));
+        let (plain, bin) = (dir.join("plain"), dir.join("bin"));
+        std::fs::create_dir_all(&plain).unwrap();
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(plain.join("mpv"), "").unwrap();
+        std::fs::write(bin.join("mpv"), "").unwrap();
+        std::fs::set_permissions(bin.join("mpv"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = std::env::join_paths([dir.join("missing"), plain.clone(), bin.clone()]).unwrap();
+        assert_eq!(find_on_path("mpv", &path), Some(bin.join("mpv")));
+        // Not executable, so not mpv.
+        let path = std::env::join_paths([plain]).unwrap();
+        assert_eq!(find_on_path("mpv", &path), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_end_file_error() {
+        let line = r#"{"event":"end-file","reason":"error","playlist_entry_id":1,"file_error":"loading failed"}"#;
+        match parse_event(line).unwrap() {
+            MpvEvent::PlaybackFailed(e) => assert_eq!(e, "loading failed"),
+            _ => panic!("unexpected event"),
+        }
+        let line = r#"{"event":"end-file","reason":"stop","playlist_entry_id":1}"#;
+        assert!(parse_event(line).is_err());
+        let line = r#"{"event":"end-file","reason":"eof","playlist_entry_id":1}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::StreamEnded));
+        let line = r#"{"event":"playback-restart"}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::PlaybackStarted));
+    }
+
+    #[test]
+    fn isolates_from_user_config_by_default() {
+        assert!(MpvOptions::default().args().contains(&"--no-config".to_string()));
+        let shared = MpvOptions {
+            user_config: true,
+            ..MpvOptions::default()
+        };
+        assert!(!shared.args().contains(&"--no-config".to_string()));
+    }
+
+    #[test]
+    fn names_the_audio_stream() {
+        let args = MpvOptions::default().args();
+        assert_eq!(args[0], "--audio-client-name=Radio");
+        let [(_, pipewire), (_, pulse)] = client_props();
+        assert!(pipewire.contains(r#""media.role": "Music""#));
+        assert!(pulse.contains("application.icon_name=io.github.xinia.RadioWidget-symbolic"));
+    }
+
+    #[test]
+    fn explicit_cache_overrides_low_resource_limits() {
+        let options = MpvOptions {
+            low_resource: true,
+            cache: Some((60, 64)),
+            ..MpvOptions::default()
+        };
+        let args = options.args();
+        let last = |prefix: &str| args.iter().rev().find(|a| a.starts_with(prefix)).cloned();
+        assert_eq!(last("--cache-secs=").as_deref(), Some("--cache-secs=60"));
+        assert_eq!(last("--demuxer-max-bytes=").as_deref(), Some("--demuxer-max-bytes=64MiB"));
+    }
+
+    #[test]
+    fn leaves_hls_variant_choice_to_mpv() {
+        assert!(!MpvOptions::default().args().iter().any(|a| a.starts_with("--hls-bitrate")));
+        let saver = MpvOptions {
+            hls_lowest: true,
+            ..MpvOptions::default()
+        };
+        assert!(saver.args().contains(&"--hls-bitrate=min".to_string()));
+    }
+
+    #[test]
+    fn builds_equalizer_filter() {
+        assert_eq!(equalizer_filter(0.0, 0.0, 0.0), "");
+        assert_eq!(
+            equalizer_filter(4.0, -1.0, 3.0),
+            "@eq:lavfi=[firequalizer=gain_entry='entry(0,4.0);entry(100,4.0);entry(1000,-1.0);entry(10000,3.0);entry(20000,3.0)']"
+        );
+    }
+
+    #[test]
+    fn builds_channel_filter() {
+        assert_eq!(channel_filter(false, 0.0), "");
+        assert_eq!(
+            channel_filter(true, 0.0),
+            "@channels:lavfi=[aformat=channel_layouts=stereo,pan=stereo|c0=0.500*c0+0.500*c1|c1=0.500*c0+0.500*c1]"
+        );
+        assert_eq!(
+            channel_filter(false, -0.25),
+            "@channels:lavfi=[aformat=channel_layouts=stereo,pan=stereo|c0=1.000*c0|c1=0.750*c1]"
+        );
+        assert_eq!(
+            channel_filter(true, 3.0),
+            "@channels:lavfi=[aformat=channel_layouts=stereo,pan=stereo|c0=0.000*c0+0.000*c1|c1=0.500*c0+0.500*c1]"
+        );
+    }
+
+    #[test]
+    fn reads_audio_levels() {
+        let data = serde_json::json!({
+            "lavfi.astats.Overall.RMS_level": "-18.420000",
+            "lavfi.astats.Overall.Peak_level": "-3.6",
+        });
+        let level = AudioLevel::from_metadata(Some(data)).unwrap();
+        assert_eq!(level, AudioLevel { rms_db: -18.0, peak_db: -4.0 });
+        assert!((AudioLevel::fraction(level.rms_db) - 0.7).abs() < 1e-6);
+
+        let silence = serde_json::json!({
+            "lavfi.astats.Overall.RMS_level": "-inf",
+            "lavfi.astats.Overall.Peak_level": "-inf",
+        });
+        let level = AudioLevel::from_metadata(Some(silence)).unwrap();
+        assert_eq!(AudioLevel::fraction(level.peak_db), 0.0);
+        assert!(AudioLevel::from_metadata(None).is_none());
+        assert!(AudioLevel::from_metadata(Some(serde_json::json!({}))).is_none());
+    }
+
+    #[test]
+    fn parses_audio_device_list() {
+        let line = r#"{"event":"property-change","name":"audio-device-list","data":[{"name":"auto","description":"Autoselect device"},{"name":"pipewire/alsa_output.usb-headset","description":"USB Headset"}]}"#;
+        match parse_event(line).unwrap() {
+            MpvEvent::AudioDevices(devices) => {
+                assert_eq!(devices.len(), 2);
+                assert_eq!(devices[1].name, "pipewire/alsa_output.usb-headset");
+                assert_eq!(devices[1].description, "USB Headset");
+            }
+            _ => panic!("unexpected event"),
+        }
+    }
+
+    #[test]
+    fn detects_video_tracks_but_not_cover_art() {
+        let line = r#"{"event":"property-change","name":"track-list","data":[{"id":1,"type":"video","albumart":true},{"id":1,"type":"audio"}]}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::HasVideo(false)));
+        let line = r#"{"event":"property-change","name":"track-list","data":[{"id":1,"type":"video","albumart":false,"codec":"h264"},{"id":1,"type":"audio"}]}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::HasVideo(true)));
+    }
+
+    #[test]
+    fn parses_proc_counters() {
+        let stat = "4242 (mpv) S 1000 4242 4242 0 -1 4194560 1234 0 0 0 150 30 0 0 20 0 9 0";
+        assert_eq!(parse_cpu_ticks(stat), Some(180));
+        let io = "rchar: 52428800\nwchar: 1024\nsyscr: 10\n";
+        assert_eq!(parse_rchar(io), Some(52_428_800));
+    }
+
+    #[test]
+    fn parses_audio_params() {
+        let data = serde_json::json!({
+            "format": "floatp",
+            "samplerate": 44100,
+            "channels": "stereo",
+            "hr-channels": "stereo",
+            "channel-count": 2
+        });
+        let params: AudioParams = serde_json::from_value(data).unwrap();
+        assert_eq!(params.samplerate, 44100);
+        assert_eq!(params.channel_count, 2);
+    }
+
+    #[test]
+    fn parses_position_reply() {
+        let line = r#"{"data":812.25,"request_id":1,"error":"success"}"#;
+        match parse_event(line).unwrap() {
+            MpvEvent::Position(Some(p)) => assert_eq!(p, 812.25),
+            _ => panic!("unexpected event"),
+        }
+        let line = r#"{"request_id":1,"error":"property unavailable"}"#;
+        assert!(matches!(parse_event(line).unwrap(), MpvEvent::Position(None)));
+    }
+}
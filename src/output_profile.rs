@@ -0,0 +1,66 @@
+//! Finds the system's default audio output so volume, mute and device can
+//! follow it: after undocking, the radio shouldn't play at docked volume
+//! through the laptop speakers. Asks `pactl`, which PipeWire (through
+//! pipewire-pulse) and PulseAudio both answer; without it the settings stay
+//! as they are.
+
+use crate::mpv::AudioDevice;
+use anyhow::{anyhow, Context, Result};
+use std::time::Duration;
+use tokio::process::Command;
+
+const PACTL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The sound server's name for the default output, e.g.
+/// `alsa_output.usb-Dock_Audio-00.analog-stereo`.
+pub async fn default_sink() -> Result<String> {
+    let mut pactl = Command::new("pactl");
+    // A hung pactl is dropped with the timed-out future; don't leave it behind.
+    pactl.arg("get-default-sink").kill_on_drop(true);
+    let output = tokio::time::timeout(PACTL_TIMEOUT, pactl.output())
+        .await
+        .context("pactl did not answer")?
+        .context("Run pactl")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "pactl get-default-sink failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let sink = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if sink.is_empty() {
+        return Err(anyhow!("No default output"));
+    }
+    Ok(sink)
+}
+
+/// The mpv device that is `sink`. mpv lists sinks under its audio output's
+/// name, e.g. `pipewire/alsa_output.usb-Dock_Audio-00.analog-stereo`.
+pub fn sink_device<'a>(devices: &'a [AudioDevice], sink: &str) -> Option<&'a AudioDevice> {
+    devices
+        .iter()
+        .find(|d| d.name.split_once('/').is_some_and(|(_, name)| name == sink))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_mpv_device_for_a_sink() {
+        let device = |name: &str, description: &str| AudioDevice {
+            name: name.to_string(),
+            description: description.to_string(),
+        };
+        let devices = [
+            device("auto", "Autoselect device"),
+            device("pipewire", "Default (pipewire)"),
+            device("pipewire/alsa_output.pci-0000_00_1f.3.analog-stereo", "Built-in Audio"),
+            device("pulse/alsa_output.usb-Dock-00.analog-stereo", "Dock Audio"),
+        ];
+        let found = |sink| sink_device(&devices, sink).map(|d| d.description.as_str());
+        assert_eq!(found("alsa_output.usb-Dock-00.analog-stereo"), Some("Dock Audio"));
+        assert_eq!(found("alsa_output.pci-0000_00_1f.3.analog-stereo"), Some("Built-in Audio"));
+        assert_eq!(found("bluez_output.00_11_22"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Resume positions for non-live custom stations (podcasts, archives).
+
+use crate::config::{state_dir, write_atomic};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Positions this close to either end aren't worth resuming from.
+const MIN_RESUME_SECS: f64 = 10.0;
+const END_MARGIN_SECS: f64 = 15.0;
+/// Positions not updated for this long are forgotten: the episode was
+/// probably abandoned, or the URL is gone.
+const MAX_AGE_SECS: u64 = 90 * 24 * 3600;
+/// Positions kept at most; the oldest go first.
+const MAX_POSITIONS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Position {
+    /// Seconds into the file.
+    secs: f64,
+    /// Unix seconds it was saved at.
+    saved: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaybackPositions {
+    /// By stream URL.
+    #[serde(default)]
+    positions: BTreeMap<String, Position>,
+}
+
+impl PlaybackPositions {
+    /// Loads saved positions; a missing or corrupt file yields an empty map.
+    pub fn load() -> Self {
+        let path = match positions_path() {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(error = ?e, "resume positions disabled");
+                return Self::default();
+            }
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(error = ?e, ?path, "discarding unreadable resume positions");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data =
+            serde_json::to_vec_pretty(self).context("Failed to serialize resume positions")?;
+        write_atomic(&positions_path()?, &data)
+    }
+
+    pub fn get(&self, url: &str) -> Option<f64> {
+        self.positions.get(url).map(|p| p.secs)
+    }
+
+    /// Records where playback of `url` stopped at Unix time `now`, dropping
+    /// positions that are too old or too many. Returns whether anything changed.
+    pub fn record(&mut self, url: &str, position: f64, duration: f64, now: u64) -> bool {
+        let before = self.positions.len();
+        self.positions.retain(|_, p| now.saturating_sub(p.saved) < MAX_AGE_SECS);
+        let mut changed = self.positions.len() != before;
+        if position >= MIN_RESUME_SECS && position < duration - END_MARGIN_SECS {
+            if self.get(url) != Some(position) {
+                self.positions.insert(url.to_string(), Position { secs: position, saved: now });
+                changed = true;
+            }
+        } else {
+            // Barely started or finished: start from the top next time.
+            changed |= self.positions.remove(url).is_some();
+        }
+        while self.positions.len() > MAX_POSITIONS {
+            let Some(oldest) = self.positions.iter().min_by_key(|(_, p)| p.saved).map(|(url, _)| url.clone()) else {
+                break;
+            };
+            self.positions.remove(&oldest);
+        }
+        changed
+    }
+}
+
+fn positions_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join("positions.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_only_resumable_positions() {
+        let mut p = PlaybackPositions::default();
+        let url = "https://example.org/episode.mp3";
+
+        assert!(p.record(url, 812.5, 3600.0, 0));
+        assert_eq!(p.get(url), Some(812.5));
+        assert!(!p.record(url, 812.5, 3600.0, 0));
+
+        assert!(p.record(url, 3595.0, 3600.0, 0));
+        assert_eq!(p.get(url), None);
+
+        assert!(!p.record(url, 3.0, 3600.0, 0));
+        assert_eq!(p.get(url), None);
+    }
+
+    #[test]
+    fn forgets_old_and_surplus_positions() {
+        let mut p = PlaybackPositions::default();
+        p.record("https://example.org/old.mp3", 600.0, 3600.0, 0);
+        for i in 0..MAX_POSITIONS as u64 {
+            p.record(&format!("https://example.org/{i}.mp3"), 600.0, 3600.0, MAX_AGE_SECS - 1 + i);
+        }
+        assert_eq!(p.positions.len(), MAX_POSITIONS);
+        assert_eq!(p.get("https://example.org/old.mp3"), None);
+
+        p.record("https://example.org/new.mp3", 600.0, 3600.0, MAX_AGE_SECS + 1000);
+        assert_eq!(p.positions.len(), MAX_POSITIONS);
+        assert_eq!(p.get("https://example.org/0.mp3"), None);
+        assert_eq!(p.get("https://example.org/new.mp3"), Some(600.0));
+    }
+}
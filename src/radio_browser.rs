@@ -1,10 +1,14 @@
 use crate::models::{RadioBrowserServer, Station};
+use crate::ranked_search;
 use anyhow::{anyhow, Context, Result};
 use futures_util::StreamExt;
 use rand::seq::SliceRandom;
 use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
 use serde::Deserialize;
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::debug;
 use url::Url;
 
 const BOOTSTRAP_BASE: &str = "https://all.api.radio-browser.info";
@@ -14,6 +18,43 @@ const MAX_BODY_BYTES: usize = 1_000_000;
 pub struct RadioBrowserClient {
     http: reqwest::Client,
     last_server: Option<String>,
+    /// Mirror chosen by the user; always tried first.
+    pinned_server: Option<String>,
+    health: BTreeMap<String, MirrorHealth>,
+}
+
+/// Request outcomes for one API mirror during this session.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MirrorHealth {
+    pub server: String,
+    pub requests: u32,
+    pub failures: u32,
+    /// Summed latency of successful requests.
+    total_latency: Duration,
+}
+
+impl MirrorHealth {
+    pub fn avg_latency(&self) -> Option<Duration> {
+        let successes = self.requests - self.failures;
+        (successes > 0).then(|| self.total_latency / successes)
+    }
+
+    pub fn failure_rate(&self) -> f32 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.failures as f32 / self.requests as f32
+        }
+    }
+
+    fn record(&mut self, latency: Duration, ok: bool) {
+        self.requests += 1;
+        if ok {
+            self.total_latency += latency;
+        } else {
+            self.failures += 1;
+        }
+    }
 }
 
 impl RadioBrowserClient {
@@ -33,13 +74,27 @@ impl RadioBrowserClient {
             .build()
             .context("Failed to build HTTP client")?;
 
-        Ok(Self { http, last_server })
+        Ok(Self {
+            http,
+            last_server,
+            pinned_server: None,
+            health: BTreeMap::new(),
+        })
     }
 
     pub fn last_server(&self) -> Option<&str> {
         self.last_server.as_deref()
     }
 
+    pub fn set_pinned_server(&mut self, server: Option<String>) {
+        self.pinned_server = server;
+    }
+
+    /// Per-mirror statistics for every mirror used this session, by name.
+    pub fn mirror_health(&self) -> Vec<MirrorHealth> {
+        self.health.values().cloned().collect()
+    }
+
     pub async fn discover_servers(&self) -> Result<Vec<String>> {
         let url = format!("{BOOTSTRAP_BASE}/json/servers");
         let resp = self.http.get(url).send().await.context("Server discovery failed")?;
@@ -55,6 +110,8 @@ impl RadioBrowserClient {
         Ok(names)
     }
 
+    /// Stations matching `query` by name, tag or country, best matches first;
+    /// see [`crate::ranked_search`].
     pub async fn search(&mut self, query: &str, limit: u32) -> Result<Vec<Station>> {
         let query = query.trim();
         if query.is_empty() {
@@ -62,37 +119,52 @@ impl RadioBrowserClient {
         }
 
         let http = self.http.clone();
-        let query = query.to_string();
-        self.with_server_retry("search", move |base| {
-            let http = http.clone();
-            let query = query.clone();
-            async move {
-            let mut url = Url::parse(&format!("{base}/json/stations/search"))
-                .context("Invalid Radio Browser base URL")?;
-            url.query_pairs_mut()
-                .append_pair("name", &urlencoding::encode(&query))
-                .append_pair("hidebroken", "true")
-                .append_pair("limit", &limit.to_string())
-                .append_pair("order", "votes")
-                .append_pair("reverse", "true");
-            eprintln!("[RadioWidget][search] GET {}", url);
-            let resp = http.get(url).send().await?;
-            eprintln!("[RadioWidget][search] Response: status = {}", resp.status());
-            let bytes = read_limited(resp, MAX_BODY_BYTES).await?;
-            let stations: Vec<Station> =
-                serde_json::from_slice(&bytes).context("Invalid stations search response")?;
-            eprintln!("[RadioWidget][search] Got {} stations", stations.len());
-            Ok(stations)
-            }
-        })
-        .await
+        let queries = ranked_search::queries(query);
+        let batches = self
+            .with_server_retry("search", move |base| {
+                let http = http.clone();
+                let queries = queries.clone();
+                async move {
+                    let (http, base) = (&http, &base);
+                    let lookups = queries.iter().map(|(field, value)| async move {
+                        search_by(http, base, &[(*field, value.as_str())], limit).await
+                    });
+                    let mut batches = Vec::new();
+                    let mut failed = None;
+                    for res in futures_util::future::join_all(lookups).await {
+                        match res {
+                            Ok(stations) => batches.push(stations),
+                            Err(e) => failed = Some(e),
+                        }
+                    }
+                    // One lookup failing still leaves the others' results.
+                    match failed {
+                        Some(e) if batches.is_empty() => Err(e),
+                        _ => Ok(batches),
+                    }
+                }
+            })
+            .await?;
+        Ok(ranked_search::rank(query, batches, limit as usize))
     }
 
-    pub async fn resolve_station_url(&mut self, stationuuid: &str) -> Result<Url> {
+    /// Resolves the playable stream URL for a station. With `count_click` the
+    /// `/json/url` endpoint is used, which also registers a play on Radio Browser;
+    /// otherwise the station record is looked up without side effects. The
+    /// record is also the fallback when `/json/url` fails or answers nonsense,
+    /// so a partial API outage doesn't stop playback.
+    pub async fn resolve_station_url(
+        &mut self,
+        stationuuid: &str,
+        count_click: bool,
+    ) -> Result<Url> {
         let stationuuid = stationuuid.trim();
         if stationuuid.is_empty() {
             return Err(anyhow!("Missing station UUID"));
         }
+        if !count_click {
+            return self.lookup_station_url(stationuuid).await;
+        }
 
         let http = self.http.clone();
         let stationuuid = stationuuid.to_string();
@@ -100,27 +172,77 @@ impl RadioBrowserClient {
             let http = http.clone();
             let stationuuid = stationuuid.clone();
             async move {
-            let url = format!("{base}/json/url/{stationuuid}");
-            eprintln!("[RadioWidget][resolve] GET {}", url);
-            let resp = http.get(url).send().await?;
-            eprintln!("[RadioWidget][resolve] Response: status = {}", resp.status());
-            if resp.status().is_redirection() {
-                if let Some(loc) = resp.headers().get(reqwest::header::LOCATION) {
-                    let loc = loc.to_str().context("Invalid redirect Location header")?;
-                    eprintln!("[RadioWidget][resolve] Redirected to {}", loc);
-                    return parse_stream_url(loc);
-                }
+                click_or_lookup(
+                    clicked_url(&http, &base, &stationuuid),
+                    looked_up_url(&http, &base, &stationuuid),
+                )
+                .await
             }
-            let bytes = read_limited(resp, 64 * 1024).await?;
-            let text = String::from_utf8_lossy(&bytes);
-            eprintln!("[RadioWidget][resolve] Body: {}", &text);
-            // Try to parse as JSON and extract the url field
-            if let Ok(json) = serde_json::from_str::<UrlResponse>(&text) {
-                eprintln!("[RadioWidget][resolve] Extracted stream URL: {}", json.url);
-                return parse_stream_url(&json.url);
+        })
+        .await
+    }
+
+    async fn lookup_station_url(&mut self, stationuuid: &str) -> Result<Url> {
+        let http = self.http.clone();
+        let stationuuid = stationuuid.to_string();
+        self.with_server_retry("lookup", move |base| {
+            let http = http.clone();
+            let stationuuid = stationuuid.clone();
+            async move { looked_up_url(&http, &base, &stationuuid).await }
+        })
+        .await
+    }
+
+    /// Working stations listed under exactly this name, most voted first.
+    /// Radio Browser often carries several entries (mirrors, bitrates) for
+    /// one broadcaster, which makes these good fallbacks for a dead stream.
+    pub async fn stations_named(&mut self, name: &str, limit: u32) -> Result<Vec<Station>> {
+        let http = self.http.clone();
+        let name = name.trim().to_string();
+        self.with_server_retry("alternates", move |base| {
+            let http = http.clone();
+            let name = name.clone();
+            async move { search_by(&http, &base, &[("name", name.as_str()), ("nameExact", "true")], limit).await }
+        })
+        .await
+    }
+
+    /// The most voted working stations, optionally only those tagged `tag`.
+    pub async fn top_stations(&mut self, tag: Option<&str>, limit: u32) -> Result<Vec<Station>> {
+        let http = self.http.clone();
+        let tag = tag.map(str::to_string);
+        self.with_server_retry("top stations", move |base| {
+            let http = http.clone();
+            let tag = tag.clone();
+            async move {
+                match &tag {
+                    Some(tag) => search_by(&http, &base, &[("tag", tag.as_str()), ("tagExact", "true")], limit).await,
+                    None => search_by(&http, &base, &[], limit).await,
+                }
             }
-            // fallback: try to parse as plain URL
-            parse_stream_url(text.trim())
+        })
+        .await
+    }
+
+    /// Fetches the full station records for the given UUIDs in one request.
+    /// Unknown UUIDs are simply missing from the result.
+    pub async fn stations_by_uuid(&mut self, uuids: &[String]) -> Result<Vec<Station>> {
+        if uuids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let http = self.http.clone();
+        let uuids = uuids.join(",");
+        self.with_server_retry("stations", move |base| {
+            let http = http.clone();
+            let uuids = uuids.clone();
+            async move {
+                let mut url = Url::parse(&format!("{base}/json/stations/byuuid"))
+                    .context("Invalid Radio Browser base URL")?;
+                url.query_pairs_mut().append_pair("uuids", &uuids);
+                let resp = http.get(url).send().await?;
+                let bytes = read_limited(resp, MAX_BODY_BYTES).await?;
+                serde_json::from_slice(&bytes).context("Invalid stations lookup response")
             }
         })
         .await
@@ -138,6 +260,11 @@ impl RadioBrowserClient {
                 servers.swap(0, pos);
             }
         }
+        if let Some(pinned) = self.pinned_server.clone() {
+            // A pinned mirror may be missing from the discovered list; use it anyway.
+            servers.retain(|s| *s != pinned);
+            servers.insert(0, pinned);
+        }
 
         let max_attempts = 4usize;
         let mut last_err: Option<anyhow::Error> = None;
@@ -148,7 +275,16 @@ impl RadioBrowserClient {
                 .unwrap_or_else(|| BOOTSTRAP_BASE.trim_start_matches("https://").to_string());
             let base = format!("https://{server}");
 
-            match f(base.clone()).await {
+            let started = Instant::now();
+            let res = f(base.clone()).await;
+            self.health
+                .entry(server.clone())
+                .or_insert_with(|| MirrorHealth {
+                    server: server.clone(),
+                    ..MirrorHealth::default()
+                })
+                .record(started.elapsed(), res.is_ok());
+            match res {
                 Ok(v) => {
                     self.last_server = Some(server);
                     return Ok(v);
@@ -165,11 +301,103 @@ impl RadioBrowserClient {
     }
 }
 
+/// One `/json/stations/search` lookup by a single field, most voted first.
+/// Working stations matching all of `filters`, most voted first.
+async fn search_by(http: &reqwest::Client, base: &str, filters: &[(&str, &str)], limit: u32) -> Result<Vec<Station>> {
+    let mut url = Url::parse(&format!("{base}/json/stations/search")).context("Invalid Radio Browser base URL")?;
+    url.query_pairs_mut()
+        .extend_pairs(filters)
+        .append_pair("hidebroken", "true")
+        .append_pair("limit", &limit.to_string())
+        .append_pair("order", "votes")
+        .append_pair("reverse", "true");
+    let resp = http.get(url).send().await?;
+    let bytes = read_limited(resp, MAX_BODY_BYTES).await?;
+    let stations: Vec<Station> = serde_json::from_slice(&bytes).context("Invalid stations search response")?;
+    debug!(?filters, found = stations.len(), "station search");
+    Ok(stations)
+}
+
+/// `click`'s URL, or `lookup`'s if that fails. `lookup` only runs when needed.
+async fn click_or_lookup(
+    click: impl Future<Output = Result<Url>>,
+    lookup: impl Future<Output = Result<Url>>,
+) -> Result<Url> {
+    let e = match click.await {
+        Ok(url) => return Ok(url),
+        Err(e) => e,
+    };
+    debug!(error = ?e, "/json/url failed; using the station record");
+    lookup.await.map_err(|lookup_err| lookup_err.context(format!("/json/url also failed: {e:#}")))
+}
+
+/// The stream URL from `/json/url`, which counts a click for the station.
+async fn clicked_url(http: &reqwest::Client, base: &str, stationuuid: &str) -> Result<Url> {
+    let url = format!("{base}/json/url/{stationuuid}");
+    let resp = http.get(&url).send().await?;
+    let status = resp.status();
+    debug!(%url, %status, "/json/url answered");
+    if status.is_redirection() {
+        if let Some(loc) = resp.headers().get(reqwest::header::LOCATION) {
+            let loc = loc.to_str().context("Invalid redirect Location header")?;
+            debug!(location = loc, "/json/url redirected");
+            return parse_stream_url(loc);
+        }
+    }
+    if !status.is_success() {
+        return Err(anyhow!("/json/url answered {status}"));
+    }
+    let bytes = read_limited(resp, 64 * 1024).await?;
+    parse_click_response(&bytes)
+}
+
+/// The stream URL from the station record, without counting a click.
+async fn looked_up_url(http: &reqwest::Client, base: &str, stationuuid: &str) -> Result<Url> {
+    let url = format!("{base}/json/stations/byuuid/{stationuuid}");
+    let resp = http.get(url).send().await?;
+    let bytes = read_limited(resp, 64 * 1024).await?;
+    parse_station_lookup(&bytes)
+}
+
+fn parse_click_response(bytes: &[u8]) -> Result<Url> {
+    let text = String::from_utf8_lossy(bytes);
+    // Try to parse as JSON and extract the url field
+    if let Ok(json) = serde_json::from_str::<UrlResponse>(&text) {
+        debug!(url = %json.url, "stream URL from /json/url");
+        return parse_stream_url(json.url.trim());
+    }
+    // fallback: try to parse as plain URL
+    parse_stream_url(text.trim())
+}
+
 #[derive(Debug, Deserialize)]
 struct UrlResponse {
     url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct StationUrls {
+    url: String,
+    #[serde(default)]
+    url_resolved: Option<String>,
+}
+
+fn parse_station_lookup(bytes: &[u8]) -> Result<Url> {
+    let stations: Vec<StationUrls> =
+        serde_json::from_slice(bytes).context("Invalid station lookup response")?;
+    let station = stations
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Station not found"))?;
+    // `url_resolved` can be empty or stale junk; `url` is what was submitted.
+    match station.url_resolved.as_deref().map(str::trim) {
+        Some(resolved) if !resolved.is_empty() => {
+            parse_stream_url(resolved).or_else(|_| parse_stream_url(station.url.trim()))
+        }
+        _ => parse_stream_url(station.url.trim()),
+    }
+}
+
 fn parse_stream_url(s: &str) -> Result<Url> {
     let url = Url::parse(s).context("Invalid stream URL")?;
     match url.scheme() {
@@ -178,7 +406,7 @@ fn parse_stream_url(s: &str) -> Result<Url> {
     }
 }
 
-async fn read_limited(resp: reqwest::Response, limit: usize) -> Result<Vec<u8>> {
+pub async fn read_limited(resp: reqwest::Response, limit: usize) -> Result<Vec<u8>> {
     if let Some(len) = resp.content_length() {
         if len as usize > limit {
             return Err(anyhow!("HTTP response too large ({len} bytes)"));
@@ -218,10 +446,81 @@ mod tests {
         assert_eq!(stations[0].bitrate, Some(128));
     }
 
+    #[test]
+    fn mirror_health_averages_successful_requests() {
+        let mut h = MirrorHealth::default();
+        assert_eq!(h.avg_latency(), None);
+        h.record(Duration::from_millis(100), true);
+        h.record(Duration::from_millis(300), true);
+        h.record(Duration::from_secs(15), false);
+        assert_eq!(h.avg_latency(), Some(Duration::from_millis(200)));
+        assert!((h.failure_rate() - 1.0 / 3.0).abs() < f32::EPSILON);
+    }
+
     #[test]
     fn validates_stream_url_schemes() {
         assert!(parse_stream_url("https://example.com/stream").is_ok());
         assert!(parse_stream_url("http://example.com/stream").is_ok());
         assert!(parse_stream_url("file:///etc/passwd").is_err());
     }
+
+    #[test]
+    fn station_lookup_prefers_resolved_url() {
+        let body = br#"[{"url":"http://example.com/list.pls","url_resolved":"http://example.com/live"}]"#;
+        let url = parse_station_lookup(body).unwrap();
+        assert_eq!(url.as_str(), "http://example.com/live");
+
+        let body = br#"[{"url":"http://example.com/live","url_resolved":""}]"#;
+        let url = parse_station_lookup(body).unwrap();
+        assert_eq!(url.as_str(), "http://example.com/live");
+
+        assert!(parse_station_lookup(b"[]").is_err());
+    }
+
+    #[test]
+    fn station_lookup_skips_unusable_resolved_url() {
+        let body = br#"[{"url":"http://example.com/live","url_resolved":"rtsp://example.com/live"}]"#;
+        assert_eq!(parse_station_lookup(body).unwrap().as_str(), "http://example.com/live");
+
+        let body = br#"[{"url":"not a url","url_resolved":"garbage"}]"#;
+        assert!(parse_station_lookup(body).is_err());
+    }
+
+    #[test]
+    fn parses_click_responses() {
+        let body = br#"{"ok":true,"message":"retrieved station url","url":"http://example.com/live"}"#;
+        assert_eq!(parse_click_response(body).unwrap().as_str(), "http://example.com/live");
+        assert_eq!(
+            parse_click_response(b"https://example.com/live\n").unwrap().as_str(),
+            "https://example.com/live"
+        );
+        assert!(parse_click_response(br#"{"ok":false,"message":"station not found","url":""}"#).is_err());
+        assert!(parse_click_response(b"<html>502 Bad Gateway</html>").is_err());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_station_record() {
+        let url = |s: &str| Url::parse(s).unwrap();
+        let lookup_ran = std::cell::Cell::new(false);
+        let lookup = |res: Result<Url>| {
+            let lookup_ran = &lookup_ran;
+            async move {
+                lookup_ran.set(true);
+                res
+            }
+        };
+
+        let clicked = click_or_lookup(async { Ok(url("http://a/click")) }, lookup(Ok(url("http://a/record")))).await;
+        assert_eq!(clicked.unwrap().as_str(), "http://a/click");
+        assert!(!lookup_ran.get());
+
+        let garbage = async { parse_click_response(b"<html>oops</html>") };
+        let recorded = click_or_lookup(garbage, lookup(Ok(url("http://a/record")))).await;
+        assert_eq!(recorded.unwrap().as_str(), "http://a/record");
+        assert!(lookup_ran.get());
+
+        let down = async { Err(anyhow!("503")) };
+        let err = click_or_lookup(down, lookup(Err(anyhow!("timeout")))).await.unwrap_err();
+        assert!(format!("{err:#}").contains("503"));
+    }
 }
@@ -0,0 +1,160 @@
+//! Search that doesn't need the user to know which field a word is in:
+//! "jazz france" is looked up by name, tag and country at once, and the
+//! merged results are ranked here.
+
+use crate::models::Station;
+use std::collections::HashSet;
+
+/// Words of the query that get a tag lookup of their own.
+const MAX_TERMS: usize = 2;
+
+/// A Radio Browser search parameter and its value, e.g. `("tag", "jazz")`.
+pub type Query = (&'static str, String);
+
+/// The lookups to run for `query`: the whole query by name, the first words
+/// by tag, and the last word by country, since a place usually comes last
+/// ("jazz france"). At most four requests per search.
+pub fn queries(query: &str) -> Vec<Query> {
+    let mut out = vec![("name", query.trim().to_string())];
+    let terms = terms(query);
+    for term in terms.iter().take(MAX_TERMS) {
+        out.push(("tag", term.clone()));
+    }
+    if let Some(last) = terms.last() {
+        out.push(("country", last.clone()));
+    }
+    out
+}
+
+/// Merges the result lists of [`queries`] into one, best matches first, and
+/// keeps the first `limit`. Stations covering more of the query's words come
+/// first; exact hits, votes and bitrate decide among them.
+pub fn rank(query: &str, batches: impl IntoIterator<Item = Vec<Station>>, limit: usize) -> Vec<Station> {
+    let terms = terms(query);
+    let whole = query.trim().to_lowercase();
+    let mut seen = HashSet::new();
+    let mut scored: Vec<(f64, Station)> = batches
+        .into_iter()
+        .flatten()
+        .filter(|s| seen.insert(s.stationuuid.clone()))
+        .map(|s| (score(&s, &terms, &whole), s))
+        .collect();
+    // Stable, so ties keep the server's order.
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(limit).map(|(_, s)| s).collect()
+}
+
+/// Lowercased words worth a lookup of their own, without repeats.
+fn terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.split_whitespace().map(str::to_lowercase) {
+        if word.chars().count() >= 2 && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms
+}
+
+fn score(s: &Station, terms: &[String], whole: &str) -> f64 {
+    // Two points for an exact word, tag or country, one for part of one.
+    let grade = |exact: bool, partial: bool| match (exact, partial) {
+        (true, _) => 2.0,
+        (false, true) => 1.0,
+        (false, false) => 0.0,
+    };
+    let name = s.name.trim().to_lowercase();
+    let name_words: Vec<&str> = name.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let tags: Vec<String> = s
+        .tags
+        .as_deref()
+        .unwrap_or("")
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let country = s.country.as_deref().unwrap_or("").trim().to_lowercase();
+
+    let mut covered = 0;
+    let mut hits = 0.0;
+    for term in terms {
+        let term_hits = grade(name_words.contains(&term.as_str()), name.contains(term.as_str()))
+            + grade(tags.contains(term), tags.iter().any(|t| t.contains(term.as_str())))
+            + grade(country == *term, country.contains(term.as_str()));
+        if term_hits > 0.0 {
+            covered += 1;
+        }
+        hits += term_hits;
+    }
+
+    let coverage = if terms.is_empty() { 0.0 } else { f64::from(covered) / terms.len() as f64 };
+    let exact_name = if name == whole { 50.0 } else { 0.0 };
+    let votes = f64::from(s.votes.unwrap_or(0)).ln_1p() * 2.0;
+    let bitrate = f64::from(s.bitrate.unwrap_or(0).min(320)) / 64.0;
+    coverage * 100.0 + hits * 5.0 + exact_name + votes + bitrate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(uuid: &str, name: &str, tags: &str, country: &str, votes: u32) -> Station {
+        Station {
+            stationuuid: uuid.to_string(),
+            name: name.to_string(),
+            country: Some(country.to_string()),
+            tags: Some(tags.to_string()),
+            votes: Some(votes),
+            ..Default::default()
+        }
+    }
+
+    fn uuids(stations: &[Station]) -> Vec<&str> {
+        stations.iter().map(|s| s.stationuuid.as_str()).collect()
+    }
+
+    #[test]
+    fn looks_up_words_by_tag_and_the_last_by_country() {
+        assert_eq!(
+            queries(" Jazz  France jazz "),
+            [
+                ("name", "Jazz  France jazz".to_string()),
+                ("tag", "jazz".to_string()),
+                ("tag", "france".to_string()),
+                ("country", "france".to_string()),
+            ]
+        );
+        assert_eq!(queries("smooth jazz from france").len(), 4);
+        // Single letters would match nearly everything.
+        assert_eq!(queries("radio x").len(), 3);
+        assert_eq!(queries("a b c d e f g h").len(), 1);
+    }
+
+    #[test]
+    fn ranks_stations_matching_every_word_first() {
+        let by_name = vec![station("jazz-fm", "Jazz FM", "jazz", "United Kingdom", 5000)];
+        let by_tag = vec![
+            station("jazz-fm", "Jazz FM", "jazz", "United Kingdom", 5000),
+            station("nova", "Radio Nova", "jazz,eclectic", "France", 100),
+            station("tsf", "TSF Jazz", "jazz,smooth jazz", "France", 500),
+        ];
+        let by_country = vec![
+            station("inter", "France Inter", "news,talk", "France", 3000),
+            station("tsf", "TSF Jazz", "jazz,smooth jazz", "France", 500),
+        ];
+        let ranked = rank("jazz france", [by_name, by_tag, by_country], 10);
+        assert_eq!(uuids(&ranked[..2]), ["tsf", "nova"]);
+        // Merged, not repeated.
+        assert_eq!(ranked.len(), 4);
+
+        assert_eq!(uuids(&rank("jazz france", [ranked.clone()], 1)), ["tsf"]);
+    }
+
+    #[test]
+    fn exact_name_beats_popularity() {
+        let stations = vec![
+            station("big", "Jazz FM Hits", "jazz", "United Kingdom", 90_000),
+            station("exact", "Jazz FM", "", "", 10),
+        ];
+        assert_eq!(uuids(&rank("jazz fm", [stations], 10)), ["exact", "big"]);
+    }
+}
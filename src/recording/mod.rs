@@ -0,0 +1,303 @@
+//! Recording streams to disk with mpv's `stream-record`.
+//!
+//! A second mpv with no audio output copies the stream as it arrives, so a
+//! recording keeps going whatever the main player does. The file is opened
+//! once the codec is known, which picks the extension; the data is written
+//! as received, without re-encoding. With `split_songs` a new file starts
+//! whenever the song title changes, and the finished one is tagged with it.
+
+mod tags;
+
+use crate::mpv::{MpvCommand, MpvEvent, MpvOptions, MpvProcess};
+use tags::SongTags;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+/// How long closing a file may take before mpv is stopped anyway.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Extensions recordings are written with, for the recordings list.
+const EXTENSIONS: [&str; 6] = ["mp3", "aac", "ogg", "opus", "flac", "mka"];
+
+/// What a [`Recorder`] reports to the controller.
+#[derive(Debug)]
+pub enum RecorderEvent {
+    /// Data is being written to this file.
+    Started(PathBuf),
+    /// The stream broke or couldn't be opened; nothing more is written.
+    Failed(String),
+}
+
+/// One stream being recorded. Dropping it stops mpv without closing the file
+/// cleanly; call [`Recorder::finish`] instead.
+#[derive(Debug)]
+pub struct Recorder {
+    mpv: MpvProcess,
+    events: mpsc::UnboundedReceiver<MpvEvent>,
+    url: String,
+    dir: PathBuf,
+    /// File name without extension, e.g. "Radio X 2026-10-17 1800".
+    stem: String,
+    file: Option<PathBuf>,
+    split_songs: bool,
+    /// The song the current file is for, with `split_songs`.
+    title: Option<String>,
+}
+
+impl Recorder {
+    /// Starts recording `url` into `dir`, naming files after `stem` (and the
+    /// song, with `split_songs`).
+    pub async fn start(socket_path: PathBuf, url: String, dir: PathBuf, stem: &str, split_songs: bool) -> Result<Self> {
+        std::fs::create_dir_all(&dir).with_context(|| format!("Create recordings folder {dir:?}"))?;
+        let options = MpvOptions {
+            silent: true,
+            ..MpvOptions::default()
+        };
+        let (mpv, events) = MpvProcess::spawn(socket_path, options).await?;
+        Ok(Self {
+            mpv,
+            events,
+            url,
+            dir,
+            stem: file_stem(stem),
+            file: None,
+            split_songs,
+            title: None,
+        })
+    }
+
+    /// The file being written, once the stream has started.
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// mpv only logs a file it can't create, so the controller checks on the
+    /// file now and then.
+    pub fn missing_file(&self) -> Option<&Path> {
+        self.file.as_deref().filter(|p| !p.exists())
+    }
+
+    /// Waits for the next thing worth reporting.
+    pub async fn next_event(&mut self) -> RecorderEvent {
+        loop {
+            let Some(ev) = self.events.recv().await else {
+                return RecorderEvent::Failed("mpv stopped".to_string());
+            };
+            match ev {
+                MpvEvent::Ready => {
+                    // Also after a crash: mpv came back without the property
+                    // set, so the recording goes on in a new file.
+                    self.file = None;
+                    let _ = self.mpv.command(MpvCommand::LoadUrl { url: self.url.clone(), start: None });
+                }
+                MpvEvent::StreamTech(tech) if self.file.is_none() => {
+                    let Some(codec) = tech.codec else {
+                        continue;
+                    };
+                    let path = self.next_path(extension(&codec));
+                    debug!(?path, %codec, "recording started");
+                    let _ = self.mpv.command(MpvCommand::SetStreamRecord(Some(path.clone())));
+                    self.file = Some(path.clone());
+                    return RecorderEvent::Started(path);
+                }
+                MpvEvent::MediaTitle(title) if self.split_songs && title != self.title => {
+                    let song = std::mem::replace(&mut self.title, title);
+                    let Some(old) = self.file.clone() else {
+                        continue;
+                    };
+                    let ext = old.extension().and_then(|e| e.to_str()).unwrap_or("mka").to_string();
+                    let path = self.next_path(&ext);
+                    debug!(?path, "next song");
+                    // Switching the property closes the old file first.
+                    let _ = self.mpv.command(MpvCommand::SetStreamRecord(Some(path.clone())));
+                    self.file = Some(path.clone());
+                    if self.closed().await {
+                        tag_in_background(old, song);
+                    }
+                    return RecorderEvent::Started(path);
+                }
+                MpvEvent::PlaybackFailed(e) => return RecorderEvent::Failed(e),
+                MpvEvent::StreamEnded => return RecorderEvent::Failed("The stream ended".to_string()),
+                MpvEvent::GaveUp(e) => return RecorderEvent::Failed(e),
+                MpvEvent::Missing => return RecorderEvent::Failed("mpv is not installed".to_string()),
+                MpvEvent::CommandError { command, error } if command == "set_property stream-record" => {
+                    return RecorderEvent::Failed(format!("Could not write the recording: {error}"));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Closes the file and stops mpv; returns the file written, if any.
+    pub async fn finish(self) -> Option<PathBuf> {
+        if let Some(file) = &self.file {
+            let _ = self.mpv.command(MpvCommand::SetStreamRecord(None));
+            if self.closed().await && self.split_songs {
+                if let Some(tags) = self.title.as_deref().map(SongTags::parse) {
+                    let file = file.clone();
+                    match tokio::task::spawn_blocking(move || tags::write(&file, &tags)).await {
+                        Ok(Err(e)) => warn!(error = ?e, "could not tag the recording"),
+                        Err(e) => warn!(error = %e, "tagging the recording panicked"),
+                        Ok(Ok(())) => {}
+                    }
+                }
+            }
+        }
+        let _ = self.mpv.command(MpvCommand::Shutdown);
+        self.file
+    }
+
+    /// Waits until mpv has handled the commands sent so far; it handles them
+    /// in order, so a file it was told to close is closed then.
+    async fn closed(&self) -> bool {
+        let answer = tokio::time::timeout(CLOSE_TIMEOUT, self.mpv.path())
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("mpv didn't answer in time")));
+        if let Err(e) = &answer {
+            warn!(error = ?e, "recording may not have been closed cleanly");
+        }
+        answer.is_ok()
+    }
+
+    /// A free path for the next file, named after the song when splitting.
+    fn next_path(&self, ext: &str) -> PathBuf {
+        let stem = match self.title.as_deref().filter(|_| self.split_songs) {
+            Some(title) => file_stem(&format!("{} - {title}", self.stem)),
+            None => self.stem.clone(),
+        };
+        unique_path(&self.dir, &stem, ext)
+    }
+}
+
+/// Tags a song that has just been closed, off the event loop.
+fn tag_in_background(path: PathBuf, title: Option<String>) {
+    let Some(tags) = title.as_deref().map(SongTags::parse) else {
+        return;
+    };
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = tags::write(&path, &tags) {
+            warn!(error = ?e, "could not tag the recording");
+        }
+    });
+}
+
+/// A finished or running recording on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingFile {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Unix seconds of the last write.
+    pub modified: u64,
+}
+
+impl RecordingFile {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Recordings in `dir`, newest first; an absent folder has none.
+pub fn list(dir: &Path) -> Result<Vec<RecordingFile>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Read recordings folder {dir:?}")),
+    };
+    let mut files: Vec<RecordingFile> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let path = entry.path();
+            let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+            if !EXTENSIONS.contains(&ext.as_str()) {
+                return None;
+            }
+            let meta = entry.metadata().ok().filter(|m| m.is_file())?;
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            Some(RecordingFile { path, bytes: meta.len(), modified })
+        })
+        .collect();
+    files.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.path.cmp(&b.path)));
+    Ok(files)
+}
+
+/// Deletes a recording, refusing anything outside `dir`.
+pub fn delete(dir: &Path, path: &Path) -> Result<()> {
+    if path.parent() != Some(dir) {
+        anyhow::bail!("{path:?} is not in the recordings folder");
+    }
+    std::fs::remove_file(path).with_context(|| format!("Delete {path:?}"))
+}
+
+/// Container for the raw stream of a codec as mpv names it.
+fn extension(codec: &str) -> &'static str {
+    match codec {
+        "mp3" => "mp3",
+        "aac" | "aac_latm" => "aac",
+        "vorbis" => "ogg",
+        "opus" => "opus",
+        "flac" => "flac",
+        _ => "mka",
+    }
+}
+
+/// `name` with characters file systems or file managers choke on replaced.
+fn file_stem(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c == '/' || c == '\\' || c == ':' || c.is_control() { '_' } else { c })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.');
+    if cleaned.is_empty() {
+        "Recording".to_string()
+    } else {
+        cleaned.chars().take(120).collect()
+    }
+}
+
+/// `dir/stem.ext`, or `dir/stem (2).ext` and so on if that's taken.
+fn unique_path(dir: &Path, stem: &str, ext: &str) -> PathBuf {
+    let mut path = dir.join(format!("{stem}.{ext}"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem} ({n}).{ext}"));
+        n += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_files_safely_and_uniquely() {
+        assert_eq!(file_stem("AC/DC: Live"), "AC_DC_ Live");
+        assert_eq!(file_stem(" ../ "), "_");
+        assert_eq!(file_stem(""), "Recording");
+
+        let dir = std::env::temp_dir().join(format!("radiowidget-recording-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = unique_path(&dir, "Show", extension("vorbis"));
+        assert_eq!(first, dir.join("Show.ogg"));
+        std::fs::write(&first, b"x").unwrap();
+        assert_eq!(unique_path(&dir, "Show", "ogg"), dir.join("Show (2).ogg"));
+
+        std::fs::write(dir.join("notes.txt"), b"x").unwrap();
+        let listed = list(&dir).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name(), "Show.ogg");
+        assert!(delete(&dir, Path::new("/etc/passwd")).is_err());
+        delete(&dir, &first).unwrap();
+        assert!(list(&dir).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
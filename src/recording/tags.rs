@@ -0,0 +1,428 @@
+//! Artist and title tags for recorded songs.
+//!
+//! mpv writes the stream without the song's metadata, so each file gets its
+//! tags afterwards: an ID3v2 tag for MP3 and AAC, a Vorbis comment block
+//! for FLAC, and a new comment header for Ogg Vorbis and Opus. Matroska is
+//! left alone.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+
+/// Stream titles are usually "Artist - Title".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongTags {
+    pub artist: Option<String>,
+    pub title: String,
+}
+
+impl SongTags {
+    pub fn parse(stream_title: &str) -> Self {
+        match stream_title.split_once(" - ") {
+            Some((artist, title)) if !artist.trim().is_empty() && !title.trim().is_empty() => Self {
+                artist: Some(artist.trim().to_string()),
+                title: title.trim().to_string(),
+            },
+            _ => Self { artist: None, title: stream_title.trim().to_string() },
+        }
+    }
+
+    fn comments(&self) -> Vec<(&'static str, &str)> {
+        let mut comments: Vec<(&'static str, &str)> = self.artist.iter().map(|a| ("ARTIST", a.as_str())).collect();
+        comments.push(("TITLE", &self.title));
+        comments
+    }
+}
+
+/// Tags the finished file at `path` according to its extension.
+pub fn write(path: &Path, tags: &SongTags) -> Result<()> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    let retag: fn(&[u8], &SongTags) -> Result<Vec<u8>> = match ext {
+        "mp3" | "aac" => with_id3,
+        "flac" => with_flac_comments,
+        "ogg" | "opus" => with_ogg_comments,
+        _ => return Ok(()),
+    };
+    let data = std::fs::read(path).with_context(|| format!("Read {path:?}"))?;
+    let tagged = retag(&data, tags).with_context(|| format!("Tag {path:?}"))?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("recording");
+    let tmp = path.with_file_name(format!(".{name}.tagging"));
+    std::fs::write(&tmp, tagged).with_context(|| format!("Write {tmp:?}"))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Replace {path:?}"))
+}
+
+/// `data` with its ID3v2 tag, if any, replaced by one carrying `tags`.
+fn with_id3(data: &[u8], tags: &SongTags) -> Result<Vec<u8>> {
+    let audio = match data {
+        [b'I', b'D', b'3', _, _, flags, size @ ..] if size.len() >= 4 => {
+            let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+            let len = 10 + syncsafe_decode(&size[..4]) as usize + footer;
+            data.get(len..).ok_or_else(|| anyhow!("ID3 tag runs past the end of the file"))?
+        }
+        _ => data,
+    };
+    let mut frames = Vec::new();
+    for (id, text) in [("TPE1", tags.artist.as_deref()), ("TIT2", Some(tags.title.as_str()))] {
+        let Some(text) = text else {
+            continue;
+        };
+        // Encoding 3 is UTF-8.
+        let body: Vec<u8> = std::iter::once(3).chain(text.bytes()).collect();
+        frames.extend_from_slice(id.as_bytes());
+        frames.extend_from_slice(&syncsafe_encode(body.len())?);
+        frames.extend_from_slice(&[0, 0]);
+        frames.extend_from_slice(&body);
+    }
+    let mut out = Vec::with_capacity(10 + frames.len() + audio.len());
+    out.extend_from_slice(b"ID3\x04\x00\x00");
+    out.extend_from_slice(&syncsafe_encode(frames.len())?);
+    out.extend_from_slice(&frames);
+    out.extend_from_slice(audio);
+    Ok(out)
+}
+
+fn syncsafe_decode(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |n, b| n << 7 | u32::from(b & 0x7f))
+}
+
+fn syncsafe_encode(n: usize) -> Result<[u8; 4]> {
+    if n >= 1 << 28 {
+        bail!("ID3 frame too large");
+    }
+    Ok([(n >> 21) as u8 & 0x7f, (n >> 14) as u8 & 0x7f, (n >> 7) as u8 & 0x7f, n as u8 & 0x7f])
+}
+
+const FLAC_STREAMINFO: u8 = 0;
+const FLAC_VORBIS_COMMENT: u8 = 4;
+
+/// `data` with its Vorbis comment block replaced by, or extended with, `tags`.
+fn with_flac_comments(data: &[u8], tags: &SongTags) -> Result<Vec<u8>> {
+    let mut rest = data.strip_prefix(b"fLaC").ok_or_else(|| anyhow!("Not a FLAC file"))?;
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    loop {
+        let [header, l1, l2, l3, tail @ ..] = rest else {
+            bail!("FLAC metadata is cut off");
+        };
+        let len = usize::from(*l1) << 16 | usize::from(*l2) << 8 | usize::from(*l3);
+        let body = tail.get(..len).ok_or_else(|| anyhow!("FLAC metadata is cut off"))?;
+        blocks.push((header & 0x7f, body.to_vec()));
+        rest = &tail[len..];
+        if header & 0x80 != 0 {
+            break;
+        }
+    }
+    let existing = blocks.iter().position(|(kind, _)| *kind == FLAC_VORBIS_COMMENT);
+    let old = existing.map(|i| VorbisComments::parse(&blocks[i].1)).transpose()?;
+    let comments = old.unwrap_or_default().with(tags).encode();
+    match existing {
+        Some(i) => blocks[i].1 = comments,
+        None => {
+            let after_info = blocks.iter().position(|(kind, _)| *kind == FLAC_STREAMINFO).map_or(0, |i| i + 1);
+            blocks.insert(after_info, (FLAC_VORBIS_COMMENT, comments));
+        }
+    }
+
+    let mut out = b"fLaC".to_vec();
+    let last = blocks.len() - 1;
+    for (i, (kind, body)) in blocks.iter().enumerate() {
+        if body.len() >= 1 << 24 {
+            bail!("FLAC metadata block too large");
+        }
+        out.push(kind | if i == last { 0x80 } else { 0 });
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+    }
+    out.extend_from_slice(rest);
+    Ok(out)
+}
+
+/// A Vorbis comment list as used by FLAC, Ogg Vorbis and Opus, without the
+/// codec's own packet prefix.
+#[derive(Debug, Default)]
+struct VorbisComments {
+    vendor: Vec<u8>,
+    comments: Vec<Vec<u8>>,
+    /// Whatever follows the list (Vorbis' framing bit, Opus padding).
+    trailer: Vec<u8>,
+}
+
+impl VorbisComments {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let mut at = 0;
+        let vendor = length_prefixed(data, &mut at)?.to_vec();
+        let count = le_u32(data, at)?;
+        at += 4;
+        let comments = (0..count)
+            .map(|_| length_prefixed(data, &mut at).map(<[u8]>::to_vec))
+            .collect::<Result<_>>()?;
+        Ok(Self { vendor, comments, trailer: data[at..].to_vec() })
+    }
+
+    /// Replaces any artist and title with `tags`.
+    fn with(mut self, tags: &SongTags) -> Self {
+        self.comments.retain(|c| {
+            let key = c.split(|b| *b == b'=').next().unwrap_or_default();
+            !["ARTIST", "TITLE"].iter().any(|k| key.eq_ignore_ascii_case(k.as_bytes()))
+        });
+        self.comments.extend(tags.comments().iter().map(|(k, v)| format!("{k}={v}").into_bytes()));
+        self
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.vendor);
+        out.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+        for comment in &self.comments {
+            out.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            out.extend_from_slice(comment);
+        }
+        out.extend_from_slice(&self.trailer);
+        out
+    }
+}
+
+fn le_u32(data: &[u8], at: usize) -> Result<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("Comment header is cut off"))
+}
+
+/// A field with a little-endian 32-bit length in front, moving `at` past it.
+fn length_prefixed<'a>(data: &'a [u8], at: &mut usize) -> Result<&'a [u8]> {
+    let len = le_u32(data, *at)? as usize;
+    let value = data
+        .get(*at + 4..*at + 4 + len)
+        .ok_or_else(|| anyhow!("Comment header is cut off"))?;
+    *at += 4 + len;
+    Ok(value)
+}
+
+/// One Ogg page, borrowed from the file.
+struct OggPage<'a> {
+    header_type: u8,
+    granule: u64,
+    serial: u32,
+    sequence: u32,
+    lacing: &'a [u8],
+    body: &'a [u8],
+}
+
+impl<'a> OggPage<'a> {
+    fn parse(data: &'a [u8]) -> Result<(Self, &'a [u8])> {
+        if data.len() < 27 || &data[..4] != b"OggS" {
+            bail!("Not an Ogg page");
+        }
+        let segments = usize::from(data[26]);
+        let lacing = data.get(27..27 + segments).ok_or_else(|| anyhow!("Ogg page is cut off"))?;
+        let len: usize = lacing.iter().map(|l| usize::from(*l)).sum();
+        let start = 27 + segments;
+        let body = data.get(start..start + len).ok_or_else(|| anyhow!("Ogg page is cut off"))?;
+        let page = Self {
+            header_type: data[5],
+            granule: u64::from_le_bytes(data[6..14].try_into()?),
+            serial: u32::from_le_bytes(data[14..18].try_into()?),
+            sequence: u32::from_le_bytes(data[18..22].try_into()?),
+            lacing,
+            body,
+        };
+        Ok((page, &data[start + len..]))
+    }
+
+    /// Whether the last packet on the page continues on the next one.
+    fn ends_mid_packet(&self) -> bool {
+        self.lacing.last() == Some(&255)
+    }
+
+    fn write(&self, sequence: u32, out: &mut Vec<u8>) {
+        write_ogg_page(out, self.header_type, self.granule, self.serial, sequence, self.lacing, self.body);
+    }
+}
+
+fn write_ogg_page(out: &mut Vec<u8>, header_type: u8, granule: u64, serial: u32, sequence: u32, lacing: &[u8], body: &[u8]) {
+    let start = out.len();
+    out.extend_from_slice(b"OggS\x00");
+    out.push(header_type);
+    out.extend_from_slice(&granule.to_le_bytes());
+    out.extend_from_slice(&serial.to_le_bytes());
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.push(lacing.len() as u8);
+    out.extend_from_slice(lacing);
+    out.extend_from_slice(body);
+    let crc = ogg_crc(&out[start..]);
+    out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// CRC-32 with polynomial 0x04c11db7, no reflection and zero start, as Ogg
+/// uses it.
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0u32, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte) << 24, |crc, _| {
+            if crc & 0x8000_0000 != 0 {
+                crc << 1 ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+/// `data` with the comment header of its first stream rewritten.
+///
+/// The header packets after the first are paged anew, and later pages of
+/// the same stream renumbered to follow them.
+fn with_ogg_comments(data: &[u8], tags: &SongTags) -> Result<Vec<u8>> {
+    let mut pages = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (page, tail) = OggPage::parse(rest)?;
+        pages.push(page);
+        rest = tail;
+    }
+    let first = pages.first().ok_or_else(|| anyhow!("Empty Ogg file"))?;
+    let serial = first.serial;
+    let (prefix, header_packets) = match first.body {
+        b if b.starts_with(b"\x01vorbis") => (&b"\x03vorbis"[..], 3),
+        b if b.starts_with(b"OpusHead") => (&b"OpusTags"[..], 2),
+        _ => bail!("Not Ogg Vorbis or Opus"),
+    };
+
+    // Header packets after the identification one; they end a page.
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut packet = Vec::new();
+    let mut header_pages = 1;
+    for page in pages.iter().skip(1).filter(|p| p.serial == serial) {
+        header_pages += 1;
+        let mut at = 0;
+        for len in page.lacing {
+            packet.extend_from_slice(&page.body[at..at + usize::from(*len)]);
+            at += usize::from(*len);
+            if *len < 255 {
+                packets.push(std::mem::take(&mut packet));
+            }
+        }
+        if packets.len() >= header_packets - 1 && !page.ends_mid_packet() {
+            break;
+        }
+    }
+    if packets.len() != header_packets - 1 {
+        bail!("Ogg headers are incomplete");
+    }
+    let comments = packets[0]
+        .strip_prefix(prefix)
+        .ok_or_else(|| anyhow!("Missing comment header"))?;
+    packets[0] = [prefix, &VorbisComments::parse(comments)?.with(tags).encode()].concat();
+
+    let mut out = Vec::with_capacity(data.len() + 256);
+    first.write(first.sequence, &mut out);
+    let mut sequence = first.sequence + 1;
+    for packet in &packets {
+        let mut lacing = vec![255u8; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        let mut body = &packet[..];
+        let mut continued = false;
+        for chunk in lacing.chunks(255) {
+            let len: usize = chunk.iter().map(|l| usize::from(*l)).sum();
+            let (page_body, tail) = body.split_at(len);
+            write_ogg_page(&mut out, u8::from(continued), 0, serial, sequence, chunk, page_body);
+            sequence += 1;
+            body = tail;
+            continued = true;
+        }
+    }
+    let mut seen = 0;
+    for page in pages.iter().skip(1) {
+        if page.serial != serial {
+            page.write(page.sequence, &mut out);
+            continue;
+        }
+        seen += 1;
+        if seen >= header_pages {
+            page.write(sequence, &mut out);
+            sequence += 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> SongTags {
+        SongTags::parse("Nina Simone - Feeling Good")
+    }
+
+    #[test]
+    fn splits_artist_from_title() {
+        assert_eq!(tags().artist.as_deref(), Some("Nina Simone"));
+        assert_eq!(tags().title, "Feeling Good");
+        assert_eq!(SongTags::parse("Jingle").artist, None);
+        assert_eq!(SongTags::parse(" - Intro").title, "- Intro");
+    }
+
+    #[test]
+    fn replaces_id3_tags() {
+        let audio = b"\xff\xfb\x90\x00frames";
+        let tagged = with_id3(audio, &tags()).unwrap();
+        assert!(tagged.starts_with(b"ID3\x04"));
+        assert!(tagged.ends_with(audio));
+        let size = syncsafe_decode(&tagged[6..10]) as usize;
+        let frames = &tagged[10..10 + size];
+        assert!(frames.windows(4).any(|w| w == b"TPE1"));
+        assert!(frames.ends_with(b"TIT2\x00\x00\x00\x0d\x00\x00\x03Feeling Good"));
+
+        let again = with_id3(&tagged, &SongTags::parse("Other")).unwrap();
+        assert!(again.ends_with(audio));
+        assert!(!again.windows(4).any(|w| w == b"TPE1"));
+    }
+
+    #[test]
+    fn adds_flac_comments() {
+        let mut flac = b"fLaC\x80\x00\x00\x02\xaa\xbb".to_vec();
+        flac.extend_from_slice(b"audio");
+        let tagged = with_flac_comments(&flac, &tags()).unwrap();
+        // STREAMINFO no longer last, then the comments as the last block.
+        assert_eq!(&tagged[4..10], b"\x00\x00\x00\x02\xaa\xbb");
+        assert_eq!(tagged[10], 0x80 | FLAC_VORBIS_COMMENT);
+        assert!(tagged.ends_with(b"TITLE=Feeling Goodaudio"));
+
+        let retagged = with_flac_comments(&tagged, &SongTags::parse("Next")).unwrap();
+        let text = String::from_utf8_lossy(&retagged);
+        assert!(text.contains("TITLE=Next") && !text.contains("Feeling") && !text.contains("ARTIST"));
+    }
+
+    #[test]
+    fn checksums_like_ogg() {
+        assert_eq!(ogg_crc(b"123456789"), 0x89a1_897f);
+    }
+
+    #[test]
+    fn rewrites_opus_comments() {
+        let mut ogg = Vec::new();
+        write_ogg_page(&mut ogg, 2, 0, 7, 0, &[19], b"OpusHead\x01\x02\x38\x01\x80\xbb\x00\x00\x00\x00\x00");
+        let old_tags = [&b"OpusTags"[..], &VorbisComments { vendor: b"mpv".to_vec(), ..Default::default() }.encode()].concat();
+        write_ogg_page(&mut ogg, 0, 0, 7, 1, &[old_tags.len() as u8], &old_tags);
+        write_ogg_page(&mut ogg, 0, 960, 7, 2, &[3], b"abc");
+
+        let tagged = with_ogg_comments(&ogg, &tags()).unwrap();
+        let mut rest = &tagged[..];
+        let mut pages = Vec::new();
+        while !rest.is_empty() {
+            let start = rest;
+            let (page, tail) = OggPage::parse(rest).unwrap();
+            let len = start.len() - tail.len();
+            let mut zeroed = start[..len].to_vec();
+            zeroed[22..26].fill(0);
+            assert_eq!(ogg_crc(&zeroed).to_le_bytes(), start[22..26]);
+            pages.push((page.sequence, page.granule, page.body.to_vec()));
+            rest = tail;
+        }
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages.iter().map(|p| p.0).collect::<Vec<_>>(), [0, 1, 2]);
+        let comments = VorbisComments::parse(pages[1].2.strip_prefix(b"OpusTags").unwrap()).unwrap();
+        assert_eq!(comments.vendor, b"mpv");
+        assert_eq!(comments.comments, [b"ARTIST=Nina Simone".to_vec(), b"TITLE=Feeling Good".to_vec()]);
+        assert_eq!((pages[2].1, &pages[2].2[..]), (960, &b"abc"[..]));
+    }
+}
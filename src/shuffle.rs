@@ -0,0 +1,136 @@
+//! Random station picks that avoid recently played stations.
+
+use crate::models::StationRef;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// When each station last started playing this session.
+#[derive(Debug, Clone, Default)]
+pub struct RecentPlays {
+    played_at: HashMap<String, u64>,
+    /// Per favorites group, the UUIDs still to come this round, next last.
+    decks: HashMap<String, Vec<String>>,
+}
+
+impl RecentPlays {
+    pub fn record(&mut self, stationuuid: &str, now: u64) {
+        self.played_at.insert(stationuuid.to_string(), now);
+    }
+
+    /// Picks a random station from `pool` that hasn't played within `avoid`.
+    /// If everything has played recently, the one heard longest ago wins.
+    pub fn pick<'a, R: Rng>(
+        &self,
+        pool: &'a [StationRef],
+        avoid: Duration,
+        now: u64,
+        rng: &mut R,
+    ) -> Option<&'a StationRef> {
+        let fresh: Vec<&StationRef> = pool
+            .iter()
+            .filter(|s| match self.played_at.get(&s.stationuuid) {
+                Some(t) => now.saturating_sub(*t) >= avoid.as_secs(),
+                None => true,
+            })
+            .collect();
+        if let Some(s) = fresh.choose(rng) {
+            return Some(s);
+        }
+        pool.iter()
+            .min_by_key(|s| self.played_at.get(&s.stationuuid).copied().unwrap_or(0))
+    }
+
+    /// The next station of a group's shuffled round: every member plays once
+    /// before any repeats, and a new round starts with the ones heard longest
+    /// ago.
+    pub fn next_in_group<'a, R: Rng>(
+        &mut self,
+        group: &str,
+        members: &'a [StationRef],
+        rng: &mut R,
+    ) -> Option<&'a StationRef> {
+        let deck = self.decks.entry(group.to_string()).or_default();
+        deck.retain(|uuid| members.iter().any(|m| m.stationuuid == *uuid));
+        if deck.is_empty() {
+            deck.extend(members.iter().map(|m| m.stationuuid.clone()));
+            deck.shuffle(rng);
+            deck.sort_by_key(|uuid| Reverse(self.played_at.get(uuid).copied().unwrap_or(0)));
+        }
+        let uuid = deck.pop()?;
+        members.iter().find(|m| m.stationuuid == uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn station(uuid: &str) -> StationRef {
+        StationRef {
+            stationuuid: uuid.to_string(),
+            name: uuid.to_uppercase(),
+            url: None,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn skips_recently_played_stations() {
+        let pool = [station("a"), station("b"), station("c")];
+        let mut recent = RecentPlays::default();
+        recent.record("a", 1_000);
+        recent.record("b", 1_000);
+        let avoid = Duration::from_secs(3600);
+        let mut rng = StdRng::seed_from_u64(7);
+
+        for _ in 0..20 {
+            let pick = recent.pick(&pool, avoid, 2_000, &mut rng).unwrap();
+            assert_eq!(pick.stationuuid, "c");
+        }
+        // Once the window has passed everything is eligible again.
+        let picks: Vec<_> = (0..50)
+            .map(|_| recent.pick(&pool, avoid, 10_000, &mut rng).unwrap().stationuuid.clone())
+            .collect();
+        assert!(picks.iter().any(|p| p == "a"));
+    }
+
+    #[test]
+    fn falls_back_to_least_recent_when_all_played() {
+        let pool = [station("a"), station("b")];
+        let mut recent = RecentPlays::default();
+        recent.record("a", 500);
+        recent.record("b", 100);
+        let mut rng = StdRng::seed_from_u64(1);
+        let pick = recent.pick(&pool, Duration::from_secs(3600), 600, &mut rng);
+        assert_eq!(pick.unwrap().stationuuid, "b");
+        assert!(recent.pick(&[], Duration::ZERO, 600, &mut rng).is_none());
+    }
+
+    #[test]
+    fn cycles_through_a_group() {
+        let group = [station("a"), station("b"), station("c")];
+        let mut recent = RecentPlays::default();
+        recent.record("b", 100);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let mut round: Vec<String> = Vec::new();
+        for t in 0..3 {
+            let pick = recent.next_in_group("Jazz", &group, &mut rng).unwrap().stationuuid.clone();
+            recent.record(&pick, 200 + t);
+            round.push(pick);
+        }
+        // The one heard before the round comes last.
+        assert_eq!(round[2], "b");
+        // The next round opens with the first one of this round.
+        let next = recent.next_in_group("Jazz", &group, &mut rng).unwrap();
+        assert_eq!(next.stationuuid, round[0]);
+        round.sort();
+        assert_eq!(round, ["a", "b", "c"]);
+        assert!(recent.next_in_group("Empty", &[], &mut rng).is_none());
+    }
+}
@@ -0,0 +1,271 @@
+//! End-to-end smoke test: plays a local Icecast-style stream through the real
+//! controller and mpv, without touching the network or the user's files.
+//!
+//! The stream is a generated tone (WAV, looped) with ICY metadata, so no media
+//! files or encoders are needed. Run with `radiowidget --smoke`.
+
+use crate::controller::{start_controller, ControllerState, PlaybackPhase, UiCommand};
+use crate::models::StationRef;
+use crate::soak::TempDir;
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tracing::info;
+
+const STATION_NAME: &str = "RadioWidget Smoke Test";
+const TITLE: &str = "Smoke Test Tone";
+const SAMPLE_RATE: u32 = 22_050;
+/// Audio bytes between ICY metadata blocks.
+const METAINT: usize = 8192;
+/// Paces the stream in real time, like a live server.
+const CHUNK_INTERVAL: Duration = Duration::from_millis(100);
+const STEP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Points config, cache, state and runtime directories at a fresh temp dir,
+/// which goes away with the returned guard. Must run before anything reads
+/// those paths (logging, profiles).
+pub fn isolate() -> Result<TempDir> {
+    let dir = TempDir::create("smoke")?;
+    for var in ["XDG_CONFIG_HOME", "XDG_CACHE_HOME", "XDG_STATE_HOME", "XDG_RUNTIME_DIR"] {
+        let path = dir.path().join(var.to_ascii_lowercase());
+        std::fs::create_dir_all(&path).with_context(|| format!("Create smoke dir: {path:?}"))?;
+        // Still single-threaded: nothing else reads the environment yet.
+        std::env::set_var(var, &path);
+    }
+    std::env::remove_var("RADIOWIDGET_PROFILE");
+    Ok(dir)
+}
+
+/// Runs the test in the dir from [`isolate`], removing it afterwards.
+pub async fn run(_dir: TempDir) -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.context("Bind smoke server")?;
+    let url = format!("http://{}/stream", listener.local_addr()?);
+    tokio::spawn(serve(listener));
+
+    let controller = start_controller();
+    let mut states = controller.state_rx.clone();
+    wait_for(&mut states, "controller start", |s| {
+        s.phase == PlaybackPhase::NotConfigured
+    })
+    .await?;
+
+    let station = StationRef {
+        stationuuid: format!("custom:{url}"),
+        name: STATION_NAME.to_string(),
+        url: Some(url.clone()),
+        group: None,
+    };
+    controller.cmd_tx.send(UiCommand::Play(station))?;
+    wait_for(&mut states, "playback", |s| s.phase == PlaybackPhase::Playing).await?;
+    wait_for(&mut states, "ICY title", |s| s.media_title.as_deref() == Some(TITLE)).await?;
+    wait_for(&mut states, "ICY station name", |s| {
+        s.stream_tags.as_ref().and_then(|t| t.name.as_deref()) == Some(STATION_NAME)
+    })
+    .await?;
+
+    controller.cmd_tx.send(UiCommand::TogglePause)?;
+    wait_for(&mut states, "pause", |s| s.phase == PlaybackPhase::Paused).await?;
+    controller.cmd_tx.send(UiCommand::TogglePause)?;
+    wait_for(&mut states, "resume", |s| s.phase == PlaybackPhase::Playing).await?;
+
+    controller.cmd_tx.send(UiCommand::Stop)?;
+    wait_for(&mut states, "stop", |s| {
+        s.phase == PlaybackPhase::NotConfigured && s.station.is_none()
+    })
+    .await?;
+    info!("smoke test passed");
+    Ok(())
+}
+
+/// Waits until `done` holds, failing early if playback errors out.
+async fn wait_for(
+    states: &mut watch::Receiver<ControllerState>,
+    step: &str,
+    done: impl Fn(&ControllerState) -> bool,
+) -> Result<()> {
+    let result = tokio::time::timeout(STEP_TIMEOUT, async {
+        loop {
+            {
+                let state = states.borrow_and_update();
+                if done(&state) {
+                    return Ok(());
+                }
+                if state.phase == PlaybackPhase::MissingBackend {
+                    bail!("mpv is not installed");
+                }
+                if state.phase == PlaybackPhase::Error {
+                    let errors: Vec<&str> = state.errors.active().iter().map(|e| e.message.as_str()).collect();
+                    bail!("playback error: {}", errors.join("; "));
+                }
+            }
+            states.changed().await.context("controller stopped")?;
+        }
+    })
+    .await;
+    match result {
+        Ok(res) => res.with_context(|| format!("waiting for {step}"))?,
+        Err(_) => {
+            let state = states.borrow();
+            bail!(
+                "timed out waiting for {step} (phase {:?}, title {:?})",
+                state.phase,
+                state.media_title
+            );
+        }
+    }
+    info!(step, "smoke step ok");
+    Ok(())
+}
+
+async fn serve(listener: TcpListener) {
+    while let Ok((socket, _)) = listener.accept().await {
+        // Both mpv and the stream probe connect; each gets its own loop.
+        tokio::spawn(async move {
+            let _ = stream_to(socket).await;
+        });
+    }
+}
+
+async fn stream_to(mut socket: TcpStream) -> Result<()> {
+    let request = read_request(&mut socket).await?;
+    let icy = request
+        .lines()
+        .any(|l| l.to_ascii_lowercase().replace(' ', "") == "icy-metadata:1");
+    let mut head = format!(
+        "HTTP/1.0 200 OK\r\nContent-Type: audio/wav\r\nicy-name: {STATION_NAME}\r\nicy-genre: Test\r\n"
+    );
+    if icy {
+        head.push_str(&format!("icy-metaint: {METAINT}\r\n"));
+    }
+    head.push_str("\r\n");
+    socket.write_all(head.as_bytes()).await?;
+
+    let tone = tone_second();
+    let mut icy = icy.then(|| IcyWriter::new(icy_block(TITLE)));
+    let mut out = Vec::new();
+    send(&mut socket, icy.as_mut(), &wav_header(), &mut out).await?;
+    let chunk = tone.len() / 10;
+    let mut ticker = tokio::time::interval(CHUNK_INTERVAL);
+    for part in tone.chunks(chunk).cycle() {
+        ticker.tick().await;
+        send(&mut socket, icy.as_mut(), part, &mut out).await?;
+    }
+    Ok(())
+}
+
+async fn send(socket: &mut TcpStream, icy: Option<&mut IcyWriter>, audio: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    out.clear();
+    match icy {
+        Some(icy) => icy.write(audio, out),
+        None => out.extend_from_slice(audio),
+    }
+    socket.write_all(out).await?;
+    Ok(())
+}
+
+async fn read_request(socket: &mut TcpStream) -> Result<String> {
+    let mut buf = Vec::new();
+    let mut byte = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut byte).await?;
+        if n == 0 || buf.len() > 16 * 1024 {
+            bail!("bad request");
+        }
+        buf.extend_from_slice(&byte[..n]);
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Mono 16-bit WAV header with an open-ended length, as streaming servers send it.
+fn wav_header() -> Vec<u8> {
+    let mut h = Vec::with_capacity(44);
+    h.extend_from_slice(b"RIFF");
+    h.extend_from_slice(&u32::MAX.to_le_bytes());
+    h.extend_from_slice(b"WAVEfmt ");
+    h.extend_from_slice(&16u32.to_le_bytes());
+    h.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    h.extend_from_slice(&1u16.to_le_bytes()); // mono
+    h.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    h.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    h.extend_from_slice(&2u16.to_le_bytes());
+    h.extend_from_slice(&16u16.to_le_bytes());
+    h.extend_from_slice(b"data");
+    h.extend_from_slice(&u32::MAX.to_le_bytes());
+    h
+}
+
+/// One second of a quiet 441 Hz tone, which loops without a click.
+fn tone_second() -> Vec<u8> {
+    (0..SAMPLE_RATE)
+        .flat_map(|i| {
+            let phase = i as f32 * 441.0 / SAMPLE_RATE as f32 * std::f32::consts::TAU;
+            ((phase.sin() * 4000.0) as i16).to_le_bytes()
+        })
+        .collect()
+}
+
+/// `StreamTitle` block: a length byte (in 16-byte units) and zero-padded text.
+fn icy_block(title: &str) -> Vec<u8> {
+    let text = format!("StreamTitle='{title}';");
+    let units = text.len().div_ceil(16);
+    let mut block = vec![units as u8];
+    block.extend_from_slice(text.as_bytes());
+    block.resize(1 + units * 16, 0);
+    block
+}
+
+/// Interleaves a metadata block after every [`METAINT`] audio bytes.
+struct IcyWriter {
+    block: Vec<u8>,
+    until_meta: usize,
+}
+
+impl IcyWriter {
+    fn new(block: Vec<u8>) -> Self {
+        Self { block, until_meta: METAINT }
+    }
+
+    fn write(&mut self, mut audio: &[u8], out: &mut Vec<u8>) {
+        while !audio.is_empty() {
+            let n = audio.len().min(self.until_meta);
+            out.extend_from_slice(&audio[..n]);
+            audio = &audio[n..];
+            self.until_meta -= n;
+            if self.until_meta == 0 {
+                out.extend_from_slice(&self.block);
+                self.until_meta = METAINT;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaves_icy_metadata() {
+        let block = icy_block("Hi");
+        assert_eq!(block.len(), 33);
+        assert_eq!(block[0], 2);
+        assert!(block[1..].starts_with(b"StreamTitle='Hi';"));
+
+        let mut icy = IcyWriter::new(block.clone());
+        let mut out = Vec::new();
+        icy.write(&[7u8; METAINT - 10], &mut out);
+        icy.write(&[7u8; 20], &mut out);
+        assert_eq!(out.len(), METAINT + 10 + block.len());
+        assert_eq!(&out[METAINT..METAINT + block.len()], block.as_slice());
+        assert!(out[METAINT + block.len()..].iter().all(|b| *b == 7));
+    }
+
+    #[test]
+    fn tone_loops_in_whole_periods() {
+        let tone = tone_second();
+        assert_eq!(tone.len(), SAMPLE_RATE as usize * 2);
+        assert_eq!(&tone[..2], &[0, 0]);
+        assert_eq!(wav_header().len(), 44);
+    }
+}
@@ -0,0 +1,272 @@
+//! Long-running fault injection against the mpv supervisor.
+//!
+//! Plays a generated local tone, repeatedly kills or respawns mpv or breaks
+//! its IPC socket, and checks that the supervisor recovers without leaking
+//! processes or file descriptors.
+//! Run with `radiowidget --soak [seconds]`.
+
+use crate::mpv::{MpvCommand, MpvEvent, MpvOptions, MpvProcess};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::info;
+
+/// Generated by mpv itself, so no network or media files are needed.
+const TEST_STREAM: &str = "av://lavfi:sine=frequency=440";
+const RECOVERY_TIMEOUT: Duration = Duration::from_secs(15);
+const FAULT_INTERVAL: Duration = Duration::from_secs(5);
+/// Descriptors the runtime may legitimately open over time (timers, DNS, …).
+const FD_SLACK: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    /// SIGKILL: exercises the child-exit and IPC EOF paths.
+    Kill,
+    /// SIGTERM: mpv shuts down and closes the socket itself.
+    Terminate,
+    /// Supervisor-initiated restart with new options.
+    Respawn,
+    /// Our end of the IPC socket shut down while mpv keeps running:
+    /// exercises the read error path with a live child to clean up.
+    SocketError,
+}
+
+const FAULTS: [Fault; 4] = [Fault::Kill, Fault::Terminate, Fault::Respawn, Fault::SocketError];
+
+/// A temp dir removed again when the run ends, however it ends.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn create(name: &str) -> Result<Self> {
+        let dir = std::env::temp_dir().join(format!("radiowidget-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).with_context(|| format!("Create {name} dir: {dir:?}"))?;
+        Ok(Self(dir))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub async fn run(duration: Duration) -> Result<()> {
+    let dir = TempDir::create("soak")?;
+    let (mpv, mut events) = MpvProcess::spawn(dir.path().join("mpv.sock"), soak_options()).await?;
+
+    wait_ready(&mut events).await.context("initial mpv start")?;
+    start_playback(&mpv, &mut events).await?;
+    let fd_baseline = open_fds()?;
+    info!(fd_baseline, audio = ?mpv.audio_params().await?, "soak started");
+
+    let deadline = tokio::time::Instant::now() + duration;
+    let mut cycles = 0u64;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(FAULT_INTERVAL).await;
+        let fault = FAULTS[(cycles % FAULTS.len() as u64) as usize];
+        inject(&mpv, fault)?;
+
+        wait_ready(&mut events)
+            .await
+            .with_context(|| format!("recovery after {fault:?} (cycle {cycles})"))?;
+        start_playback(&mpv, &mut events).await?;
+
+        let children = mpv_children()?;
+        if children > 1 {
+            bail!("{children} mpv processes alive after {fault:?} (cycle {cycles})");
+        }
+        let fds = open_fds()?;
+        if fds > fd_baseline + FD_SLACK {
+            bail!("file descriptors grew from {fd_baseline} to {fds} (cycle {cycles})");
+        }
+
+        cycles += 1;
+        info!(cycles, ?fault, fds, "soak cycle ok");
+    }
+
+    let _ = mpv.command(MpvCommand::Shutdown);
+    info!(cycles, "soak finished");
+    Ok(())
+}
+
+fn inject(mpv: &MpvProcess, fault: Fault) -> Result<()> {
+    let signal = match fault {
+        Fault::Kill => libc::SIGKILL,
+        Fault::Terminate => libc::SIGTERM,
+        Fault::Respawn => return mpv.command(MpvCommand::Respawn(soak_options())),
+        Fault::SocketError => return break_ipc_socket(),
+    };
+    let pid = mpv_child_pids()?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no mpv child to signal"))?;
+    // SAFETY: plain kill(2) on a pid we just read from procfs.
+    unsafe {
+        libc::kill(pid, signal);
+    }
+    Ok(())
+}
+
+/// Shuts down every socket this process holds. The soak opens no others, so
+/// that is exactly the supervisor's connection to mpv.
+fn break_ipc_socket() -> Result<()> {
+    let mut broken = 0;
+    for entry in std::fs::read_dir("/proc/self/fd").context("Read /proc/self/fd")? {
+        let Ok(entry) = entry else { continue };
+        let Ok(target) = std::fs::read_link(entry.path()) else {
+            continue;
+        };
+        let Some(fd) = entry.file_name().to_str().and_then(|s| s.parse::<libc::c_int>().ok()) else {
+            continue;
+        };
+        if target.to_string_lossy().starts_with("socket:") {
+            // SAFETY: shutdown(2) leaves the descriptor open; its owner
+            // sees the connection fail and closes it.
+            unsafe {
+                libc::shutdown(fd, libc::SHUT_RDWR);
+            }
+            broken += 1;
+        }
+    }
+    if broken == 0 {
+        bail!("no IPC socket to break");
+    }
+    Ok(())
+}
+
+/// Faults come every few seconds, far more often than the supervisor's
+/// crash limit allows, so the limit is off.
+fn soak_options() -> MpvOptions {
+    MpvOptions {
+        restart_forever: true,
+        ..MpvOptions::default()
+    }
+}
+
+async fn wait_ready(events: &mut mpsc::UnboundedReceiver<MpvEvent>) -> Result<()> {
+    tokio::time::timeout(RECOVERY_TIMEOUT, async {
+        loop {
+            match events.recv().await {
+                Some(MpvEvent::Ready) => return Ok(()),
+                Some(MpvEvent::GaveUp(e)) => bail!("supervisor gave up: {e}"),
+                Some(MpvEvent::Missing) => bail!("mpv is not installed"),
+                Some(_) => {}
+                None => bail!("mpv supervisor stopped"),
+            }
+        }
+    })
+    .await
+    .context("timed out waiting for mpv to become ready")?
+}
+
+/// Loads the test stream and waits until mpv reports a media title, which
+/// proves the IPC connection works in both directions.
+async fn start_playback(
+    mpv: &MpvProcess,
+    events: &mut mpsc::UnboundedReceiver<MpvEvent>,
+) -> Result<()> {
+    mpv.command(MpvCommand::LoadUrl {
+        url: TEST_STREAM.to_string(),
+        start: None,
+    })?;
+    tokio::time::timeout(RECOVERY_TIMEOUT, async {
+        loop {
+            match events.recv().await {
+                Some(MpvEvent::MediaTitle(Some(_))) => return Ok(()),
+                Some(MpvEvent::Crashed(e)) => bail!("mpv crashed while loading: {e}"),
+                Some(_) => {}
+                None => bail!("mpv supervisor stopped"),
+            }
+        }
+    })
+    .await
+    .context("timed out waiting for playback")??;
+    check_properties(mpv).await
+}
+
+/// Reads properties back, which proves replies still reach the caller that
+/// asked after a restart.
+async fn check_properties(mpv: &MpvProcess) -> Result<()> {
+    let path = mpv.path().await.context("read path")?;
+    if path.as_deref() != Some(TEST_STREAM) {
+        bail!("mpv reports {path:?} loaded instead of the test stream");
+    }
+    mpv.volume()
+        .await
+        .context("read volume")?
+        .context("mpv reports no volume")?;
+    // The tone never ends, so there may be no duration; it still has to answer.
+    mpv.duration().await.context("read duration")?;
+    Ok(())
+}
+
+fn open_fds() -> Result<usize> {
+    Ok(std::fs::read_dir("/proc/self/fd")
+        .context("Read /proc/self/fd")?
+        .count())
+}
+
+fn mpv_children() -> Result<usize> {
+    Ok(mpv_child_pids()?.len())
+}
+
+fn mpv_child_pids() -> Result<Vec<libc::pid_t>> {
+    let me = std::process::id() as libc::pid_t;
+    let mut pids = Vec::new();
+    for entry in std::fs::read_dir("/proc").context("Read /proc")? {
+        let Ok(entry) = entry else { continue };
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+            continue;
+        };
+        let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        if let Some((comm, ppid)) = parse_stat(&stat) {
+            if comm == "mpv" && ppid == me {
+                pids.push(pid);
+            }
+        }
+    }
+    Ok(pids)
+}
+
+/// Extracts `(comm, ppid)` from `/proc/<pid>/stat`.
+fn parse_stat(stat: &str) -> Option<(&str, libc::pid_t)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = &stat[open + 1..close];
+    let mut rest = stat[close + 1..].split_whitespace();
+    let _state = rest.next()?;
+    let ppid = rest.next()?.parse().ok()?;
+    Some((comm, ppid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_stat() {
+        let stat = "4242 (mpv) S 1000 4242 4242 0 -1 4194560 1234 0 0 0";
+        assert_eq!(parse_stat(stat), Some(("mpv", 1000)));
+        let odd = "77 (my (odd) name) R 1 77 77 0";
+        assert_eq!(parse_stat(odd), Some(("my (odd) name", 1)));
+    }
+
+    /// Needs `mpv` installed. Duration defaults to one hour and can be set via
+    /// `RADIOWIDGET_SOAK_SECS`.
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn mpv_supervision_soak() {
+        let secs = std::env::var("RADIOWIDGET_SOAK_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+        run(Duration::from_secs(secs)).await.unwrap();
+    }
+}
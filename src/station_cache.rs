@@ -0,0 +1,208 @@
+//! Station metadata cache keyed by UUID.
+//!
+//! Search results and station lookups feed the cache so favorites, the
+//! details panel and the last played station have data without refetching,
+//! including while offline. It keeps the most recently used stations.
+
+use crate::config::{cache_dir, write_atomic};
+use crate::models::Station;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Entries older than this are refetched when next needed.
+pub const MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Beyond this many stations the least recently used are dropped.
+const MAX_ENTRIES: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedStation {
+    station: Station,
+    /// Unix timestamp (seconds) of the fetch.
+    fetched_at: u64,
+    /// Unix timestamp (seconds) of the last read or fetch.
+    #[serde(default)]
+    used_at: Cell<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StationCache {
+    #[serde(default)]
+    entries: HashMap<String, CachedStation>,
+}
+
+impl StationCache {
+    /// Loads the on-disk cache; a missing or corrupt file yields an empty cache.
+    pub fn load() -> Self {
+        let path = match cache_path() {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(error = ?e, "station cache disabled");
+                return Self::default();
+            }
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(error = ?e, ?path, "discarding unreadable station cache");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec(self).context("Failed to serialize station cache")?;
+        write_atomic(&cache_path()?, &data)
+    }
+
+    pub fn insert(&mut self, stations: impl IntoIterator<Item = Station>) {
+        self.insert_at(stations, now_secs());
+    }
+
+    fn insert_at(&mut self, stations: impl IntoIterator<Item = Station>, now: u64) {
+        for station in stations {
+            self.entries.insert(
+                station.stationuuid.clone(),
+                CachedStation {
+                    station,
+                    fetched_at: now,
+                    used_at: Cell::new(now),
+                },
+            );
+        }
+        self.evict(MAX_ENTRIES);
+    }
+
+    /// Drops the least recently used entries until `max` are left.
+    fn evict(&mut self, max: usize) {
+        let Some(excess) = self.entries.len().checked_sub(max).filter(|&n| n > 0) else {
+            return;
+        };
+        let mut by_use: Vec<(u64, String)> = self
+            .entries
+            .iter()
+            .map(|(uuid, c)| (c.used_at.get(), uuid.clone()))
+            .collect();
+        by_use.sort_unstable();
+        for (_, uuid) in by_use.into_iter().take(excess) {
+            self.entries.remove(&uuid);
+        }
+    }
+
+    /// Edits a cached station in place without making it count as fresh.
+    pub fn update(&mut self, stationuuid: &str, f: impl FnOnce(&mut Station)) -> bool {
+        match self.entries.get_mut(stationuuid) {
+            Some(entry) => {
+                f(&mut entry.station);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cached station regardless of age; stale data beats no data offline.
+    pub fn get(&self, stationuuid: &str) -> Option<&Station> {
+        self.get_at(stationuuid, now_secs())
+    }
+
+    fn get_at(&self, stationuuid: &str, now: u64) -> Option<&Station> {
+        let entry = self.entries.get(stationuuid)?;
+        entry.used_at.set(now);
+        Some(&entry.station)
+    }
+
+    /// UUIDs from `uuids` that are missing or older than [`MAX_AGE`].
+    pub fn needs_refresh<'a>(&self, uuids: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        self.needs_refresh_at(uuids, now_secs())
+    }
+
+    fn needs_refresh_at<'a>(
+        &self,
+        uuids: impl IntoIterator<Item = &'a str>,
+        now: u64,
+    ) -> Vec<String> {
+        uuids
+            .into_iter()
+            .filter(|uuid| match self.entries.get(*uuid) {
+                Some(c) => now.saturating_sub(c.fetched_at) > MAX_AGE.as_secs(),
+                None => true,
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn invalidate<'a>(&mut self, uuids: impl IntoIterator<Item = &'a str>) {
+        for uuid in uuids {
+            if let Some(c) = self.entries.get_mut(uuid) {
+                c.fetched_at = 0;
+            }
+        }
+    }
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("stations.json"))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(uuid: &str) -> Station {
+        Station {
+            stationuuid: uuid.to_string(),
+            name: format!("Station {uuid}"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn refresh_policy_follows_max_age() {
+        let mut cache = StationCache::default();
+        let t0 = 1_000_000;
+        cache.insert_at([station("a")], t0);
+
+        assert!(cache.needs_refresh_at(["a"], t0 + 60).is_empty());
+        assert_eq!(cache.needs_refresh_at(["a", "b"], t0 + 60), vec!["b"]);
+        assert_eq!(
+            cache.needs_refresh_at(["a"], t0 + MAX_AGE.as_secs() + 1),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn invalidated_entries_stay_readable() {
+        let mut cache = StationCache::default();
+        cache.insert_at([station("a")], 1_000_000);
+        cache.invalidate(["a"]);
+
+        assert_eq!(cache.needs_refresh_at(["a"], 1_000_001), vec!["a"]);
+        assert_eq!(cache.get("a").unwrap().name, "Station a");
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = StationCache::default();
+        cache.insert_at([station("a"), station("b"), station("c")], 1_000);
+        cache.get_at("a", 2_000);
+        cache.insert_at([station("d")], 3_000);
+        cache.evict(3);
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("d").is_some());
+        assert_eq!(cache.entries.len(), 3);
+        // "b" and "c" were equally old; one of them had to go.
+        assert!(cache.get("b").is_none() || cache.get("c").is_none());
+    }
+}
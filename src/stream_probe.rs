@@ -0,0 +1,773 @@
+//! Lightweight inspection of arbitrary stream URLs (custom stations).
+
+use crate::models::StationRef;
+use crate::radio_browser::read_limited;
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RANGE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::debug;
+use url::Url;
+
+/// Enough for the status of a server carrying a few dozen mounts.
+const MAX_STATUS_BYTES: usize = 64 * 1024;
+/// How much of a stream is read to recognise its format.
+const SNIFF_BYTES: usize = 8 * 1024;
+const SNIFF_TIMEOUT: Duration = Duration::from_secs(3);
+/// The check before loading gives up after this long and lets mpv try.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(4);
+/// Playlist files bigger than this aren't playlists.
+const MAX_PLAYLIST_BYTES: usize = 64 * 1024;
+/// Playlist entries tried before giving up on the station.
+const MAX_PLAYLIST_TRIES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    /// A direct audio stream mpv can play as-is.
+    Direct,
+    /// An `.m3u`/`.pls` style playlist pointing at the real stream.
+    Playlist,
+    /// An HLS playlist.
+    Hls,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub url: String,
+    pub kind: StreamKind,
+    pub content_type: Option<String>,
+    pub name: Option<String>,
+    pub genre: Option<String>,
+    pub bitrate: Option<u32>,
+    /// Codec recognised from the first bytes, or guessed from the content type.
+    pub codec: Option<String>,
+    /// Current song as reported by the Icecast server, if available.
+    pub now_playing: Option<String>,
+}
+
+impl StreamInfo {
+    /// Display name: the advertised station name, falling back to the host.
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| {
+                Url::parse(&self.url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(str::to_string))
+            })
+            .unwrap_or_else(|| self.url.clone())
+    }
+
+    /// Reference for playing or saving this stream as a custom station.
+    pub fn station_ref(&self) -> StationRef {
+        StationRef {
+            stationuuid: format!("custom:{}", self.url),
+            name: self.display_name(),
+            url: Some(self.url.clone()),
+            group: None,
+        }
+    }
+}
+
+/// How a stream answered the check before loading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamCheck {
+    pub origin: StreamOrigin,
+    pub content_type: Option<String>,
+    /// Going by the content type and file name; playlists served as audio
+    /// pass for direct streams.
+    pub kind: StreamKind,
+}
+
+/// Where a stream is actually served from once redirects are followed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamOrigin {
+    pub url: String,
+    pub host: String,
+    pub https: bool,
+    /// The URL we were given sent us somewhere else.
+    pub redirected: bool,
+}
+
+impl StreamOrigin {
+    fn new(requested: &Url, served: &Url) -> Self {
+        Self {
+            url: served.to_string(),
+            host: served.host_str().unwrap_or_default().to_string(),
+            https: served.scheme() == "https",
+            redirected: requested != served,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProbeClient {
+    http: reqwest::Client,
+}
+
+impl ProbeClient {
+    pub fn new() -> Result<Self> {
+        let http = reqwest::ClientBuilder::new()
+            .user_agent("RadioWidget/0.1 (COSMIC applet)")
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::limited(5))
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self { http })
+    }
+
+    /// Requests the stream with ICY metadata enabled and inspects the response
+    /// headers plus the first few KB of the body.
+    pub async fn probe(&self, url: Url) -> Result<StreamInfo> {
+        let resp = self.request_head(url).await?;
+        let final_url = resp.url().clone();
+        let mut info = info_from_headers(&final_url, resp.headers())?;
+
+        if info.kind == StreamKind::Direct {
+            let head = read_head(resp).await;
+            // Servers often send playlists and error pages as audio/mpeg.
+            if let Some(kind) = sniff_kind(&head)? {
+                info.kind = kind;
+            } else if let Some(codec) = sniff_codec(&head) {
+                info.codec = Some(codec.to_string());
+            }
+        }
+
+        if info.kind == StreamKind::Direct {
+            match self.icecast_status(&final_url).await {
+                Ok(Some(source)) => source.merge_into(&mut info),
+                Ok(None) => {}
+                Err(e) => debug!(error = ?e, "no Icecast status for stream"),
+            }
+        }
+        Ok(info)
+    }
+
+    /// Makes sure a stream answers before it's handed to mpv, so a dead
+    /// station fails in seconds rather than after mpv's own timeout. Fails
+    /// only when the stream clearly won't play; `None` when the server is too
+    /// slow or unusual to tell (old Shoutcast servers don't even speak HTTP).
+    pub async fn check(&self, url: Url) -> Result<Option<StreamCheck>> {
+        let request = self.http.get(url.clone()).header(RANGE, format!("bytes=0-{}", SNIFF_BYTES - 1));
+        let resp = match tokio::time::timeout(CHECK_TIMEOUT, request.send()).await {
+            Ok(Ok(resp)) => resp,
+            Ok(Err(e)) if e.is_connect() && !e.is_timeout() => {
+                debug!(error = ?e, "stream refused the connection");
+                return Err(anyhow!("The station's stream can't be reached"));
+            }
+            Ok(Err(e)) => {
+                debug!(error = ?e, "stream check inconclusive");
+                return Ok(None);
+            }
+            Err(_) => return Ok(None),
+        };
+        let content_type = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if let Some(reason) = unplayable(resp.status(), content_type.as_deref()) {
+            return Err(anyhow!(reason));
+        }
+        Ok(Some(StreamCheck {
+            origin: StreamOrigin::new(&url, resp.url()),
+            kind: classify(resp.url(), content_type.as_deref()).unwrap_or(StreamKind::Direct),
+            content_type,
+        }))
+    }
+
+    /// Reads an `.m3u`/`.pls` playlist and picks the stream to play: the
+    /// first of its entries that answers the check. `None` for HLS playlists,
+    /// which mpv plays itself (a master playlist's variants can depend on
+    /// separate audio renditions only mpv pairs up), and when the playlist
+    /// can't be read this time;
+    /// like [`Self::check`], only a clearly broken playlist is an error.
+    pub async fn playlist_entry(&self, url: Url) -> Result<Option<(Url, Option<StreamCheck>)>> {
+        let fetch = async {
+            let resp = self.http.get(url).send().await.context("Playlist did not answer")?;
+            if !resp.status().is_success() {
+                return Err(anyhow!("Playlist returned HTTP {}", resp.status()));
+            }
+            let base = resp.url().clone();
+            Ok((base, read_playlist(resp).await?))
+        };
+        let (base, body) = match tokio::time::timeout(CHECK_TIMEOUT, fetch).await {
+            Ok(Ok(fetched)) => fetched,
+            Ok(Err(e)) => {
+                debug!(error = ?e, "playlist unreadable; leaving it to mpv");
+                return Ok(None);
+            }
+            Err(_) => {
+                debug!("playlist timed out; leaving it to mpv");
+                return Ok(None);
+            }
+        };
+        let text = String::from_utf8_lossy(&body);
+        if text.contains("#EXT-X-") {
+            return Ok(None);
+        }
+        let entries = parse_playlist(&base, &text);
+        if entries.is_empty() {
+            return Err(anyhow!("The station's playlist doesn't list any streams"));
+        }
+        for entry in entries.into_iter().take(MAX_PLAYLIST_TRIES) {
+            match self.check(entry.clone()).await {
+                Ok(check) => return Ok(Some((entry, check))),
+                Err(e) => debug!(error = ?e, %entry, "playlist entry won't play"),
+            }
+        }
+        Err(anyhow!("None of the streams in the station's playlist answer"))
+    }
+
+    /// Connects to a stream about to play to see where it is served from.
+    /// With `sniff` the start is read as well and the codec named, for
+    /// stations whose codec metadata is missing.
+    pub async fn inspect(&self, url: Url, sniff: bool) -> Result<(StreamOrigin, Option<&'static str>)> {
+        let resp = self.request_head(url.clone()).await?;
+        let origin = StreamOrigin::new(&url, resp.url());
+        if !sniff {
+            return Ok((origin, None));
+        }
+        let head = read_head(resp).await;
+        Ok((origin, sniff_codec(&head)))
+    }
+
+    /// GET for the first [`SNIFF_BYTES`]. Live streams ignore the range and
+    /// keep sending, so callers only read as much as they need.
+    async fn request_head(&self, url: Url) -> Result<reqwest::Response> {
+        let resp = self
+            .http
+            .get(url)
+            .header("Icy-MetaData", "1")
+            .header(RANGE, format!("bytes=0-{}", SNIFF_BYTES - 1))
+            .send()
+            .await
+            .context("Stream did not answer")?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("Stream returned HTTP {}", resp.status()));
+        }
+        Ok(resp)
+    }
+
+    /// Looks up the mount in the server's `status-json.xsl`, which Icecast
+    /// exposes next to the streams.
+    async fn icecast_status(&self, stream_url: &Url) -> Result<Option<IcecastSource>> {
+        let status_url = stream_url
+            .join("/status-json.xsl")
+            .context("Invalid status URL")?;
+        let resp = self.http.get(status_url).send().await?;
+        if !resp.status().is_success() {
+            return Ok(None);
+        }
+        let bytes = read_limited(resp, MAX_STATUS_BYTES)
+            .await
+            .context("Read Icecast status")?;
+        parse_icecast_status(&bytes, stream_url)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IcecastStatus {
+    icestats: IcecastStats,
+}
+
+#[derive(Debug, Deserialize)]
+struct IcecastStats {
+    #[serde(default)]
+    source: Option<OneOrMany<IcecastSource>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct IcecastSource {
+    #[serde(default)]
+    listenurl: Option<String>,
+    #[serde(default)]
+    server_name: Option<String>,
+    #[serde(default)]
+    genre: Option<String>,
+    #[serde(default)]
+    bitrate: Option<serde_json::Value>,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+impl IcecastSource {
+    /// Fills fields the ICY headers did not provide.
+    fn merge_into(self, info: &mut StreamInfo) {
+        let clean = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        if info.name.is_none() {
+            info.name = clean(self.server_name);
+        }
+        if info.genre.is_none() {
+            info.genre = clean(self.genre);
+        }
+        if info.bitrate.is_none() {
+            info.bitrate = self.bitrate.and_then(|b| match b {
+                serde_json::Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+                serde_json::Value::String(s) => s.trim().parse().ok(),
+                _ => None,
+            });
+        }
+        info.now_playing = clean(self.title);
+    }
+}
+
+fn parse_icecast_status(bytes: &[u8], stream_url: &Url) -> Result<Option<IcecastSource>> {
+    let status: IcecastStatus =
+        serde_json::from_slice(bytes).context("Invalid Icecast status JSON")?;
+    let sources = match status.icestats.source {
+        None => return Ok(None),
+        Some(OneOrMany::One(s)) => vec![s],
+        Some(OneOrMany::Many(v)) => v,
+    };
+    let mount = stream_url.path();
+    let matching = sources.iter().find(|s| {
+        s.listenurl
+            .as_deref()
+            .and_then(|l| Url::parse(l).ok())
+            .is_some_and(|l| l.path() == mount)
+    });
+    Ok(match matching {
+        Some(s) => Some(s.clone()),
+        None if sources.len() == 1 => sources.into_iter().next(),
+        None => None,
+    })
+}
+
+/// Reads up to [`SNIFF_BYTES`] of the body, giving up quietly on slow streams.
+async fn read_head(mut resp: reqwest::Response) -> Vec<u8> {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    let read = async {
+        while head.len() < SNIFF_BYTES {
+            match resp.chunk().await {
+                Ok(Some(chunk)) => head.extend_from_slice(&chunk),
+                _ => break,
+            }
+        }
+    };
+    let _ = tokio::time::timeout(SNIFF_TIMEOUT, read).await;
+    head.truncate(SNIFF_BYTES);
+    head
+}
+
+/// Reads a whole playlist file, refusing anything too big to be one.
+async fn read_playlist(mut resp: reqwest::Response) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = resp.chunk().await.context("Read playlist")? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_PLAYLIST_BYTES {
+            return Err(anyhow!("Playlist too large"));
+        }
+    }
+    Ok(body)
+}
+
+/// The http(s) streams listed in an `.m3u` or `.pls` file, in the order the
+/// file gives them; relative entries are resolved against `base`.
+fn parse_playlist(base: &Url, text: &str) -> Vec<Url> {
+    let text = text.trim_start_matches('\u{feff}');
+    let pls = text.trim_start().to_ascii_lowercase().starts_with("[playlist]");
+    let mut entries: Vec<(u32, &str)> = Vec::new();
+    for (line_no, line) in text.lines().map(str::trim).enumerate() {
+        if pls {
+            // `File2=...`; the numbers, not the lines, give the order.
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            if let Some(n) = key.strip_prefix("file") {
+                entries.push((n.parse().unwrap_or(u32::MAX), value.trim()));
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push((line_no as u32, line));
+        }
+    }
+    entries.sort_by_key(|&(n, _)| n);
+    let mut urls: Vec<Url> = Vec::new();
+    for (_, entry) in entries {
+        let Ok(url) = base.join(entry) else {
+            continue;
+        };
+        if matches!(url.scheme(), "http" | "https") && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Skips a raw `ICY 200 OK` response header that some SHOUTcast servers put
+/// in front of the audio.
+fn skip_icy_header(head: &[u8]) -> &[u8] {
+    if !head.starts_with(b"ICY ") {
+        return head;
+    }
+    match head.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => &head[end + 4..],
+        None => &[],
+    }
+}
+
+/// Recognises bodies that aren't audio despite what the headers said.
+fn sniff_kind(head: &[u8]) -> Result<Option<StreamKind>> {
+    let text = String::from_utf8_lossy(&head[..head.len().min(512)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let lower = text.to_ascii_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        return Err(anyhow!("URL points to a web page, not an audio stream"));
+    }
+    if text.starts_with("#EXTM3U") {
+        let hls = text.contains("#EXT-X-");
+        return Ok(Some(if hls { StreamKind::Hls } else { StreamKind::Playlist }));
+    }
+    if lower.starts_with("[playlist]") {
+        return Ok(Some(StreamKind::Playlist));
+    }
+    Ok(None)
+}
+
+/// Names the codec from the container or frame sync at the start of `head`.
+pub fn sniff_codec(head: &[u8]) -> Option<&'static str> {
+    let mut data = skip_icy_header(head);
+    if data.starts_with(b"ID3") && data.len() >= 10 {
+        // Syncsafe size, 7 bits per byte.
+        let size = data[6..10].iter().fold(0usize, |n, b| (n << 7) | usize::from(b & 0x7f));
+        data = data.get(10 + size..)?;
+    }
+    if data.starts_with(b"OggS") {
+        let page = &data[..data.len().min(128)];
+        let has = |magic: &[u8]| page.windows(magic.len()).any(|w| w == magic);
+        return Some(if has(b"OpusHead") {
+            "Opus"
+        } else if has(b"\x01vorbis") {
+            "Vorbis"
+        } else if has(b"\x7fFLAC") {
+            "FLAC"
+        } else {
+            "Ogg"
+        });
+    }
+    if data.starts_with(b"fLaC") {
+        return Some("FLAC");
+    }
+    // Frame sync can start anywhere after a cut; insist on two frames in a row.
+    (0..data.len()).find_map(|i| {
+        let (codec, len) = frame_at(&data[i..])?;
+        let next = data.get(i + len..)?;
+        match frame_at(next) {
+            Some((c, _)) if c == codec => Some(codec),
+            // The buffer ended mid-frame; one full frame will have to do.
+            None if next.len() < 7 => Some(codec),
+            _ => None,
+        }
+    })
+}
+
+/// Codec and length of an ADTS or MPEG audio frame starting at `data`.
+fn frame_at(data: &[u8]) -> Option<(&'static str, usize)> {
+    if data.len() < 7 || data[0] != 0xff || data[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+    let version = (data[1] >> 3) & 0x03;
+    let layer = (data[1] >> 1) & 0x03;
+    if layer == 0 {
+        // ADTS: layer is always 0, the 13-bit frame length spans bytes 3-5.
+        if data[1] & 0xf6 != 0xf0 || (data[2] >> 2) & 0x0f > 12 {
+            return None;
+        }
+        let len = (usize::from(data[3] & 0x03) << 11)
+            | (usize::from(data[4]) << 3)
+            | usize::from(data[5] >> 5);
+        return (len >= 7).then_some(("AAC", len));
+    }
+
+    const MPEG1_L3: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const MPEG1_L2: [u32; 15] = [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384];
+    const MPEG2_L23: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    const RATES: [u32; 3] = [44100, 48000, 32000];
+
+    // Layer I is too rare in radio to be worth the false positives.
+    if version == 1 || layer == 3 {
+        return None;
+    }
+    let bitrate_idx = usize::from(data[2] >> 4);
+    let rate_idx = usize::from((data[2] >> 2) & 0x03);
+    if bitrate_idx == 0 || bitrate_idx == 15 || rate_idx == 3 {
+        return None;
+    }
+    let mpeg1 = version == 3;
+    let bitrate = match (mpeg1, layer) {
+        (true, 1) => MPEG1_L3[bitrate_idx],
+        (true, _) => MPEG1_L2[bitrate_idx],
+        (false, _) => MPEG2_L23[bitrate_idx],
+    } * 1000;
+    let rate = RATES[rate_idx] >> (3 - version).min(2);
+    let padding = usize::from((data[2] >> 1) & 0x01);
+    let samples = if layer == 1 && !mpeg1 { 72 } else { 144 };
+    let len = (samples * bitrate / rate) as usize + padding;
+    Some((if layer == 1 { "MP3" } else { "MP2" }, len))
+}
+
+/// Codec implied by a stream's content type, for when sniffing finds nothing.
+fn codec_from_mime(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+    Some(match mime.as_str() {
+        "audio/mpeg" | "audio/mp3" => "MP3",
+        "audio/aac" | "audio/aacp" | "audio/x-aac" => "AAC",
+        "audio/ogg" | "application/ogg" => "Ogg",
+        "audio/opus" => "Opus",
+        "audio/flac" | "audio/x-flac" => "FLAC",
+        _ => return None,
+    })
+}
+
+/// Finds the first http(s) URL in free-form text such as clipboard contents.
+pub fn find_stream_url(text: &str) -> Option<Url> {
+    text.split(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"')
+        .filter(|token| token.starts_with("http://") || token.starts_with("https://"))
+        .find_map(|token| Url::parse(token.trim_end_matches([',', ';', ')'])).ok())
+}
+
+fn classify(url: &Url, content_type: Option<&str>) -> Result<StreamKind> {
+    let mime = content_type
+        .and_then(|ct| ct.split(';').next())
+        .map(|ct| ct.trim().to_ascii_lowercase());
+    match mime.as_deref() {
+        Some("application/vnd.apple.mpegurl") | Some("application/x-mpegurl") => {
+            return Ok(StreamKind::Hls)
+        }
+        Some("audio/x-mpegurl") | Some("audio/mpegurl") | Some("audio/x-scpls")
+        | Some("application/pls+xml") => return Ok(StreamKind::Playlist),
+        Some(m) if m.starts_with("text/html") => {
+            return Err(anyhow!("URL points to a web page, not an audio stream"))
+        }
+        _ => {}
+    }
+
+    let path = url.path().to_ascii_lowercase();
+    if path.ends_with(".m3u8") {
+        Ok(StreamKind::Hls)
+    } else if path.ends_with(".m3u") || path.ends_with(".pls") {
+        Ok(StreamKind::Playlist)
+    } else {
+        Ok(StreamKind::Direct)
+    }
+}
+
+/// Why a stream answering like this won't play, if it clearly won't.
+fn unplayable(status: StatusCode, content_type: Option<&str>) -> Option<String> {
+    // Some servers refuse ranges on live streams; that's not an outage.
+    if (status.is_client_error() && status != StatusCode::RANGE_NOT_SATISFIABLE) || status.is_server_error() {
+        return Some(format!("The station's stream isn't available (HTTP {status})"));
+    }
+    let mime = content_type.and_then(|c| c.split(';').next()).map(str::trim);
+    if mime.is_some_and(|m| m.eq_ignore_ascii_case("text/html")) {
+        return Some("The station's address leads to a web page, not a stream".to_string());
+    }
+    None
+}
+
+fn info_from_headers(url: &Url, headers: &HeaderMap) -> Result<StreamInfo> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let kind = classify(url, content_type.as_deref())?;
+    Ok(StreamInfo {
+        url: url.to_string(),
+        kind,
+        name: header("icy-name"),
+        genre: header("icy-genre"),
+        bitrate: header("icy-br").and_then(|b| b.split(',').next()?.trim().parse().ok()),
+        codec: content_type.as_deref().and_then(codec_from_mime).map(str::to_string),
+        content_type,
+        now_playing: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn describes_stream_origin() {
+        let asked = Url::parse("http://radio.example/live").unwrap();
+        let origin = StreamOrigin::new(&asked, &Url::parse("https://cdn.example.net:8443/live.mp3").unwrap());
+        assert_eq!(origin.host, "cdn.example.net");
+        assert!(origin.https);
+        assert!(origin.redirected);
+
+        let origin = StreamOrigin::new(&asked, &asked);
+        assert_eq!(origin.url, "http://radio.example/live");
+        assert!(!origin.https && !origin.redirected);
+    }
+
+    #[test]
+    fn tells_dead_streams_from_odd_ones() {
+        assert!(unplayable(StatusCode::OK, Some("audio/mpeg")).is_none());
+        assert!(unplayable(StatusCode::PARTIAL_CONTENT, None).is_none());
+        assert!(unplayable(StatusCode::RANGE_NOT_SATISFIABLE, None).is_none());
+        assert!(unplayable(StatusCode::OK, Some("application/vnd.apple.mpegurl")).is_none());
+        assert_eq!(
+            unplayable(StatusCode::NOT_FOUND, Some("text/html")).as_deref(),
+            Some("The station's stream isn't available (HTTP 404 Not Found)")
+        );
+        assert!(unplayable(StatusCode::SERVICE_UNAVAILABLE, None).is_some());
+        assert!(unplayable(StatusCode::OK, Some("Text/HTML; charset=utf-8")).is_some());
+    }
+
+    #[test]
+    fn finds_url_in_clipboard_text() {
+        let url = find_stream_url("Listen at https://ice.example.org/live.mp3, it's great").unwrap();
+        assert_eq!(url.as_str(), "https://ice.example.org/live.mp3");
+        assert!(find_stream_url("no links here").is_none());
+        assert!(find_stream_url("ftp://example.org/file.mp3").is_none());
+    }
+
+    #[test]
+    fn classifies_playlists() {
+        let url = Url::parse("http://example.org/radio.pls").unwrap();
+        assert_eq!(classify(&url, None).unwrap(), StreamKind::Playlist);
+        let url = Url::parse("http://example.org/live").unwrap();
+        assert_eq!(
+            classify(&url, Some("application/vnd.apple.mpegurl")).unwrap(),
+            StreamKind::Hls
+        );
+        assert_eq!(classify(&url, Some("audio/mpeg")).unwrap(), StreamKind::Direct);
+        assert!(classify(&url, Some("text/html; charset=utf-8")).is_err());
+    }
+
+    #[test]
+    fn lists_playlist_entries_in_order() {
+        let base = Url::parse("http://radio.example/listen/station.m3u").unwrap();
+        let m3u = "\u{feff}#EXTM3U\r\n#EXTINF:-1,Station\r\nhttp://a.example/live.mp3\r\n\r\nlow.aac\r\nhttp://a.example/live.mp3\r\n";
+        assert_eq!(
+            parse_playlist(&base, m3u).iter().map(Url::as_str).collect::<Vec<_>>(),
+            ["http://a.example/live.mp3", "http://radio.example/listen/low.aac"]
+        );
+
+        let pls = "[playlist]\nNumberOfEntries=3\nFile2=https://b.example/backup\nTitle1=Main\nfile1 = https://b.example/main\nFile3=ftp://b.example/x\nVersion=2\n";
+        assert_eq!(
+            parse_playlist(&base, pls).iter().map(Url::as_str).collect::<Vec<_>>(),
+            ["https://b.example/main", "https://b.example/backup"]
+        );
+        assert!(parse_playlist(&base, "#EXTM3U\n# nothing here\n").is_empty());
+    }
+
+    #[test]
+    fn reads_icy_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("audio/aac"));
+        headers.insert("icy-name", HeaderValue::from_static("Jazz Radio"));
+        headers.insert("icy-genre", HeaderValue::from_static("Jazz"));
+        headers.insert("icy-br", HeaderValue::from_static("128"));
+        let url = Url::parse("http://example.org/jazz").unwrap();
+        let info = info_from_headers(&url, &headers).unwrap();
+        assert_eq!(info.kind, StreamKind::Direct);
+        assert_eq!(info.display_name(), "Jazz Radio");
+        assert_eq!(info.genre.as_deref(), Some("Jazz"));
+        assert_eq!(info.bitrate, Some(128));
+        assert_eq!(info.codec.as_deref(), Some("AAC"));
+    }
+
+    /// Two back-to-back frames with the given 4-byte header.
+    fn frames(header: [u8; 4], len: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; len];
+        frame[..4].copy_from_slice(&header);
+        let mut out = b"\x00\x12garbage".to_vec();
+        out.extend_from_slice(&frame);
+        out.extend_from_slice(&frame);
+        out
+    }
+
+    #[test]
+    fn sniffs_mpeg_and_adts_frames() {
+        // MPEG-1 Layer III, 128 kbps, 44.1 kHz: 417-byte frames.
+        assert_eq!(sniff_codec(&frames([0xff, 0xfb, 0x90, 0x00], 417)), Some("MP3"));
+        // Wrong spacing between the sync words is not a stream.
+        assert_eq!(sniff_codec(&frames([0xff, 0xfb, 0x90, 0x00], 300)), None);
+        // ADTS, 371-byte frames.
+        let mut adts = frames([0xff, 0xf1, 0x50, 0x80], 371);
+        for start in [9, 9 + 371] {
+            adts[start + 3] = 0x80;
+            adts[start + 4] = (371 >> 3) as u8;
+            adts[start + 5] = ((371 & 0x07) << 5) as u8;
+        }
+        assert_eq!(sniff_codec(&adts), Some("AAC"));
+    }
+
+    #[test]
+    fn sniffs_containers_behind_icy_and_id3_headers() {
+        let mut ogg = b"ICY 200 OK\r\nicy-name: Test\r\n\r\nOggS".to_vec();
+        ogg.extend_from_slice(&[0; 24]);
+        ogg.extend_from_slice(b"OpusHead");
+        assert_eq!(sniff_codec(&ogg), Some("Opus"));
+
+        let mut flac = b"ID3\x04\x00\x00\x00\x00\x00\x02xx".to_vec();
+        flac.extend_from_slice(b"fLaC");
+        assert_eq!(sniff_codec(&flac), Some("FLAC"));
+        assert_eq!(sniff_codec(b"hello world"), None);
+    }
+
+    #[test]
+    fn sniffs_playlists_served_as_audio() {
+        assert_eq!(sniff_kind(b"#EXTM3U\n#EXT-X-VERSION:3\n").unwrap(), Some(StreamKind::Hls));
+        assert_eq!(sniff_kind(b"#EXTM3U\nhttp://a/b\n").unwrap(), Some(StreamKind::Playlist));
+        assert_eq!(sniff_kind(b"[playlist]\nFile1=x").unwrap(), Some(StreamKind::Playlist));
+        assert!(sniff_kind(b"  <!DOCTYPE html><html>").is_err());
+        assert_eq!(sniff_kind(&[0xff, 0xfb, 0x90, 0x00]).unwrap(), None);
+    }
+
+    #[test]
+    fn picks_matching_icecast_mount() {
+        let body = br#"{"icestats":{"source":[
+            {"listenurl":"http://example.org:8000/low","server_name":"Low","bitrate":64},
+            {"listenurl":"http://example.org:8000/high","server_name":"High FM","genre":"Rock","bitrate":"192","title":"Band - Song"}
+        ]}}"#;
+        let url = Url::parse("http://example.org:8000/high").unwrap();
+        let source = parse_icecast_status(body, &url).unwrap().unwrap();
+
+        let mut info = StreamInfo {
+            url: url.to_string(),
+            kind: StreamKind::Direct,
+            content_type: None,
+            name: None,
+            genre: None,
+            bitrate: None,
+            codec: None,
+            now_playing: None,
+        };
+        source.merge_into(&mut info);
+        assert_eq!(info.name.as_deref(), Some("High FM"));
+        assert_eq!(info.genre.as_deref(), Some("Rock"));
+        assert_eq!(info.bitrate, Some(192));
+        assert_eq!(info.now_playing.as_deref(), Some("Band - Song"));
+    }
+
+    #[test]
+    fn single_icecast_source_is_used_without_mount_match() {
+        let body = br#"{"icestats":{"source":{"listenurl":"http://127.0.0.1:8000/stream","server_name":"Solo"}}}"#;
+        let url = Url::parse("https://radio.example.org/live").unwrap();
+        let source = parse_icecast_status(body, &url).unwrap().unwrap();
+        assert_eq!(source.server_name.as_deref(), Some("Solo"));
+
+        let empty = br#"{"icestats":{}}"#;
+        assert!(parse_icecast_status(empty, &url).unwrap().is_none());
+    }
+}
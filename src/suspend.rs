@@ -0,0 +1,79 @@
+//! Follows logind's sleep notifications so playback can pause before the
+//! machine suspends and the stream can be reloaded once it wakes. A delay
+//! inhibitor gives the controller a moment to pause before logind goes ahead.
+
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tracing::debug;
+
+/// Longest suspend waits for the controller to pause.
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Gives the network a moment to come back before the stream is reloaded.
+const WAKE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub enum SuspendEvent {
+    /// The system is about to sleep; reply once playback is paused.
+    Suspending(oneshot::Sender<()>),
+    Resumed,
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<zbus::zvariant::OwnedFd>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Forwards sleep and wake until the receiver goes away. Fails when logind
+/// can't be reached (no system bus, some containers).
+pub async fn watch(tx: mpsc::UnboundedSender<SuspendEvent>) -> Result<()> {
+    let conn = zbus::Connection::system().await.context("Connect to the system bus")?;
+    let manager = ManagerProxy::new(&conn).await.context("Reach logind")?;
+    let mut signals = manager
+        .receive_prepare_for_sleep()
+        .await
+        .context("Subscribe to PrepareForSleep")?;
+    let mut lock = inhibit(&manager).await;
+    while let Some(signal) = signals.next().await {
+        let start = *signal.args().context("Read PrepareForSleep")?.start();
+        if start {
+            let (done_tx, done_rx) = oneshot::channel();
+            if tx.send(SuspendEvent::Suspending(done_tx)).is_err() {
+                break;
+            }
+            let _ = tokio::time::timeout(PAUSE_TIMEOUT, done_rx).await;
+            // Closing the descriptor lets the suspend go ahead.
+            drop(lock.take());
+        } else {
+            lock = inhibit(&manager).await;
+            tokio::time::sleep(WAKE_DELAY).await;
+            if tx.send(SuspendEvent::Resumed).is_err() {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Takes a delay lock so the next suspend waits for us; `None` if logind
+/// refuses, in which case playback may still be cut off mid-stream.
+async fn inhibit(manager: &ManagerProxy<'_>) -> Option<zbus::zvariant::OwnedFd> {
+    match manager
+        .inhibit("sleep", "RadioWidget", "Pause the radio before suspend", "delay")
+        .await
+    {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            debug!(error = %e, "no sleep inhibitor");
+            None
+        }
+    }
+}
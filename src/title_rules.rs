@@ -0,0 +1,134 @@
+//! Cleans stream titles before they're shown. Many stations wrap the song in
+//! their own name and decoration ("JAZZ FM *** Artist - Title ***"); rules
+//! from the config strip that, for every station or just one.
+
+use crate::config::TitleConfig;
+use crate::models::StationRef;
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug)]
+struct Rule {
+    station: Option<String>,
+    regex: Regex,
+    replace: String,
+}
+
+#[derive(Debug, Default)]
+pub struct TitleRules {
+    strip_station_name: bool,
+    rules: Vec<Rule>,
+    /// The station name prefix pattern, for the station it was built for.
+    prefix: Option<(String, Option<Regex>)>,
+}
+
+impl TitleRules {
+    /// Compiles the configured rules, leaving out patterns that don't compile;
+    /// those come back as messages for the user.
+    pub fn new(config: &TitleConfig) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let rules = config
+            .rules
+            .iter()
+            .filter_map(|r| match RegexBuilder::new(&r.pattern).case_insensitive(true).build() {
+                Ok(regex) => Some(Rule {
+                    station: r.station.clone(),
+                    regex,
+                    replace: r.replace.clone(),
+                }),
+                Err(e) => {
+                    errors.push(format!("Ignoring title rule {:?}: {e}", r.pattern));
+                    None
+                }
+            })
+            .collect();
+        (
+            Self {
+                strip_station_name: config.strip_station_name,
+                rules,
+                prefix: None,
+            },
+            errors,
+        )
+    }
+
+    /// `title` with the rules applied, in order; `None` if nothing is left.
+    pub fn clean(&mut self, station: Option<&StationRef>, title: &str) -> Option<String> {
+        let mut title = title.to_string();
+        if let Some(name) = station.map(|s| s.name.trim()).filter(|n| !n.is_empty() && self.strip_station_name) {
+            if self.prefix.as_ref().is_none_or(|(for_name, _)| for_name != name) {
+                // Only as a whole word: "Radio" must not eat "Radiohead".
+                let re = Regex::new(&format!(r"(?i)^\W*{}\b[\s*~=#|:·–-]*", regex::escape(name))).ok();
+                self.prefix = Some((name.to_string(), re));
+            }
+            let prefix = self.prefix.as_ref().and_then(|(_, re)| re.as_ref());
+            if let Some(end) = prefix.and_then(|re| re.find(&title).map(|m| m.end())) {
+                if !title[end..].trim().is_empty() {
+                    title = title[end..].to_string();
+                }
+            }
+        }
+        let uuid = station.map(|s| s.stationuuid.as_str());
+        for rule in self.rules.iter().filter(|r| r.station.is_none() || r.station.as_deref() == uuid) {
+            title = rule.regex.replace_all(&title, rule.replace.as_str()).into_owned();
+        }
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!title.is_empty()).then_some(title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TitleRule;
+
+    fn station(uuid: &str, name: &str) -> StationRef {
+        StationRef {
+            stationuuid: uuid.to_string(),
+            name: name.to_string(),
+            url: None,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn cleans_station_decoration() {
+        let (mut rules, errors) = TitleRules::new(&TitleConfig::default());
+        assert!(errors.is_empty());
+        let jazz = station("a", "Jazz FM");
+        assert_eq!(
+            rules.clean(Some(&jazz), "JAZZ FM *** Miles Davis - So What ***").as_deref(),
+            Some("Miles Davis - So What")
+        );
+        assert_eq!(rules.clean(Some(&jazz), "  ~~ Jazz FM ~~  ").as_deref(), Some("Jazz FM"));
+        assert_eq!(rules.clean(Some(&jazz), "| Bill Evans - Peace Piece #").as_deref(), Some("Bill Evans - Peace Piece"));
+        assert_eq!(rules.clean(Some(&station("b", "Radio")), "Radiohead - Creep").as_deref(), Some("Radiohead - Creep"));
+        assert_eq!(rules.clean(None, "***"), None);
+        // Single marks that belong to the title stay.
+        assert_eq!(rules.clean(None, "Chopin - Prelude in C#").as_deref(), Some("Chopin - Prelude in C#"));
+        assert_eq!(rules.clean(None, "*NSYNC - Bye Bye Bye").as_deref(), Some("*NSYNC - Bye Bye Bye"));
+    }
+
+    #[test]
+    fn applies_station_rules_only_to_their_station() {
+        let config = TitleConfig {
+            rules: vec![
+                TitleRule {
+                    pattern: r"\s*\[\d+:\d+\]$".to_string(),
+                    replace: String::new(),
+                    station: Some("a".to_string()),
+                },
+                TitleRule {
+                    pattern: "(".to_string(),
+                    replace: String::new(),
+                    station: None,
+                },
+            ],
+            ..TitleConfig::default()
+        };
+        let (mut rules, errors) = TitleRules::new(&config);
+        assert_eq!(errors.len(), 1);
+        let title = "Nina Simone - Sinnerman [10:22]";
+        assert_eq!(rules.clean(Some(&station("a", "Soul")), title).as_deref(), Some("Nina Simone - Sinnerman"));
+        assert_eq!(rules.clean(Some(&station("b", "Soul")), title).as_deref(), Some(title));
+    }
+}
@@ -1,36 +1,410 @@
-use crate::controller::{start_controller, UiCommand, PlaybackPhase};
-use crate::models::{Station, StationRef};
+use crate::cast::Renderer;
+use crate::config::{
+    AdMuteConfig, AlarmConfig, CachePreset, ChannelConfig, EqPreset, EqualizerConfig, HlsVariant, LabelMode, LogLevel, MarqueeSpeed, PanelStyle,
+    PopupView, PrivacyConfig, QualityConfig, RecordingConfig, RecordingSchedule, ResumeConfig, RowAction, StartView, StreamChoice,
+    StreamFallback, UiConfig,
+    QUALITY_CODECS,
+};
+use crate::controller::{latest_states, start_controller, UiCommand, PlaybackPhase, MAX_RECONNECTS};
+use crate::discovery::{group_suggestions, GroupSuggestion};
+use crate::fuzzy;
+use crate::models::{sort_stations, LabelColor, ResultOrder, Station, StationRef};
+use crate::mpv::AudioLevel;
+use crate::stream_probe::{find_stream_url, StreamInfo, StreamKind};
+use crate::ui::avatar::{AvatarCache, Palette, Rgb};
+use crate::ui::text::{
+    countdown, data_size, ellipsize_middle, level_bar, listening_time, marquee_frame, station_facts,
+    station_subtitle, strip_name_prefix, time_ago,
+};
+use crate::ui::widgets::{now_playing_heading, station_entry, station_list, LIST_NAME_CHARS};
 use cosmic::app::{Core, Task};
-use cosmic::iced::{Length, Rectangle};
+use cosmic::iced::mouse::{self, ScrollDelta};
+use cosmic::iced::{Length, Rectangle, Vector};
 use cosmic::iced_runtime::core::window;
 use cosmic::surface::action::{app_popup, destroy_popup};
 use cosmic::widget;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
 
 const APP_ID: &str = "io.github.xinia.RadioWidget";
+/// Approximate width of a body-text character in the panel label.
+const PANEL_CHAR_WIDTH: f32 = 8.0;
+/// A popup that hasn't rendered by then is treated as failed.
+const POPUP_TIMEOUT: Duration = Duration::from_secs(2);
+/// Width of the level meter under now playing, in characters.
+const LEVEL_CELLS: usize = 16;
+const START_VIEWS: [StartView; 3] = [StartView::Search, StartView::Favorites, StartView::Last];
+const START_VIEW_LABELS: [&str; 3] = ["Search", "Favorites", "Last used"];
+const LABEL_MODES: [LabelMode; 3] = [
+    LabelMode::Station,
+    LabelMode::Title,
+    LabelMode::TitleAndStation,
+];
+const LABEL_MODE_LABELS: [&str; 3] = ["Station", "Song", "Song — Station"];
+const MARQUEE_SPEEDS: [MarqueeSpeed; 3] = [MarqueeSpeed::Slow, MarqueeSpeed::Normal, MarqueeSpeed::Fast];
+const MARQUEE_SPEED_LABELS: [&str; 3] = ["Slow", "Normal", "Fast"];
+const CACHE_PRESETS: [CachePreset; 4] = [
+    CachePreset::Default,
+    CachePreset::LowLatency,
+    CachePreset::Robust,
+    CachePreset::Custom,
+];
+const CACHE_PRESET_LABELS: [&str; 4] = ["mpv default", "Low latency", "Robust (shaky Wi-Fi)", "Custom"];
+const HLS_VARIANTS: [HlsVariant; 2] = [HlsVariant::Quality, HlsVariant::DataSaver];
+const HLS_VARIANT_LABELS: [&str; 2] = ["Best quality", "Data saver"];
+const CACHE_SECS: [u32; 7] = [2, 5, 10, 20, 30, 60, 120];
+const CACHE_SECS_LABELS: [&str; 7] = ["2 s", "5 s", "10 s", "20 s", "30 s", "60 s", "120 s"];
+const CACHE_MIBS: [u32; 6] = [1, 4, 16, 32, 64, 150];
+const CACHE_MIB_LABELS: [&str; 6] = ["1 MiB", "4 MiB", "16 MiB", "32 MiB", "64 MiB", "150 MiB"];
+const SNAP_MINUTES: [u32; 6] = [0, 2, 5, 10, 30, 60];
+const SNAP_MINUTE_LABELS: [&str; 6] = ["Never", "2 min", "5 min", "10 min", "30 min", "1 h"];
+const AD_DUCKS: [u8; 4] = [0, 10, 25, 50];
+const AD_DUCK_LABELS: [&str; 4] = ["Muted", "10%", "25%", "50%"];
+const AD_SILENCE_SECS: [u32; 4] = [0, 2, 5, 10];
+const AD_SILENCE_SECS_LABELS: [&str; 4] = ["Off", "2 s", "5 s", "10 s"];
+const AD_SILENCE_DBS: [i32; 3] = [-40, -50, -60];
+const AD_SILENCE_DB_LABELS: [&str; 3] = ["−40 dB", "−50 dB", "−60 dB"];
+const MIN_BITRATES: [u32; 6] = [0, 64, 96, 128, 192, 320];
+const MIN_BITRATE_LABELS: [&str; 6] = ["Any", "64 kbps", "96 kbps", "128 kbps", "192 kbps", "320 kbps"];
+/// "Any", then [`QUALITY_CODECS`].
+const PREFERRED_CODEC_LABELS: [&str; 6] = ["Any", "MP3", "AAC", "AAC+", "OGG", "FLAC"];
+const STREAM_FALLBACKS: [StreamFallback; 3] = [StreamFallback::Listed, StreamFallback::Highest, StreamFallback::Lowest];
+const STREAM_FALLBACK_LABELS: [&str; 3] = ["The listed stream", "Highest bitrate", "Lowest data use"];
+/// A favorite's own stream choice: index 0 follows the global one.
+const PANEL_STYLES: [PanelStyle; 3] = [PanelStyle::Auto, PanelStyle::Label, PanelStyle::Compact];
+const PANEL_STYLE_LABELS: [&str; 3] = ["Automatic", "Label", "Icon only"];
+const LOG_LEVELS: [LogLevel; 3] = [LogLevel::Off, LogLevel::Error, LogLevel::Debug];
+const LOG_LEVEL_LABELS: [&str; 3] = ["Off", "Errors", "Debug"];
+const RESULT_ORDERS: [ResultOrder; 5] = [
+    ResultOrder::Relevance,
+    ResultOrder::Name,
+    ResultOrder::Country,
+    ResultOrder::Bitrate,
+    ResultOrder::Reliability,
+];
+const RESULT_ORDER_LABELS: [&str; 5] = ["Best match", "Name", "Country", "Bitrate", "Reliability"];
+const ROW_ACTIONS: [RowAction; 5] = [
+    RowAction::Play,
+    RowAction::Favorite,
+    RowAction::Details,
+    RowAction::Menu,
+    RowAction::Nothing,
+];
+const ROW_ACTION_LABELS: [&str; 5] = ["Play", "Add or remove favorite", "Show details", "Show actions", "Nothing"];
+/// How long a row has to be held before it counts as a long press.
+const LONG_PRESS: Duration = Duration::from_millis(500);
+const EQ_PRESETS: [EqPreset; 4] = [
+    EqPreset::Flat,
+    EqPreset::Rock,
+    EqPreset::Speech,
+    EqPreset::Custom,
+];
+const EQ_PRESET_LABELS: [&str; 4] = ["Flat", "Rock", "Speech", "Custom"];
+const SPINNER: [&str; 8] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧"];
+const SPINNER_STEP: Duration = Duration::from_millis(120);
+const VOLUME_STEP: f64 = 5.0;
+const VOLUME_OVERLAY_TIMEOUT: Duration = Duration::from_millis(1500);
+const ALREADY_PLAYING_FLASH: Duration = Duration::from_millis(2500);
+/// Sleep timer shortcuts, in minutes.
+const SLEEP_MINUTES: [u64; 3] = [15, 30, 60];
+const ALARM_FADES: [u32; 5] = [1, 5, 15, 30, 60];
+const ALARM_FADE_LABELS: [&str; 5] = ["1 s", "5 s", "15 s", "30 s", "60 s"];
+const RECORDING_LENGTHS: [u32; 6] = [30, 60, 90, 120, 180, 240];
+const RECORDING_LENGTH_LABELS: [&str; 6] = ["30 min", "1 h", "1.5 h", "2 h", "3 h", "4 h"];
+/// Newest recordings listed in the settings; the folder has the rest.
+const MAX_RECORDINGS_SHOWN: usize = 10;
+/// Sunday first, as in [`RecordingSchedule::days`].
+const WEEKDAY_LABELS: [&str; 7] = ["Su", "Mo", "Tu", "We", "Th", "Fr", "Sa"];
 
 pub struct RadioWidget {
     core: Core,
     controller: crate::controller::ControllerHandle,
+    /// Bumped on every controller restart so the state subscription follows
+    /// the new one.
+    controller_generation: u32,
     state: crate::controller::ControllerState,
+    /// Latest level meter reading.
+    level: Option<AudioLevel>,
     popup: Option<cosmic::iced::window::Id>,
-    show_favorites: bool,
+    view: View,
+    consent_draft: PrivacyConfig,
+    /// Generation of the visible volume overlay; `None` when hidden.
+    volume_overlay: Option<u32>,
+    volume_overlay_seq: u32,
+    /// Shows "Already playing" until the matching hide message arrives.
+    already_playing_flash: Option<u64>,
+    search_id: widget::Id,
+    custom_url: String,
+    /// Minutes typed for a custom sleep timer.
+    sleep_minutes: String,
+    /// Ad title pattern being typed.
+    ad_pattern: String,
+    /// Dropdown labels for `state.audio_devices`, kept here so the view can borrow them.
+    audio_device_labels: Vec<String>,
+    /// "00"–"23" and "00"–"55" in five-minute steps, for the alarm time dropdowns.
+    alarm_hour_labels: Vec<String>,
+    alarm_minute_labels: Vec<String>,
+    /// Quick filter typed while the favorites view is open.
+    favorites_filter: String,
+    favorites_edit: Option<FavoritesEdit>,
+    /// Tags whose group suggestion was turned down this session.
+    declined_groups: BTreeSet<String>,
+    /// Whether the persisted UI preferences have been applied yet.
+    ui_applied: bool,
+    /// Generation of the last popup request, for the open watchdog.
+    popup_seq: u32,
+    /// Set by the popup's view, which only runs once the surface exists.
+    popup_shown: Cell<bool>,
+    /// Times the compositor didn't open our popup this session.
+    popup_failures: u32,
+    /// Standalone window used instead of the popup after a failure.
+    fallback_window: Option<window::Id>,
+    // Text derived from `state`, rebuilt when a new state arrives rather than
+    // on every redraw.
+    tooltip_text: String,
+    panel_text: String,
+    /// Width `panel_text` was shortened for, in characters.
+    panel_text_chars: usize,
+    /// Full panel label, for the marquee.
+    panel_full: String,
+    /// Characters the marquee has scrolled by.
+    marquee_offset: usize,
+    /// The pointer is over the panel button; the marquee holds still.
+    panel_hovered: bool,
+    /// Current frame of the buffering spinner.
+    spinner_frame: usize,
+    /// Station subtitles by UUID.
+    subtitles: HashMap<String, String>,
+    avatars: AvatarCache,
+    /// Indices into `state.search_results` in the chosen display order.
+    result_order: Vec<usize>,
+    /// Show results below the quality minimum for the current search.
+    show_filtered: bool,
+    /// Results the quality minimum hides right now.
+    hidden_results: usize,
+    /// Station row currently held down.
+    row_press: Option<RowPress>,
+    row_press_seq: u32,
+    /// Station UUID whose menu or details are open under its row.
+    row_panel: Option<(String, RowPanel)>,
+}
+
+struct RowPress {
+    station: StationRef,
+    /// Generation, so a stale long-press timer is ignored.
+    seq: u32,
+    /// A long press or double-click already acted; letting go does nothing.
+    handled: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RowPanel {
+    Menu,
+    Details,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    Search,
+    /// Suggestions rather than results: the station of the day.
+    Browse,
+    Favorites,
+    Cast,
+    Settings,
+}
+
+/// Favorites being edited in bulk; nothing is saved until the edit is applied.
+struct FavoritesEdit {
+    draft: Vec<StationRef>,
+    /// Station UUIDs.
+    selected: BTreeSet<String>,
+    prefix: String,
+    /// Group to put the selection in; empty takes it out of its group.
+    group: String,
+}
+
+/// A change to one recording schedule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleEdit {
+    ToggleDay(u8),
+    Hour(usize),
+    /// Index into the five-minute steps.
+    Minute(usize),
+    /// Index into [`RECORDING_LENGTHS`].
+    Length(usize),
+    Muted(bool),
+    Enabled(bool),
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EqBand {
+    Bass,
+    Mid,
+    Treble,
 }
 
 #[derive(Clone, Debug)]
 pub enum Message {
     PopupClosed(cosmic::iced::window::Id),
+    OpenPopup(Vector, Rectangle),
+    PopupWatchdog(u32),
     Surface(cosmic::surface::Action),
     ControllerState(crate::controller::ControllerState),
+    Level(Option<AudioLevel>),
     SearchInput(String),
     SearchSubmit,
+    Dictate,
     PlayStation(StationRef),
     ToggleFavorite(StationRef),
-    ToggleFavoritesView,
+    RemoveFavorite(StationRef),
+    UndoRemoveFavorite(String),
+    EditFavorites,
+    FavoritesEditSelect(String, bool),
+    FavoritesEditSelectAll(bool),
+    FavoritesEditPrefix(String),
+    FavoritesEditStripPrefix,
+    FavoritesEditGroup(String),
+    FavoritesEditSetGroup,
+    FavoritesEditDelete,
+    FavoritesEditApply,
+    FavoritesEditCancel,
+    DismissError(u64),
+    DismissNotice,
+    DismissStationOfDay,
+    SetDiscovery(bool),
+    ToggleView(View),
     TogglePause,
+    SeekRelative(f64),
     Stop,
     Noop,
     PlayCurrent,
+    Reload,
+    HideAlreadyPlaying(u64),
+    /// Minutes until playback stops; `None` cancels.
+    SetSleepTimer(Option<u64>),
+    SleepMinutesInput(String),
+    SleepMinutesSubmit,
+    /// Redraw the sleep timer countdown.
+    ClockTick,
+    SetAlarmEnabled(bool),
+    SetAlarmHour(usize),
+    /// Index into the five-minute steps.
+    SetAlarmMinute(usize),
+    /// Wake up to the current station.
+    SetAlarmStation,
+    SetAlarmVolume(f64),
+    SetAlarmFade(usize),
+    StartRecording,
+    StopRecording,
+    /// Record the current station every week.
+    AddRecordingSchedule,
+    EditRecordingSchedule(usize, ScheduleEdit),
+    DeleteRecording(PathBuf),
+    OpenRecordingsFolder,
+    SetSplitSongs(bool),
     ClearCurrent,
+    ConsentCountClicks(bool),
+    ConsentRememberHistory(bool),
+    AcceptConsent,
+    DiscoverRenderers,
+    RefreshFavorites,
+    PlayRandomFavorite,
+    ShuffleGroup(String),
+    AcceptGroupSuggestion(GroupSuggestion),
+    /// Stop suggesting a group for this tag until the next start.
+    DeclineGroupSuggestion(String),
+    PinMirror(Option<String>),
+    SetCastTarget(Option<Renderer>),
+    SetSnapcast(bool),
+    SetLowResource(bool),
+    SetCachePreset(usize),
+    SetCacheSecs(usize),
+    SetCacheSize(usize),
+    SetHlsVariant(usize),
+    SetStandby(bool),
+    SetPauseOnDisconnect(bool),
+    SetPerOutput(bool),
+    SetUsageStats(bool),
+    ClearUsageStats,
+    SetMute(bool),
+    SetResumeOnReconnect(bool),
+    SetSnapToLive(usize),
+    SetInhibitSuspend(bool),
+    SetAdMuteEnabled(bool),
+    SetAdDuck(usize),
+    AdPatternInput(String),
+    AddAdPattern,
+    RemoveAdPattern(usize),
+    SetAdSilence(usize),
+    SetAdSilenceDb(usize),
+    SetMinBitrate(usize),
+    SetPreferredCodec(usize),
+    SetPreferredBitrate(usize),
+    SetStreamFallback(usize),
+    SetFavoriteStream(String, Option<StreamChoice>),
+    SetQualityCodec(&'static str, bool),
+    ShowFilteredResults,
+    /// Pointer or finger down on a station row.
+    RowPressed(StationRef),
+    RowReleased,
+    RowDoubleClicked(StationRef),
+    /// Still held after [`LONG_PRESS`]; carries the press generation.
+    RowHeld(u32),
+    /// The pointer left the row, e.g. to scroll; no click when it's let go.
+    RowPressCancelled,
+    RowAction(RowAction, StationRef),
+    CloseRowPanel,
+    BlockStation(StationRef),
+    UnblockStation(String),
+    SetClickAction(usize),
+    SetDoubleClickAction(usize),
+    SetLongPressAction(usize),
+    SetAudioDevice(usize),
+    SetLogLevel(usize),
+    SetEqPreset(usize),
+    SetEqGain(EqBand, f32),
+    SetMono(bool),
+    SetBalance(f32),
+    VolumeScroll(ScrollDelta),
+    SetVolume(f64),
+    HideVolumeOverlay(u32),
+    WindowFocused(window::Id),
+    CycleFavoriteColor(String),
+    SetTintPanel(bool),
+    SetWrapTitles(bool),
+    SetLockScreenControls(bool),
+    SetLevelMeter(bool),
+    SetMarquee(bool),
+    SetMarqueeSpeed(usize),
+    SetPanelStyle(usize),
+    MarqueeTick,
+    SpinnerTick,
+    PanelHovered(bool),
+    SetStartView(usize),
+    SetLabelMode(usize),
+    SetResultOrder(usize),
+    PasteStream,
+    CustomUrlInput(String),
+    FavoritesFilterInput(String),
+    /// Play the best match for the favorites filter.
+    FavoritesFilterSubmit,
+    CustomUrlSubmit,
+    ClipboardRead(Option<String>),
+    CopyStreamUrl,
+    PlayStreamOffer,
+    SaveStreamOffer,
+    DismissStreamOffer,
+    OpenConfigFolder,
+    ReloadConfig,
+    RetryBackend,
+    RestartBackend,
+    StopReconnecting,
+    RestartController,
 }
 
 impl cosmic::Application for RadioWidget {
@@ -50,16 +424,64 @@ impl cosmic::Application for RadioWidget {
     fn init(core: Core, _flags: Self::Flags) -> (Self, Task<Message>) {
         let controller = start_controller();
         let state = controller.state_rx.borrow().clone();
-        (
-            Self {
-                core,
-                controller,
-                state,
-                popup: None,
-                show_favorites: false,
-            },
-            Task::none(),
-        )
+        let mut app = Self {
+            core,
+            controller,
+            controller_generation: 0,
+            state,
+            level: None,
+            popup: None,
+            view: View::Search,
+            consent_draft: PrivacyConfig::default(),
+            volume_overlay: None,
+            volume_overlay_seq: 0,
+            already_playing_flash: None,
+            search_id: widget::Id::unique(),
+            custom_url: String::new(),
+            sleep_minutes: String::new(),
+            ad_pattern: String::new(),
+            favorites_filter: String::new(),
+            favorites_edit: None,
+            declined_groups: BTreeSet::new(),
+            audio_device_labels: Vec::new(),
+            alarm_hour_labels: (0..24).map(|h| format!("{h:02}")).collect(),
+            alarm_minute_labels: (0..60).step_by(5).map(|m| format!("{m:02}")).collect(),
+            ui_applied: false,
+            popup_seq: 0,
+            popup_shown: Cell::new(false),
+            popup_failures: 0,
+            fallback_window: None,
+            tooltip_text: String::new(),
+            panel_text: String::new(),
+            panel_text_chars: 0,
+            panel_full: String::new(),
+            marquee_offset: 0,
+            panel_hovered: false,
+            spinner_frame: 0,
+            subtitles: HashMap::new(),
+            avatars: AvatarCache::default(),
+            result_order: Vec::new(),
+            show_filtered: false,
+            hidden_results: 0,
+            row_press: None,
+            row_press_seq: 0,
+            row_panel: None,
+        };
+        app.avatars.set_palette(theme_palette(cosmic::theme::active().cosmic()));
+        app.refresh_labels();
+        app.refresh_avatars();
+        (app, Task::none())
+    }
+
+    fn system_theme_update(
+        &mut self,
+        _keys: &[&'static str],
+        new_theme: &cosmic::cosmic_theme::Theme,
+    ) -> Task<Message> {
+        if self.avatars.set_palette(theme_palette(new_theme)) {
+            self.refresh_avatars();
+        }
+        Task::none()
     }
 
     fn on_close_requested(&self, id: window::Id) -> Option<Message> {
@@ -67,21 +489,47 @@ impl cosmic::Application for RadioWidget {
     }
 
     fn subscription(&self) -> cosmic::iced::Subscription<Message> {
-        use cosmic::iced_futures::futures::SinkExt;
-
-        let mut rx = self.controller.state_rx.clone();
-        cosmic::iced::Subscription::run_with_id(
-            "controller_state",
-            cosmic::iced_futures::stream::channel(16, move |mut output| async move {
-                loop {
-                    if rx.changed().await.is_err() {
-                        break;
-                    }
-                    let snapshot = rx.borrow().clone();
-                    let _ = output.send(Message::ControllerState(snapshot)).await;
-                }
-            }),
-        )
+        use cosmic::iced::{event, Event};
+        use cosmic::iced_futures::futures::StreamExt;
+
+        let state = cosmic::iced::Subscription::run_with_id(
+            ("controller_state", self.controller_generation),
+            latest_states(self.controller.state_rx.clone()).map(Message::ControllerState),
+        );
+        let level = cosmic::iced::Subscription::run_with_id(
+            ("controller_level", self.controller_generation),
+            latest_states(self.controller.level_rx.clone()).map(Message::Level),
+        );
+
+        // Popups open unfocused from the input's point of view; route window
+        // focus back through update so the search field can grab it.
+        let focus = event::listen_with(|event, _status, id| match event {
+            Event::Window(window::Event::Opened { .. } | window::Event::Focused) => {
+                Some(Message::WindowFocused(id))
+            }
+            _ => None,
+        });
+
+        let marquee = if self.marquee_running() {
+            let step = Duration::from_millis(self.state.ui.marquee_speed.step_ms());
+            cosmic::iced::time::every(step).map(|_| Message::MarqueeTick)
+        } else {
+            cosmic::iced::Subscription::none()
+        };
+
+        let spinner = if self.waiting_for_audio() {
+            cosmic::iced::time::every(SPINNER_STEP).map(|_| Message::SpinnerTick)
+        } else {
+            cosmic::iced::Subscription::none()
+        };
+
+        let clock = if self.state.sleep_until.is_some() {
+            cosmic::iced::time::every(Duration::from_secs(1)).map(|_| Message::ClockTick)
+        } else {
+            cosmic::iced::Subscription::none()
+        };
+
+        cosmic::iced::Subscription::batch([state, level, focus, marquee, spinner, clock])
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
@@ -89,14 +537,231 @@ impl cosmic::Application for RadioWidget {
             Message::PopupClosed(id) => {
                 if self.popup.as_ref() == Some(&id) {
                     self.popup = None;
+                    self.view = self.start_view();
+                }
+                if self.fallback_window == Some(id) {
+                    self.fallback_window = None;
+                    self.view = self.start_view();
                 }
                 Task::none()
             }
+            Message::OpenPopup(offset, bounds) => {
+                if self.popup_failures > 0 {
+                    // Don't make every click wait out another timeout.
+                    return self.toggle_fallback_window();
+                }
+                let Some(main_window) = self.core.main_window_id() else {
+                    return self.popup_failed("applet has no main window");
+                };
+                self.popup_seq = self.popup_seq.wrapping_add(1);
+                self.popup_shown.set(false);
+                let seq = self.popup_seq;
+                let popup = app_popup::<RadioWidget>(
+                    move |state: &mut RadioWidget| {
+                        let new_id = cosmic::iced::window::Id::unique();
+                        state.popup = Some(new_id);
+                        let mut popup_settings = state.core.applet.get_popup_settings(
+                            main_window,
+                            new_id,
+                            None,
+                            None,
+                            None,
+                        );
+
+                        popup_settings.positioner.anchor_rect = Rectangle {
+                            x: (bounds.x - offset.x) as i32,
+                            y: (bounds.y - offset.y) as i32,
+                            width: bounds.width as i32,
+                            height: bounds.height as i32,
+                        };
+
+                        popup_settings
+                    },
+                    Some(Box::new(|state: &RadioWidget| {
+                        state.popup_shown.set(true);
+                        state.popup_content().map(cosmic::Action::App)
+                    })),
+                );
+                Task::batch([
+                    cosmic::task::message(cosmic::Action::Cosmic(cosmic::app::Action::Surface(
+                        popup,
+                    ))),
+                    cosmic::task::future(async move {
+                        tokio::time::sleep(POPUP_TIMEOUT).await;
+                        Message::PopupWatchdog(seq)
+                    }),
+                ])
+            }
+            Message::PopupWatchdog(seq) => {
+                if seq != self.popup_seq || self.popup_shown.get() || self.popup.is_none() {
+                    return Task::none();
+                }
+                self.popup_failed("popup surface did not appear")
+            }
             Message::Surface(a) => cosmic::task::message(cosmic::Action::Cosmic(
                 cosmic::app::Action::Surface(a),
             )),
             Message::ControllerState(s) => {
+                let flash = (s.already_playing != self.state.already_playing).then_some(s.already_playing);
                 self.state = s;
+                self.audio_device_labels = self
+                    .state
+                    .audio_devices
+                    .iter()
+                    .map(|d| {
+                        if d.description.is_empty() {
+                            d.name.clone()
+                        } else {
+                            d.description.clone()
+                        }
+                    })
+                    .collect();
+                self.refresh_labels();
+                self.refresh_avatars();
+                if self.state.ready && !self.ui_applied {
+                    self.ui_applied = true;
+                    self.view = self.start_view();
+                }
+                let Some(seq) = flash else {
+                    return Task::none();
+                };
+                self.already_playing_flash = Some(seq);
+                cosmic::task::future(async move {
+                    tokio::time::sleep(ALREADY_PLAYING_FLASH).await;
+                    Message::HideAlreadyPlaying(seq)
+                })
+            }
+            Message::Level(level) => {
+                self.level = level;
+                Task::none()
+            }
+            Message::HideAlreadyPlaying(seq) => {
+                if self.already_playing_flash == Some(seq) {
+                    self.already_playing_flash = None;
+                }
+                Task::none()
+            }
+            Message::SetSleepTimer(minutes) => {
+                let after = minutes.map(|m| Duration::from_secs(m * 60));
+                let _ = self.controller.cmd_tx.send(UiCommand::SetSleepTimer(after));
+                Task::none()
+            }
+            Message::SleepMinutesInput(text) => {
+                self.sleep_minutes = text.chars().filter(char::is_ascii_digit).take(3).collect();
+                Task::none()
+            }
+            Message::SleepMinutesSubmit => {
+                if let Some(minutes) = self.sleep_minutes.parse().ok().filter(|m| *m > 0) {
+                    self.sleep_minutes.clear();
+                    return self.update(Message::SetSleepTimer(Some(minutes)));
+                }
+                Task::none()
+            }
+            Message::ClockTick => Task::none(),
+            Message::SetAlarmEnabled(enabled) => {
+                let station = self.state.station.clone();
+                self.send_alarm(|a| {
+                    a.enabled = enabled;
+                    if a.station.is_none() {
+                        a.station = station;
+                    }
+                });
+                Task::none()
+            }
+            Message::SetAlarmHour(hour) => {
+                self.send_alarm(|a| a.hour = hour as u8);
+                Task::none()
+            }
+            Message::SetAlarmMinute(idx) => {
+                self.send_alarm(|a| a.minute = idx as u8 * 5);
+                Task::none()
+            }
+            Message::SetAlarmStation => {
+                let station = self.state.station.clone();
+                self.send_alarm(|a| a.station = station);
+                Task::none()
+            }
+            Message::SetAlarmVolume(volume) => {
+                self.send_alarm(|a| a.volume = volume.round() as u8);
+                Task::none()
+            }
+            Message::SetAlarmFade(idx) => {
+                if let Some(&secs) = ALARM_FADES.get(idx) {
+                    self.send_alarm(|a| a.fade_in_secs = secs);
+                }
+                Task::none()
+            }
+            Message::StartRecording => {
+                let _ = self.controller.cmd_tx.send(UiCommand::StartRecording);
+                Task::none()
+            }
+            Message::StopRecording => {
+                let _ = self.controller.cmd_tx.send(UiCommand::StopRecording);
+                Task::none()
+            }
+            Message::AddRecordingSchedule => {
+                if let Some(station) = self.state.station.clone() {
+                    self.send_recording(|r| {
+                        r.schedules.push(RecordingSchedule {
+                            station,
+                            days: Vec::new(),
+                            hour: 18,
+                            minute: 0,
+                            minutes: 60,
+                            muted: true,
+                            enabled: true,
+                        })
+                    });
+                }
+                Task::none()
+            }
+            Message::EditRecordingSchedule(i, edit) => {
+                self.send_recording(|r| {
+                    if edit == ScheduleEdit::Remove {
+                        if i < r.schedules.len() {
+                            r.schedules.remove(i);
+                        }
+                        return;
+                    }
+                    let Some(schedule) = r.schedules.get_mut(i) else {
+                        return;
+                    };
+                    match edit {
+                        ScheduleEdit::ToggleDay(day) => match schedule.days.iter().position(|d| *d == day) {
+                            Some(pos) => {
+                                schedule.days.remove(pos);
+                            }
+                            None => schedule.days.push(day),
+                        },
+                        ScheduleEdit::Hour(hour) => schedule.hour = hour as u8,
+                        ScheduleEdit::Minute(idx) => schedule.minute = idx as u8 * 5,
+                        ScheduleEdit::Length(idx) => {
+                            if let Some(&minutes) = RECORDING_LENGTHS.get(idx) {
+                                schedule.minutes = minutes;
+                            }
+                        }
+                        ScheduleEdit::Muted(muted) => schedule.muted = muted,
+                        ScheduleEdit::Enabled(enabled) => schedule.enabled = enabled,
+                        ScheduleEdit::Remove => {}
+                    }
+                });
+                Task::none()
+            }
+            Message::DeleteRecording(path) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::DeleteRecording(path));
+                Task::none()
+            }
+            Message::OpenRecordingsFolder => {
+                let _ = self.controller.cmd_tx.send(UiCommand::OpenRecordingsFolder);
+                Task::none()
+            }
+            Message::SetSplitSongs(split) => {
+                self.send_recording(|r| r.split_songs = split);
+                Task::none()
+            }
+            Message::Reload => {
+                self.already_playing_flash = None;
+                let _ = self.controller.cmd_tx.send(UiCommand::Reload);
                 Task::none()
             }
             Message::SearchInput(s) => {
@@ -104,12 +769,18 @@ impl cosmic::Application for RadioWidget {
                 Task::none()
             }
             Message::SearchSubmit => {
+                self.show_filtered = false;
                 let _ = self
                     .controller
                     .cmd_tx
                     .send(UiCommand::Search(self.state.search_query.clone()));
                 Task::none()
             }
+            Message::Dictate => {
+                self.show_filtered = false;
+                let _ = self.controller.cmd_tx.send(UiCommand::Dictate);
+                Task::none()
+            }
             Message::PlayCurrent => {
                 if let Some(st) = &self.state.station {
                     let _ = self.controller.cmd_tx.send(UiCommand::Play(st.clone()));
@@ -124,265 +795,2791 @@ impl cosmic::Application for RadioWidget {
                 let _ = self.controller.cmd_tx.send(UiCommand::Play(s));
                 Task::none()
             }
+            Message::RemoveFavorite(s) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::RemoveFavorite(s));
+                Task::none()
+            }
+            Message::UndoRemoveFavorite(stationuuid) => {
+                let _ = self
+                    .controller
+                    .cmd_tx
+                    .send(UiCommand::UndoRemoveFavorite(stationuuid));
+                Task::none()
+            }
+            Message::DismissNotice => {
+                self.state.notice = None;
+                let _ = self.controller.cmd_tx.send(UiCommand::DismissNotice);
+                Task::none()
+            }
+            Message::DismissStationOfDay => {
+                self.state.station_of_day = None;
+                let _ = self.controller.cmd_tx.send(UiCommand::DismissStationOfDay);
+                Task::none()
+            }
+            Message::SetDiscovery(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetDiscovery(enabled));
+                Task::none()
+            }
+            Message::DismissError(id) => {
+                // Input errors only exist here; the rest go away with the next state.
+                self.state.errors.dismiss(id);
+                let _ = self.controller.cmd_tx.send(UiCommand::DismissError(id));
+                Task::none()
+            }
             Message::ToggleFavorite(s) => {
                 let _ = self.controller.cmd_tx.send(UiCommand::ToggleFavorite(s));
                 Task::none()
             }
-            Message::ToggleFavoritesView => {
-                self.show_favorites = !self.show_favorites;
+            Message::ToggleView(view) => {
+                self.view = if self.view == view { View::Search } else { view };
+                self.favorites_edit = None;
+                let list = match self.view {
+                    View::Search => Some(PopupView::Search),
+                    View::Favorites => Some(PopupView::Favorites),
+                    View::Browse | View::Cast | View::Settings => None,
+                };
+                if let Some(list) = list {
+                    self.update_ui(|ui| ui.last_view = list);
+                }
+                if self.view == View::Cast
+                    && self.state.renderers.is_empty()
+                    && !self.state.renderers_loading
+                {
+                    let _ = self.controller.cmd_tx.send(UiCommand::DiscoverRenderers);
+                }
                 Task::none()
             }
-            Message::TogglePause => {
-                let _ = self.controller.cmd_tx.send(UiCommand::TogglePause);
+            Message::EditFavorites => {
+                self.favorites_edit = Some(FavoritesEdit {
+                    draft: self.state.favorites.clone(),
+                    selected: BTreeSet::new(),
+                    prefix: "Radio ".to_string(),
+                    group: String::new(),
+                });
                 Task::none()
             }
-            Message::Stop => {
-                let _ = self.controller.cmd_tx.send(UiCommand::Stop);
+            Message::FavoritesEditSelect(uuid, selected) => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    if selected {
+                        edit.selected.insert(uuid);
+                    } else {
+                        edit.selected.remove(&uuid);
+                    }
+                }
                 Task::none()
-            }            
-            Message::Noop => Task::none(),
-        }
-    }
-
-    fn view(&self) -> cosmic::Element<'_, Message> {
-        let have_popup = self.popup;
-
-        let tooltip_text = self
-            .state
-            .station
-            .as_ref()
-            .map(|s| s.name.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "Radio".to_string());
-
-        // What we show in the panel:
-        let is_horizontal = self.core.applet.is_horizontal();
-
-        let btn = (if is_horizontal {
-            let label = ellipsize_chars(&tooltip_text, 30);
-
-            self.core.applet.text_button(
-                widget::text::body(label).width(Length::Fixed(240.0)),
-                Message::Noop,
-            )
-            .width(Length::Fixed(240.0))
-        } else {
-            // Vertical panels: keep it compact.
-            self.core.applet.icon_button("audio-x-generic-symbolic")
-            // If icon doesn't show, fallback to short text:
-            // self.core.applet.text_button(widget::text::body("RAD"), Message::Noop)
-        })
-        .on_press_with_rectangle(move |offset, bounds| {
-            if let Some(id) = have_popup {
-                Message::Surface(destroy_popup(id))
-            } else {
-                Message::Surface(app_popup::<RadioWidget>(
-                    move |state: &mut RadioWidget| {
-                        let new_id = cosmic::iced::window::Id::unique();
-                        state.popup = Some(new_id);
-                        let mut popup_settings = state.core.applet.get_popup_settings(
-                            state.core.main_window_id().unwrap(),
-                            new_id,
-                            None,
-                            None,
-                            None,
-                        );
-
-                        popup_settings.positioner.anchor_rect = Rectangle {
-                            x: (bounds.x - offset.x) as i32,
-                            y: (bounds.y - offset.y) as i32,
-                            width: bounds.width as i32,
-                            height: bounds.height as i32,
-                        };
-
-                        popup_settings
-                    },
-                    Some(Box::new(|state: &RadioWidget| {
-                        state.popup_content().map(cosmic::Action::App)
-                    })),
-                ))
             }
-        });
-
-        let with_tooltip = self.core.applet.applet_tooltip::<Message>(
-            btn,
-            tooltip_text,
-            self.popup.is_some(),
-            Message::Surface,
-            None,
-        );
-
-        // Only autosize when horizontal (vertical should stay compact)
-        if is_horizontal {
-            self.core.applet.autosize_window(with_tooltip).into()
-        } else {
-            with_tooltip.into()
-        }
-    }
-
-    fn view_window(&self, _id: cosmic::iced::window::Id) -> cosmic::Element<'_, Message> {
-        "RadioWidget".into()
-    }
-
-    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
-        Some(cosmic::applet::style())
-    }
-}
-
-// Simple char-based ellipsis
-fn ellipsize_chars(s: &str, max_chars: usize) -> String {
-    let mut it = s.chars();
-    let taken: String = it.by_ref().take(max_chars).collect();
-    if it.next().is_some() {
-        format!("{taken}…")
-    } else {
-        taken
-    }
-}
-
-impl RadioWidget {
-    fn popup_content(&self) -> cosmic::Element<'_, Message> {
-        let cosmic::cosmic_theme::Spacing {
-            space_xxs,
-            space_s,
-            ..
-        } = cosmic::theme::spacing();
-
-        let search = widget::search_input("Search stations…", &self.state.search_query)
-            .on_input(Message::SearchInput)
-            .on_submit(|_| Message::SearchSubmit);
-
-        let fav_star = if self.show_favorites { "★" } else { "☆" };
-        let header = widget::row()
-            .spacing(space_xxs)
-            .push(search.width(Length::Fill))
-            .push(widget::button::text(fav_star).on_press(Message::ToggleFavoritesView));
-
-        let mut content = widget::column()
-            .spacing(space_s)
-            .padding(space_s)
-            .push(header);
-
-        // Idle-with-station: explicit play/clear
-        if self.state.phase == PlaybackPhase::Idle && self.state.station.is_some() {
-            let controls = widget::row()
-                .spacing(space_xxs)
-                .push(widget::button::text("Play").on_press(Message::PlayCurrent))
-                .push(widget::button::text("Clear").on_press(Message::ClearCurrent));
-            content = content.push(controls);
-        }
-
-        // Playing/Paused: pause/stop (single block)
-        if matches!(self.state.phase, PlaybackPhase::Playing | PlaybackPhase::Paused) {
-            let pause_label = if self.state.phase == PlaybackPhase::Paused {
-                "Resume"
-            } else {
-                "Pause"
-            };
-
-            let controls = widget::row()
-                .spacing(space_xxs)
-                .push(widget::button::text(pause_label).on_press(Message::TogglePause))
-                .push(widget::button::text("Stop").on_press(Message::Stop));
-
-            content = content.push(controls);
-        }
-
-        // Main body (favorites vs search/results/errors)
-        if self.show_favorites {
-            if self.state.favorites.is_empty() {
-                content = content.push(widget::text::body("No favorites yet."));
-            } else {
-                content = content.push(self.favorites_list(&self.state.favorites));
+            Message::FavoritesEditSelectAll(selected) => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    edit.selected = if selected {
+                        edit.draft.iter().map(|s| s.stationuuid.clone()).collect()
+                    } else {
+                        BTreeSet::new()
+                    };
+                }
+                Task::none()
             }
-        } else if let Some(err) = &self.state.error {
-            content = content.push(widget::text::body(err));
-        } else if self.state.search_loading {
-            content = content.push(widget::text::body("Loading…"));
-        } else if self.state.search_results.is_empty() {
-            content = content.push(widget::text::body("Search to choose a station."));
-        } else {
-            content = content.push(self.results_list(&self.state.search_results));
-        }
-
-        cosmic::Element::from(self.core.applet.popup_container(content))
-    }
-
-    fn results_list<'a>(&'a self, stations: &'a [Station]) -> cosmic::Element<'a, Message> {
-        let mut list = widget::list_column().padding(0).spacing(0);
-
-        for s in stations {
-            let subtitle = station_subtitle(s);
-            let station_ref = StationRef {
-                stationuuid: s.stationuuid.clone(),
-                name: s.name.clone(),
-            };
-            let is_fav = self
+            Message::FavoritesEditPrefix(prefix) => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    edit.prefix = prefix;
+                }
+                Task::none()
+            }
+            Message::FavoritesEditStripPrefix => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    for s in edit.draft.iter_mut().filter(|s| edit.selected.contains(&s.stationuuid)) {
+                        if let Some(name) = strip_name_prefix(&s.name, &edit.prefix) {
+                            s.name = name;
+                        }
+                    }
+                }
+                Task::none()
+            }
+            Message::FavoritesEditGroup(group) => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    edit.group = group;
+                }
+                Task::none()
+            }
+            Message::FavoritesEditSetGroup => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    let group = Some(edit.group.trim().to_string()).filter(|g| !g.is_empty());
+                    for s in edit.draft.iter_mut().filter(|s| edit.selected.contains(&s.stationuuid)) {
+                        s.group = group.clone();
+                    }
+                }
+                Task::none()
+            }
+            Message::FavoritesEditDelete => {
+                if let Some(edit) = self.favorites_edit.as_mut() {
+                    edit.draft.retain(|s| !edit.selected.contains(&s.stationuuid));
+                    edit.selected.clear();
+                }
+                Task::none()
+            }
+            Message::FavoritesEditApply => {
+                if let Some(edit) = self.favorites_edit.take() {
+                    let _ = self.controller.cmd_tx.send(UiCommand::EditFavorites(edit.draft));
+                }
+                Task::none()
+            }
+            Message::FavoritesEditCancel => {
+                self.favorites_edit = None;
+                Task::none()
+            }
+            Message::PinMirror(server) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::PinMirror(server));
+                Task::none()
+            }
+            Message::PlayRandomFavorite => {
+                let _ = self.controller.cmd_tx.send(UiCommand::PlayRandomFavorite);
+                Task::none()
+            }
+            Message::ShuffleGroup(group) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::ShuffleGroup(group));
+                Task::none()
+            }
+            Message::AcceptGroupSuggestion(suggestion) => {
+                let mut favorites = self.state.favorites.clone();
+                for f in favorites.iter_mut().filter(|f| suggestion.members.contains(&f.stationuuid)) {
+                    f.group = Some(suggestion.name.clone());
+                }
+                let _ = self.controller.cmd_tx.send(UiCommand::EditFavorites(favorites));
+                Task::none()
+            }
+            Message::DeclineGroupSuggestion(tag) => {
+                self.declined_groups.insert(tag);
+                Task::none()
+            }
+            Message::RefreshFavorites => {
+                let _ = self.controller.cmd_tx.send(UiCommand::RefreshFavorites);
+                Task::none()
+            }
+            Message::DiscoverRenderers => {
+                let _ = self.controller.cmd_tx.send(UiCommand::DiscoverRenderers);
+                Task::none()
+            }
+            Message::SetCastTarget(target) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetCastTarget(target));
+                Task::none()
+            }
+            Message::SetSnapcast(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetSnapcast(enabled));
+                Task::none()
+            }
+            Message::SetAudioDevice(idx) => {
+                if let Some(device) = self.state.audio_devices.get(idx) {
+                    let device = (device.name != "auto").then(|| device.name.clone());
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetAudioDevice(device));
+                }
+                Task::none()
+            }
+            Message::SetStandby(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetStandby(enabled));
+                Task::none()
+            }
+            Message::SetPauseOnDisconnect(pause) => {
+                let resume = self.state.output.resume_on_reconnect;
+                let _ = self.controller.cmd_tx.send(UiCommand::SetDisconnectPolicy { pause, resume });
+                Task::none()
+            }
+            Message::SetPerOutput(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetPerOutput(enabled));
+                Task::none()
+            }
+            Message::SetUsageStats(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetUsageStats(enabled));
+                Task::none()
+            }
+            Message::ClearUsageStats => {
+                let _ = self.controller.cmd_tx.send(UiCommand::ClearUsageStats);
+                Task::none()
+            }
+            Message::SetMute(mute) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetMute(mute));
+                Task::none()
+            }
+            Message::SetResumeOnReconnect(resume) => {
+                let pause = self.state.output.pause_on_disconnect;
+                let _ = self.controller.cmd_tx.send(UiCommand::SetDisconnectPolicy { pause, resume });
+                Task::none()
+            }
+            Message::SetInhibitSuspend(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetInhibitSuspend(enabled));
+                Task::none()
+            }
+            Message::SetAdMuteEnabled(enabled) => {
+                self.send_ad_mute(|a| a.enabled = enabled);
+                Task::none()
+            }
+            Message::SetAdDuck(idx) => {
+                if let Some(&percent) = AD_DUCKS.get(idx) {
+                    self.send_ad_mute(|a| a.duck_percent = percent);
+                }
+                Task::none()
+            }
+            Message::AdPatternInput(text) => {
+                self.ad_pattern = text;
+                Task::none()
+            }
+            Message::AddAdPattern => {
+                let pattern = std::mem::take(&mut self.ad_pattern).trim().to_string();
+                if !pattern.is_empty() && !self.state.ad_mute.patterns.contains(&pattern) {
+                    self.send_ad_mute(|a| a.patterns.push(pattern));
+                }
+                Task::none()
+            }
+            Message::RemoveAdPattern(idx) => {
+                if idx < self.state.ad_mute.patterns.len() {
+                    self.send_ad_mute(|a| {
+                        a.patterns.remove(idx);
+                    });
+                }
+                Task::none()
+            }
+            Message::SetAdSilence(idx) => {
+                if let Some(&secs) = AD_SILENCE_SECS.get(idx) {
+                    self.send_ad_mute(|a| a.silence_secs = secs);
+                }
+                Task::none()
+            }
+            Message::SetAdSilenceDb(idx) => {
+                if let Some(&db) = AD_SILENCE_DBS.get(idx) {
+                    self.send_ad_mute(|a| a.silence_db = db);
+                }
+                Task::none()
+            }
+            Message::SetMinBitrate(idx) => {
+                if let Some(&kbps) = MIN_BITRATES.get(idx) {
+                    self.send_quality(|q| q.min_bitrate = kbps);
+                }
+                Task::none()
+            }
+            Message::SetPreferredCodec(idx) => {
+                let mut choice = self.state.stream_choice.clone();
+                choice.codec = preferred_codec(idx);
+                let _ = self.controller.cmd_tx.send(UiCommand::SetStreamChoice(choice));
+                Task::none()
+            }
+            Message::SetPreferredBitrate(idx) => {
+                if let Some(&kbps) = MIN_BITRATES.get(idx) {
+                    let mut choice = self.state.stream_choice.clone();
+                    choice.min_bitrate = kbps;
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetStreamChoice(choice));
+                }
+                Task::none()
+            }
+            Message::SetStreamFallback(idx) => {
+                if let Some(&otherwise) = STREAM_FALLBACKS.get(idx) {
+                    let mut choice = self.state.stream_choice.clone();
+                    choice.otherwise = otherwise;
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetStreamChoice(choice));
+                }
+                Task::none()
+            }
+            Message::SetFavoriteStream(stationuuid, choice) => {
+                let _ = self
+                    .controller
+                    .cmd_tx
+                    .send(UiCommand::SetFavoriteStreamChoice(stationuuid, choice));
+                Task::none()
+            }
+            Message::SetQualityCodec(codec, allowed) => {
+                self.send_quality(|q| q.set_codec(codec, allowed));
+                Task::none()
+            }
+            Message::ShowFilteredResults => {
+                self.show_filtered = true;
+                self.refresh_labels();
+                Task::none()
+            }
+            Message::RowPressed(station) => {
+                self.row_press_seq = self.row_press_seq.wrapping_add(1);
+                let seq = self.row_press_seq;
+                self.row_press = Some(RowPress {
+                    station,
+                    seq,
+                    handled: false,
+                });
+                cosmic::task::future(async move {
+                    tokio::time::sleep(LONG_PRESS).await;
+                    Message::RowHeld(seq)
+                })
+            }
+            Message::RowHeld(seq) => {
+                if let Some(press) = self.row_press.as_mut().filter(|p| p.seq == seq && !p.handled) {
+                    press.handled = true;
+                    let station = press.station.clone();
+                    self.row_action(self.state.ui.row_actions.long_press, station);
+                }
+                Task::none()
+            }
+            Message::RowReleased => {
+                if let Some(press) = self.row_press.take().filter(|p| !p.handled) {
+                    self.row_action(self.state.ui.row_actions.click, press.station);
+                }
+                Task::none()
+            }
+            Message::RowDoubleClicked(station) => {
+                let action = self.state.ui.row_actions.double_click;
+                if action != RowAction::Nothing {
+                    // The second click's release mustn't run the click action again.
+                    if let Some(press) = self.row_press.as_mut() {
+                        press.handled = true;
+                    }
+                    self.row_action(action, station);
+                }
+                Task::none()
+            }
+            Message::RowPressCancelled => {
+                self.row_press = None;
+                Task::none()
+            }
+            Message::RowAction(action, station) => {
+                self.row_action(action, station);
+                Task::none()
+            }
+            Message::CloseRowPanel => {
+                self.row_panel = None;
+                Task::none()
+            }
+            Message::BlockStation(station) => {
+                self.row_panel = None;
+                self.update_ui(|ui| {
+                    if !ui.blocked.iter().any(|b| b.stationuuid == station.stationuuid) {
+                        ui.blocked.push(station);
+                    }
+                });
+                Task::none()
+            }
+            Message::UnblockStation(stationuuid) => {
+                self.update_ui(|ui| ui.blocked.retain(|b| b.stationuuid != stationuuid));
+                Task::none()
+            }
+            Message::SetClickAction(idx) => {
+                if let Some(&action) = ROW_ACTIONS.get(idx) {
+                    self.update_ui(|ui| ui.row_actions.click = action);
+                }
+                Task::none()
+            }
+            Message::SetDoubleClickAction(idx) => {
+                if let Some(&action) = ROW_ACTIONS.get(idx) {
+                    self.update_ui(|ui| ui.row_actions.double_click = action);
+                }
+                Task::none()
+            }
+            Message::SetLongPressAction(idx) => {
+                if let Some(&action) = ROW_ACTIONS.get(idx) {
+                    self.update_ui(|ui| ui.row_actions.long_press = action);
+                }
+                Task::none()
+            }
+            Message::SetSnapToLive(idx) => {
+                if let Some(&minutes) = SNAP_MINUTES.get(idx) {
+                    let resume = ResumeConfig { snap_to_live_minutes: minutes };
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetResume(resume));
+                }
+                Task::none()
+            }
+            Message::SetCachePreset(idx) => {
+                if let Some(&preset) = CACHE_PRESETS.get(idx) {
+                    self.send_cache(preset, self.state.output.cache_secs, self.state.output.demuxer_max_mib);
+                }
+                Task::none()
+            }
+            Message::SetCacheSecs(idx) => {
+                if let Some(&secs) = CACHE_SECS.get(idx) {
+                    self.send_cache(CachePreset::Custom, secs, self.state.output.demuxer_max_mib);
+                }
+                Task::none()
+            }
+            Message::SetCacheSize(idx) => {
+                if let Some(&mib) = CACHE_MIBS.get(idx) {
+                    self.send_cache(CachePreset::Custom, self.state.output.cache_secs, mib);
+                }
+                Task::none()
+            }
+            Message::SetHlsVariant(idx) => {
+                if let Some(&hls) = HLS_VARIANTS.get(idx) {
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetHlsVariant(hls));
+                }
+                Task::none()
+            }
+            Message::SetLowResource(enabled) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SetLowResource(enabled));
+                Task::none()
+            }
+            Message::TogglePause => {
+                let _ = self.controller.cmd_tx.send(UiCommand::TogglePause);
+                Task::none()
+            }
+            Message::SeekRelative(secs) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::SeekRelative(secs));
+                Task::none()
+            }
+            Message::Stop => {
+                let _ = self.controller.cmd_tx.send(UiCommand::Stop);
+                Task::none()
+            }            
+            Message::ConsentCountClicks(v) => {
+                self.consent_draft.count_clicks = v;
+                Task::none()
+            }
+            Message::ConsentRememberHistory(v) => {
+                self.consent_draft.remember_history = v;
+                Task::none()
+            }
+            Message::AcceptConsent => {
+                let _ = self
+                    .controller
+                    .cmd_tx
+                    .send(UiCommand::AcceptConsent(self.consent_draft.clone()));
+                Task::none()
+            }
+            Message::VolumeScroll(delta) => {
+                let y = match delta {
+                    ScrollDelta::Lines { y, .. } | ScrollDelta::Pixels { y, .. } => y,
+                };
+                if y == 0.0 {
+                    return Task::none();
+                }
+                let step = if y > 0.0 { VOLUME_STEP } else { -VOLUME_STEP };
+                let _ = self.controller.cmd_tx.send(UiCommand::AdjustVolume(step));
+
+                self.volume_overlay_seq = self.volume_overlay_seq.wrapping_add(1);
+                let seq = self.volume_overlay_seq;
+                self.volume_overlay = Some(seq);
+                cosmic::task::future(async move {
+                    tokio::time::sleep(VOLUME_OVERLAY_TIMEOUT).await;
+                    Message::HideVolumeOverlay(seq)
+                })
+            }
+            Message::SetVolume(volume) => {
+                // Move the slider right away; mpv confirms through the observer.
+                self.state.volume = volume;
+                let _ = self.controller.cmd_tx.send(UiCommand::SetVolume(volume));
+                Task::none()
+            }
+            Message::HideVolumeOverlay(seq) => {
+                if self.volume_overlay == Some(seq) {
+                    self.volume_overlay = None;
+                }
+                Task::none()
+            }
+            Message::WindowFocused(id) => {
+                if self.popup != Some(id) && self.fallback_window != Some(id) {
+                    return Task::none();
+                }
+                Task::batch([
+                    widget::text_input::focus(self.search_id.clone()),
+                    widget::text_input::select_all(self.search_id.clone()),
+                ])
+            }
+            Message::CycleFavoriteColor(stationuuid) => {
+                let current = self.state.favorite_colors.get(&stationuuid).copied();
+                let next = LabelColor::cycle(current);
+                let _ = self
+                    .controller
+                    .cmd_tx
+                    .send(UiCommand::SetFavoriteColor(stationuuid, next));
+                Task::none()
+            }
+            Message::SetTintPanel(enabled) => {
+                self.update_ui(|ui| ui.tint_panel = enabled);
+                Task::none()
+            }
+            Message::SetWrapTitles(enabled) => {
+                self.update_ui(|ui| ui.wrap_titles = enabled);
+                Task::none()
+            }
+            Message::SetLockScreenControls(enabled) => {
+                self.update_ui(|ui| ui.lock_screen_controls = enabled);
+                Task::none()
+            }
+            Message::SetLevelMeter(enabled) => {
+                self.update_ui(|ui| ui.level_meter = enabled);
+                Task::none()
+            }
+            Message::SetPanelStyle(idx) => {
+                if let Some(&style) = PANEL_STYLES.get(idx) {
+                    self.update_ui(|ui| ui.panel_style = style);
+                }
+                Task::none()
+            }
+            Message::SetMarquee(enabled) => {
+                self.update_ui(|ui| ui.marquee = enabled);
+                Task::none()
+            }
+            Message::SetMarqueeSpeed(idx) => {
+                if let Some(&speed) = MARQUEE_SPEEDS.get(idx) {
+                    self.update_ui(|ui| ui.marquee_speed = speed);
+                }
+                Task::none()
+            }
+            Message::SpinnerTick => {
+                self.spinner_frame = (self.spinner_frame + 1) % SPINNER.len();
+                Task::none()
+            }
+            Message::MarqueeTick => {
+                self.marquee_offset = self.marquee_offset.wrapping_add(1);
+                Task::none()
+            }
+            Message::PanelHovered(hovered) => {
+                self.panel_hovered = hovered;
+                Task::none()
+            }
+            Message::SetStartView(idx) => {
+                if let Some(&start_view) = START_VIEWS.get(idx) {
+                    self.update_ui(|ui| ui.start_view = start_view);
+                }
+                Task::none()
+            }
+            Message::SetLabelMode(idx) => {
+                if let Some(&label_mode) = LABEL_MODES.get(idx) {
+                    self.update_ui(|ui| ui.label_mode = label_mode);
+                }
+                Task::none()
+            }
+            Message::SetEqPreset(idx) => {
+                // Picking "Custom" keeps the current gains.
+                if let Some(&preset) = EQ_PRESETS.get(idx).filter(|p| **p != EqPreset::Custom) {
+                    let eq = EqualizerConfig::from_preset(preset);
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetEqualizer(eq));
+                }
+                Task::none()
+            }
+            Message::SetEqGain(band, gain) => {
+                let mut eq = self.state.equalizer;
+                match band {
+                    EqBand::Bass => eq.bass = gain,
+                    EqBand::Mid => eq.mid = gain,
+                    EqBand::Treble => eq.treble = gain,
+                }
+                eq.preset = EqPreset::Custom;
+                self.state.equalizer = eq;
+                let _ = self.controller.cmd_tx.send(UiCommand::SetEqualizer(eq));
+                Task::none()
+            }
+            Message::SetMono(mono) => {
+                let channels = ChannelConfig { mono, ..self.state.channels };
+                let _ = self.controller.cmd_tx.send(UiCommand::SetChannels(channels));
+                Task::none()
+            }
+            Message::SetBalance(balance) => {
+                let channels = ChannelConfig { balance, ..self.state.channels };
+                self.state.channels = channels;
+                let _ = self.controller.cmd_tx.send(UiCommand::SetChannels(channels));
+                Task::none()
+            }
+            Message::SetResultOrder(idx) => {
+                if let Some(&result_order) = RESULT_ORDERS.get(idx) {
+                    self.update_ui(|ui| ui.result_order = result_order);
+                }
+                Task::none()
+            }
+            Message::SetLogLevel(idx) => {
+                if let Some(&level) = LOG_LEVELS.get(idx) {
+                    let _ = self.controller.cmd_tx.send(UiCommand::SetLogLevel(level));
+                }
+                Task::none()
+            }
+            Message::CopyStreamUrl => match &self.state.stream_origin {
+                Some(origin) => cosmic::iced::clipboard::write(origin.url.clone()),
+                None => Task::none(),
+            },
+            Message::PasteStream => cosmic::iced::clipboard::read()
+                .map(|text| cosmic::Action::App(Message::ClipboardRead(text))),
+            Message::ClipboardRead(text) => {
+                match text.as_deref().and_then(find_stream_url) {
+                    Some(url) => {
+                        let _ = self
+                            .controller
+                            .cmd_tx
+                            .send(UiCommand::ProbeStream(url.to_string()));
+                    }
+                    None => {
+                        let error = "Clipboard does not contain a stream URL.".to_string();
+                        let _ = self.controller.cmd_tx.send(UiCommand::ReportError(error));
+                    }
+                }
+                Task::none()
+            }
+            Message::FavoritesFilterInput(s) => {
+                self.favorites_filter = s;
+                Task::none()
+            }
+            Message::FavoritesFilterSubmit => {
+                let best = fuzzy::rank(&self.favorites_filter, &self.state.favorites, |f| &f.name)
+                    .first()
+                    .map(|f| (*f).clone());
+                if let Some(station) = best {
+                    self.favorites_filter.clear();
+                    let _ = self.controller.cmd_tx.send(UiCommand::Play(station));
+                }
+                Task::none()
+            }
+            Message::CustomUrlInput(url) => {
+                self.custom_url = url;
+                Task::none()
+            }
+            Message::CustomUrlSubmit => {
+                match find_stream_url(&self.custom_url) {
+                    Some(url) => {
+                        self.custom_url.clear();
+                        let _ = self
+                            .controller
+                            .cmd_tx
+                            .send(UiCommand::ProbeStream(url.to_string()));
+                    }
+                    None => {
+                        let error = "Enter an http(s) stream URL.".to_string();
+                        let _ = self.controller.cmd_tx.send(UiCommand::ReportError(error));
+                    }
+                }
+                Task::none()
+            }
+            Message::PlayStreamOffer => {
+                if let Some(offer) = &self.state.stream_offer {
+                    let _ = self.controller.cmd_tx.send(UiCommand::Play(offer.station_ref()));
+                    let _ = self.controller.cmd_tx.send(UiCommand::DismissStreamOffer);
+                }
+                Task::none()
+            }
+            Message::SaveStreamOffer => {
+                if let Some(offer) = &self.state.stream_offer {
+                    let station = offer.station_ref();
+                    if !self
+                        .state
+                        .favorites
+                        .iter()
+                        .any(|f| f.stationuuid == station.stationuuid)
+                    {
+                        let _ = self.controller.cmd_tx.send(UiCommand::ToggleFavorite(station));
+                    }
+                    let _ = self.controller.cmd_tx.send(UiCommand::DismissStreamOffer);
+                }
+                Task::none()
+            }
+            Message::DismissStreamOffer => {
+                let _ = self.controller.cmd_tx.send(UiCommand::DismissStreamOffer);
+                Task::none()
+            }
+            Message::OpenConfigFolder => {
+                let _ = self.controller.cmd_tx.send(UiCommand::OpenConfigFolder);
+                Task::none()
+            }
+            Message::ReloadConfig => {
+                let _ = self.controller.cmd_tx.send(UiCommand::ReloadConfig);
+                Task::none()
+            }
+            Message::StopReconnecting => {
+                let _ = self.controller.cmd_tx.send(UiCommand::StopReconnecting);
+                Task::none()
+            }
+            Message::RestartBackend => {
+                let _ = self.controller.cmd_tx.send(UiCommand::RestartBackend);
+                Task::none()
+            }
+            Message::RetryBackend => {
+                let _ = self.controller.cmd_tx.send(UiCommand::RetryBackend);
+                Task::none()
+            }
+            Message::RestartController => {
+                self.controller = start_controller();
+                self.controller_generation = self.controller_generation.wrapping_add(1);
+                self.state = self.controller.state_rx.borrow().clone();
+                self.level = None;
+                self.refresh_labels();
+                Task::none()
+            }
+            Message::Noop => Task::none(),
+        }
+    }
+
+    fn view(&self) -> cosmic::Element<'_, Message> {
+        let have_popup = self.popup;
+
+        // What we show in the panel:
+        let is_horizontal = self.core.applet.is_horizontal();
+
+        let chars = self.panel_chars();
+        let btn = (if self.compact_panel() {
+            self.compact_button()
+        } else if is_horizontal {
+            let label = match self.volume_overlay {
+                Some(_) if self.state.muted => Cow::Borrowed("Muted"),
+                Some(_) => Cow::Owned(volume_label(self.state.volume)),
+                None if self.state.ui.marquee => {
+                    marquee_frame(&self.panel_full, chars, self.marquee_offset)
+                }
+                // The panel was resized since the label was last shortened.
+                None if self.panel_text_chars != chars => ellipsize_middle(&self.panel_full, chars),
+                None => Cow::Borrowed(self.panel_text.as_str()),
+            };
+            let label = if self.waiting_for_audio() && self.volume_overlay.is_none() {
+                Cow::Owned(format!("{} {label}", SPINNER[self.spinner_frame]))
+            } else {
+                label
+            };
+
+            let width = chars as f32 * PANEL_CHAR_WIDTH;
+            let mut text = widget::text::body(label).width(Length::Fixed(width));
+            if let Some(color) = self.playing_label_color() {
+                text = text.class(cosmic::theme::Text::Color(label_rgb(color)));
+            }
+
+            self.core.applet.text_button(text, Message::Noop)
+            .width(Length::Fixed(width))
+        } else {
+            // Vertical panels: keep it compact.
+            self.core.applet.icon_button("audio-x-generic-symbolic")
+            // If icon doesn't show, fallback to short text:
+            // self.core.applet.text_button(widget::text::body("RAD"), Message::Noop)
+        })
+        .on_press_with_rectangle(move |offset, bounds| {
+            if let Some(id) = have_popup {
+                Message::Surface(destroy_popup(id))
+            } else {
+                Message::OpenPopup(offset, bounds)
+            }
+        });
+
+        // Scrolling over the panel button adjusts the radio volume.
+        let btn = cosmic::iced_widget::mouse_area(btn)
+            .on_scroll(Message::VolumeScroll)
+            .on_enter(Message::PanelHovered(true))
+            .on_exit(Message::PanelHovered(false));
+
+        let with_tooltip = self.core.applet.applet_tooltip::<Message>(
+            btn,
+            self.tooltip_text.clone(),
+            self.popup.is_some(),
+            Message::Surface,
+            None,
+        );
+
+        // Only autosize the label (vertical panels and the icon stay compact)
+        if is_horizontal && !self.compact_panel() {
+            self.core.applet.autosize_window(with_tooltip).into()
+        } else {
+            with_tooltip.into()
+        }
+    }
+
+    fn view_window(&self, id: cosmic::iced::window::Id) -> cosmic::Element<'_, Message> {
+        if self.fallback_window == Some(id) {
+            return self.popup_content();
+        }
+        "RadioWidget".into()
+    }
+
+    fn style(&self) -> Option<cosmic::iced_runtime::Appearance> {
+        Some(cosmic::applet::style())
+    }
+}
+
+fn label_rgb(color: LabelColor) -> cosmic::iced::Color {
+    match color {
+        LabelColor::Red => cosmic::iced::Color::from_rgb8(0xe0, 0x1b, 0x24),
+        LabelColor::Orange => cosmic::iced::Color::from_rgb8(0xff, 0x78, 0x00),
+        LabelColor::Yellow => cosmic::iced::Color::from_rgb8(0xf6, 0xd3, 0x2d),
+        LabelColor::Green => cosmic::iced::Color::from_rgb8(0x33, 0xd1, 0x7a),
+        LabelColor::Blue => cosmic::iced::Color::from_rgb8(0x35, 0x84, 0xe4),
+        LabelColor::Purple => cosmic::iced::Color::from_rgb8(0x91, 0x41, 0xac),
+    }
+}
+
+fn theme_palette(theme: &cosmic::cosmic_theme::Theme) -> Palette {
+    let accent = theme.accent_color();
+    Palette {
+        accent: [accent.red, accent.green, accent.blue],
+        dark: theme.is_dark,
+    }
+}
+
+fn rgb(color: cosmic::iced::Color) -> Rgb {
+    [color.r, color.g, color.b]
+}
+
+fn volume_label(volume: f64) -> String {
+    format!("Volume {}%", volume.round() as i64)
+}
+
+/// Index into [`PREFERRED_CODEC_LABELS`]; "Any" for no preference.
+fn preferred_codec_index(codec: Option<&str>) -> Option<usize> {
+    match codec {
+        None => Some(0),
+        Some(codec) => QUALITY_CODECS.iter().position(|c| c.eq_ignore_ascii_case(codec)).map(|i| i + 1),
+    }
+}
+
+fn preferred_codec(idx: usize) -> Option<String> {
+    idx.checked_sub(1).and_then(|i| QUALITY_CODECS.get(i)).map(|c| c.to_string())
+}
+
+/// A favorite's own codec, bitrate and fallback, as in the settings.
+fn favorite_stream_row<'a>(stationuuid: &str, choice: &StreamChoice) -> cosmic::Element<'a, Message> {
+    let edited = |edit: fn(&mut StreamChoice, usize)| {
+        let stationuuid = stationuuid.to_string();
+        let choice = choice.clone();
+        move |idx| {
+            let mut choice = choice.clone();
+            edit(&mut choice, idx);
+            Message::SetFavoriteStream(stationuuid.clone(), Some(choice))
+        }
+    };
+    widget::row()
+        .spacing(8)
+        .align_y(cosmic::iced::Alignment::Center)
+        .push(widget::dropdown(
+            &PREFERRED_CODEC_LABELS,
+            preferred_codec_index(choice.codec.as_deref()),
+            edited(|c, idx| c.codec = preferred_codec(idx)),
+        ))
+        .push(widget::text::caption("at least"))
+        .push(widget::dropdown(
+            &MIN_BITRATE_LABELS,
+            MIN_BITRATES.iter().position(|b| *b == choice.min_bitrate),
+            edited(|c, idx| c.min_bitrate = MIN_BITRATES.get(idx).copied().unwrap_or(0)),
+        ))
+        .push(widget::text::caption("otherwise"))
+        .push(widget::dropdown(
+            &STREAM_FALLBACK_LABELS,
+            STREAM_FALLBACKS.iter().position(|f| *f == choice.otherwise),
+            edited(|c, idx| c.otherwise = STREAM_FALLBACKS.get(idx).copied().unwrap_or_default()),
+        ))
+        .into()
+}
+
+fn stream_offer_card(offer: &StreamInfo) -> cosmic::Element<'_, Message> {
+    let mut details: Vec<String> = Vec::new();
+    if let Some(genre) = &offer.genre {
+        details.push(genre.clone());
+    }
+    if let Some(codec) = &offer.codec {
+        details.push(codec.clone());
+    }
+    if let Some(br) = offer.bitrate {
+        details.push(format!("{br} kbps"));
+    }
+    details.push(
+        match offer.kind {
+            StreamKind::Direct => "Direct stream",
+            StreamKind::Playlist => "Playlist",
+            StreamKind::Hls => "HLS",
+        }
+        .to_string(),
+    );
+
+    let actions = widget::row()
+        .spacing(8)
+        .push(widget::button::suggested("Play").on_press(Message::PlayStreamOffer))
+        .push(widget::button::standard("Save").on_press(Message::SaveStreamOffer))
+        .push(widget::button::text("Dismiss").on_press(Message::DismissStreamOffer));
+
+    widget::container(
+        widget::column()
+            .spacing(4)
+            .push(widget::text::body(ellipsize_middle(&offer.display_name(), LIST_NAME_CHARS).into_owned()))
+            .push(widget::text::caption(details.join(" · ")))
+            .push_maybe(
+                offer
+                    .now_playing
+                    .as_deref()
+                    .map(|t| widget::text::caption(format!("Now playing: {t}"))),
+            )
+            .push(actions),
+    )
+    .class(cosmic::theme::Container::Card)
+    .padding(8)
+    .into()
+}
+
+impl RadioWidget {
+    /// The controller thread is gone (it failed to start, or crashed), so
+    /// nothing sent to it would do anything.
+    fn controller_stopped(&self) -> bool {
+        self.state.stopped.is_some() || self.controller.cmd_tx.is_closed()
+    }
+
+    fn stopped_view(&self) -> cosmic::Element<'_, Message> {
+        let reason = self
+            .state
+            .stopped
+            .as_deref()
+            .unwrap_or("It quit unexpectedly; the log may say why.");
+        let cosmic::cosmic_theme::Spacing {
+            space_xxs,
+            space_s,
+            ..
+        } = cosmic::theme::spacing();
+        widget::column()
+            .spacing(space_xxs)
+            .padding(space_s)
+            .push(widget::text::heading("Radio stopped working"))
+            .push(widget::text::body(reason))
+            .push(widget::button::suggested("Restart").on_press(Message::RestartController))
+            .into()
+    }
+
+    fn popup_content(&self) -> cosmic::Element<'_, Message> {
+        let cosmic::cosmic_theme::Spacing {
+            space_xxs,
+            space_s,
+            ..
+        } = cosmic::theme::spacing();
+
+        if self.controller_stopped() {
+            return cosmic::Element::from(self.core.applet.popup_container(self.stopped_view()));
+        }
+        if self.state.consent_pending {
+            return cosmic::Element::from(self.core.applet.popup_container(self.consent_view()));
+        }
+
+        let search = if self.view == View::Favorites {
+            widget::search_input("Filter favorites…", &self.favorites_filter)
+                .id(self.search_id.clone())
+                .on_input(Message::FavoritesFilterInput)
+                .on_submit(|_| Message::FavoritesFilterSubmit)
+        } else {
+            widget::search_input("Search stations…", &self.state.search_query)
+                .id(self.search_id.clone())
+                .on_input(Message::SearchInput)
+                .on_submit(|_| Message::SearchSubmit)
+        };
+
+        let fav_star = if self.view == View::Favorites { "★" } else { "☆" };
+        let cast_label = if self.state.cast_target.is_some() { "Cast ●" } else { "Cast" };
+        let header = widget::row()
+            .spacing(space_xxs)
+            .push(search.width(Length::Fill))
+            .push_maybe(self.mic_button())
+            .push(widget::button::text("Browse").on_press(Message::ToggleView(View::Browse)))
+            .push(widget::button::text(fav_star).on_press(Message::ToggleView(View::Favorites)))
+            .push(widget::button::text(cast_label).on_press(Message::ToggleView(View::Cast)))
+            .push(widget::button::text("⚙").on_press(Message::ToggleView(View::Settings)));
+
+        let mut content = widget::column().spacing(space_s).padding(space_s);
+
+        if self.volume_overlay.is_some() {
+            content = content.push(
+                widget::container(widget::text::title4(volume_label(self.state.volume)))
+                    .center_x(Length::Fill),
+            );
+        }
+
+        // Now-playing strip stays pinned above whichever view is open.
+        if let Some(strip) = self.now_playing_strip() {
+            content = content.push(strip);
+        }
+        if let Some(warning) = &self.state.resource_warning {
+            content = content.push(
+                widget::container(
+                    widget::column()
+                        .spacing(space_xxs)
+                        .push(widget::text::body(warning))
+                        .push(
+                            widget::button::standard("Restart in low-resource mode")
+                                .on_press(Message::SetLowResource(true)),
+                        ),
+                )
+                .padding(8)
+                .class(cosmic::theme::Container::Card),
+            );
+        }
+        if self.state.phase == PlaybackPhase::MissingBackend {
+            content = content.push(
+                widget::container(
+                    widget::column()
+                        .spacing(space_xxs)
+                        .push(widget::text::heading("mpv isn't installed"))
+                        .push(widget::text::body(
+                            "Radio plays streams through mpv. Install the mpv package from your \
+                             distribution (for example with sudo apt install mpv), then try again.",
+                        ))
+                        .push(widget::button::standard("Try again").on_press(Message::RetryBackend)),
+                )
+                .padding(8)
+                .class(cosmic::theme::Container::Card),
+            );
+        }
+        if self.state.phase == PlaybackPhase::Error && !self.state.alternates.is_empty() {
+            content = content.push(self.alternates_card());
+        }
+        if let Some(notice) = &self.state.notice {
+            content = content.push(
+                widget::container(
+                    widget::row()
+                        .spacing(space_xxs)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::text::body(notice).width(Length::Fill))
+                        .push(widget::button::text("✕").on_press(Message::DismissNotice)),
+                )
+                .padding(8)
+                .class(cosmic::theme::Container::Card),
+            );
+        }
+        for err in self.state.errors.active() {
+            content = content.push(
+                widget::container(
+                    widget::row()
+                        .spacing(space_xxs)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::text::body(&err.message).width(Length::Fill))
+                        .push(widget::button::text("✕").on_press(Message::DismissError(err.id))),
+                )
+                .padding(8)
+                .class(cosmic::theme::Container::Card),
+            );
+        }
+
+        content = content.push(header);
+
+        if let Some(removed) = self.state.pending_removals.last() {
+            content = content.push(
+                widget::row()
+                    .spacing(space_xxs)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(
+                        widget::text::caption(format!("Removed “{}” from favorites", removed.name))
+                            .width(Length::Fill),
+                    )
+                    .push(
+                        widget::button::text("Undo")
+                            .on_press(Message::UndoRemoveFavorite(removed.stationuuid.clone())),
+                    ),
+            );
+        }
+
+        if let Some(offer) = &self.state.stream_offer {
+            content = content.push(stream_offer_card(offer));
+        }
+
+        // Main body (favorites vs search/results/errors)
+        if self.view == View::Settings {
+            content = content.push(self.settings_view());
+        } else if self.view == View::Browse {
+            content = content.push(match &self.state.station_of_day {
+                Some(station) => self.station_of_day_card(station),
+                None if !self.state.discovery_enabled => {
+                    widget::text::body("Station of the day is off; turn it on under Appearance in the settings.").into()
+                }
+                None => widget::text::body("No station of the day yet. Check back later.").into(),
+            });
+        } else if self.view == View::Cast {
+            content = content.push(self.cast_view());
+        } else if self.view == View::Favorites {
+            if let Some(edit) = &self.favorites_edit {
+                content = content.push(self.favorites_edit_view(edit));
+            } else if self.state.favorites.is_empty() {
+                content = content.push(widget::text::body("No favorites yet."));
+            } else {
+                content = content.push(if self.favorites_filter.trim().is_empty() {
+                    self.grouped_favorites()
+                } else {
+                    self.favorites_list(fuzzy::rank(&self.favorites_filter, &self.state.favorites, |f| &f.name))
+                });
+                let refresh = widget::button::text(if self.state.favorites_refreshing {
+                    "Refreshing…"
+                } else {
+                    "Refresh favorites"
+                });
+                let refresh = if self.state.favorites_refreshing {
+                    refresh
+                } else {
+                    refresh.on_press(Message::RefreshFavorites)
+                };
+                content = content.push(
+                    widget::row()
+                        .spacing(space_xxs)
+                        .push(widget::button::text("Surprise me").on_press(Message::PlayRandomFavorite))
+                        .push(refresh)
+                        .push(widget::button::text("Edit").on_press(Message::EditFavorites)),
+                );
+            }
+            let url_input = widget::text_input("Add stream URL…", &self.custom_url)
+                .on_input(Message::CustomUrlInput)
+                .on_submit(|_| Message::CustomUrlSubmit);
+            let paste = widget::button::text("Paste");
+            let paste = if self.state.stream_probe_loading {
+                paste
+            } else {
+                paste.on_press(Message::PasteStream)
+            };
+            content = content.push(
+                widget::row()
+                    .spacing(space_xxs)
+                    .push(url_input.width(Length::Fill))
+                    .push(paste),
+            );
+            if self.state.stream_probe_loading {
+                content = content.push(widget::text::caption("Checking stream…"));
+            }
+        } else if self.state.search_loading {
+            content = content.push(widget::text::body("Loading…"));
+        } else if self.state.search_results.is_empty() {
+            content = content.push(widget::text::body("Search to choose a station."));
+        } else {
+            content = content
+                .push(
+                    widget::row()
+                        .spacing(space_xxs)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::text::caption("Sort by"))
+                        .push(widget::dropdown(
+                            &RESULT_ORDER_LABELS,
+                            RESULT_ORDERS.iter().position(|o| *o == self.state.ui.result_order),
+                            Message::SetResultOrder,
+                        )),
+                )
+                .push_maybe((self.hidden_results > 0).then(|| {
+                    widget::row()
+                        .spacing(space_xxs)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(
+                            widget::text::caption(format!(
+                                "{} below your quality minimum hidden",
+                                self.hidden_results
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(widget::button::text("Show anyway").on_press(Message::ShowFilteredResults))
+                }))
+                .push(self.results_list());
+        }
+
+        cosmic::Element::from(self.core.applet.popup_container(content))
+    }
+
+    fn station_of_day_card<'a>(&'a self, station: &'a Station) -> cosmic::Element<'a, Message> {
+        let station_ref = StationRef {
+            stationuuid: station.stationuuid.clone(),
+            name: station.name.clone(),
+            url: None,
+            group: None,
+        };
+        let favorite = self.state.favorites.iter().any(|f| f.stationuuid == station.stationuuid);
+        let column = widget::column()
+            .spacing(4)
+            .push(
+                widget::row()
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(widget::text::heading("Station of the day").width(Length::Fill))
+                    .push(widget::button::text("✕").on_press(Message::DismissStationOfDay)),
+            )
+            .push(station_entry(&station.name, Some(self.subtitle(&station.stationuuid)), None))
+            .push(
+                widget::row()
+                    .spacing(4)
+                    .push(widget::button::suggested("Play").on_press(Message::PlayStation(station_ref.clone())))
+                    .push(
+                        widget::button::text(if favorite { "★ Favorite" } else { "☆ Favorite" })
+                            .on_press(Message::ToggleFavorite(station_ref)),
+                    ),
+            );
+        widget::container(column)
+            .padding(8)
+            .class(cosmic::theme::Container::Card)
+            .into()
+    }
+
+    fn alternates_card(&self) -> cosmic::Element<'_, Message> {
+        let mut column = widget::column()
+            .spacing(4)
+            .push(widget::text::heading("Try another stream"));
+        for s in &self.state.alternates {
+            let station = StationRef {
+                stationuuid: s.stationuuid.clone(),
+                name: s.name.clone(),
+                url: None,
+                group: None,
+            };
+            column = column.push(
+                widget::button::custom(station_entry(&s.name, Some(self.subtitle(&s.stationuuid)), None))
+                    .on_press(Message::PlayStation(station))
+                    .width(Length::Fill),
+            );
+        }
+        widget::container(column)
+            .padding(8)
+            .class(cosmic::theme::Container::Card)
+            .into()
+    }
+
+    fn cast_view(&self) -> cosmic::Element<'_, Message> {
+        let mut list = widget::list_column().padding(0).spacing(0);
+
+        let local_mark = if self.state.cast_target.is_none() { "●" } else { "" };
+        list = list.add(
+            widget::button::custom(
+                widget::row()
+                    .spacing(8)
+                    .push(widget::text::body("This computer").width(Length::Fill))
+                    .push(widget::text::body(local_mark)),
+            )
+            .on_press(Message::SetCastTarget(None))
+            .width(Length::Fill),
+        );
+
+        for r in &self.state.renderers {
+            let mark = if self.state.cast_target.as_ref() == Some(r) { "●" } else { "" };
+            list = list.add(
+                widget::button::custom(
+                    widget::row()
+                        .spacing(8)
+                        .push(widget::text::body(&r.name).width(Length::Fill))
+                        .push(widget::text::body(mark)),
+                )
+                .on_press(Message::SetCastTarget(Some(r.clone())))
+                .width(Length::Fill),
+            );
+        }
+
+        let status = if self.state.renderers_loading {
+            "Looking for speakers…"
+        } else if self.state.renderers.is_empty() {
+            "No network speakers found."
+        } else {
+            ""
+        };
+
+        let refresh = widget::button::text("Search again");
+        let refresh = if self.state.renderers_loading {
+            refresh
+        } else {
+            refresh.on_press(Message::DiscoverRenderers)
+        };
+
+        widget::column()
+            .spacing(8)
+            .push(widget::text::caption(status))
+            .push(cosmic::iced_widget::scrollable(list.into_element()).height(Length::Fixed(300.0)))
+            .push(refresh)
+            .into()
+    }
+
+    fn settings_view(&self) -> cosmic::Element<'_, Message> {
+        let mut output = widget::settings::section()
+            .title("Output")
+            .add(
+                widget::settings::item::builder("Stream to Snapcast")
+                    .description(format!(
+                        "Writes audio to {}",
+                        self.state.output.snapcast_fifo.display()
+                    ))
+                    .toggler(self.state.output.snapcast, Message::SetSnapcast),
+            )
+            .add(widget::settings::item(
+                "Output device",
+                widget::dropdown(
+                    &self.audio_device_labels,
+                    self.state.audio_devices.iter().position(|d| {
+                        d.name == self.state.output.audio_device.as_deref().unwrap_or("auto")
+                    }),
+                    Message::SetAudioDevice,
+                ),
+            ))
+            .add(
+                widget::settings::item::builder("Low-resource playback")
+                    .description("Small buffers, no video or web page extraction")
+                    .toggler(self.state.output.low_resource, Message::SetLowResource),
+            )
+            .add(
+                widget::settings::item::builder("Buffering")
+                    .description(match self.state.output.cache_preset {
+                        CachePreset::Default => "mpv's own cache settings",
+                        CachePreset::LowLatency => "2 s ahead: closest to live, more dropouts",
+                        CachePreset::Robust => "60 s ahead: rides out Wi-Fi dropouts",
+                        CachePreset::Custom => "Cache length and size below",
+                    })
+                    .control(widget::dropdown(
+                        &CACHE_PRESET_LABELS,
+                        CACHE_PRESETS.iter().position(|p| *p == self.state.output.cache_preset),
+                        Message::SetCachePreset,
+                    )),
+            );
+        if self.state.output.cache_preset == CachePreset::Custom {
+            output = output
+                .add(widget::settings::item(
+                    "Cache length",
+                    widget::dropdown(
+                        &CACHE_SECS_LABELS,
+                        CACHE_SECS.iter().position(|s| *s == self.state.output.cache_secs),
+                        Message::SetCacheSecs,
+                    ),
+                ))
+                .add(widget::settings::item(
+                    "Cache size",
+                    widget::dropdown(
+                        &CACHE_MIB_LABELS,
+                        CACHE_MIBS.iter().position(|m| *m == self.state.output.demuxer_max_mib),
+                        Message::SetCacheSize,
+                    ),
+                ));
+        }
+        output = output
+            .add(
+                widget::settings::item::builder("HLS streams")
+                    .description(match self.state.output.hls_variant {
+                        HlsVariant::Quality => "Highest bitrate the station offers",
+                        HlsVariant::DataSaver => "Lowest bitrate, for metered connections",
+                    })
+                    .control(widget::dropdown(
+                        &HLS_VARIANT_LABELS,
+                        HLS_VARIANTS.iter().position(|h| *h == self.state.output.hls_variant),
+                        Message::SetHlsVariant,
+                    )),
+            )
+            .add(
+                widget::settings::item::builder("Seamless station switching")
+                    .description(
+                        "Keeps a second mpv ready so the next station buffers while the current one plays. \
+                         Not available with Snapcast or low-resource playback.",
+                    )
+                    .toggler(self.state.output.standby, Message::SetStandby),
+            )
+            .add(
+                widget::settings::item::builder("Pause when the output disconnects")
+                    .description("Headphones unplugged or Bluetooth dropped")
+                    .toggler(self.state.output.pause_on_disconnect, Message::SetPauseOnDisconnect),
+            );
+        if self.state.output.pause_on_disconnect {
+            output = output.add(
+                widget::settings::item::builder("Resume when it's back")
+                    .toggler(self.state.output.resume_on_reconnect, Message::SetResumeOnReconnect),
+            );
+        }
+        output = output
+            .add(
+                widget::settings::item::builder("Remember volume per output")
+                    .description("Volume, mute and device follow the system's default output, e.g. docked or not")
+                    .toggler(self.state.output.per_output, Message::SetPerOutput),
+            )
+            .add(
+                widget::settings::item::builder("Jump to live after a pause of")
+                    .description("Reloads live stations instead of playing minutes-old audio")
+                    .control(widget::dropdown(
+                        &SNAP_MINUTE_LABELS,
+                        SNAP_MINUTES.iter().position(|m| *m == self.state.resume.snap_to_live_minutes),
+                        Message::SetSnapToLive,
+                    )),
+            )
+            .add(
+                widget::settings::item::builder("Keep the computer awake while playing")
+                    .description("Blocks automatic suspend; the screen can still turn off")
+                    .toggler(self.state.power.inhibit_suspend, Message::SetInhibitSuspend),
+            )
+            .add(
+                widget::settings::item::builder("Playback engine")
+                    .description("Restart mpv if audio misbehaves, e.g. after switching outputs")
+                    .control(widget::button::standard("Restart").on_press(Message::RestartBackend)),
+            );
+
+        let eq = self.state.equalizer;
+        let gain = |label: &'static str, value: f32, band: EqBand| {
+            widget::settings::item(
+                label,
+                widget::row()
+                    .spacing(8)
+                    .push(
+                        widget::slider(
+                            -EqualizerConfig::MAX_GAIN..=EqualizerConfig::MAX_GAIN,
+                            value,
+                            move |v| Message::SetEqGain(band, v),
+                        )
+                        .step(0.5),
+                    )
+                    .push(widget::text::caption(format!("{value:+.1} dB"))),
+            )
+        };
+        let equalizer = widget::settings::section()
+            .title("Equalizer")
+            .add(widget::settings::item(
+                "Preset",
+                widget::dropdown(
+                    &EQ_PRESET_LABELS,
+                    EQ_PRESETS.iter().position(|p| *p == eq.preset),
+                    Message::SetEqPreset,
+                ),
+            ))
+            .add(gain("Bass", eq.bass, EqBand::Bass))
+            .add(gain("Mid", eq.mid, EqBand::Mid))
+            .add(gain("Treble", eq.treble, EqBand::Treble));
+
+        let balance = self.state.channels.balance;
+        let balance_label = match (balance * 100.0).round() as i32 {
+            0 => "Centered".to_string(),
+            p if p < 0 => format!("Left {}%", -p),
+            p => format!("Right {p}%"),
+        };
+        let channels = widget::settings::section()
+            .title("Channels")
+            .add(
+                widget::settings::item::builder("Mono")
+                    .description("Both channels in each ear, e.g. for a single earbud")
+                    .toggler(self.state.channels.mono, Message::SetMono),
+            )
+            .add(widget::settings::item(
+                "Balance",
+                widget::row()
+                    .spacing(8)
+                    .push(widget::slider(-1.0..=1.0, balance, Message::SetBalance).step(0.05))
+                    .push(widget::text::caption(balance_label)),
+            ));
+
+        let alarm = &self.state.alarm;
+        let alarm_station = alarm.station.as_ref().map_or("No station chosen", |s| s.name.as_str());
+        let alarm_section = widget::settings::section()
+            .title("Alarm")
+            .add(
+                widget::settings::item::builder("Wake up to the radio")
+                    .description(format!(
+                        "{:02}:{:02} · {alarm_station}",
+                        alarm.hour, alarm.minute
+                    ))
+                    .toggler(alarm.enabled, Message::SetAlarmEnabled),
+            )
+            .add(widget::settings::item(
+                "Time",
+                widget::row()
+                    .spacing(4)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(widget::dropdown(
+                        &self.alarm_hour_labels,
+                        Some(usize::from(alarm.hour.min(23))),
+                        Message::SetAlarmHour,
+                    ))
+                    .push(widget::text::body(":"))
+                    .push(widget::dropdown(
+                        &self.alarm_minute_labels,
+                        (alarm.minute % 5 == 0).then_some(usize::from(alarm.minute / 5)),
+                        Message::SetAlarmMinute,
+                    )),
+            ))
+            .add(widget::settings::item(
+                "Station",
+                widget::button::standard("Use current station").on_press_maybe(
+                    self.state
+                        .station
+                        .as_ref()
+                        .filter(|s| alarm.station.as_ref().map(|a| &a.stationuuid) != Some(&s.stationuuid))
+                        .map(|_| Message::SetAlarmStation),
+                ),
+            ))
+            .add(widget::settings::item(
+                "Volume",
+                widget::row()
+                    .spacing(8)
+                    .push(widget::slider(0.0..=100.0, f64::from(alarm.volume), Message::SetAlarmVolume).step(1.0))
+                    .push(widget::text::caption(format!("{}%", alarm.volume))),
+            ))
+            .add(widget::settings::item(
+                "Fade in",
+                widget::dropdown(
+                    &ALARM_FADE_LABELS,
+                    ALARM_FADES.iter().position(|s| *s == alarm.fade_in_secs),
+                    Message::SetAlarmFade,
+                ),
+            ));
+
+        let ad_mute = &self.state.ad_mute;
+        let mut ads_section = widget::settings::section().title("Ads and jingles").add(
+            widget::settings::item::builder("Turn down ads")
+                .description("While the song title matches one of the patterns below, or after dead air")
+                .toggler(ad_mute.enabled, Message::SetAdMuteEnabled),
+        );
+        if ad_mute.enabled {
+            ads_section = ads_section.add(widget::settings::item(
+                "Volume during ads",
+                widget::dropdown(
+                    &AD_DUCK_LABELS,
+                    AD_DUCKS.iter().position(|p| *p == ad_mute.duck_percent),
+                    Message::SetAdDuck,
+                ),
+            ));
+            for (i, pattern) in ad_mute.patterns.iter().enumerate() {
+                ads_section = ads_section.add(
+                    widget::settings::item::builder(pattern.clone())
+                        .control(widget::button::text("Remove").on_press(Message::RemoveAdPattern(i))),
+                );
+            }
+            ads_section = ads_section
+                .add(
+                    widget::row()
+                        .spacing(4)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(
+                            widget::text_input("Title pattern (regular expression)", &self.ad_pattern)
+                                .on_input(Message::AdPatternInput)
+                                .on_submit(|_| Message::AddAdPattern)
+                                .width(Length::Fill),
+                        )
+                        .push(
+                            widget::button::standard("Add")
+                                .on_press_maybe((!self.ad_pattern.trim().is_empty()).then_some(Message::AddAdPattern)),
+                        ),
+                )
+                .add(
+                    widget::settings::item::builder("Dead air starts a break")
+                        .description("Turned back up when the title changes, or after 4 minutes")
+                        .control(widget::dropdown(
+                            &AD_SILENCE_SECS_LABELS,
+                            AD_SILENCE_SECS.iter().position(|s| *s == ad_mute.silence_secs),
+                            Message::SetAdSilence,
+                        )),
+                );
+            if ad_mute.silence_secs > 0 {
+                ads_section = ads_section.add(widget::settings::item(
+                    "Dead air is quieter than",
+                    widget::dropdown(
+                        &AD_SILENCE_DB_LABELS,
+                        AD_SILENCE_DBS.iter().position(|d| *d == ad_mute.silence_db),
+                        Message::SetAdSilenceDb,
+                    ),
+                ));
+            }
+        }
+
+        let quality = &self.state.quality;
+        let quality_section = QUALITY_CODECS.iter().fold(
+            widget::settings::section().title("Search quality").add(
+                widget::settings::item::builder("Minimum bitrate")
+                    .description("Stations that don't report one are always shown")
+                    .control(widget::dropdown(
+                        &MIN_BITRATE_LABELS,
+                        MIN_BITRATES.iter().position(|b| *b == quality.min_bitrate),
+                        Message::SetMinBitrate,
+                    )),
+            ),
+            |section, &codec| {
+                section.add(
+                    widget::settings::item::builder(codec)
+                        .toggler(quality.allows_codec(codec), move |on| Message::SetQualityCodec(codec, on)),
+                )
+            },
+        );
+
+        let choice = &self.state.stream_choice;
+        let stream_section = widget::settings::section()
+            .title("Stream choice")
+            .add(
+                widget::settings::item::builder("Preferred codec")
+                    .description("When Radio Browser lists a station more than once, play this codec")
+                    .control(widget::dropdown(
+                        &PREFERRED_CODEC_LABELS,
+                        preferred_codec_index(choice.codec.as_deref()),
+                        Message::SetPreferredCodec,
+                    )),
+            )
+            .add(widget::settings::item(
+                "At least",
+                widget::dropdown(
+                    &MIN_BITRATE_LABELS,
+                    MIN_BITRATES.iter().position(|b| *b == choice.min_bitrate),
+                    Message::SetPreferredBitrate,
+                ),
+            ))
+            .add(widget::settings::item(
+                "Otherwise play",
+                widget::dropdown(
+                    &STREAM_FALLBACK_LABELS,
+                    STREAM_FALLBACKS.iter().position(|f| *f == choice.otherwise),
+                    Message::SetStreamFallback,
+                ),
+            ));
+
+        let mut appearance = widget::settings::section()
+            .title("Appearance")
+            .add(
+                widget::settings::item(
+                    "Open popup on",
+                    widget::dropdown(
+                        &START_VIEW_LABELS,
+                        START_VIEWS.iter().position(|v| *v == self.state.ui.start_view),
+                        Message::SetStartView,
+                    ),
+                ),
+            )
+            .add(
+                widget::settings::item::builder("Station of the day")
+                    .description("A popular station in your favorites' genres, above the search results")
+                    .toggler(self.state.discovery_enabled, Message::SetDiscovery),
+            )
+            .add(
+                widget::settings::item(
+                    "Panel label",
+                    widget::dropdown(
+                        &LABEL_MODE_LABELS,
+                        LABEL_MODES.iter().position(|m| *m == self.state.ui.label_mode),
+                        Message::SetLabelMode,
+                    ),
+                ),
+            )
+            .add(widget::settings::item(
+                "Panel button",
+                widget::dropdown(
+                    &PANEL_STYLE_LABELS,
+                    PANEL_STYLES.iter().position(|s| *s == self.state.ui.panel_style),
+                    Message::SetPanelStyle,
+                ),
+            ))
+            .add(
+                widget::settings::item::builder("Tint panel with favorite color")
+                    .toggler(self.state.ui.tint_panel, Message::SetTintPanel),
+            )
+            .add(
+                widget::settings::item::builder("Wrap long names in now playing")
+                    .toggler(self.state.ui.wrap_titles, Message::SetWrapTitles),
+            )
+            .add(
+                widget::settings::item::builder("Scroll long panel labels")
+                    .toggler(self.state.ui.marquee, Message::SetMarquee),
+            )
+            .add(
+                widget::settings::item::builder("Media controls on the lock screen")
+                    .description("Play, pause, stop and volume only; the station and title are shown")
+                    .toggler(self.state.ui.lock_screen_controls, Message::SetLockScreenControls),
+            )
+            .add(
+                widget::settings::item::builder("Level meter")
+                    .description("Shows that audio is coming through; measuring uses a little CPU")
+                    .toggler(self.state.ui.level_meter, Message::SetLevelMeter),
+            );
+        if self.state.ui.marquee {
+            appearance = appearance.add(widget::settings::item(
+                "Scroll speed",
+                widget::dropdown(
+                    &MARQUEE_SPEED_LABELS,
+                    MARQUEE_SPEEDS.iter().position(|s| *s == self.state.ui.marquee_speed),
+                    Message::SetMarqueeSpeed,
+                ),
+            ));
+        }
+
+        let row_actions = self.state.ui.row_actions;
+        let rows_section = [
+            ("Click", row_actions.click, Message::SetClickAction as fn(usize) -> Message),
+            ("Double-click", row_actions.double_click, Message::SetDoubleClickAction),
+            ("Press and hold", row_actions.long_press, Message::SetLongPressAction),
+        ]
+        .into_iter()
+        .fold(widget::settings::section().title("Station rows"), |section, (label, action, on_select)| {
+            section.add(widget::settings::item(
+                label,
+                widget::dropdown(&ROW_ACTION_LABELS, ROW_ACTIONS.iter().position(|a| *a == action), on_select),
+            ))
+        });
+        let hidden_section = (!self.state.ui.blocked.is_empty()).then(|| {
+            self.state.ui.blocked.iter().fold(
+                widget::settings::section().title("Hidden from search results"),
+                |section, station| {
+                    section.add(
+                        widget::settings::item::builder(station.name.as_str()).control(
+                            widget::button::text("Show again")
+                                .on_press(Message::UnblockStation(station.stationuuid.clone())),
+                        ),
+                    )
+                },
+            )
+        });
+
+        let path = crate::config::config_path()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|e| e.to_string());
+        let config = widget::settings::section()
+            .title("Configuration")
+            .add(widget::text::caption(path))
+            .add(
+                widget::row()
+                    .spacing(8)
+                    .push(widget::button::standard("Open folder").on_press(Message::OpenConfigFolder))
+                    .push(widget::button::standard("Reload").on_press(Message::ReloadConfig)),
+            )
+            .add(
+                widget::settings::item::builder("Log to file")
+                    .description(
+                        crate::logging::log_dir()
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_else(|e| e.to_string()),
+                    )
+                    .control(widget::dropdown(
+                        &LOG_LEVEL_LABELS,
+                        LOG_LEVELS.iter().position(|l| *l == self.state.log_level),
+                        Message::SetLogLevel,
+                    )),
+            );
+
+        let mut column = widget::column()
+            .spacing(8)
+            .push(output)
+            .push(equalizer)
+            .push(channels)
+            .push(alarm_section)
+            .push(self.recording_section())
+            .push(ads_section)
+            .push(quality_section)
+            .push(stream_section)
+            .push_maybe(hidden_section)
+            .push(appearance)
+            .push(rows_section)
+            .push(self.mirrors_section())
+            .push(self.start_time_section())
+            .push(self.usage_section())
+            .push(config);
+        if self.popup_failures > 0 {
+            column = column.push(widget::text::caption(format!(
+                "The panel popup failed to open {} time(s) this session; using a separate window instead.",
+                self.popup_failures
+            )));
+        }
+        column = column.push(self.recent_errors_section());
+        cosmic::iced_widget::scrollable(column)
+            .height(Length::Fixed(360.0))
+            .into()
+    }
+
+    fn recent_errors_section(&self) -> cosmic::Element<'_, Message> {
+        let mut section = widget::settings::section().title("Recent errors");
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut any = false;
+        for err in self.state.errors.recent().rev() {
+            any = true;
+            section = section.add(widget::text::caption(format!(
+                "{} · {}",
+                time_ago(now.saturating_sub(err.at)),
+                err.message
+            )));
+        }
+        if !any {
+            section = section.add(widget::text::caption("None this session."));
+        }
+        section.into()
+    }
+
+    fn start_time_section(&self) -> cosmic::Element<'_, Message> {
+        let latency = &self.state.start_latency;
+        let secs = |ms: u32| format!("{:.1} s", ms as f32 / 1000.0);
+        let section = widget::settings::section().title("Station start time");
+        let Some(median) = latency.median_ms() else {
+            return section.add(widget::text::caption("No stations started yet.")).into();
+        };
+        let mut last = latency.last_ms().map(secs).unwrap_or_default();
+        if let Some(resolve) = latency.last_resolve_ms {
+            last = format!("{last} (lookup {})", secs(resolve));
+        }
+        section
+            .add(widget::text::caption(format!(
+                "Median {} · last {last} · target {}",
+                secs(median),
+                secs(latency.budget_ms())
+            )))
+            .into()
+    }
+
+    fn recording_section(&self) -> cosmic::Element<'_, Message> {
+        let folder = self
+            .state
+            .recording
+            .dir()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|e| e.to_string());
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let current = match &self.state.recorder {
+            Some(rec) => {
+                let mut about = match &rec.file {
+                    Some(file) => file.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                    None => "Starting…".to_string(),
+                };
+                if let Some(until) = rec.until {
+                    about = format!("{about} · ends in {}", countdown(until.saturating_sub(now)));
+                }
+                widget::settings::item::builder(format!("Recording {}", rec.station))
+                    .description(about)
+                    .control(widget::button::destructive("Stop").on_press(Message::StopRecording))
+            }
+            None => widget::settings::item::builder("Record this station")
+                .description(folder)
+                .control(
+                    widget::button::standard("Record")
+                        .on_press_maybe(self.state.station.as_ref().map(|_| Message::StartRecording)),
+                ),
+        };
+        let mut section = widget::settings::section().title("Recording").add(current).add(
+            widget::settings::item::builder("One file per song")
+                .description("Named and tagged after the stream's \"Artist - Title\"")
+                .toggler(self.state.recording.split_songs, Message::SetSplitSongs),
+        );
+
+        for (i, schedule) in self.state.recording.schedules.iter().enumerate() {
+            let days: Vec<&str> = schedule.days.iter().filter_map(|d| WEEKDAY_LABELS.get(usize::from(*d)).copied()).collect();
+            let summary = format!(
+                "{} {:02}:{:02} · {} min",
+                if days.is_empty() { "No days chosen".to_string() } else { days.join(" ") },
+                schedule.hour,
+                schedule.minute,
+                schedule.minutes
+            );
+            let day_buttons = WEEKDAY_LABELS.iter().enumerate().fold(widget::row().spacing(2), |row, (day, label)| {
+                let day = day as u8;
+                let button = if schedule.days.contains(&day) {
+                    widget::button::suggested(*label)
+                } else {
+                    widget::button::text(*label)
+                };
+                row.push(button.on_press(Message::EditRecordingSchedule(i, ScheduleEdit::ToggleDay(day))))
+            });
+            section = section
+                .add(
+                    widget::settings::item::builder(schedule.station.name.clone())
+                        .description(summary)
+                        .toggler(schedule.enabled, move |on| Message::EditRecordingSchedule(i, ScheduleEdit::Enabled(on))),
+                )
+                .add(day_buttons)
+                .add(
+                    widget::row()
+                        .spacing(4)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::dropdown(
+                            &self.alarm_hour_labels,
+                            Some(usize::from(schedule.hour.min(23))),
+                            move |h| Message::EditRecordingSchedule(i, ScheduleEdit::Hour(h)),
+                        ))
+                        .push(widget::text::body(":"))
+                        .push(widget::dropdown(
+                            &self.alarm_minute_labels,
+                            (schedule.minute % 5 == 0).then_some(usize::from(schedule.minute / 5)),
+                            move |m| Message::EditRecordingSchedule(i, ScheduleEdit::Minute(m)),
+                        ))
+                        .push(widget::dropdown(
+                            &RECORDING_LENGTH_LABELS,
+                            RECORDING_LENGTHS.iter().position(|m| *m == schedule.minutes),
+                            move |l| Message::EditRecordingSchedule(i, ScheduleEdit::Length(l)),
+                        ))
+                        .push(widget::checkbox("Silent", schedule.muted).on_toggle(move |m| {
+                            Message::EditRecordingSchedule(i, ScheduleEdit::Muted(m))
+                        }))
+                        .push(
+                            widget::button::text("Remove")
+                                .on_press(Message::EditRecordingSchedule(i, ScheduleEdit::Remove)),
+                        ),
+                );
+        }
+        section = section.add(
+            widget::button::standard("Record this station weekly")
+                .on_press_maybe(self.state.station.as_ref().map(|_| Message::AddRecordingSchedule)),
+        );
+
+        for file in self.state.recordings.iter().take(MAX_RECORDINGS_SHOWN) {
+            let writing = self.state.recorder.as_ref().and_then(|r| r.file.as_ref()) == Some(&file.path);
+            section = section.add(
+                widget::settings::item::builder(ellipsize_middle(&file.name(), 40))
+                    .description(if writing {
+                        "Recording…".to_string()
+                    } else {
+                        format!("{} · {}", data_size(file.bytes), time_ago(now.saturating_sub(file.modified)))
+                    })
+                    .control(
+                        widget::button::text("Delete")
+                            .on_press_maybe((!writing).then(|| Message::DeleteRecording(file.path.clone()))),
+                    ),
+            );
+        }
+        section
+            .add(widget::button::text("Open recordings folder").on_press(Message::OpenRecordingsFolder))
+            .into()
+    }
+
+    fn usage_section(&self) -> cosmic::Element<'_, Message> {
+        let usage = &self.state.usage;
+        let mut section = widget::settings::section().title("Listening statistics").add(
+            widget::settings::item::builder("Keep listening statistics")
+                .description("Stays on this computer and is never sent anywhere")
+                .toggler(self.state.privacy.usage_stats, Message::SetUsageStats),
+        );
+        if usage.is_empty() {
+            return section.into();
+        }
+        section = section.add(widget::text::caption(format!(
+            "{} listened in total",
+            listening_time(usage.listened_secs)
+        )));
+        for station in &usage.top_stations {
+            section = section.add(widget::text::caption(format!(
+                "{} · {} · {} play(s)",
+                ellipsize_middle(&station.name, 32),
+                listening_time(station.listened_secs),
+                station.plays
+            )));
+        }
+        if !usage.features.is_empty() {
+            let features: Vec<String> = usage.features.iter().map(|(f, n)| format!("{} {n}", f.label())).collect();
+            section = section.add(widget::text::caption(features.join(" · ")));
+        }
+        section
+            .add(widget::button::standard("Clear statistics").on_press(Message::ClearUsageStats))
+            .into()
+    }
+
+    fn mirrors_section(&self) -> cosmic::Element<'_, Message> {
+        let mut section = widget::settings::section().title("API mirrors");
+        let pinned = self.state.pinned_server.as_deref();
+        if let Some(p) = pinned.filter(|p| !self.state.mirrors.iter().any(|m| m.server == *p)) {
+            section = section.add(
+                widget::settings::item::builder(p)
+                    .description("Pinned, not used yet")
+                    .control(widget::button::text("Unpin").on_press(Message::PinMirror(None))),
+            );
+        }
+        if self.state.mirrors.is_empty() && pinned.is_none() {
+            section = section.add(widget::text::caption("No requests made this session."));
+        }
+        for m in &self.state.mirrors {
+            let latency = m
+                .avg_latency()
+                .map(|l| format!("{} ms", l.as_millis()))
+                .unwrap_or_else(|| "no answers".to_string());
+            let description = format!(
+                "{latency} · {} of {} failed ({:.0}%)",
+                m.failures,
+                m.requests,
+                m.failure_rate() * 100.0
+            );
+            let pin = if pinned == Some(m.server.as_str()) {
+                widget::button::text("Unpin").on_press(Message::PinMirror(None))
+            } else {
+                widget::button::text("Pin").on_press(Message::PinMirror(Some(m.server.clone())))
+            };
+            section = section.add(
+                widget::settings::item::builder(m.server.clone())
+                    .description(description)
+                    .control(pin),
+            );
+        }
+        section.into()
+    }
+
+    fn consent_view(&self) -> cosmic::Element<'_, Message> {
+        let cosmic::cosmic_theme::Spacing {
+            space_xxs,
+            space_s,
+            ..
+        } = cosmic::theme::spacing();
+
+        let summary = widget::column()
+            .spacing(space_xxs)
+            .push(widget::text::heading("Before you start"))
+            .push(widget::text::body(
+                "RadioWidget connects to the following services:",
+            ))
+            .push(widget::text::caption(
+                "• Radio Browser API mirrors (*.api.radio-browser.info) for search and stream lookup",
+            ))
+            .push(widget::text::caption(
+                "• The stream host of each station you play, via mpv",
+            ))
+            .push(widget::text::caption(
+                "• No station artwork, favicons or analytics are fetched",
+            ));
+
+        let settings = widget::column()
+            .spacing(space_xxs)
+            .push(widget::settings::item(
+                "Count plays on Radio Browser",
+                widget::toggler(self.consent_draft.count_clicks)
+                    .on_toggle(Message::ConsentCountClicks),
+            ))
+            .push(widget::settings::item(
+                "Remember last station and mirror",
+                widget::toggler(self.consent_draft.remember_history)
+                    .on_toggle(Message::ConsentRememberHistory),
+            ));
+
+        widget::column()
+            .spacing(space_s)
+            .padding(space_s)
+            .push(summary)
+            .push(settings)
+            .push(widget::button::suggested("Continue").on_press(Message::AcceptConsent))
+            .into()
+    }
+
+    fn start_view(&self) -> View {
+        match self.state.ui.start_view.resolve(self.state.ui.last_view) {
+            PopupView::Search => View::Search,
+            PopupView::Favorites => View::Favorites,
+        }
+    }
+
+    /// Records a popup that couldn't be shown and opens the standalone window
+    /// instead, so the click isn't silently lost.
+    fn popup_failed(&mut self, reason: &str) -> Task<Message> {
+        warn!(reason, "applet popup failed, falling back to a window");
+        self.popup_failures += 1;
+        let destroy = match self.popup.take() {
+            Some(id) => cosmic::task::message(cosmic::Action::Cosmic(
+                cosmic::app::Action::Surface(destroy_popup(id)),
+            )),
+            None => Task::none(),
+        };
+        Task::batch([destroy, self.toggle_fallback_window()])
+    }
+
+    fn toggle_fallback_window(&mut self) -> Task<Message> {
+        if let Some(id) = self.fallback_window.take() {
+            self.view = self.start_view();
+            return cosmic::iced::window::close(id);
+        }
+        let (id, open) = cosmic::iced::window::open(cosmic::iced::window::Settings {
+            size: cosmic::iced::Size::new(420.0, 600.0),
+            ..Default::default()
+        });
+        self.fallback_window = Some(id);
+        open.map(|_| cosmic::Action::App(Message::Noop))
+    }
+
+    /// Favorites waiting out their undo window already count as removed.
+    fn is_favorite(&self, stationuuid: &str) -> bool {
+        self.state.favorites.iter().any(|f| f.stationuuid == stationuuid)
+            && !self
                 .state
-                .favorites
+                .pending_removals
                 .iter()
-                .any(|f| f.stationuuid == s.stationuuid);
-            let fav_text = if is_fav { "★" } else { "☆" };
+                .any(|f| f.stationuuid == stationuuid)
+    }
 
-            let item = widget::row()
-                .spacing(8)
+    fn row_action(&mut self, action: RowAction, station: StationRef) {
+        let panel = match action {
+            RowAction::Play => {
+                let _ = self.controller.cmd_tx.send(UiCommand::Play(station));
+                return;
+            }
+            // Removing goes through the usual undo window.
+            RowAction::Favorite if self.is_favorite(&station.stationuuid) => {
+                let _ = self.controller.cmd_tx.send(UiCommand::RemoveFavorite(station));
+                return;
+            }
+            RowAction::Favorite => {
+                let _ = self.controller.cmd_tx.send(UiCommand::ToggleFavorite(station));
+                return;
+            }
+            RowAction::Details => RowPanel::Details,
+            RowAction::Menu => RowPanel::Menu,
+            RowAction::Nothing => return,
+        };
+        // Asking again for what's already open closes it.
+        let open = (station.stationuuid, panel);
+        self.row_panel = (self.row_panel.as_ref() != Some(&open)).then_some(open);
+    }
+
+    fn update_ui(&self, f: impl FnOnce(&mut UiConfig)) {
+        let mut ui = self.state.ui.clone();
+        f(&mut ui);
+        if ui != self.state.ui {
+            let _ = self.controller.cmd_tx.send(UiCommand::UpdateUi(ui));
+        }
+    }
+
+    /// Dictation button, in voice-search builds with a transcriber configured.
+    fn mic_button(&self) -> Option<cosmic::Element<'_, Message>> {
+        if !cfg!(feature = "voice-search") || self.view == View::Favorites || self.state.voice.transcriber.is_empty() {
+            return None;
+        }
+        let label = if self.state.dictating { "Listening…" } else { "🎤" };
+        let button = widget::button::text(label).on_press_maybe((!self.state.dictating).then_some(Message::Dictate));
+        Some(widget::tooltip(button, widget::text::body("Say a station name"), widget::tooltip::Position::Bottom).into())
+    }
+
+    fn send_alarm(&mut self, edit: impl FnOnce(&mut AlarmConfig)) {
+        edit(&mut self.state.alarm);
+        let _ = self.controller.cmd_tx.send(UiCommand::SetAlarm(self.state.alarm.clone()));
+    }
+
+    fn send_recording(&mut self, edit: impl FnOnce(&mut RecordingConfig)) {
+        edit(&mut self.state.recording);
+        let _ = self.controller.cmd_tx.send(UiCommand::SetRecording(self.state.recording.clone()));
+    }
+
+    fn send_ad_mute(&mut self, edit: impl FnOnce(&mut AdMuteConfig)) {
+        edit(&mut self.state.ad_mute);
+        let _ = self.controller.cmd_tx.send(UiCommand::SetAdMute(self.state.ad_mute.clone()));
+    }
+
+    fn send_quality(&mut self, edit: impl FnOnce(&mut QualityConfig)) {
+        edit(&mut self.state.quality);
+        self.refresh_labels();
+        let _ = self.controller.cmd_tx.send(UiCommand::SetQuality(self.state.quality.clone()));
+    }
+
+    fn send_cache(&self, preset: CachePreset, secs: u32, max_mib: u32) {
+        let _ = self.controller.cmd_tx.send(UiCommand::SetCache { preset, secs, max_mib });
+    }
+
+    /// Countdown while the sleep timer runs, shortcuts to start one otherwise.
+    fn sleep_timer_row(&self) -> Option<cosmic::Element<'_, Message>> {
+        let cosmic::cosmic_theme::Spacing { space_xxs, .. } = cosmic::theme::spacing();
+        let row = widget::row()
+            .spacing(space_xxs)
+            .align_y(cosmic::iced::Alignment::Center);
+        if let Some(until) = self.state.sleep_until {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let row = row
+                .push(widget::text::caption(format!(
+                    "Stops in {}",
+                    countdown(until.saturating_sub(now))
+                )))
                 .push(
-                    widget::button::custom(
-                        widget::column()
-                            .spacing(2)
-                            .push(widget::text::body(&s.name))
-                            .push(widget::text::caption(subtitle)),
-                    )
-                    .on_press(Message::PlayStation(station_ref.clone()))
-                    .width(Length::Fill),
+                    widget::button::text("Cancel")
+                        .class(cosmic::theme::Button::Link)
+                        .on_press(Message::SetSleepTimer(None)),
+                );
+            return Some(row.into());
+        }
+        if !matches!(
+            self.state.phase,
+            PlaybackPhase::Buffering | PlaybackPhase::Reconnecting | PlaybackPhase::Playing | PlaybackPhase::Paused
+        ) {
+            return None;
+        }
+        let mut row = row.push(widget::text::caption("Sleep"));
+        for minutes in SLEEP_MINUTES {
+            row = row.push(
+                widget::button::text(format!("{minutes} min"))
+                    .on_press(Message::SetSleepTimer(Some(minutes))),
+            );
+        }
+        let custom = widget::text_input("min", &self.sleep_minutes)
+            .on_input(Message::SleepMinutesInput)
+            .on_submit(|_| Message::SleepMinutesSubmit)
+            .width(Length::Fixed(56.0));
+        Some(row.push(custom).into())
+    }
+
+    /// Keeps a placeholder avatar for every station a list can show.
+    fn refresh_avatars(&mut self) {
+        let colors = &self.state.favorite_colors;
+        let favorites = self.state.favorites.iter().map(|s| (s.stationuuid.as_str(), s.name.as_str()));
+        let listed = self
+            .state
+            .search_results
+            .iter()
+            .map(|s| (s.stationuuid.as_str(), s.name.as_str()));
+        self.avatars.refresh(
+            favorites
+                .chain(listed)
+                .map(|(uuid, name)| (uuid, name, colors.get(uuid).map(|c| rgb(label_rgb(*c))))),
+        );
+    }
+
+    /// Initials badge standing in for station artwork.
+    fn avatar_badge(&self, uuid: &str) -> Option<cosmic::Element<'_, Message>> {
+        const SIZE: f32 = 28.0;
+        let avatar = self.avatars.get(uuid)?;
+        let [r, g, b] = avatar.fill;
+        let fill = cosmic::iced::Color::from_rgb(r, g, b);
+        let [r, g, b] = avatar.ink;
+        let ink = cosmic::iced::Color::from_rgb(r, g, b);
+        let badge = widget::container(
+            widget::text::caption_heading(avatar.initials.as_str()).class(cosmic::theme::Text::Color(ink)),
+        )
+        .center(Length::Fixed(SIZE))
+        .class(cosmic::theme::Container::custom(move |_| cosmic::iced::widget::container::Style {
+            background: Some(fill.into()),
+            border: cosmic::iced::Border {
+                radius: (SIZE / 2.0).into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+        Some(badge.into())
+    }
+
+    /// Recomputes the cached label and subtitle text from `state`.
+    fn refresh_labels(&mut self) {
+        self.tooltip_text = self
+            .state
+            .station
+            .as_ref()
+            .map(|s| s.name.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "Radio".to_string());
+        let panel_full = self.panel_label(&self.tooltip_text);
+        self.panel_text_chars = self.panel_chars();
+        self.panel_text = ellipsize_middle(&panel_full, self.panel_text_chars).into_owned();
+        if panel_full != self.panel_full {
+            // New song or station: start from the beginning.
+            self.marquee_offset = 0;
+            self.panel_full = panel_full;
+        }
+
+        let state = &self.state;
+        let listed: Vec<usize> = (0..state.search_results.len())
+            .filter(|&i| !state.ui.blocked.iter().any(|b| b.stationuuid == state.search_results[i].stationuuid))
+            .collect();
+        self.result_order = listed
+            .iter()
+            .copied()
+            .filter(|&i| self.show_filtered || state.quality.allows(&state.search_results[i]))
+            .collect();
+        self.hidden_results = listed.len() - self.result_order.len();
+        sort_stations(&mut self.result_order, state.ui.result_order, |&i| &state.search_results[i]);
+        self.subtitles = state
+            .search_results
+            .iter()
+            .chain(state.station_details.values())
+            .chain(&state.alternates)
+            .chain(&state.station_of_day)
+            .map(|s| {
+                let video = state.video_stations.contains(&s.stationuuid);
+                (s.stationuuid.clone(), station_subtitle(s, video))
+            })
+            .collect();
+    }
+
+    fn subtitle(&self, stationuuid: &str) -> &str {
+        self.subtitles.get(stationuuid).map(String::as_str).unwrap_or("")
+    }
+
+    fn panel_label(&self, station_name: &str) -> String {
+        let title = self
+            .state
+            .media_title
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty() && *t != station_name);
+        match (self.state.ui.label_mode, title) {
+            (LabelMode::Title, Some(t)) => t.to_string(),
+            (LabelMode::TitleAndStation, Some(t)) => format!("{t} — {station_name}"),
+            _ => station_name.to_string(),
+        }
+    }
+
+    /// A stream is loaded but not audible yet.
+    fn waiting_for_audio(&self) -> bool {
+        matches!(self.state.phase, PlaybackPhase::Buffering | PlaybackPhase::Reconnecting)
+    }
+
+    /// Whether the panel label is scrolling right now.
+    fn marquee_running(&self) -> bool {
+        self.state.ui.marquee
+            && self.core.applet.is_horizontal()
+            && !self.panel_hovered
+            && self.volume_overlay.is_none()
+            && !self.compact_panel()
+            && self.panel_full.chars().nth(self.panel_chars()).is_some()
+    }
+
+    /// Label length in characters, scaled with the panel's icon size
+    /// (30 on a medium panel).
+    fn panel_chars(&self) -> usize {
+        let (icon, _) = self.core.applet.suggested_size(true);
+        (usize::from(icon) * 3 / 2).clamp(20, 40)
+    }
+
+    /// Icon plus state dot instead of the label.
+    fn compact_panel(&self) -> bool {
+        use cosmic::applet::{cosmic_panel_config::PanelSize, Size};
+        match self.state.ui.panel_style {
+            PanelStyle::Label => false,
+            PanelStyle::Compact => true,
+            PanelStyle::Auto => {
+                self.core.applet.is_horizontal()
+                    && matches!(self.core.applet.size, Size::PanelSize(PanelSize::XS))
+            }
+        }
+    }
+
+    fn compact_button(&self) -> widget::Button<'_, Message> {
+        let (icon_size, _) = self.core.applet.suggested_size(true);
+        let icon = widget::icon::from_name("audio-x-generic-symbolic")
+            .size(icon_size)
+            .symbolic(true)
+            .icon();
+        let dot = match self.state.phase {
+            PlaybackPhase::Playing => Some(LabelColor::Green),
+            PlaybackPhase::Buffering | PlaybackPhase::Reconnecting => Some(LabelColor::Yellow),
+            PlaybackPhase::Paused => Some(LabelColor::Blue),
+            PlaybackPhase::Error | PlaybackPhase::MissingBackend => Some(LabelColor::Red),
+            PlaybackPhase::Idle | PlaybackPhase::NotConfigured => None,
+        };
+        let content = widget::row()
+            .spacing(2)
+            .align_y(cosmic::iced::Alignment::Center)
+            .push(icon)
+            .push_maybe(dot.map(|c| {
+                widget::text::caption("●").class(cosmic::theme::Text::Color(label_rgb(c)))
+            }));
+        widget::button::custom(content)
+            .padding(self.core.applet.suggested_padding(true))
+            .class(cosmic::theme::Button::AppletIcon)
+    }
+
+    fn playing_label_color(&self) -> Option<LabelColor> {
+        if !self.state.ui.tint_panel
+            || !matches!(
+                self.state.phase,
+                PlaybackPhase::Buffering
+                    | PlaybackPhase::Reconnecting
+                    | PlaybackPhase::Playing
+                    | PlaybackPhase::Paused
+            )
+        {
+            return None;
+        }
+        let station = self.state.station.as_ref()?;
+        self.state.favorite_colors.get(&station.stationuuid).copied()
+    }
+
+    fn now_playing_strip(&self) -> Option<cosmic::Element<'_, Message>> {
+        let station = self.state.station.as_ref()?;
+        let cosmic::cosmic_theme::Spacing { space_xxs, .. } = cosmic::theme::spacing();
+
+        let title = self
+            .state
+            .media_title
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty() && *t != station.name.trim());
+        let mut info = now_playing_heading(station.name.trim(), title, self.state.ui.wrap_titles);
+        if self.state.ad_ducked {
+            info = info.push(widget::text::caption("Turned down until the ad is over"));
+        }
+        if let Some(tech) = self.state.stream_tech.as_ref().filter(|_| !self.waiting_for_audio()) {
+            info = info.push(widget::text::caption(tech.summary()));
+        }
+        let level = self
+            .level
+            .filter(|_| self.state.ui.level_meter && self.state.phase == PlaybackPhase::Playing)
+            .filter(|_| self.state.cast_target.is_none());
+        if let Some(level) = level {
+            info = info.push(widget::text::caption(format!(
+                "{} {:.0} dB",
+                level_bar(level, LEVEL_CELLS),
+                level.rms_db
+            )));
+        }
+        if self.already_playing_flash.is_some() {
+            info = info.push(
+                widget::row()
+                    .spacing(space_xxs)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(widget::text::caption("Already playing"))
+                    .push(
+                        widget::button::text("Reload")
+                            .class(cosmic::theme::Button::Link)
+                            .on_press(Message::Reload),
+                    ),
+            );
+        }
+        if let Some(tags) = &self.state.stream_tags {
+            // A name that differs from the directory entry often means a misconfigured station.
+            let name = tags
+                .name
+                .as_deref()
+                .filter(|n| !n.eq_ignore_ascii_case(station.name.trim()))
+                .map(|n| format!("Stream says “{}”", ellipsize_middle(n, 32)));
+            let parts: Vec<String> = name
+                .into_iter()
+                .chain(tags.genre.clone())
+                .chain(tags.url.as_deref().map(|u| ellipsize_middle(u, 32).into_owned()))
+                .collect();
+            if !parts.is_empty() {
+                info = info.push(widget::text::caption(parts.join(" · ")));
+            }
+        }
+        if self.state.session_bytes > 0 {
+            info = info.push(widget::text::caption(format!(
+                "{} used this session",
+                data_size(self.state.session_bytes)
+            )));
+        }
+        if self.waiting_for_audio() {
+            let spinner = SPINNER[self.spinner_frame];
+            let reconnect = self.state.reconnect.filter(|_| self.state.phase == PlaybackPhase::Reconnecting);
+            if let Some(r) = reconnect {
+                info = info.push(
+                    widget::row()
+                        .spacing(space_xxs)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::text::caption(format!(
+                            "{spinner} Reconnecting, try {} of {MAX_RECONNECTS}: {}",
+                            r.attempt,
+                            r.reason.describe()
+                        )))
+                        .push(
+                            widget::button::text("Stop retrying")
+                                .class(cosmic::theme::Button::Link)
+                                .on_press(Message::StopReconnecting),
+                        ),
+                );
+            } else {
+                let status = match (self.state.phase, self.state.buffer_percent) {
+                    (PlaybackPhase::Reconnecting, _) => format!("{spinner} Reconnecting…"),
+                    (_, Some(percent)) => format!("{spinner} Buffering… {percent}%"),
+                    _ => format!("{spinner} Buffering…"),
+                };
+                info = info.push(widget::text::caption(status));
+            }
+        }
+        let origin = self.state.stream_origin.as_ref().map(|o| {
+            let mut parts = vec![if o.https { "HTTPS" } else { "HTTP (unencrypted)" }, o.host.as_str()];
+            if o.redirected {
+                parts.push("redirected");
+            }
+            widget::row()
+                .spacing(space_xxs)
+                .align_y(cosmic::iced::Alignment::Center)
+                .push(widget::text::caption(parts.join(" · ")))
+                .push(
+                    widget::button::text("Copy URL")
+                        .class(cosmic::theme::Button::Link)
+                        .on_press(Message::CopyStreamUrl),
                 )
-                .push(widget::button::text(fav_text).on_press(Message::ToggleFavorite(station_ref)));
+        });
 
-            list = list.add(item);
+        let mut controls = widget::row().spacing(space_xxs);
+        match self.state.phase {
+            PlaybackPhase::Buffering
+            | PlaybackPhase::Reconnecting
+            | PlaybackPhase::Playing
+            | PlaybackPhase::Paused => {
+                let pause_label = if self.state.phase == PlaybackPhase::Paused {
+                    "Resume"
+                } else {
+                    "Pause"
+                };
+                // Replaying what was just missed needs a local player with
+                // that much of the stream still cached.
+                let can_seek = |secs: u32| {
+                    self.state.cast_target.is_none()
+                        && matches!(self.state.phase, PlaybackPhase::Playing | PlaybackPhase::Paused)
+                        && self.state.rewindable >= secs
+                };
+                controls = controls
+                    .push(
+                        widget::button::text("−60s")
+                            .on_press_maybe(can_seek(60).then_some(Message::SeekRelative(-60.0))),
+                    )
+                    .push(
+                        widget::button::text("−30s")
+                            .on_press_maybe(can_seek(30).then_some(Message::SeekRelative(-30.0))),
+                    )
+                    .push(widget::button::text(pause_label).on_press(Message::TogglePause))
+                    .push(widget::button::text("Stop").on_press(Message::Stop));
+            }
+            _ => {
+                controls = controls
+                    .push(widget::button::text("Play").on_press(Message::PlayCurrent))
+                    .push(widget::button::text("Clear").on_press(Message::ClearCurrent));
+            }
         }
 
-        let scroll =
-            cosmic::iced_widget::scrollable(list.into_element()).height(Length::Fixed(300.0));
-        scroll.into()
+        let strip = widget::row()
+            .spacing(space_xxs)
+            .align_y(cosmic::iced::Alignment::Center)
+            .push(info.width(Length::Fill))
+            .push(controls);
+
+        let volume = widget::row()
+            .spacing(space_xxs)
+            .align_y(cosmic::iced::Alignment::Center)
+            .push(widget::text::caption("Volume"))
+            .push(widget::slider(0.0..=100.0, self.state.volume, Message::SetVolume).step(1.0))
+            .push(widget::text::caption(format!("{}%", self.state.volume.round() as i64)))
+            .push(
+                widget::button::text(if self.state.muted { "Unmute" } else { "Mute" })
+                    .on_press(Message::SetMute(!self.state.muted)),
+            );
+
+        Some(
+            widget::container(
+                widget::column()
+                    .spacing(space_xxs)
+                    .push(strip)
+                    .push_maybe(origin)
+                    .push(volume)
+                    .push_maybe(self.sleep_timer_row()),
+            )
+                .class(cosmic::theme::Container::Card)
+                .padding(space_xxs)
+                .into(),
+        )
     }
 
-    fn favorites_list<'a>(&'a self, favorites: &'a [StationRef]) -> cosmic::Element<'a, Message> {
-        let mut list = widget::list_column().padding(0).spacing(0);
-        for s in favorites {
-            let fav_text = "★";
+    fn results_list(&self) -> cosmic::Element<'_, Message> {
+        station_list(self.result_order.iter().filter_map(|&i| self.state.search_results.get(i)).map(|s| {
+            let station_ref = StationRef {
+                stationuuid: s.stationuuid.clone(),
+                name: s.name.clone(),
+                url: None,
+                group: None,
+            };
+            let is_fav = self.is_favorite(&s.stationuuid);
+            let fav_text = if is_fav { "★" } else { "☆" };
+
+            let entry = station_entry(&s.name, Some(self.subtitle(&s.stationuuid)), self.avatar_badge(&s.stationuuid));
             let item = widget::row()
                 .spacing(8)
-                .push(
-                    widget::button::custom(
-                        widget::column()
-                            .spacing(2)
-                            .push(widget::text::body(&s.name)),
-                    )
-                    .on_press(Message::PlayStation(s.clone()))
+                .push(self.station_row(&station_ref, entry))
+                .push(widget::button::text(fav_text).on_press(Message::ToggleFavorite(station_ref.clone())));
+
+            widget::column()
+                .spacing(4)
+                .push(item)
+                .push_maybe(self.row_panel_view(&station_ref, true))
+                .into()
+        }))
+    }
+
+    /// Multi-select list with bulk rename, grouping and delete; applied in one save.
+    fn favorites_edit_view<'a>(&'a self, edit: &'a FavoritesEdit) -> cosmic::Element<'a, Message> {
+        let any = !edit.selected.is_empty();
+        let all = any && edit.selected.len() == edit.draft.len();
+        let toolbar = widget::row()
+            .spacing(8)
+            .align_y(cosmic::iced::Alignment::Center)
+            .push(widget::checkbox("", all).on_toggle(Message::FavoritesEditSelectAll))
+            .push(
+                widget::text_input("Prefix", &edit.prefix)
+                    .on_input(Message::FavoritesEditPrefix)
                     .width(Length::Fill),
-                )
-                .push(widget::button::text(fav_text).on_press(Message::ToggleFavorite(s.clone())));
-            list = list.add(item);
+            )
+            .push(
+                widget::button::standard("Strip prefix")
+                    .on_press_maybe(any.then_some(Message::FavoritesEditStripPrefix)),
+            )
+            .push(widget::button::destructive("Delete").on_press_maybe(any.then_some(Message::FavoritesEditDelete)));
+        let group_bar = widget::row()
+            .spacing(8)
+            .align_y(cosmic::iced::Alignment::Center)
+            .push(
+                widget::text_input("Group (empty for none)", &edit.group)
+                    .on_input(Message::FavoritesEditGroup)
+                    .width(Length::Fill),
+            )
+            .push(widget::button::standard("Set group").on_press_maybe(any.then_some(Message::FavoritesEditSetGroup)));
+
+        let mut list = widget::list_column().padding(0).spacing(0);
+        for s in &edit.draft {
+            let uuid = s.stationuuid.clone();
+            let label = match &s.group {
+                Some(group) => format!("{} · {group}", s.name),
+                None => s.name.clone(),
+            };
+            list = list.add(
+                widget::checkbox(label, edit.selected.contains(&s.stationuuid))
+                    .on_toggle(move |on| Message::FavoritesEditSelect(uuid.clone(), on))
+                    .width(Length::Fill),
+            );
         }
-        let scroll = cosmic::iced_widget::scrollable(list.into_element()).height(Length::Fixed(300.0));
-        scroll.into()
+        let removed = self.state.favorites.len().saturating_sub(edit.draft.len());
+        let summary = match removed {
+            0 => format!("{} selected", edit.selected.len()),
+            n => format!("{} selected · {n} to delete", edit.selected.len()),
+        };
+        let changed = edit.draft != self.state.favorites;
+        widget::column()
+            .spacing(8)
+            .push(toolbar)
+            .push(group_bar)
+            .push(cosmic::iced_widget::scrollable(list.into_element()).height(Length::Fixed(260.0)))
+            .push(
+                widget::row()
+                    .spacing(8)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(widget::text::caption(summary).width(Length::Fill))
+                    .push(widget::button::text("Cancel").on_press(Message::FavoritesEditCancel))
+                    .push(widget::button::suggested("Apply").on_press_maybe(changed.then_some(Message::FavoritesEditApply))),
+            )
+            .into()
+    }
+
+    /// A station row's name and details, reacting to the configured click,
+    /// double-click and long-press actions.
+    fn station_row<'a>(
+        &self,
+        station: &StationRef,
+        content: impl Into<cosmic::Element<'a, Message>>,
+    ) -> cosmic::Element<'a, Message> {
+        let content = widget::container(content).padding([4, 8]).width(Length::Fill);
+        cosmic::iced_widget::mouse_area(content)
+            .interaction(mouse::Interaction::Pointer)
+            .on_press(Message::RowPressed(station.clone()))
+            .on_release(Message::RowReleased)
+            .on_double_click(Message::RowDoubleClicked(station.clone()))
+            .on_exit(Message::RowPressCancelled)
+            .into()
     }
-}
 
-fn station_subtitle(s: &Station) -> String {
-    let mut parts: Vec<String> = Vec::new();
-    if let Some(c) = s.country.as_ref().map(|x| x.trim()).filter(|x| !x.is_empty()) {
-        parts.push(c.to_string());
+    /// The menu or details opened under `station`'s row, if any. Only search
+    /// results can be hidden; favorites are removed instead.
+    fn row_panel_view(&self, station: &StationRef, hideable: bool) -> Option<cosmic::Element<'_, Message>> {
+        let (uuid, panel) = self.row_panel.as_ref()?;
+        if *uuid != station.stationuuid {
+            return None;
+        }
+        let content: cosmic::Element<'_, Message> = match panel {
+            RowPanel::Menu => {
+                let favorite = if self.is_favorite(uuid) { "Unfavorite" } else { "Favorite" };
+                widget::row()
+                    .spacing(8)
+                    .push(widget::button::text("Play").on_press(Message::RowAction(RowAction::Play, station.clone())))
+                    .push(
+                        widget::button::text(favorite)
+                            .on_press(Message::RowAction(RowAction::Favorite, station.clone())),
+                    )
+                    .push(
+                        widget::button::text("Details")
+                            .on_press(Message::RowAction(RowAction::Details, station.clone())),
+                    )
+                    .push_maybe(
+                        hideable.then(|| widget::button::text("Hide").on_press(Message::BlockStation(station.clone()))),
+                    )
+                    .push(widget::button::text("Close").on_press(Message::CloseRowPanel))
+                    .into()
+            }
+            RowPanel::Details => {
+                let facts = match self.find_station(uuid) {
+                    Some(s) => station_facts(s),
+                    // Pasted streams have nothing but their address.
+                    None => station.url.iter().cloned().collect(),
+                };
+                let mut column = widget::column().spacing(2);
+                if facts.is_empty() {
+                    column = column.push(widget::text::caption("Radio Browser has nothing more on this station"));
+                }
+                for fact in facts {
+                    column = column.push(widget::text::caption(fact));
+                }
+                if self.is_favorite(uuid) && station.url.is_none() {
+                    let own = self.state.favorite_stream_choice.get(uuid);
+                    let global = self.state.stream_choice.clone();
+                    let toggled = uuid.clone();
+                    column = column.push(
+                        widget::checkbox("Own stream choice", own.is_some()).on_toggle(move |on| {
+                            Message::SetFavoriteStream(toggled.clone(), on.then(|| global.clone()))
+                        }),
+                    );
+                    if let Some(choice) = own {
+                        column = column.push(favorite_stream_row(uuid, choice));
+                    }
+                }
+                widget::row()
+                    .spacing(8)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(column.width(Length::Fill))
+                    .push(widget::button::text("Close").on_press(Message::CloseRowPanel))
+                    .into()
+            }
+        };
+        Some(
+            widget::container(content)
+                .padding(8)
+                .class(cosmic::theme::Container::Card)
+                .into(),
+        )
     }
-    if let Some(codec) = s.codec.as_ref().map(|x| x.trim()).filter(|x| !x.is_empty()) {
-        parts.push(codec.to_string());
+
+    fn find_station(&self, stationuuid: &str) -> Option<&Station> {
+        self.state
+            .station_details
+            .get(stationuuid)
+            .or_else(|| self.state.search_results.iter().find(|s| s.stationuuid == stationuuid))
     }
-    if let Some(br) = s.bitrate {
-        parts.push(format!("{br} kbps"));
+
+    /// Favorites without a group first, then each group under its name with
+    /// a button to shuffle through it.
+    fn grouped_favorites(&self) -> cosmic::Element<'_, Message> {
+        let favorites = &self.state.favorites;
+        let mut groups: Vec<&str> = Vec::new();
+        for group in favorites.iter().filter_map(|f| f.group.as_deref()) {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        let ungrouped: Vec<&StationRef> = favorites.iter().filter(|f| f.group.is_none()).collect();
+        let mut column = widget::column().spacing(8);
+        for suggestion in group_suggestions(favorites, &self.state.station_details) {
+            if self.declined_groups.contains(&suggestion.tag) {
+                continue;
+            }
+            column = column.push(
+                widget::row()
+                    .spacing(8)
+                    .align_y(cosmic::iced::Alignment::Center)
+                    .push(
+                        widget::text::caption(format!(
+                            "You have {} {} stations — create a {} group?",
+                            suggestion.members.len(),
+                            suggestion.tag,
+                            suggestion.name
+                        ))
+                        .width(Length::Fill),
+                    )
+                    .push(widget::button::text("Not now").on_press(Message::DeclineGroupSuggestion(suggestion.tag.clone())))
+                    .push(widget::button::suggested("Create").on_press(Message::AcceptGroupSuggestion(suggestion))),
+            );
+        }
+        column = column
+            .push_maybe((!ungrouped.is_empty()).then(|| self.favorites_list(ungrouped)));
+        for group in groups {
+            let members: Vec<&StationRef> = favorites.iter().filter(|f| f.group.as_deref() == Some(group)).collect();
+            column = column
+                .push(
+                    widget::row()
+                        .spacing(8)
+                        .align_y(cosmic::iced::Alignment::Center)
+                        .push(widget::text::heading(group).width(Length::Fill))
+                        .push(widget::button::text("Shuffle").on_press(Message::ShuffleGroup(group.to_string()))),
+                )
+                .push(self.favorites_list(members));
+        }
+        column.into()
     }
-    if parts.is_empty() {
-        String::new()
-    } else {
-        parts.join(" · ")
+
+    fn favorites_list<'a>(&'a self, favorites: Vec<&'a StationRef>) -> cosmic::Element<'a, Message> {
+        station_list(favorites.into_iter().map(|s| {
+            let removing = !self.is_favorite(&s.stationuuid);
+            let color = self.state.favorite_colors.get(&s.stationuuid).copied();
+            let subtitle = Some(self.subtitle(&s.stationuuid)).filter(|t| !t.is_empty());
+            let dot = match color {
+                Some(c) => {
+                    widget::text::body("●").class(cosmic::theme::Text::Color(label_rgb(c)))
+                }
+                None => widget::text::body("○"),
+            };
+            let entry = station_entry(&s.name, subtitle, self.avatar_badge(&s.stationuuid));
+            let item = widget::row()
+                .spacing(8)
+                .push(
+                    widget::button::custom(dot)
+                        .class(cosmic::theme::Button::Text)
+                        .on_press(Message::CycleFavoriteColor(s.stationuuid.clone())),
+                )
+                .push(if removing {
+                    // Rows pending removal stay put but render disabled until undone.
+                    widget::button::custom(entry).width(Length::Fill).into()
+                } else {
+                    self.station_row(s, entry)
+                })
+                .push(if removing {
+                    widget::button::text("Undo")
+                        .on_press(Message::UndoRemoveFavorite(s.stationuuid.clone()))
+                } else {
+                    widget::button::text("★").on_press(Message::RemoveFavorite(s.clone()))
+                });
+            widget::column()
+                .spacing(4)
+                .push(item)
+                .push_maybe((!removing).then(|| self.row_panel_view(s, false)).flatten())
+                .into()
+        }))
     }
 }
@@ -0,0 +1,152 @@
+//! Placeholder artwork: a station's initials on a colored circle. Favicons are
+//! never fetched, so every station gets one. They're cached per station and
+//! rebuilt when the theme changes, since the lists redraw on every update.
+
+use std::collections::HashMap;
+
+/// Words that say nothing about which station it is.
+const FILLER_WORDS: [&str; 3] = ["radio", "the", "webradio"];
+
+/// sRGB components in 0..=1.
+pub type Rgb = [f32; 3];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub accent: Rgb,
+    pub dark: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Avatar {
+    pub initials: String,
+    pub fill: Rgb,
+    /// Text color that reads on `fill`.
+    pub ink: Rgb,
+    /// Label color or accent the fill was derived from.
+    base: Rgb,
+}
+
+impl Avatar {
+    fn new(name: &str, base: Rgb, dark: bool) -> Self {
+        // Muted on dark themes so the circles don't glare; a touch lighter on light ones.
+        let fill = if dark { mix(base, [0.0; 3], 0.2) } else { mix(base, [1.0; 3], 0.1) };
+        let ink = if luminance(fill) > 0.5 { [0.1; 3] } else { [1.0; 3] };
+        Self {
+            initials: initials_or_note(name),
+            fill,
+            ink,
+            base,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AvatarCache {
+    palette: Option<Palette>,
+    entries: HashMap<String, Avatar>,
+}
+
+impl AvatarCache {
+    /// Returns whether the palette changed, in which case every avatar is rebuilt.
+    pub fn set_palette(&mut self, palette: Palette) -> bool {
+        if self.palette == Some(palette) {
+            return false;
+        }
+        self.palette = Some(palette);
+        self.entries.clear();
+        true
+    }
+
+    /// Keeps avatars for exactly these `(uuid, name, label color)` stations,
+    /// reusing the ones that haven't changed.
+    pub fn refresh<'a>(&mut self, stations: impl IntoIterator<Item = (&'a str, &'a str, Option<Rgb>)>) {
+        let Some(palette) = self.palette else {
+            return;
+        };
+        let mut old = std::mem::take(&mut self.entries);
+        for (uuid, name, label) in stations {
+            if self.entries.contains_key(uuid) {
+                continue;
+            }
+            let base = label.unwrap_or(palette.accent);
+            let avatar = match old.remove(uuid) {
+                Some(a) if a.base == base && a.initials == initials_or_note(name) => a,
+                _ => Avatar::new(name, base, palette.dark),
+            };
+            self.entries.insert(uuid.to_string(), avatar);
+        }
+    }
+
+    pub fn get(&self, uuid: &str) -> Option<&Avatar> {
+        self.entries.get(uuid)
+    }
+}
+
+/// Initials, or a note for names without letters or digits.
+fn initials_or_note(name: &str) -> String {
+    match initials(name) {
+        i if i.is_empty() => "♪".to_string(),
+        i => i,
+    }
+}
+
+/// First letters of the first two telling words, e.g. "BBC Radio 4" → "B4".
+pub fn initials(name: &str) -> String {
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let telling: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|w| !FILLER_WORDS.contains(&w.to_lowercase().as_str()))
+        .collect();
+    let pick = if telling.is_empty() { &words } else { &telling };
+    pick.iter()
+        .take(2)
+        .filter_map(|w| w.chars().next())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn mix(a: Rgb, b: Rgb, t: f32) -> Rgb {
+    [0, 1, 2].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+fn luminance(c: Rgb) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_telling_initials() {
+        assert_eq!(initials("BBC Radio 4"), "B4");
+        assert_eq!(initials("The Jazz Groove"), "JG");
+        assert_eq!(initials("radio paradise - main mix"), "PM");
+        assert_eq!(initials("Radio"), "R");
+        assert_eq!(initials("ößé radio"), "Ö");
+        assert_eq!(initials(" - "), "");
+    }
+
+    #[test]
+    fn follows_the_theme_and_label_color() {
+        let mut cache = AvatarCache::default();
+        let yellow = [0.96, 0.83, 0.18];
+        let blue = [0.21, 0.52, 0.89];
+        assert!(cache.set_palette(Palette { accent: blue, dark: true }));
+        cache.refresh([("a", "Jazz FM", None), ("b", "Sun Radio", Some(yellow))]);
+        assert_eq!(cache.get("a").unwrap().initials, "JF");
+        // Dark ink on a light fill, light ink on a dark one.
+        assert_eq!(cache.get("b").unwrap().ink, [0.1; 3]);
+        assert_eq!(cache.get("a").unwrap().ink, [1.0; 3]);
+
+        assert!(!cache.set_palette(Palette { accent: blue, dark: true }));
+        assert!(cache.set_palette(Palette { accent: blue, dark: false }));
+        cache.refresh([("b", "Sun Radio", None)]);
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.get("b").unwrap().fill, mix(blue, [1.0; 3], 0.1));
+    }
+}
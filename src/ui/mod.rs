@@ -1,4 +1,6 @@
 mod applet;
+mod avatar;
+mod text;
+pub mod widgets;
 
-pub use applet::RadioWidget;
-
+pub(crate) use applet::RadioWidget;
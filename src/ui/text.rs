@@ -0,0 +1,321 @@
+use crate::models::Station;
+use crate::mpv::AudioLevel;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Shortens `s` to at most `max_chars` characters by cutting out the middle,
+/// keeping the trailing words intact (e.g. "Radio Internation…FM 104.5").
+/// Strings that already fit are borrowed, not copied.
+pub fn ellipsize_middle(s: &str, max_chars: usize) -> Cow<'_, str> {
+    // Most names fit; skip collecting chars for them.
+    if s.len() <= max_chars || s.chars().nth(max_chars).is_none() {
+        return Cow::Borrowed(s);
+    }
+    if max_chars < 2 {
+        return Cow::Borrowed(if max_chars == 1 { "…" } else { "" });
+    }
+    let chars: Vec<char> = s.chars().collect();
+
+    let budget = max_chars - 1;
+    let mut tail_len = budget / 3;
+    // Snap the tail to the next word start so we don't keep half a word.
+    let tail_start = chars.len() - tail_len;
+    if let Some(ws) = chars[tail_start..].iter().position(|c| c.is_whitespace()) {
+        if tail_start + ws + 1 < chars.len() {
+            tail_len -= ws + 1;
+        }
+    }
+    let head_len = budget - tail_len;
+
+    let head: String = chars[..head_len].iter().collect();
+    let tail: String = chars[chars.len() - tail_len..].iter().collect();
+    Cow::Owned(format!("{}…{}", head.trim_end(), tail.trim_start()))
+}
+
+/// Separates the end of a scrolling label from its start coming round again.
+const MARQUEE_GAP: &str = "   ·   ";
+
+/// The `width` characters of a scrolling label visible after `offset` steps.
+/// Labels that fit don't scroll and are borrowed as-is.
+pub fn marquee_frame(s: &str, width: usize, offset: usize) -> Cow<'_, str> {
+    if s.chars().nth(width).is_none() {
+        return Cow::Borrowed(s);
+    }
+    let cycle: Vec<char> = s.chars().chain(MARQUEE_GAP.chars()).collect();
+    let start = offset % cycle.len();
+    Cow::Owned(cycle.iter().cycle().skip(start).take(width).collect())
+}
+
+/// How long ago `secs` seconds were, roughly: "just now", "5 min ago", "3 h ago".
+pub fn time_ago(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} d ago", secs / 86400),
+    }
+}
+
+/// Remaining time as "m:ss", or "h:mm:ss" from an hour up.
+pub fn countdown(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// Total listening time to the minute: "45 min", "3 h 20 min".
+pub fn listening_time(secs: u64) -> String {
+    let (h, m) = (secs / 3600, secs / 60 % 60);
+    match (h, m) {
+        (0, m) => format!("{m} min"),
+        (h, 0) => format!("{h} h"),
+        (h, m) => format!("{h} h {m} min"),
+    }
+}
+
+/// Downloaded data in decimal units, as metered plans count it: "740 kB", "12.3 MB".
+pub fn data_size(bytes: u64) -> String {
+    match bytes {
+        0..=999_999 => format!("{} kB", bytes / 1000),
+        1_000_000..=999_999_999 => format!("{:.1} MB", bytes as f64 / 1e6),
+        _ => format!("{:.2} GB", bytes as f64 / 1e9),
+    }
+}
+
+/// A `cells` wide level meter: solid up to the average level, shaded up to
+/// the peak, e.g. "████▒▒░░░░".
+pub fn level_bar(level: AudioLevel, cells: usize) -> String {
+    let filled = |db: f32| (AudioLevel::fraction(db) * cells as f32).round() as usize;
+    let rms = filled(level.rms_db);
+    let peak = filled(level.peak_db).max(rms);
+    ["█".repeat(rms), "▒".repeat(peak - rms), "░".repeat(cells - peak)].concat()
+}
+
+/// `name` without a leading `prefix` (any case) and the separators after it.
+/// `None` if the name doesn't start with the prefix as a whole word, or if
+/// nothing would be left.
+pub fn strip_name_prefix(name: &str, prefix: &str) -> Option<String> {
+    let prefix = prefix.trim_start();
+    if prefix.trim().is_empty() {
+        return None;
+    }
+    let head = name.get(..prefix.len())?;
+    if head.to_lowercase() != prefix.to_lowercase() {
+        return None;
+    }
+    let rest = &name[prefix.len()..];
+    if prefix.ends_with(char::is_alphanumeric) && rest.starts_with(char::is_alphanumeric) {
+        // "Radio" is not a prefix of "Radiohead".
+        return None;
+    }
+    let rest = rest.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | ':' | '|' | '·'));
+    (!rest.is_empty()).then(|| rest.to_string())
+}
+
+/// "Country · codec · bitrate" line under a station name, with a marker for
+/// stations known to send video.
+pub fn station_subtitle(s: &Station, video: bool) -> String {
+    let mut out = String::new();
+    let mut push = |part: std::fmt::Arguments| {
+        if !out.is_empty() {
+            out.push_str(" · ");
+        }
+        let _ = out.write_fmt(part);
+    };
+    if let Some(c) = s.country.as_deref().map(str::trim).filter(|x| !x.is_empty()) {
+        push(format_args!("{c}"));
+    }
+    if let Some(codec) = s.codec.as_deref().map(str::trim).filter(|x| !x.is_empty()) {
+        push(format_args!("{codec}"));
+    }
+    if let Some(br) = s.bitrate {
+        push(format_args!("{br} kbps"));
+    }
+    if video {
+        push(format_args!("video stream"));
+    }
+    out
+}
+
+/// Lines for a station's details panel, beyond what the subtitle shows.
+pub fn station_facts(s: &Station) -> Vec<String> {
+    let mut facts = Vec::new();
+    if let Some(votes) = s.votes {
+        facts.push(format!("{votes} votes on Radio Browser"));
+    }
+    match s.lastcheckok {
+        Some(0) => facts.push("Failed Radio Browser's last check".to_string()),
+        Some(_) => facts.push("Passed Radio Browser's last check".to_string()),
+        None => {}
+    }
+    if let Some(url) = s.stream_url() {
+        facts.push(url.to_string());
+    }
+    facts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_name_prefixes() {
+        assert_eq!(strip_name_prefix("Radio Paradise", "Radio ").as_deref(), Some("Paradise"));
+        assert_eq!(strip_name_prefix("radio 1 - Hits", "Radio").as_deref(), Some("1 - Hits"));
+        assert_eq!(strip_name_prefix("Radio - Swiss Jazz", "Radio").as_deref(), Some("Swiss Jazz"));
+        assert_eq!(strip_name_prefix("Radiohead FM", "Radio"), None);
+        assert_eq!(strip_name_prefix("Radio", "Radio "), None);
+        assert_eq!(strip_name_prefix("Radio –", "Radio"), None);
+        assert_eq!(strip_name_prefix("Jazz FM", " "), None);
+    }
+
+    #[test]
+    fn marquee_scrolls_and_wraps_around() {
+        assert_eq!(marquee_frame("Jazz FM", 10, 3), "Jazz FM");
+
+        let s = "Radio Paradise Main Mix";
+        assert_eq!(marquee_frame(s, 10, 0), "Radio Para");
+        assert_eq!(marquee_frame(s, 10, 6), "Paradise M");
+        // The end runs into the gap and then the start again.
+        assert_eq!(marquee_frame(s, 10, 20), "Mix   ·   ");
+        assert_eq!(marquee_frame(s, 10, 27), "   Radio P");
+        let cycle = s.chars().count() + MARQUEE_GAP.chars().count();
+        assert_eq!(marquee_frame(s, 10, cycle + 6), marquee_frame(s, 10, 6));
+    }
+
+    #[test]
+    fn rough_ages() {
+        assert_eq!(time_ago(5), "just now");
+        assert_eq!(time_ago(125), "2 min ago");
+        assert_eq!(time_ago(7200), "2 h ago");
+        assert_eq!(time_ago(3 * 86400 + 5), "3 d ago");
+    }
+
+    #[test]
+    fn countdowns() {
+        assert_eq!(countdown(59), "0:59");
+        assert_eq!(countdown(23 * 60 + 4), "23:04");
+        assert_eq!(countdown(3600 + 65), "1:01:05");
+    }
+
+    #[test]
+    fn listening_times() {
+        assert_eq!(listening_time(59), "0 min");
+        assert_eq!(listening_time(45 * 60), "45 min");
+        assert_eq!(listening_time(7200), "2 h");
+        assert_eq!(listening_time(3 * 3600 + 20 * 60 + 5), "3 h 20 min");
+    }
+
+    #[test]
+    fn level_bars() {
+        let level = |rms_db, peak_db| AudioLevel { rms_db, peak_db };
+        assert_eq!(level_bar(level(-30.0, -12.0), 10), "█████▒▒▒░░");
+        assert_eq!(level_bar(level(-60.0, -60.0), 4), "░░░░");
+        assert_eq!(level_bar(level(0.0, -6.0), 4), "████");
+    }
+
+    #[test]
+    fn data_sizes() {
+        assert_eq!(data_size(740_123), "740 kB");
+        assert_eq!(data_size(12_345_678), "12.3 MB");
+        assert_eq!(data_size(2_500_000_000), "2.50 GB");
+    }
+
+    #[test]
+    fn short_strings_are_untouched() {
+        assert_eq!(ellipsize_middle("Jazz FM", 30), "Jazz FM");
+    }
+
+    #[test]
+    fn middle_ellipsis_keeps_suffix_words() {
+        let s = "Radio Station International Broadcasting FM 104.5";
+        let out = ellipsize_middle(s, 30);
+        assert!(out.chars().count() <= 30, "{out}");
+        assert!(out.ends_with("…FM 104.5"), "{out}");
+        assert!(out.starts_with("Radio Station"), "{out}");
+    }
+
+    #[test]
+    fn middle_ellipsis_without_spaces() {
+        let out = ellipsize_middle("abcdefghijklmnopqrstuvwxyz", 10);
+        assert_eq!(out.chars().count(), 10);
+        assert_eq!(out, "abcdef…xyz");
+    }
+
+    #[test]
+    fn counts_chars_not_bytes() {
+        assert_eq!(ellipsize_middle("Größenwahn Ökö", 8), "Größe…kö");
+        assert!(matches!(ellipsize_middle("Größenwahn", 10), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn tiny_budgets() {
+        assert_eq!(ellipsize_middle("abc", 1), "…");
+        assert_eq!(ellipsize_middle("abc", 0), "");
+    }
+
+    fn station() -> Station {
+        Station {
+            stationuuid: "u".to_string(),
+            name: "Jazz FM".to_string(),
+            country: Some(" United Kingdom ".to_string()),
+            codec: Some("MP3".to_string()),
+            bitrate: Some(128),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn subtitle_joins_known_fields() {
+        let mut s = station();
+        assert_eq!(station_subtitle(&s, false), "United Kingdom · MP3 · 128 kbps");
+        s.country = None;
+        s.bitrate = None;
+        assert_eq!(station_subtitle(&s, true), "MP3 · video stream");
+        s.codec = Some(" ".to_string());
+        assert_eq!(station_subtitle(&s, false), "");
+    }
+
+    #[test]
+    fn facts_skip_unknown_fields() {
+        let mut s = station();
+        assert!(station_facts(&s).is_empty());
+        s.votes = Some(12);
+        s.lastcheckok = Some(0);
+        s.url = Some("ftp://example.org/a".to_string());
+        s.url_resolved = Some(" https://example.org/live ".to_string());
+        assert_eq!(
+            station_facts(&s),
+            ["12 votes on Radio Browser", "Failed Radio Browser's last check", "https://example.org/live"]
+        );
+    }
+
+    /// Rough per-call cost of the label helpers. Run with
+    /// `cargo test --release -- --ignored --nocapture label_bench`.
+    #[test]
+    #[ignore]
+    fn label_bench() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        const N: u32 = 200_000;
+        let short = "Jazz FM";
+        let long = "Radio Station International Broadcasting FM 104.5";
+        let s = station();
+        let cases: [(&str, &dyn Fn() -> usize); 3] = [
+            ("ellipsize (fits)", &|| ellipsize_middle(black_box(short), 30).len()),
+            ("ellipsize (cut)", &|| ellipsize_middle(black_box(long), 30).len()),
+            ("subtitle", &|| station_subtitle(black_box(&s), false).len()),
+        ];
+        for (name, f) in cases {
+            let start = Instant::now();
+            for _ in 0..N {
+                black_box(f());
+            }
+            println!("{name}: {:?}/call", start.elapsed() / N);
+        }
+    }
+}
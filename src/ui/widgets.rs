@@ -0,0 +1,71 @@
+//! Station list and now-playing pieces that don't know about the applet:
+//! they take plain data and are generic over the message type, so another
+//! COSMIC app can build the same rows with its own messages.
+
+use crate::ui::text::ellipsize_middle;
+use cosmic::iced::Length;
+use cosmic::widget;
+use std::borrow::Cow;
+
+/// Longest station name shown in a list before it's shortened.
+pub const LIST_NAME_CHARS: usize = 40;
+/// Height of a scrolling station list.
+pub const LIST_HEIGHT: f32 = 300.0;
+
+/// A station name for a list; shortened ones show the full name on hover.
+pub fn list_name<'a, M: 'a>(name: &'a str) -> cosmic::Element<'a, M> {
+    let name = name.trim();
+    let Cow::Owned(short) = ellipsize_middle(name, LIST_NAME_CHARS) else {
+        return widget::text::body(name).into();
+    };
+    widget::tooltip(
+        widget::text::body(short),
+        widget::text::body(name),
+        widget::tooltip::Position::Top,
+    )
+    .into()
+}
+
+/// Artwork, name and subtitle of one station, for the caller to make
+/// clickable.
+pub fn station_entry<'a, M: 'a>(
+    name: &'a str,
+    subtitle: Option<&'a str>,
+    badge: Option<cosmic::Element<'a, M>>,
+) -> cosmic::Element<'a, M> {
+    widget::row()
+        .spacing(8)
+        .align_y(cosmic::iced::Alignment::Center)
+        .push_maybe(badge)
+        .push(
+            widget::column()
+                .spacing(2)
+                .push(list_name(name))
+                .push_maybe(subtitle.map(widget::text::caption)),
+        )
+        .into()
+}
+
+/// Station rows in a scrolling list.
+pub fn station_list<'a, M: 'a>(rows: impl IntoIterator<Item = cosmic::Element<'a, M>>) -> cosmic::Element<'a, M> {
+    let list = rows
+        .into_iter()
+        .fold(widget::list_column().padding(0).spacing(0), |list, row| list.add(row));
+    cosmic::iced_widget::scrollable(list.into_element())
+        .height(Length::Fixed(LIST_HEIGHT))
+        .into()
+}
+
+/// Station name and song title at the top of the now-playing strip; more
+/// lines can be pushed below. Long text is shortened unless `wrap` is set.
+pub fn now_playing_heading<'a, M: 'a>(
+    station: &'a str,
+    title: Option<&'a str>,
+    wrap: bool,
+) -> widget::Column<'a, M> {
+    let fit = |s: &'a str, max: usize| if wrap { Cow::Borrowed(s) } else { ellipsize_middle(s, max) };
+    widget::column()
+        .spacing(2)
+        .push(widget::text::body(fit(station, 40)))
+        .push_maybe(title.map(|t| widget::text::caption(fit(t, 48))))
+}
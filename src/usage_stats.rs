@@ -0,0 +1,277 @@
+//! Listening statistics kept on this machine only: how often and how long
+//! each station played and which features get used. Nothing here is ever
+//! sent anywhere. Recording is off until the user turns it on, and the file
+//! can be wiped from the settings.
+
+use crate::config::{state_dir, write_atomic};
+use crate::models::StationRef;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Stations listed in the summary.
+const TOP_STATIONS: usize = 5;
+
+/// Features worth counting. Stored by [`Feature::key`], so renaming a
+/// variant doesn't lose its count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Search,
+    VoiceSearch,
+    RandomFavorite,
+    SleepTimer,
+    Alarm,
+    Cast,
+    Seek,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 7] = [
+        Feature::Search,
+        Feature::VoiceSearch,
+        Feature::RandomFavorite,
+        Feature::SleepTimer,
+        Feature::Alarm,
+        Feature::Cast,
+        Feature::Seek,
+    ];
+
+    fn key(self) -> &'static str {
+        match self {
+            Feature::Search => "search",
+            Feature::VoiceSearch => "voice_search",
+            Feature::RandomFavorite => "random_favorite",
+            Feature::SleepTimer => "sleep_timer",
+            Feature::Alarm => "alarm",
+            Feature::Cast => "cast",
+            Feature::Seek => "seek",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Feature::Search => "Searches",
+            Feature::VoiceSearch => "Voice searches",
+            Feature::RandomFavorite => "Random favorites",
+            Feature::SleepTimer => "Sleep timers",
+            Feature::Alarm => "Alarms",
+            Feature::Cast => "Casts",
+            Feature::Seek => "Seeks",
+        }
+    }
+}
+
+/// Something to count, passed to [`UsageStats::record`].
+#[derive(Debug)]
+pub enum UsageEvent<'a> {
+    Played(&'a StationRef),
+    /// Another stretch of audio from the station.
+    Listened { stationuuid: &'a str, secs: u64 },
+    Used(Feature),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationUsage {
+    /// As of the last play, for stations no longer among the favorites.
+    pub name: String,
+    pub plays: u64,
+    pub listened_secs: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// By station UUID.
+    #[serde(default)]
+    stations: BTreeMap<String, StationUsage>,
+    #[serde(default)]
+    features: BTreeMap<String, u64>,
+    /// Mirrors `privacy.usage_stats`; nothing is recorded while it's off.
+    #[serde(skip)]
+    enabled: bool,
+}
+
+/// What the settings show.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UsageSummary {
+    /// Most listened first.
+    pub top_stations: Vec<StationUsage>,
+    pub listened_secs: u64,
+    pub features: Vec<(Feature, u64)>,
+}
+
+impl UsageSummary {
+    pub fn is_empty(&self) -> bool {
+        self.top_stations.is_empty() && self.features.is_empty()
+    }
+}
+
+impl UsageStats {
+    /// Loads saved statistics; a missing or corrupt file yields empty ones.
+    pub fn load(enabled: bool) -> Self {
+        let path = match stats_path() {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(error = ?e, "usage statistics disabled");
+                return Self::default();
+            }
+        };
+        let stats = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(error = ?e, ?path, "discarding unreadable usage statistics");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+        Self { enabled, ..stats }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self).context("Failed to serialize usage statistics")?;
+        write_atomic(&stats_path()?, &data)
+    }
+
+    /// Forgets everything recorded; see [`UsageStats::remove_file`] for the disk.
+    pub fn clear(&mut self) {
+        *self = Self {
+            enabled: self.enabled,
+            ..Self::default()
+        };
+    }
+
+    pub fn remove_file() -> Result<()> {
+        match std::fs::remove_file(stats_path()?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context("Failed to delete usage statistics"),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Moves what was counted for `old` to `new`, e.g. when Radio Browser
+    /// merged the station. Returns whether anything changed.
+    pub fn remap(&mut self, old: &str, new: &str) -> bool {
+        let Some(moved) = self.stations.remove(old) else {
+            return false;
+        };
+        let entry = self.stations.entry(new.to_string()).or_insert_with(|| StationUsage {
+            name: moved.name.clone(),
+            ..StationUsage::default()
+        });
+        entry.plays += moved.plays;
+        entry.listened_secs += moved.listened_secs;
+        true
+    }
+
+    /// Counts `event` if recording is on. Returns whether anything changed.
+    pub fn record(&mut self, event: UsageEvent) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        match event {
+            UsageEvent::Played(station) => {
+                let entry = self.stations.entry(station.stationuuid.clone()).or_default();
+                entry.name = station.name.trim().to_string();
+                entry.plays += 1;
+            }
+            UsageEvent::Listened { stationuuid, secs } => {
+                // Only stations whose play was counted, so each has a name.
+                let Some(entry) = self.stations.get_mut(stationuuid) else {
+                    return false;
+                };
+                entry.listened_secs += secs;
+            }
+            UsageEvent::Used(feature) => *self.features.entry(feature.key().to_string()).or_default() += 1,
+        }
+        true
+    }
+
+    /// UUIDs of every station played while recording.
+    pub fn played(&self) -> impl Iterator<Item = &String> {
+        self.stations.keys()
+    }
+
+    pub fn summary(&self) -> UsageSummary {
+        let mut stations: Vec<&StationUsage> = self.stations.values().collect();
+        stations.sort_by(|a, b| b.listened_secs.cmp(&a.listened_secs).then(b.plays.cmp(&a.plays)));
+        UsageSummary {
+            top_stations: stations.into_iter().take(TOP_STATIONS).cloned().collect(),
+            listened_secs: self.stations.values().map(|s| s.listened_secs).sum(),
+            features: Feature::ALL
+                .into_iter()
+                .filter_map(|f| Some((f, *self.features.get(f.key())?)))
+                .collect(),
+        }
+    }
+}
+
+/// Whether submitting `query` after `previous` counts as a search of its own.
+/// Narrowing or widening the last query, or sending it again, refines that
+/// search rather than starting another.
+pub fn is_new_search(previous: &str, query: &str) -> bool {
+    let previous = previous.trim().to_lowercase();
+    let query = query.trim().to_lowercase();
+    !query.is_empty() && (previous.is_empty() || !(query.starts_with(&previous) || previous.starts_with(&query)))
+}
+
+fn stats_path() -> Result<PathBuf> {
+    Ok(state_dir()?.join("usage.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn station(uuid: &str, name: &str) -> StationRef {
+        StationRef {
+            stationuuid: uuid.to_string(),
+            name: name.to_string(),
+            url: None,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn records_only_while_enabled() {
+        let mut stats = UsageStats::default();
+        assert!(!stats.record(UsageEvent::Used(Feature::Search)));
+        assert!(stats.summary().is_empty());
+
+        stats.set_enabled(true);
+        let jazz = station("jazz", "Jazz FM ");
+        let news = station("news", "News");
+        assert!(stats.record(UsageEvent::Played(&news)));
+        assert!(stats.record(UsageEvent::Played(&jazz)));
+        assert!(stats.record(UsageEvent::Listened { stationuuid: "jazz", secs: 600 }));
+        assert!(stats.record(UsageEvent::Listened { stationuuid: "news", secs: 30 }));
+        assert!(!stats.record(UsageEvent::Listened { stationuuid: "unknown", secs: 30 }));
+        stats.record(UsageEvent::Used(Feature::Cast));
+        stats.record(UsageEvent::Used(Feature::Search));
+        stats.record(UsageEvent::Used(Feature::Search));
+
+        let summary = stats.summary();
+        let names: Vec<&str> = summary.top_stations.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Jazz FM", "News"]);
+        assert_eq!(summary.listened_secs, 630);
+        assert_eq!(summary.features, [(Feature::Search, 2), (Feature::Cast, 1)]);
+
+        assert!(stats.remap("news", "jazz"));
+        assert!(!stats.remap("news", "jazz"));
+        let summary = stats.summary();
+        assert_eq!(summary.top_stations.len(), 1);
+        assert_eq!((summary.top_stations[0].plays, summary.listened_secs), (2, 630));
+    }
+
+    #[test]
+    fn counts_refinements_as_one_search() {
+        assert!(is_new_search("", "jaz"));
+        assert!(!is_new_search("jaz", "Jazz "));
+        assert!(!is_new_search("jazz fm", "jazz"));
+        assert!(!is_new_search("jazz", "jazz"));
+        assert!(is_new_search("jazz", "news"));
+        assert!(!is_new_search("jazz", "  "));
+    }
+}
@@ -0,0 +1,93 @@
+//! Dictated search queries: records a few seconds from the default microphone
+//! with `arecord` and hands the clip to a local speech-to-text program
+//! (whisper.cpp, vosk, ...). Nothing leaves the machine.
+
+use crate::config::VoiceConfig;
+use anyhow::{bail, Context, Result};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Longest a transcription may take before it is abandoned.
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Records `record_secs` of speech and returns what the transcriber heard.
+pub async fn dictate(config: &VoiceConfig) -> Result<String> {
+    let Some((program, args)) = config.transcriber.split_first() else {
+        bail!("No speech-to-text program configured");
+    };
+    let clip = std::env::temp_dir().join(format!("radiowidget-voice-{}.wav", std::process::id()));
+    let result = async {
+        // 16 kHz mono is what whisper.cpp and vosk models expect.
+        let status = Command::new("arecord")
+            .args(["-q", "-f", "S16_LE", "-r", "16000", "-c", "1", "-d"])
+            .arg(config.record_secs.clamp(1, 15).to_string())
+            .arg(&clip)
+            .stdin(Stdio::null())
+            .status()
+            .await
+            .context("Failed to run arecord (alsa-utils)")?;
+        if !status.success() {
+            bail!("Recording failed ({status})");
+        }
+        let output = tokio::time::timeout(
+            TRANSCRIBE_TIMEOUT,
+            Command::new(program)
+                .args(args)
+                .arg(&clip)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .with_context(|| format!("{program} took too long"))?
+        .with_context(|| format!("Failed to run {program}"))?;
+        if !output.status.success() {
+            bail!("{program} failed ({})", output.status);
+        }
+        let text = clean_transcript(&String::from_utf8_lossy(&output.stdout));
+        if text.is_empty() {
+            bail!("Didn't catch that");
+        }
+        Ok(text)
+    }
+    .await;
+    let _ = tokio::fs::remove_file(&clip).await;
+    result
+}
+
+/// Turns transcriber output into a search query: drops `[...]` markers such as
+/// timestamps and `[BLANK_AUDIO]`, joins lines and trims sentence punctuation.
+fn clean_transcript(raw: &str) -> String {
+    let mut text = String::new();
+    let mut depth = 0u32;
+    for c in raw.chars() {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            _ => text.push(c),
+        }
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    words
+        .join(" ")
+        .trim_matches(|c: char| matches!(c, '.' | ',' | '!' | '?' | '"' | '-') || c.is_whitespace())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_transcripts() {
+        assert_eq!(
+            clean_transcript("\n[00:00:00.000 --> 00:00:02.000]   Jazz radio Berlin.\n"),
+            "Jazz radio Berlin"
+        );
+        assert_eq!(clean_transcript(" Radio Paradise!\n"), "Radio Paradise");
+        assert_eq!(clean_transcript("[BLANK_AUDIO]\n"), "");
+        assert_eq!(clean_transcript("(music) BBC\nWorld Service"), "BBC World Service");
+    }
+}